@@ -0,0 +1,37 @@
+//! Embeds versions of migrations, so that the readiness check can find
+//! pending ones without the migration directory at runtime.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const MIGRATION_DIRECTORY: &str = "migration";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", MIGRATION_DIRECTORY);
+
+    // the version is the prefix of the directory name (as diesel does)
+    let mut versions: Vec<String> = fs::read_dir(MIGRATION_DIRECTORY)
+        .expect("migration directory")
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            e.file_name()
+                .to_str()
+                .and_then(|n| n.split('_').next())
+                .map(|v| v.replace('-', ""))
+        })
+        .collect();
+    versions.sort();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("migrations.rs");
+    let values: Vec<String> =
+        versions.iter().map(|v| format!("{:?}", v)).collect();
+    fs::write(
+        path,
+        format!(
+            "pub const MIGRATIONS: &[&str] = &[{}];\n",
+            values.join(", ")
+        ),
+    )
+    .expect("migrations.rs");
+}
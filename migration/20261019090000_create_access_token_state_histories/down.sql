@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS access_token_state_histories_access_token_id_idx;
+
+DROP TABLE IF EXISTS access_token_state_histories;
+DROP SEQUENCE IF EXISTS access_token_state_histories_id_seq;
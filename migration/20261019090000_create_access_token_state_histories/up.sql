@@ -0,0 +1,26 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE access_token_state_histories_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE access_token_state_histories (
+  id BIGINT NOT NULL PRIMARY KEY
+    DEFAULT nextval('access_token_state_histories_id_seq'),
+  access_token_id BIGINT REFERENCES access_tokens (id) MATCH FULL NOT NULL,
+  user_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  from_state e_access_token_state NOT NULL,
+  to_state e_access_token_state NOT NULL,
+  revoked BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE access_token_state_histories_id_seq
+  OWNED BY access_token_state_histories.id;
+
+CREATE INDEX access_token_state_histories_access_token_id_idx
+  ON access_token_state_histories(access_token_id);
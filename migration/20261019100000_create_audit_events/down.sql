@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS audit_events_namespace_id_created_at_idx;
+DROP INDEX IF EXISTS audit_events_user_id_created_at_idx;
+
+DROP TABLE IF EXISTS audit_events;
+DROP SEQUENCE IF EXISTS audit_events_id_seq;
+
+DROP TYPE IF EXISTS e_audit_event_action;
@@ -0,0 +1,40 @@
+CREATE TYPE e_audit_event_action AS ENUM (
+  'login',
+  'login_failure',
+  'logout',
+  'account_activation',
+  'password_reset_request',
+  'password_reset',
+  'access_token_dump',
+  'access_token_revocation',
+  'access_token_state_change',
+  'namespace_creation',
+  'membership_creation'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE audit_events_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- user_id is NULL for an action by an unknown user (e.g. login failure)
+CREATE TABLE audit_events (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('audit_events_id_seq'),
+  user_id BIGINT REFERENCES users (id) NULL,
+  namespace_id BIGINT REFERENCES namespaces (id) NULL,
+  action e_audit_event_action NOT NULL,
+  target CHARACTER VARYING(128) NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE audit_events_id_seq OWNED BY audit_events.id;
+
+CREATE INDEX audit_events_user_id_created_at_idx ON audit_events(
+  user_id, created_at);
+CREATE INDEX audit_events_namespace_id_created_at_idx ON audit_events(
+  namespace_id, created_at);
@@ -0,0 +1,31 @@
+DROP INDEX IF EXISTS user_recovery_codes_user_id_code_idx;
+
+DROP TABLE IF EXISTS user_recovery_codes;
+DROP SEQUENCE IF EXISTS user_recovery_codes_id_seq;
+
+DROP INDEX IF EXISTS user_totps_user_id_idx;
+
+DROP TABLE IF EXISTS user_totps;
+DROP SEQUENCE IF EXISTS user_totps_id_seq;
+
+DELETE FROM audit_events WHERE action IN (
+  'totp_activation',
+  'totp_deactivation'
+);
+CREATE TYPE e_audit_event_action_new AS ENUM (
+  'login',
+  'login_failure',
+  'logout',
+  'account_activation',
+  'password_reset_request',
+  'password_reset',
+  'access_token_dump',
+  'access_token_revocation',
+  'access_token_state_change',
+  'namespace_creation',
+  'membership_creation'
+);
+ALTER TABLE audit_events ALTER COLUMN action TYPE e_audit_event_action_new
+  USING action::text::e_audit_event_action_new;
+DROP TYPE e_audit_event_action;
+ALTER TYPE e_audit_event_action_new RENAME TO e_audit_event_action;
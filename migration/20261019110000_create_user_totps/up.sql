@@ -0,0 +1,74 @@
+-- NOTE:
+-- `ALTER TYPE ... ADD VALUE` can't run in a transaction block (before
+-- PostgreSQL 12) and the value can't be removed. Thus the type is replaced
+-- with a new one having the values.
+CREATE TYPE e_audit_event_action_new AS ENUM (
+  'login',
+  'login_failure',
+  'logout',
+  'account_activation',
+  'password_reset_request',
+  'password_reset',
+  'access_token_dump',
+  'access_token_revocation',
+  'access_token_state_change',
+  'namespace_creation',
+  'membership_creation',
+  'totp_activation',
+  'totp_deactivation'
+);
+ALTER TABLE audit_events ALTER COLUMN action TYPE e_audit_event_action_new
+  USING action::text::e_audit_event_action_new;
+DROP TYPE e_audit_event_action;
+ALTER TYPE e_audit_event_action_new RENAME TO e_audit_event_action;
+
+-- user_totps
+CREATE SEQUENCE user_totps_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- confirmed_at is NULL until the first code is verified at enrollment.
+-- last_used_step prevents a code from being used twice.
+CREATE TABLE user_totps (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('user_totps_id_seq'),
+  user_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  secret BYTEA NOT NULL,
+  last_used_step BIGINT NOT NULL DEFAULT 0,
+  confirmed_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE user_totps_id_seq OWNED BY user_totps.id;
+
+CREATE UNIQUE INDEX user_totps_user_id_idx ON user_totps(user_id);
+
+-- user_recovery_codes
+CREATE SEQUENCE user_recovery_codes_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- code is a SHA-256 digest of the (random) recovery code
+CREATE TABLE user_recovery_codes (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('user_recovery_codes_id_seq'),
+  user_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  code BYTEA NOT NULL,
+  used_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE user_recovery_codes_id_seq OWNED BY user_recovery_codes.id;
+
+CREATE INDEX user_recovery_codes_user_id_code_idx ON user_recovery_codes(
+  user_id, code);
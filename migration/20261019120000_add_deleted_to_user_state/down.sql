@@ -0,0 +1,35 @@
+-- deregistered users have been anonymized, and they can't be activated
+UPDATE users SET state = 'pending' WHERE state = 'deleted';
+CREATE TYPE e_user_state_new AS ENUM (
+  'pending',
+  'active'
+);
+ALTER TABLE users ALTER COLUMN state DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN state TYPE e_user_state_new
+  USING state::text::e_user_state_new;
+ALTER TABLE users ALTER COLUMN state SET DEFAULT 'pending';
+DROP TYPE e_user_state;
+ALTER TYPE e_user_state_new RENAME TO e_user_state;
+
+DELETE FROM audit_events WHERE action IN (
+  'account_deregistration'
+);
+CREATE TYPE e_audit_event_action_new AS ENUM (
+  'login',
+  'login_failure',
+  'logout',
+  'account_activation',
+  'password_reset_request',
+  'password_reset',
+  'access_token_dump',
+  'access_token_revocation',
+  'access_token_state_change',
+  'namespace_creation',
+  'membership_creation',
+  'totp_activation',
+  'totp_deactivation'
+);
+ALTER TABLE audit_events ALTER COLUMN action TYPE e_audit_event_action_new
+  USING action::text::e_audit_event_action_new;
+DROP TYPE e_audit_event_action;
+ALTER TYPE e_audit_event_action_new RENAME TO e_audit_event_action;
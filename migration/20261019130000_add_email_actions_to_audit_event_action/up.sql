@@ -0,0 +1,28 @@
+-- NOTE:
+-- `ALTER TYPE ... ADD VALUE` can't run in a transaction block (before
+-- PostgreSQL 12) and the value can't be removed. Thus the type is replaced
+-- with a new one having the values.
+CREATE TYPE e_audit_event_action_new AS ENUM (
+  'login',
+  'login_failure',
+  'logout',
+  'account_activation',
+  'password_reset_request',
+  'password_reset',
+  'access_token_dump',
+  'access_token_revocation',
+  'access_token_state_change',
+  'namespace_creation',
+  'membership_creation',
+  'totp_activation',
+  'totp_deactivation',
+  'account_deregistration',
+  'email_addition',
+  'email_verification',
+  'primary_email_change',
+  'email_removal'
+);
+ALTER TABLE audit_events ALTER COLUMN action TYPE e_audit_event_action_new
+  USING action::text::e_audit_event_action_new;
+DROP TYPE e_audit_event_action;
+ALTER TYPE e_audit_event_action_new RENAME TO e_audit_event_action;
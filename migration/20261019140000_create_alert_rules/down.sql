@@ -0,0 +1,15 @@
+DROP INDEX IF EXISTS alerts_alert_rule_id_open_idx;
+DROP INDEX IF EXISTS alerts_uuid_idx;
+
+DROP TABLE IF EXISTS alerts;
+DROP SEQUENCE IF EXISTS alerts_id_seq;
+
+DROP INDEX IF EXISTS alert_rules_stream_id_idx;
+DROP INDEX IF EXISTS alert_rules_uuid_idx;
+
+DROP TABLE IF EXISTS alert_rules;
+DROP SEQUENCE IF EXISTS alert_rules_id_seq;
+
+DROP TYPE IF EXISTS e_alert_state;
+DROP TYPE IF EXISTS e_alert_channel;
+DROP TYPE IF EXISTS e_alert_condition;
@@ -0,0 +1,90 @@
+DROP TYPE IF EXISTS e_alert_condition;
+CREATE TYPE e_alert_condition AS ENUM (
+  'match',
+  'threshold',
+  'absence'
+);
+
+DROP TYPE IF EXISTS e_alert_channel;
+CREATE TYPE e_alert_channel AS ENUM (
+  'email',
+  'webhook'
+);
+
+DROP TYPE IF EXISTS e_alert_state;
+CREATE TYPE e_alert_state AS ENUM (
+  'firing',
+  'acknowledged',
+  'resolved'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE alert_rules_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- level (the minimum one) and code filter messages in the window. The rule
+-- fires if the number of them reaches threshold (match and threshold), or if
+-- there is none (absence).
+CREATE TABLE alert_rules (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('alert_rules_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  stream_id BIGINT REFERENCES streams (id) NOT NULL,
+  name CHARACTER VARYING(128) NOT NULL,
+  condition e_alert_condition NOT NULL DEFAULT 'match',
+  level e_log_level NULL,
+  code CHARACTER VARYING(32) NULL,
+  threshold INTEGER NOT NULL DEFAULT 1,
+  window_seconds INTEGER NOT NULL DEFAULT 300,
+  channel e_alert_channel NOT NULL DEFAULT 'email',
+  target CHARACTER VARYING(255) NOT NULL,
+  muted_until TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  CHECK (threshold > 0),
+  CHECK (window_seconds > 0)
+);
+
+ALTER SEQUENCE alert_rules_id_seq OWNED BY alert_rules.id;
+
+CREATE UNIQUE INDEX alert_rules_uuid_idx ON alert_rules(uuid);
+CREATE INDEX alert_rules_stream_id_idx ON alert_rules(stream_id);
+
+CREATE SEQUENCE alerts_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- occurrences counts evaluations which have found the rule firing while the
+-- alert is open (they are not notified again).
+CREATE TABLE alerts (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('alerts_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  alert_rule_id BIGINT REFERENCES alert_rules (id) ON DELETE CASCADE NOT NULL,
+  state e_alert_state NOT NULL DEFAULT 'firing',
+  occurrences INTEGER NOT NULL DEFAULT 1,
+  notified_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  acknowledged_by BIGINT REFERENCES users (id) NULL,
+  acknowledged_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  resolved_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE alerts_id_seq OWNED BY alerts.id;
+
+CREATE UNIQUE INDEX alerts_uuid_idx ON alerts(uuid);
+-- at most one open alert per rule
+CREATE UNIQUE INDEX alerts_alert_rule_id_open_idx ON alerts(alert_rule_id)
+  WHERE state <> 'resolved';
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS webhook_deliveries_webhook_id_created_at_idx;
+DROP INDEX IF EXISTS webhook_deliveries_uuid_idx;
+
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP SEQUENCE IF EXISTS webhook_deliveries_id_seq;
+
+DROP INDEX IF EXISTS webhooks_namespace_id_idx;
+DROP INDEX IF EXISTS webhooks_uuid_idx;
+
+DROP TABLE IF EXISTS webhooks;
+DROP SEQUENCE IF EXISTS webhooks_id_seq;
@@ -0,0 +1,60 @@
+CREATE SEQUENCE webhooks_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- A webhook subscribes to messages in the namespace, or only ones on the
+-- stream if stream_id is given. level (the minimum one) and code filter them.
+-- secret is a key of the HMAC-SHA256 signature of payloads.
+CREATE TABLE webhooks (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('webhooks_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  namespace_id BIGINT REFERENCES namespaces (id) NOT NULL,
+  stream_id BIGINT REFERENCES streams (id) ON DELETE CASCADE NULL,
+  url CHARACTER VARYING(255) NOT NULL,
+  secret CHARACTER VARYING(64) NOT NULL,
+  level e_log_level NULL,
+  code CHARACTER VARYING(32) NULL,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE webhooks_id_seq OWNED BY webhooks.id;
+
+CREATE UNIQUE INDEX webhooks_uuid_idx ON webhooks(uuid);
+CREATE INDEX webhooks_namespace_id_idx ON webhooks(namespace_id);
+
+CREATE SEQUENCE webhook_deliveries_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- Each attempt is logged. Retries of a delivery share the uuid (it's sent
+-- as a header), status_code is null if no response has been received.
+CREATE TABLE webhook_deliveries (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('webhook_deliveries_id_seq'),
+  uuid UUID NOT NULL,
+  webhook_id BIGINT REFERENCES webhooks (id) ON DELETE CASCADE NOT NULL,
+  event CHARACTER VARYING(64) NOT NULL,
+  attempt INTEGER NOT NULL DEFAULT 1,
+  status_code INTEGER NULL,
+  error TEXT NULL,
+  succeeded BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE webhook_deliveries_id_seq OWNED BY webhook_deliveries.id;
+
+CREATE INDEX webhook_deliveries_uuid_idx ON webhook_deliveries(uuid);
+CREATE INDEX webhook_deliveries_webhook_id_created_at_idx
+  ON webhook_deliveries(webhook_id, created_at DESC);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS messages_stream_id_created_at_idx;
+
+DROP INDEX IF EXISTS message_counts_bucket_at_idx;
+
+DROP TABLE IF EXISTS message_counts;
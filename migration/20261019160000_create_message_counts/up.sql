@@ -0,0 +1,16 @@
+-- Hourly counts of messages, they are rolled up by the worker (see the
+-- RollupMessageCounts job) for aggregation over long ranges. code is an empty
+-- string for messages without code.
+CREATE TABLE message_counts (
+  stream_id BIGINT REFERENCES streams (id) ON DELETE CASCADE NOT NULL,
+  bucket_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  level e_log_level NOT NULL,
+  code CHARACTER VARYING(128) NOT NULL DEFAULT '',
+  count BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (stream_id, bucket_at, level, code)
+);
+
+CREATE INDEX message_counts_bucket_at_idx ON message_counts(bucket_at);
+
+CREATE INDEX messages_stream_id_created_at_idx
+  ON messages(stream_id, created_at);
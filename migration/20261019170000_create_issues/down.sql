@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS issues_stream_id_last_seen_at_idx;
+DROP INDEX IF EXISTS issues_stream_id_fingerprint_idx;
+DROP INDEX IF EXISTS issues_uuid_idx;
+
+DROP TABLE IF EXISTS issues;
+DROP SEQUENCE IF EXISTS issues_id_seq;
+
+DROP TYPE IF EXISTS e_issue_state;
@@ -0,0 +1,44 @@
+DROP TYPE IF EXISTS e_issue_state;
+CREATE TYPE e_issue_state AS ENUM (
+  'open',
+  'resolved',
+  'ignored'
+);
+
+CREATE SEQUENCE issues_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- An issue groups recurring messages on a stream by fingerprint (a digest of
+-- level, code and normalized title). title is the one of the first message.
+-- A resolved issue is reopened when it recurs, but an ignored one is not.
+CREATE TABLE issues (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('issues_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  stream_id BIGINT REFERENCES streams (id) ON DELETE CASCADE NOT NULL,
+  fingerprint CHARACTER VARYING(64) NOT NULL,
+  level e_log_level NOT NULL,
+  code CHARACTER VARYING(128) NULL,
+  title CHARACTER VARYING(255) NOT NULL,
+  state e_issue_state NOT NULL DEFAULT 'open',
+  count BIGINT NOT NULL DEFAULT 1,
+  first_seen_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  last_seen_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  resolved_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE issues_id_seq OWNED BY issues.id;
+
+CREATE UNIQUE INDEX issues_uuid_idx ON issues(uuid);
+CREATE UNIQUE INDEX issues_stream_id_fingerprint_idx
+  ON issues(stream_id, fingerprint);
+CREATE INDEX issues_stream_id_last_seen_at_idx
+  ON issues(stream_id, last_seen_at);
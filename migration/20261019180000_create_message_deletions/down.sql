@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS message_deletions_stream_id_created_at_idx;
+
+DROP TABLE IF EXISTS message_deletions;
+DROP SEQUENCE IF EXISTS message_deletions_id_seq;
@@ -0,0 +1,24 @@
+CREATE SEQUENCE message_deletions_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- A deletion of a message by the user. message_id is not a reference as the
+-- message has gone, and title is kept to tell which one it was.
+CREATE TABLE message_deletions (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('message_deletions_id_seq'),
+  stream_id BIGINT REFERENCES streams (id) ON DELETE CASCADE NOT NULL,
+  message_id BIGINT NOT NULL,
+  user_id BIGINT REFERENCES users (id) NOT NULL,
+  title CHARACTER VARYING(255) NOT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE message_deletions_id_seq OWNED BY message_deletions.id;
+
+CREATE INDEX message_deletions_stream_id_created_at_idx
+  ON message_deletions(stream_id, created_at DESC);
@@ -0,0 +1,9 @@
+ALTER TABLE messages DROP COLUMN IF EXISTS redactions_count;
+
+DROP INDEX IF EXISTS redaction_rules_namespace_id_name_idx;
+DROP INDEX IF EXISTS redaction_rules_uuid_idx;
+
+DROP TABLE IF EXISTS redaction_rules;
+DROP SEQUENCE IF EXISTS redaction_rules_id_seq;
+
+DROP TYPE IF EXISTS e_redaction_detector;
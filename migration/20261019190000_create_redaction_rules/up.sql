@@ -0,0 +1,43 @@
+DROP TYPE IF EXISTS e_redaction_detector;
+CREATE TYPE e_redaction_detector AS ENUM (
+  'email',
+  'credit_card',
+  'jwt',
+  'bearer_token',
+  'ip_address',
+  'custom'
+);
+
+CREATE SEQUENCE redaction_rules_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- A redaction rule enables a detector of sensitive data in messages on the
+-- namespace. pattern is a regular expression only for the custom one. Matches
+-- are replaced with a placeholder tagged with the name, before messages are
+-- saved.
+CREATE TABLE redaction_rules (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('redaction_rules_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  namespace_id BIGINT REFERENCES namespaces (id) ON DELETE CASCADE NOT NULL,
+  detector e_redaction_detector NOT NULL,
+  name CHARACTER VARYING(32) NOT NULL,
+  pattern CHARACTER VARYING(255) NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE redaction_rules_id_seq OWNED BY redaction_rules.id;
+
+CREATE UNIQUE INDEX redaction_rules_uuid_idx ON redaction_rules(uuid);
+CREATE UNIQUE INDEX redaction_rules_namespace_id_name_idx
+  ON redaction_rules(namespace_id, name);
+
+-- the number of matches replaced in title and content
+ALTER TABLE messages ADD COLUMN redactions_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS annotations_message_id_idx;
+DROP INDEX IF EXISTS annotations_uuid_idx;
+
+DROP TABLE IF EXISTS annotations;
+DROP SEQUENCE IF EXISTS annotations_id_seq;
@@ -0,0 +1,29 @@
+CREATE SEQUENCE annotations_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- An annotation on a message by a member of the namespace. label marks the
+-- message (e.g. investigating), body is a note and ticket_url links a ticket.
+-- At least one of them is given.
+CREATE TABLE annotations (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('annotations_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  message_id BIGINT REFERENCES messages (id) ON DELETE CASCADE NOT NULL,
+  user_id BIGINT REFERENCES users (id) NOT NULL,
+  label CHARACTER VARYING(32) NULL,
+  body TEXT NULL,
+  ticket_url CHARACTER VARYING(255) NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE annotations_id_seq OWNED BY annotations.id;
+
+CREATE UNIQUE INDEX annotations_uuid_idx ON annotations(uuid);
+CREATE INDEX annotations_message_id_idx ON annotations(message_id);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS message_views_namespace_id_user_id_idx;
+DROP INDEX IF EXISTS message_views_uuid_idx;
+
+DROP TABLE IF EXISTS message_views;
+DROP SEQUENCE IF EXISTS message_views_id_seq;
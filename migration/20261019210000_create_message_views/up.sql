@@ -0,0 +1,35 @@
+CREATE SEQUENCE message_views_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- A saved query for messages in the namespace. It's visible only to the user
+-- who made it, or to all the members if it's shared. stream_id (all streams
+-- if null), levels (any if empty), code and lang narrow messages down, and
+-- time_window (seconds) limits them to recent ones at the time of running.
+CREATE TABLE message_views (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('message_views_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  namespace_id BIGINT REFERENCES namespaces (id) ON DELETE CASCADE NOT NULL,
+  user_id BIGINT REFERENCES users (id) ON DELETE CASCADE NOT NULL,
+  stream_id BIGINT REFERENCES streams (id) ON DELETE CASCADE NULL,
+  name CHARACTER VARYING(64) NOT NULL,
+  shared BOOLEAN NOT NULL DEFAULT FALSE,
+  levels TEXT[] NOT NULL DEFAULT '{}',
+  code CHARACTER VARYING(128) NULL,
+  lang CHARACTER VARYING(8) NULL,
+  time_window INTEGER NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE message_views_id_seq OWNED BY message_views.id;
+
+CREATE UNIQUE INDEX message_views_uuid_idx ON message_views(uuid);
+CREATE INDEX message_views_namespace_id_user_id_idx
+  ON message_views(namespace_id, user_id);
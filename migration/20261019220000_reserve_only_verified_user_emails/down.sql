@@ -0,0 +1,6 @@
+DELETE FROM user_emails a USING user_emails b
+  WHERE a.email = b.email AND a.identification_state = 'pending' AND
+    (b.identification_state = 'done' OR a.id > b.id);
+DROP INDEX user_emails_user_id_email_idx;
+DROP INDEX user_emails_email_idx;
+CREATE UNIQUE INDEX user_emails_email_idx ON user_emails(email);
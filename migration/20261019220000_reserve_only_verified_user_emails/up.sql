@@ -0,0 +1,7 @@
+-- An address is reserved only once it has been verified. Unverified ones can
+-- be added by other users, and they are deleted when one of them is verified.
+DROP INDEX user_emails_email_idx;
+CREATE UNIQUE INDEX user_emails_email_idx ON user_emails(email)
+  WHERE identification_state = 'done';
+CREATE UNIQUE INDEX user_emails_user_id_email_idx ON
+  user_emails(user_id, email);
@@ -0,0 +1,464 @@
+//! An utility inspects and operates jobs in queues.
+#![feature(rustc_private)]
+
+#[macro_use(error)]
+extern crate slog;
+
+use std::env;
+use std::process;
+
+use chrono::DateTime;
+use diesel::PgConnection;
+use dotenv::dotenv;
+use fourche::queue::Queue;
+use proctitle::set_title;
+use redis::{Client, Connection, RedisResult};
+use serde_json::{Value, json};
+
+use eloquentlog_console_api::config::{Config, Options};
+use eloquentlog_console_api::db::establish_connection;
+use eloquentlog_console_api::job::scheduler;
+use eloquentlog_console_api::job::{
+    Job, JobKind, Payload, dead_letter, delay, pending, postpone, retry,
+};
+use eloquentlog_console_api::logger::{Logger, get_logger};
+use eloquentlog_console_api::model::user_email::UserEmail;
+use eloquentlog_console_api::service::token_issuer::{TokenIssuer, TokenPurpose};
+use eloquentlog_console_api::worker::Heartbeat;
+
+const USAGE: &str = concat!(
+    "usage: eloquentlog-console-api-jobs <command> [<args>] ",
+    "[--config <path>] [--<key>=<value> ...]\n",
+    "\n",
+    "commands:\n",
+    "  queues                   shows the number of jobs in each queue\n",
+    "  peek <queue> [<count>]   prints jobs in the queue as JSON (<queue> is \
+     a\n",
+    "                           queue name, `retry`, `delayed` or `dead`)\n",
+    "  retry <id>               enqueues the retry, delayed or dead job now\n",
+    "  delete <id>              deletes the retry, delayed or dead job\n",
+    "  delete <queue> <index>   deletes the pending job at the index\n",
+    "  enqueue <kind> [<json>]  enqueues a job with the payload (for \
+     activation\n",
+    "                           or verification emails, a new token is \
+     issued\n",
+    "                           if only `user_email_id` is given)\n",
+    "  enqueue-at <time> <kind> [<json>]\n",
+    "                           enqueues a job at the time (in RFC 3339 or a\n",
+    "                           timestamp) as a delayed one\n",
+    "  heartbeats               shows alive workers",
+);
+
+const PEEK_COUNT: isize = 10;
+
+const RETRY: &str = "retry";
+const DELAYED: &str = "delayed";
+const POSTPONED: &str = "postponed";
+const DEAD: &str = "dead";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Queues,
+    Peek(String, isize),
+    Retry(String),
+    Delete(String),
+    DeletePending(String, isize),
+    Enqueue(String, String),
+    EnqueueAt(i64, String, String),
+    Heartbeats,
+}
+
+fn get_env() -> String {
+    match env::var("ENV") {
+        Ok(ref v) if v == &"test".to_string() => String::from("testing"),
+        Ok(v) => v.to_lowercase(),
+        Err(_) => String::from("development"),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let parse_number = |v: &str| {
+        v.parse::<isize>()
+            .map_err(|e| format!("invalid number {}: {}", v, e))
+    };
+    match args.as_slice() {
+        ["queues"] => Ok(Command::Queues),
+        ["peek", queue] => Ok(Command::Peek(queue.to_string(), PEEK_COUNT)),
+        ["peek", queue, count] => {
+            let count = parse_number(count)?;
+            if count < 1 {
+                return Err(format!("invalid count: {}", count));
+            }
+            Ok(Command::Peek(queue.to_string(), count))
+        },
+        ["retry", id] => Ok(Command::Retry(id.to_string())),
+        ["delete", id] => Ok(Command::Delete(id.to_string())),
+        ["delete", queue, index] => {
+            Ok(Command::DeletePending(
+                queue.to_string(),
+                parse_number(index)?,
+            ))
+        },
+        ["enqueue", kind] => {
+            Ok(Command::Enqueue(kind.to_string(), "{}".to_string()))
+        },
+        ["enqueue", kind, data] => {
+            Ok(Command::Enqueue(kind.to_string(), data.to_string()))
+        },
+        ["enqueue-at", time, kind] => {
+            Ok(Command::EnqueueAt(
+                parse_time(time)?,
+                kind.to_string(),
+                "{}".to_string(),
+            ))
+        },
+        ["enqueue-at", time, kind, data] => {
+            Ok(Command::EnqueueAt(
+                parse_time(time)?,
+                kind.to_string(),
+                data.to_string(),
+            ))
+        },
+        ["heartbeats"] => Ok(Command::Heartbeats),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// Parses a time in RFC 3339 or a timestamp, and returns the timestamp
+fn parse_time(s: &str) -> Result<i64, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.timestamp())
+        .or_else(|_| s.parse::<i64>())
+        .map_err(|_| format!("invalid time: {}", s))
+}
+
+// Builds a payload from the kind and its fields in JSON
+fn build_payload(kind: &str, data: Value) -> Result<Payload, String> {
+    let mut value = data;
+    let fields = value
+        .as_object_mut()
+        .ok_or_else(|| "payload must be an object".to_string())?;
+    fields.insert("kind".to_string(), Value::String(kind.to_string()));
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+// Issues a token to the user email in the same way as the registration (or
+// the addition of an email) does, for a job enqueued by hand. It returns the
+// session id and the token.
+fn issue_token(
+    user_email_id: i64,
+    purpose: TokenPurpose,
+    config: &Config,
+    db_conn: &PgConnection,
+    ss_conn: &mut Connection,
+    logger: &Logger,
+) -> Result<(String, String), String> {
+    let user_email = UserEmail::find_by_id(user_email_id, db_conn, logger)
+        .ok_or_else(|| format!("user_email not found: {}", user_email_id))?;
+
+    let issuer = TokenIssuer::new(config, logger);
+    let issued = issuer.grant(&user_email, db_conn)?;
+    Ok(issuer.store(issued, purpose, ss_conn)?)
+}
+
+fn queues(config: &Config, conn: &mut Connection) -> RedisResult<()> {
+    for (name, _) in &config.worker_queues {
+        println!("{:<16} {}", name, pending::len(name, conn)?);
+    }
+    println!("{:<16} {}", RETRY, retry::len(conn)?);
+    println!("{:<16} {}", DELAYED, delay::len(conn)?);
+    println!("{:<16} {}", POSTPONED, postpone::len(conn)?);
+    println!("{:<16} {}", DEAD, dead_letter::len(conn)?);
+    Ok(())
+}
+
+fn peek(queue: &str, count: isize, conn: &mut Connection) -> RedisResult<()> {
+    let stop = count - 1;
+    let values: Vec<Value> = match queue {
+        RETRY => {
+            retry::range(0, stop, conn)?
+                .iter()
+                .map(|e| json!(e))
+                .collect()
+        },
+        DELAYED => {
+            delay::range(0, stop, conn)?
+                .iter()
+                .map(|e| json!(e))
+                .collect()
+        },
+        DEAD => {
+            dead_letter::entries(0, stop, conn)?
+                .iter()
+                .map(|e| json!(e))
+                .collect()
+        },
+        _ => {
+            pending::lrange(queue, 0, stop, conn)?
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    // it's printed as a string if it can't be decoded
+                    let job = serde_json::from_str::<Value>(&v)
+                        .unwrap_or(Value::String(v));
+                    json!({ "index": i, "job": job })
+                })
+                .collect()
+        },
+    };
+    for v in values {
+        println!("{}", v);
+    }
+    Ok(())
+}
+
+fn retry_job(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    Ok(retry::run_now(id, conn)? ||
+        delay::run_now(id, conn)? ||
+        dead_letter::replay(id, conn)?)
+}
+
+fn delete_job(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    Ok(retry::del(id, conn)? ||
+        delay::del(id, conn)? ||
+        dead_letter::del(id, conn)?)
+}
+
+// Builds a job of the kind from its fields in JSON. A token is issued at this
+// time also for a delayed job, so it must run before the token expires.
+fn build_job(
+    kind: &str,
+    data: &str,
+    config: &Config,
+    logger: &Logger,
+) -> Result<Job, String> {
+    let mut value: Value =
+        serde_json::from_str(data).map_err(|e| e.to_string())?;
+
+    let purpose = match serde_json::from_value(json!(kind)) {
+        Ok(JobKind::SendUserActivationEmail) => {
+            Some(TokenPurpose::UserActivation)
+        },
+        Ok(JobKind::SendUserEmailVerificationEmail) => {
+            Some(TokenPurpose::UserEmailVerification)
+        },
+        Ok(_) => None,
+        Err(_) => return Err(format!("unknown kind: {}", kind)),
+    };
+    if let Some(purpose) = purpose {
+        if value["token"].is_null() {
+            let id = value["user_email_id"]
+                .as_i64()
+                .ok_or_else(|| "user_email_id is required".to_string())?;
+
+            let db_conn = establish_connection(config);
+            let client = Client::open(config.session_store_url.as_str())
+                .map_err(|e| e.to_string())?;
+            let mut ss_conn =
+                client.get_connection().map_err(|e| e.to_string())?;
+
+            let (session_id, token) = issue_token(
+                id,
+                purpose,
+                config,
+                &db_conn,
+                &mut ss_conn,
+                logger,
+            )?;
+            value["session_id"] = Value::String(session_id);
+            value["token"] = Value::String(token);
+        }
+    }
+
+    Ok(Job::new(build_payload(kind, value)?))
+}
+
+fn enqueue(
+    kind: &str,
+    data: &str,
+    config: &Config,
+    conn: &mut Connection,
+    logger: &Logger,
+) -> Result<Job, String> {
+    let job = build_job(kind, data, config, logger)?;
+    let mut queue = Queue::new(job.queue(), conn);
+    queue
+        .enqueue::<Job>(job.clone())
+        .map_err(|e| e.to_string())?;
+    Ok(job)
+}
+
+fn heartbeats(conn: &mut Connection) -> RedisResult<()> {
+    let leader = scheduler::leader(conn)?;
+    for h in Heartbeat::find_all(conn)? {
+        let mut value = json!(h);
+        value["leader"] = json!(leader.as_ref() == Some(&h.id));
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+fn run(
+    command: Command,
+    config: &Config,
+    conn: &mut Connection,
+    logger: &Logger,
+) -> Result<(), String> {
+    let not_found = |id: &str| format!("not found: {}", id);
+    match command {
+        Command::Queues => queues(config, conn).map_err(|e| e.to_string()),
+        Command::Peek(queue, count) => {
+            peek(&queue, count, conn).map_err(|e| e.to_string())
+        },
+        Command::Retry(id) => {
+            match retry_job(&id, conn) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(not_found(&id)),
+                Err(e) => Err(e.to_string()),
+            }
+        },
+        Command::Delete(id) => {
+            match delete_job(&id, conn) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(not_found(&id)),
+                Err(e) => Err(e.to_string()),
+            }
+        },
+        Command::DeletePending(queue, index) => {
+            match pending::del(&queue, index, conn) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(not_found(&index.to_string())),
+                Err(e) => Err(e.to_string()),
+            }
+        },
+        Command::Enqueue(kind, data) => {
+            let job = enqueue(&kind, &data, config, conn, logger)?;
+            println!("{}", json!({ "queue": job.queue(), "job": job }));
+            Ok(())
+        },
+        Command::EnqueueAt(run_at, kind, data) => {
+            let job = build_job(&kind, &data, config, logger)?;
+            let entry = delay::enqueue_at(job, run_at, conn)
+                .map_err(|e| e.to_string())?;
+            println!("{}", json!(entry));
+            Ok(())
+        },
+        Command::Heartbeats => heartbeats(conn).map_err(|e| e.to_string()),
+    }
+}
+
+fn main() {
+    set_title("eloquentlog: jobs");
+    let name = get_env();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (options, command) = Options::parse(&args)
+        .and_then(|(options, args)| Ok((options, parse_args(&args)?)))
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
+
+    dotenv().ok();
+    let config = Config::load(&name, &options).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("config: {}", e);
+        }
+        process::exit(1);
+    });
+
+    let logger = get_logger(&config);
+
+    let client = Client::open(config.message_queue_url.as_str())
+        .expect("message queue url");
+    let mut conn = client.get_connection().expect("message queue");
+
+    if let Err(e) = run(command, &config, &mut conn, &logger) {
+        error!(logger, "err: {}", e);
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&to_args("queues")), Ok(Command::Queues));
+        assert_eq!(
+            parse_args(&to_args("peek mail")),
+            Ok(Command::Peek("mail".to_string(), 10))
+        );
+        assert_eq!(
+            parse_args(&to_args("peek dead 3")),
+            Ok(Command::Peek("dead".to_string(), 3))
+        );
+        assert_eq!(
+            parse_args(&to_args("delete mail 0")),
+            Ok(Command::DeletePending("mail".to_string(), 0))
+        );
+        assert_eq!(
+            parse_args(&to_args("enqueue PurgeAuditEvents")),
+            Ok(Command::Enqueue(
+                "PurgeAuditEvents".to_string(),
+                "{}".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_args(&to_args("enqueue-at 1609459200 PurgeAuditEvents")),
+            Ok(Command::EnqueueAt(
+                1_609_459_200,
+                "PurgeAuditEvents".to_string(),
+                "{}".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_args(&to_args(
+                "enqueue-at 2021-01-01T09:00:00+09:00 PurgeAuditEvents {}"
+            )),
+            Ok(Command::EnqueueAt(
+                1_609_459_200,
+                "PurgeAuditEvents".to_string(),
+                "{}".to_string()
+            ))
+        );
+
+        assert!(parse_args(&to_args("")).is_err());
+        assert!(parse_args(&to_args("peek mail 0")).is_err());
+        assert!(parse_args(&to_args("delete mail x")).is_err());
+        assert!(parse_args(&to_args("enqueue-at tomorrow PurgeAuditEvents"))
+            .is_err());
+        assert!(parse_args(&to_args("unknown")).is_err());
+    }
+
+    #[test]
+    fn test_usage() {
+        let lines: Vec<&str> = USAGE.lines().collect();
+        assert!(lines[0].starts_with("usage: "));
+        assert!(lines[0].ends_with(" [--config <path>] [--<key>=<value> ...]"));
+        assert!(lines.iter().all(|l| !l.ends_with(' ')));
+    }
+
+    #[test]
+    fn test_build_payload() {
+        let result = build_payload(
+            "PurgeAuditEvents",
+            serde_json::from_str(r#"{"retention_days": 90}"#).unwrap(),
+        );
+        assert!(matches!(result, Ok(Payload::PurgeAuditEvents(_))));
+
+        let result = build_payload("PurgeAuditEvents", json!({}));
+        assert!(result.is_err());
+        let result = build_payload("PurgeAuditEvents", json!([]));
+        assert!(result.is_err());
+        let result = build_payload("Unknown", json!({}));
+        assert!(result.is_err());
+    }
+}
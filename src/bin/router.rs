@@ -2,12 +2,13 @@
 #![feature(rustc_private)]
 
 use std::env;
+use std::process;
 
 use dotenv::dotenv;
 use proctitle::set_title;
 
 use eloquentlog_console_api::routes;
-use eloquentlog_console_api::config::Config;
+use eloquentlog_console_api::config::{Config, Options};
 use eloquentlog_console_api::logger::get_logger;
 
 fn get_env() -> String {
@@ -40,8 +41,26 @@ fn main() {
     set_title("eloquentlog: router");
     let name = get_env();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok((_, rest)) => {
+            eprintln!("unknown arguments: {}", rest.join(" "));
+            process::exit(2);
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        },
+    };
+
     dotenv().ok();
-    let config = Config::from(name.as_str()).expect("failed to get config");
+    let config = Config::load(&name, &options).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("config: {}", e);
+        }
+        process::exit(1);
+    });
 
     let _logger = get_logger(&config);
 
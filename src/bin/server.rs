@@ -1,4 +1,5 @@
 use std::env;
+use std::process;
 
 use dotenv::dotenv;
 use proctitle::set_title;
@@ -9,7 +10,10 @@ use eloquentlog_console_api::server;
 use eloquentlog_console_api::db::init_pool_holder as init_db_pool_holder;
 use eloquentlog_console_api::mq::init_pool_holder as init_mq_pool_holder;
 use eloquentlog_console_api::ss::init_pool_holder as init_ss_pool_holder;
-use eloquentlog_console_api::config::Config;
+use eloquentlog_console_api::config::{Config, Options};
+
+const USAGE: &str = "usage: eloquentlog-console-api-server [config check] \
+                     [--config <path>] [--<key>=<value> ...]";
 
 fn get_env() -> String {
     match env::var("ENV") {
@@ -23,8 +27,30 @@ fn main() {
     set_title("eloquentlog: server");
     let name = get_env();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (options, command) = Options::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if !command.is_empty() && command != ["config", "check"] {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
     dotenv().ok();
-    let config = Config::from(name.as_str()).expect("failed to get config");
+    let config = Config::load(&name, &options).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("config: {}", e);
+        }
+        process::exit(1);
+    });
+
+    // prints the config without secrets
+    if !command.is_empty() {
+        println!("{:#?}", config);
+        return;
+    }
+
     let logger = logger::get_logger(&config);
 
     // connection pool holders
@@ -1,19 +1,22 @@
 #![feature(rustc_private)]
 
-#[macro_use(error, info)]
+#[macro_use(info)]
 extern crate slog;
 
 use std::env;
+use std::process;
 
 use dotenv::dotenv;
-use fourche::queue::Queue;
 use proctitle::set_title;
-use redis::Client;
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook::flag;
 
-use eloquentlog_console_api::config::Config;
-use eloquentlog_console_api::db::establish_connection;
-use eloquentlog_console_api::job::Job;
+use eloquentlog_console_api::config::{Config, Options};
 use eloquentlog_console_api::logger::get_logger;
+use eloquentlog_console_api::worker::Worker;
+
+const USAGE: &str = "usage: eloquentlog-console-api-worker [config check] \
+                     [--config <path>] [--<key>=<value> ...]";
 
 fn get_env() -> String {
     match env::var("ENV") {
@@ -27,33 +30,43 @@ fn main() {
     set_title("eloquentlog: worker");
     let name = get_env();
 
-    dotenv().ok();
-    let config = Config::from(name.as_str()).expect("failed to get config");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (options, command) = Options::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if !command.is_empty() && command != ["config", "check"] {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
 
-    // redis
-    let client = Client::open(config.message_queue_url.as_str()).unwrap();
-    let mut mq_conn = client.get_connection().unwrap();
+    dotenv().ok();
+    let config = Config::load(&name, &options).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("config: {}", e);
+        }
+        process::exit(1);
+    });
 
-    // postgresql
-    let db_conn = establish_connection(&config);
+    // prints the config without secrets
+    if !command.is_empty() {
+        println!("{:#?}", config);
+        return;
+    }
 
     let logger = get_logger(&config);
-    let mut queue = Queue::new("default", &mut mq_conn);
-    loop {
-        match queue.dequeue::<Job<String>>() {
-            Ok(job) => {
-                info!(
-                    logger,
-                    "kind: {}, args: {:?}",
-                    job.kind,
-                    job.args.as_slice()
-                );
-                job.invoke(&db_conn, &config, &logger);
-            },
-            Err(e) => {
-                error!(logger, "err: {}", e);
-                break;
-            },
-        }
+    info!(logger, "queues: {:?}", config.worker_queues);
+
+    let worker = Worker::new(config, logger);
+
+    // SIGTERM, SIGINT and SIGQUIT stop the worker after jobs in progress.
+    // The second one terminates it immediately.
+    let shutdown = worker.shutdown_flag();
+    for sig in TERM_SIGNALS {
+        flag::register_conditional_shutdown(*sig, 1, shutdown.clone())
+            .expect("signal handler");
+        flag::register(*sig, shutdown.clone()).expect("signal handler");
     }
+
+    worker.run();
 }
@@ -0,0 +1,309 @@
+//! Export writes messages into a gzip compressed file (NDJSON or CSV).
+//!
+//! Files are put in a directory per user under the export directory. They
+//! are downloaded through a link signed as a verification token, its subject
+//! is the path of the file relative to the directory (`<user>/<name>`).
+//! Files are removed by a job after their links have expired (see `purge`).
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chrono::NaiveDateTime;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use uuid::Uuid;
+
+use crate::model::message::MessageRow;
+
+/// The lifetime of a download link (hours).
+pub const LINK_DURATION: i64 = 24;
+
+/// The format of `since` and `until` (UTC).
+pub const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+const CSV_HEADER: &str = "id,code,lang,level,format,title,content,created_at";
+
+// The first characters of values which spreadsheets evaluate as formulas
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@', '\t', '\r'];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportFormat {
+    NDJSON, // default
+    CSV,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExportFormat::NDJSON => write!(f, "ndjson"),
+            ExportFormat::CSV => write!(f, "csv"),
+        }
+    }
+}
+
+impl From<String> for ExportFormat {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "csv" => ExportFormat::CSV,
+            _ => ExportFormat::NDJSON,
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn as_vec() -> Vec<ExportFormat> {
+        vec![ExportFormat::NDJSON, ExportFormat::CSV]
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ExportFormat::NDJSON => "ndjson.gz",
+            ExportFormat::CSV => "csv.gz",
+        }
+    }
+}
+
+/// Exporter writes rows in the format, and compresses them.
+pub struct Exporter<W: Write> {
+    format: ExportFormat,
+    encoder: GzEncoder<W>,
+    count: usize,
+}
+
+impl<W: Write> Exporter<W> {
+    pub fn new(w: W, format: ExportFormat) -> io::Result<Self> {
+        let mut encoder = GzEncoder::new(w, Compression::default());
+        if format == ExportFormat::CSV {
+            writeln!(encoder, "{}", CSV_HEADER)?;
+        }
+        Ok(Self {
+            format,
+            encoder,
+            count: 0,
+        })
+    }
+
+    pub fn write(&mut self, row: &MessageRow) -> io::Result<()> {
+        match self.format {
+            ExportFormat::NDJSON => {
+                serde_json::to_writer(&mut self.encoder, row)?;
+                writeln!(self.encoder)?;
+            },
+            ExportFormat::CSV => {
+                let created_at = row.created_at.format(TIME_FORMAT).to_string();
+                let values = [
+                    row.id.to_string(),
+                    escape(row.code.as_deref().unwrap_or_default()),
+                    escape(&row.lang),
+                    row.level.to_string(),
+                    row.format.to_string(),
+                    escape(&row.title),
+                    escape(row.content.as_deref().unwrap_or_default()),
+                    created_at,
+                ];
+                writeln!(self.encoder, "{}", values.join(","))?;
+            },
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Finishes the compression, and returns the writer with the number of
+    /// rows written.
+    pub fn finish(self) -> io::Result<(W, usize)> {
+        let w = self.encoder.finish()?;
+        Ok((w, self.count))
+    }
+}
+
+// Quotes the value if it contains any of special characters in CSV. A value
+// which starts with a character of formulas in spreadsheets is prefixed with
+// `'` so that it's not evaluated as a formula (e.g. `=HYPERLINK(...)`).
+fn escape(v: &str) -> String {
+    let v = if v.starts_with(FORMULA_PREFIXES) {
+        format!("'{}", v)
+    } else {
+        v.to_string()
+    };
+    if v.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", v.replace('"', "\"\""))
+    } else {
+        v
+    }
+}
+
+/// Parses a time in TIME_FORMAT.
+pub fn parse_time(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, TIME_FORMAT).ok()
+}
+
+/// Returns a new file name for the format.
+pub fn file_name(format: &ExportFormat) -> String {
+    format!("{}.{}", Uuid::new_v4(), format.extension())
+}
+
+/// Checks whether the name can be one returned by `file_name`.
+pub fn is_valid_file_name(name: &str) -> bool {
+    !name.is_empty() &&
+        !name.starts_with('.') &&
+        !name.contains("..") &&
+        name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Returns the subject of the download link.
+pub fn subject(user_uuid: &Uuid, name: &str) -> String {
+    format!("{}/{}", user_uuid, name)
+}
+
+/// Returns the path of the file.
+pub fn file_path(directory: &str, user_uuid: &Uuid, name: &str) -> PathBuf {
+    PathBuf::from(directory)
+        .join(user_uuid.to_string())
+        .join(name)
+}
+
+/// Removes files written before `time` in the directory, and then directories
+/// of users left empty. Returns the number of removed files.
+pub fn purge(directory: &str, time: SystemTime) -> io::Result<usize> {
+    let dirs = match fs::read_dir(directory) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        v => v?,
+    };
+    let mut count = 0;
+    for dir in dirs {
+        let dir = dir?.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(&dir)? {
+            let file = file?;
+            if file.metadata()?.modified()? < time {
+                fs::remove_file(file.path())?;
+                count += 1;
+            }
+        }
+        if fs::read_dir(&dir)?.next().is_none() {
+            fs::remove_dir(&dir)?;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::io::Read;
+    use std::time::Duration;
+
+    use flate2::read::GzDecoder;
+
+    fn build_row(id: i64, title: &str) -> MessageRow {
+        MessageRow {
+            id,
+            code: None,
+            lang: "en".to_string(),
+            level: "error".to_string(),
+            format: "toml".to_string(),
+            title: title.to_string(),
+            content: Some("line 1\nline 2".to_string()),
+            created_at: parse_time("2021-01-01T00:00:00").unwrap(),
+        }
+    }
+
+    fn export(format: ExportFormat, rows: &[MessageRow]) -> (String, usize) {
+        let mut exporter = Exporter::new(vec![], format).unwrap();
+        for row in rows {
+            exporter.write(row).unwrap();
+        }
+        let (buf, count) = exporter.finish().unwrap();
+
+        let mut s = String::new();
+        GzDecoder::new(&buf[..]).read_to_string(&mut s).unwrap();
+        (s, count)
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("title"), "title");
+        assert_eq!(escape("a, b"), "\"a, b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("a\nb"), "\"a\nb\"");
+
+        // formulas
+        assert_eq!(escape("=1+2"), "'=1+2");
+        assert_eq!(escape("+1"), "'+1");
+        assert_eq!(escape("-1"), "'-1");
+        assert_eq!(escape("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(escape("\tx"), "'\tx");
+        assert_eq!(escape("=A1,B1"), "\"'=A1,B1\"");
+        assert_eq!(escape("a=b"), "a=b");
+    }
+
+    #[test]
+    fn test_export_as_ndjson() {
+        let rows = vec![build_row(1, "one"), build_row(2, "two")];
+        let (s, count) = export(ExportFormat::NDJSON, &rows);
+        assert_eq!(count, 2);
+
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let value: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(value["id"], 2);
+        assert_eq!(value["title"], "two");
+        assert_eq!(value["content"], "line 1\nline 2");
+    }
+
+    #[test]
+    fn test_export_as_csv() {
+        let rows = vec![build_row(1, "a, b")];
+        let (s, count) = export(ExportFormat::CSV, &rows);
+        assert_eq!(count, 1);
+        assert_eq!(
+            s,
+            format!(
+                "{}\n{}\n",
+                CSV_HEADER,
+                "1,,en,error,toml,\"a, b\",\"line 1\nline \
+                 2\",2021-01-01T00:00:00"
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_valid_file_name() {
+        assert!(is_valid_file_name(&file_name(&ExportFormat::CSV)));
+        assert!(!is_valid_file_name(""));
+        assert!(!is_valid_file_name("../secret"));
+        assert!(!is_valid_file_name(".hidden"));
+        assert!(!is_valid_file_name("a/b.csv.gz"));
+    }
+
+    #[test]
+    fn test_purge() {
+        let directory = env::temp_dir()
+            .join(format!("eloquentlog-export-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let user_uuid = Uuid::new_v4();
+        let path = file_path(&directory, &user_uuid, "a.csv.gz");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "").unwrap();
+
+        let time = SystemTime::now() - Duration::from_secs(60);
+        assert_eq!(purge(&directory, time).unwrap(), 0);
+        assert!(path.exists());
+
+        let time = SystemTime::now() + Duration::from_secs(60);
+        assert_eq!(purge(&directory, time).unwrap(), 1);
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+
+        fs::remove_dir(&directory).unwrap();
+        assert_eq!(purge(&directory, time).unwrap(), 0);
+    }
+}
@@ -0,0 +1,264 @@
+//! # Health
+//!
+//! Checks of dependencies for the readiness. Each check measures its latency,
+//! and a failure of a required one makes the server not ready. SMTP is
+//! checked only if `Config::health_check_smtp` is set, and it's not required
+//! (emails are sent by the worker). Only the status and the latency of each
+//! check are serialized, as the error of a dependency may contain its
+//! address or credentials.
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use diesel::sql_types::Text;
+use diesel::{RunQueryDsl, sql_query};
+use redis::Connection;
+
+use crate::config::Config;
+use crate::db::DbPoolHolder;
+use crate::mq::MqPoolHolder;
+use crate::ss::SsPoolHolder;
+
+include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+const SMTP_TIMEOUT: u64 = 3; // seconds
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Up,
+    Down,
+    Skipped,
+}
+
+/// Check is the result of a dependency. The message of a failure is only for
+/// logs.
+#[derive(Clone, Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    #[serde(skip)]
+    pub required: bool,
+    pub latency_ms: f64,
+    #[serde(skip)]
+    pub message: Option<String>,
+}
+
+impl Check {
+    fn run<F>(name: &'static str, required: bool, f: F) -> Self
+    where F: FnOnce() -> Result<(), String> {
+        let started_at = Instant::now();
+        let result = f();
+        let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+        let (status, message) = match result {
+            Ok(_) => (CheckStatus::Up, None),
+            Err(e) => (CheckStatus::Down, Some(e)),
+        };
+        Self {
+            name,
+            status,
+            required,
+            latency_ms,
+            message,
+        }
+    }
+
+    fn skipped(name: &'static str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skipped,
+            required: false,
+            latency_ms: 0.0,
+            message: None,
+        }
+    }
+
+    /// Returns true if it's up, or it's not required.
+    pub fn is_ok(&self) -> bool {
+        !self.required || self.status == CheckStatus::Up
+    }
+}
+
+#[derive(QueryableByName)]
+struct AppliedMigration {
+    #[sql_type = "Text"]
+    version: String,
+}
+
+// Returns versions which have not been applied in the known ones
+fn pending<'a>(known: &[&'a str], applied: &[String]) -> Vec<&'a str> {
+    known
+        .iter()
+        .filter(|v| !applied.iter().any(|a| a.as_str() == **v))
+        .cloned()
+        .collect()
+}
+
+fn ping(conn: &mut Connection) -> Result<(), String> {
+    redis::cmd("PING")
+        .query::<String>(conn)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn check_database(db_pool_holder: &DbPoolHolder) -> Check {
+    Check::run("database", true, || {
+        let conn = db_pool_holder
+            .get()
+            .ok_or_else(|| "connection is not available".to_string())?;
+        sql_query("SELECT 1")
+            .execute(&*conn)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+}
+
+fn check_migrations(db_pool_holder: &DbPoolHolder) -> Check {
+    Check::run("migrations", true, || {
+        let conn = db_pool_holder
+            .get()
+            .ok_or_else(|| "connection is not available".to_string())?;
+        let applied: Vec<String> =
+            sql_query("SELECT version FROM __diesel_schema_migrations")
+                .load::<AppliedMigration>(&*conn)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|m| m.version)
+                .collect();
+        let versions = pending(MIGRATIONS, &applied);
+        if versions.is_empty() {
+            return Ok(());
+        }
+        Err(format!("pending: {}", versions.join(", ")))
+    })
+}
+
+// Resolves the address in another thread, as the resolver of the system has
+// no timeout. The thread is left (until the resolver gives up) on timeout.
+fn resolve(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<SocketAddr, String> {
+    let (tx, rx) = mpsc::channel();
+    let host = host.to_string();
+    thread::spawn(move || {
+        let result = (host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())
+            .and_then(|mut addrs| {
+                addrs
+                    .next()
+                    .ok_or_else(|| "address is not found".to_string())
+            });
+        let _ = tx.send(result);
+    });
+    rx.recv_timeout(timeout)
+        .map_err(|_| "resolution timed out".to_string())?
+}
+
+// Connects to the server (without sending any command) in the timeout
+// including the resolution
+fn check_smtp(config: &Config) -> Check {
+    if !config.health_check_smtp {
+        return Check::skipped("smtp");
+    }
+    Check::run("smtp", false, || {
+        let timeout = Duration::from_secs(SMTP_TIMEOUT);
+        let started_at = Instant::now();
+        let address = resolve(
+            &config.mailer_smtp_host,
+            config.mailer_smtp_port,
+            timeout,
+        )?;
+        let remaining = timeout
+            .checked_sub(started_at.elapsed())
+            .filter(|d| *d > Duration::from_millis(0))
+            .ok_or_else(|| "connection timed out".to_string())?;
+        TcpStream::connect_timeout(&address, remaining)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Runs all the checks for the readiness.
+pub fn check_all(
+    db_pool_holder: &DbPoolHolder,
+    mq_pool_holder: &MqPoolHolder,
+    ss_pool_holder: &SsPoolHolder,
+    config: &Config,
+) -> Vec<Check> {
+    vec![
+        check_database(db_pool_holder),
+        check_migrations(db_pool_holder),
+        Check::run("message_queue", true, || {
+            let mut conn = mq_pool_holder
+                .get()
+                .ok_or_else(|| "connection is not available".to_string())?;
+            ping(&mut *conn)
+        }),
+        Check::run("session_store", true, || {
+            let mut conn = ss_pool_holder
+                .get()
+                .ok_or_else(|| "connection is not available".to_string())?;
+            ping(&mut *conn)
+        }),
+        check_smtp(config),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pending() {
+        let known = &["00000000000000", "20190303205455", "20190326111346"];
+        let applied =
+            vec!["00000000000000".to_string(), "20190303205455".to_string()];
+        assert_eq!(pending(known, &applied), vec!["20190326111346"]);
+
+        let applied: Vec<String> =
+            known.iter().map(|v| v.to_string()).collect();
+        assert!(pending(known, &applied).is_empty());
+    }
+
+    #[test]
+    fn test_migrations() {
+        assert!(MIGRATIONS.contains(&"00000000000000"));
+        assert!(MIGRATIONS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_check_is_ok() {
+        let c = Check::run("a", true, || Err("down".to_string()));
+        assert_eq!(c.status, CheckStatus::Down);
+        assert_eq!(c.message, Some("down".to_string()));
+        assert!(!c.is_ok());
+
+        let c = Check::run("a", false, || Err("down".to_string()));
+        assert!(c.is_ok());
+
+        let c = Check::run("a", true, || Ok(()));
+        assert_eq!(c.status, CheckStatus::Up);
+        assert!(c.is_ok());
+        assert!(Check::skipped("smtp").is_ok());
+    }
+
+    #[test]
+    fn test_check_serialization() {
+        let c = Check::run("a", true, || Err("secret@host".to_string()));
+        let v = serde_json::to_value(&c).unwrap();
+        assert_eq!(v["status"], "down");
+        assert!(v["latency_ms"].as_f64().is_some());
+        assert!(v.get("message").is_none());
+        assert!(v.get("required").is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let addr = resolve("127.0.0.1", 25, Duration::from_secs(1)).unwrap();
+        assert_eq!(addr.port(), 25);
+    }
+}
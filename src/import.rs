@@ -0,0 +1,427 @@
+//! Import reads messages from NDJSON or TOML, to backfill a stream.
+//!
+//! A record is a message with its `created_at` (UTC). NDJSON has a record per
+//! line, and TOML has records as an array of tables named `messages`:
+//!
+//! ```toml
+//! [[messages]]
+//! level = "error"
+//! title = "connection refused"
+//! created_at = 2021-01-01T00:00:00Z
+//! ```
+use std::fmt;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::{Connection, PgConnection};
+use diesel::result::Error;
+use rocket_contrib::json::Json;
+
+use crate::export;
+use crate::logger::Logger;
+use crate::model::issue::Issue;
+use crate::model::message::{AgentType, Message, NewMessage};
+use crate::redaction::Redactor;
+use crate::request::message::Message as RequestData;
+use crate::validation::message::{ValidationError, Validator};
+
+/// The number of messages inserted at once.
+pub const BATCH_SIZE: usize = 500;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportFormat {
+    NDJSON, // default
+    TOML,
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImportFormat::NDJSON => write!(f, "ndjson"),
+            ImportFormat::TOML => write!(f, "toml"),
+        }
+    }
+}
+
+impl From<String> for ImportFormat {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "toml" => ImportFormat::TOML,
+            _ => ImportFormat::NDJSON,
+        }
+    }
+}
+
+impl ImportFormat {
+    pub fn as_vec() -> Vec<ImportFormat> {
+        vec![ImportFormat::NDJSON, ImportFormat::TOML]
+    }
+}
+
+/// Record is a message to import.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Record {
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub level: Option<String>,
+    pub format: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// A record which could not be imported. The position starts from 1, it's
+/// the line number in NDJSON or the index of the table in TOML.
+#[derive(Debug, Serialize)]
+pub struct ImportError {
+    pub record: usize,
+    pub errors: Vec<ValidationError>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub errors: Vec<ImportError>,
+    #[serde(skip)]
+    pub oldest: Option<NaiveDateTime>, // created_at of imported messages
+}
+
+impl ImportReport {
+    fn fail(&mut self, record: usize, errors: Vec<ValidationError>) {
+        self.failed += 1;
+        self.errors.push(ImportError { record, errors });
+    }
+}
+
+/// Parses the body into records with their positions. A record which can't
+/// be parsed has its error instead. Blank lines in NDJSON are skipped.
+#[allow(clippy::type_complexity)]
+pub fn parse(
+    format: &ImportFormat,
+    body: &str,
+) -> Result<Vec<(usize, Result<Record, String>)>, String> {
+    match format {
+        ImportFormat::NDJSON => {
+            Ok(body
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| {
+                    let record = serde_json::from_str::<Record>(line)
+                        .map_err(|e| e.to_string());
+                    (i + 1, record)
+                })
+                .collect())
+        },
+        ImportFormat::TOML => {
+            let value =
+                body.parse::<toml::Value>().map_err(|e| e.to_string())?;
+            let tables = match value.get("messages") {
+                None => return Ok(vec![]),
+                Some(toml::Value::Array(a)) => a.clone(),
+                Some(_) => {
+                    return Err(
+                        "messages must be an array of tables".to_string()
+                    )
+                },
+            };
+            Ok(tables
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut t)| {
+                    // a datetime value is read as a string
+                    if let toml::Value::Table(ref mut table) = t {
+                        if let Some(toml::Value::Datetime(d)) =
+                            table.get("created_at").cloned()
+                        {
+                            table.insert(
+                                "created_at".to_string(),
+                                toml::Value::String(d.to_string()),
+                            );
+                        }
+                    }
+                    (i + 1, t.try_into::<Record>().map_err(|e| e.to_string()))
+                })
+                .collect())
+        },
+    }
+}
+
+/// Parses a time in RFC 3339 (converted into UTC), or in TIME_FORMAT of the
+/// export as UTC.
+pub fn parse_created_at(s: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.naive_utc())
+        .ok()
+        .or_else(|| export::parse_time(s))
+}
+
+// Validates the record as a message by the person, with its created_at
+fn build(
+    record: Result<Record, String>,
+    agent_id: i64,
+    stream_id: i64,
+    now: NaiveDateTime,
+    logger: &Logger,
+) -> Result<NewMessage, Vec<ValidationError>> {
+    let record = record.map_err(|e| {
+        vec![ValidationError {
+            field: "record".to_string(),
+            messages: vec![e],
+        }]
+    })?;
+
+    let data = Json(RequestData {
+        agent_id,
+        agent_type: Some(AgentType::Person.to_string()),
+        stream_id,
+        code: record.code,
+        lang: record.lang,
+        level: record.level,
+        format: record.format,
+        title: record.title,
+        content: record.content,
+    });
+    let mut errors = Validator::new(&data, logger)
+        .validate()
+        .err()
+        .unwrap_or_default();
+
+    let created_at =
+        record.created_at.as_ref().and_then(|s| parse_created_at(s));
+    match created_at {
+        None => {
+            errors.push(ValidationError {
+                field: "created_at".to_string(),
+                messages: vec!["Must be a time in RFC 3339".to_string()],
+            })
+        },
+        Some(t) if t > now => {
+            errors.push(ValidationError {
+                field: "created_at".to_string(),
+                messages: vec!["Must not be in the future".to_string()],
+            })
+        },
+        _ => (),
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut m = NewMessage::from(data.0);
+    m.created_at = created_at;
+    Ok(m)
+}
+
+// Inserts the batch by a multi-row insert, and records them on issues in the
+// same transaction. So either all of them or none are saved.
+fn flush(
+    positions: &mut Vec<usize>,
+    messages: &mut Vec<NewMessage>,
+    report: &mut ImportReport,
+    conn: &PgConnection,
+    logger: &Logger,
+) {
+    let mut failure = "failed to insert";
+    let result = conn.transaction::<_, Error, _>(|| {
+        let n = Message::insert_all(messages, conn, logger)
+            .map_err(|_| Error::RollbackTransaction)?;
+        for m in messages.iter() {
+            if Issue::record(m, conn, logger).is_none() {
+                failure = "failed to record";
+                return Err(Error::RollbackTransaction);
+            }
+        }
+        Ok(n)
+    });
+    match result {
+        Ok(n) => {
+            report.imported += n;
+            let oldest = messages.iter().filter_map(|m| m.created_at).min();
+            report.oldest = report.oldest.into_iter().chain(oldest).min();
+            info!(logger, "imported: {}/{}", report.imported, report.total);
+        },
+        Err(_) => {
+            for record in positions.iter() {
+                report.fail(
+                    *record,
+                    vec![ValidationError {
+                        field: "record".to_string(),
+                        messages: vec![failure.to_string()],
+                    }],
+                );
+            }
+        },
+    }
+    positions.clear();
+    messages.clear();
+}
+
+/// Validates the records, and inserts valid ones into the stream by batch.
+/// They are redacted by the rules on the namespace.
+pub fn import(
+    records: Vec<(usize, Result<Record, String>)>,
+    agent_id: i64,
+    stream_id: i64,
+    conn: &PgConnection,
+    logger: &Logger,
+) -> ImportReport {
+    let now = Utc::now().naive_utc();
+    let mut report = ImportReport {
+        total: records.len(),
+        ..Default::default()
+    };
+
+    let redactor = Redactor::for_stream(stream_id, conn, logger);
+
+    let mut positions = Vec::with_capacity(BATCH_SIZE);
+    let mut messages = Vec::with_capacity(BATCH_SIZE);
+    for (position, record) in records {
+        match build(record, agent_id, stream_id, now, logger) {
+            Err(errors) => report.fail(position, errors),
+            Ok(mut m) => {
+                redactor.redact(&mut m);
+                positions.push(position);
+                messages.push(m);
+            },
+        }
+        if messages.len() >= BATCH_SIZE {
+            flush(&mut positions, &mut messages, &mut report, conn, logger);
+        }
+    }
+    if !messages.is_empty() {
+        flush(&mut positions, &mut messages, &mut report, conn, logger);
+    }
+
+    report.errors.sort_by_key(|e| e.record);
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+
+    use crate::model::message::{LogLevel, messages};
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::{Stream, streams};
+    use crate::model::user::{User, users};
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_parse_ndjson() {
+        let body = r#"{"title": "a", "created_at": "2021-01-01T00:00:00Z"}
+
+{"title": "b"
+{"title": "c", "level": "error"}"#;
+        let records = parse(&ImportFormat::NDJSON, body).unwrap();
+        assert_eq!(records.len(), 3);
+
+        assert_eq!(records[0].0, 1);
+        let record = records[0].1.as_ref().unwrap();
+        assert_eq!(record.title.as_deref(), Some("a"));
+        assert_eq!(record.created_at.as_deref(), Some("2021-01-01T00:00:00Z"));
+
+        assert_eq!(records[1].0, 3);
+        assert!(records[1].1.is_err());
+
+        assert_eq!(records[2].0, 4);
+        let record = records[2].1.as_ref().unwrap();
+        assert_eq!(record.level.as_deref(), Some("error"));
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let body = r#"
+[[messages]]
+title = "a"
+created_at = 2021-01-01T09:00:00+09:00
+
+[[messages]]
+title = 1
+"#;
+        let records = parse(&ImportFormat::TOML, body).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let record = records[0].1.as_ref().unwrap();
+        assert_eq!(record.title.as_deref(), Some("a"));
+        assert_eq!(
+            record.created_at.as_ref().and_then(|s| parse_created_at(s)),
+            export::parse_time("2021-01-01T00:00:00")
+        );
+
+        assert_eq!(records[1].0, 2);
+        assert!(records[1].1.is_err());
+
+        assert!(parse(&ImportFormat::TOML, "messages = 1").is_err());
+        assert!(parse(&ImportFormat::TOML, "[[messages]").is_err());
+    }
+
+    #[test]
+    fn test_parse_created_at() {
+        let expected = export::parse_time("2021-01-01T00:00:00");
+        assert_eq!(parse_created_at("2021-01-01T00:00:00Z"), expected);
+        assert_eq!(parse_created_at("2021-01-01T00:00:00"), expected);
+        assert_eq!(parse_created_at("2021-01-01"), None);
+    }
+
+    #[test]
+    fn test_import() {
+        run(|conn, _, logger| {
+            let user = diesel::insert_into(users::table)
+                .values(USERS.get("oswald").unwrap())
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let _ = diesel::insert_into(namespaces::table)
+                .values(NAMESPACES.get("piano").unwrap())
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let stream = diesel::insert_into(streams::table)
+                .values(STREAMS.get("oswald's stream").unwrap())
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let body = r#"{"title": "a", "level": "error", "created_at": "2021-01-01T00:00:00Z"}
+{"title": "b", "created_at": "2999-01-01T00:00:00Z"}
+{"title": "c"}
+{"level": "warning", "created_at": "2021-01-01T00:00:00Z"}"#;
+            let records = parse(&ImportFormat::NDJSON, body).unwrap();
+            let report = import(records, user.id, stream.id, conn, logger);
+
+            assert_eq!(report.total, 4);
+            assert_eq!(report.imported, 1);
+            assert_eq!(report.failed, 3);
+
+            let fields: Vec<(usize, &str)> = report
+                .errors
+                .iter()
+                .flat_map(|e| {
+                    e.errors.iter().map(move |v| (e.record, v.field.as_str()))
+                })
+                .collect();
+            assert_eq!(
+                vec![(2, "created_at"), (3, "created_at"), (4, "title")],
+                fields
+            );
+
+            let m = messages::table
+                .filter(messages::stream_id.eq(stream.id))
+                .first::<Message>(conn)
+                .unwrap();
+            assert_eq!(m.title, "a");
+            assert_eq!(m.level, LogLevel::Error);
+            assert_eq!(
+                Some(m.created_at),
+                export::parse_time("2021-01-01T00:00:00")
+            );
+        })
+    }
+}
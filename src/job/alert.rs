@@ -0,0 +1,115 @@
+//! # Alert
+//!
+//! A job evaluating alert rules of a stream and notifying their alerts.
+use chrono::Utc;
+use diesel::PgConnection;
+use slog::Logger;
+
+use crate::config::Config;
+use crate::job::{EvaluateAlertRules, JobError};
+use crate::model::alert::Alert;
+use crate::model::alert_rule::{AlertChannel, AlertRule};
+use crate::mailer::alert::AlertMailer;
+use crate::webhook;
+
+// Fires or resolves alerts of the rules, and then notifies firing ones which
+// have not been notified yet. The notification is retried by the next run if
+// it fails, so the job can be retried as a whole.
+pub fn evaluate_alert_rules(
+    p: &EvaluateAlertRules,
+    db_conn: &PgConnection,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    let now = Utc::now().naive_utc();
+    let unavailable = || JobError::Transient("unavailable".to_string());
+
+    let rules = AlertRule::find_all(p.stream_id, db_conn, logger)
+        .ok_or_else(unavailable)?;
+    for rule in rules {
+        db_conn
+            .build_transaction()
+            .serializable()
+            .read_write()
+            .run::<_, JobError, _>(|| {
+                let fires = rule
+                    .evaluate(now, db_conn, logger)
+                    .ok_or_else(unavailable)?;
+                if fires {
+                    let (alert, opened) =
+                        Alert::fire(&rule, now, db_conn, logger)
+                            .map_err(|e| JobError::Transient(e.to_string()))?;
+                    info!(logger, "fired: {} opened: {}", alert, opened);
+                } else if let Some(alert) =
+                    Alert::resolve(&rule, now, db_conn, logger)
+                        .map_err(|e| JobError::Transient(e.to_string()))?
+                {
+                    info!(logger, "resolved: {}", alert);
+                }
+                Ok(())
+            })?;
+    }
+
+    let alerts = Alert::find_all_to_notify(p.stream_id, db_conn, logger)
+        .ok_or_else(unavailable)?;
+    let mut result = Ok(());
+    for (alert, rule) in alerts {
+        if rule.is_muted(now) {
+            info!(logger, "muted: {}", rule);
+            continue;
+        }
+        let notified = match rule.channel {
+            AlertChannel::Email => {
+                let mut mailer = AlertMailer::new(config, logger);
+                mailer
+                    .to((&rule.target, ""))
+                    .send_alert_email(&rule, &alert)
+                    .map_err(JobError::from)
+            },
+            AlertChannel::Webhook => {
+                let created_at =
+                    alert.created_at.format("%Y-%m-%dT%H:%M:%S").to_string();
+                let body = json!({
+                    "event": "alert.fired",
+                    "rule": {
+                        "uuid": rule.uuid.to_string(),
+                        "name": rule.name,
+                        "condition": rule.condition.to_string(),
+                        "level": rule.level.as_ref().map(|l| l.to_string()),
+                        "code": rule.code,
+                        "threshold": rule.threshold,
+                        "window_seconds": rule.window_seconds,
+                    },
+                    "alert": {
+                        "uuid": alert.uuid.to_string(),
+                        "occurrences": alert.occurrences,
+                        "created_at": created_at,
+                    },
+                });
+                webhook::post(
+                    &rule.target,
+                    &body,
+                    config.webhook_allow_private_hosts,
+                )
+                .map(|_| ())
+                .map_err(JobError::from)
+            },
+        };
+        match notified {
+            Ok(_) => {
+                alert
+                    .mark_as_notified(now, db_conn, logger)
+                    .map_err(|e| JobError::Transient(e.to_string()))?;
+            },
+            Err(e) => {
+                error!(logger, "notification failed: {} {}", alert, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            },
+        }
+    }
+    result
+}
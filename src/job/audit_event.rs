@@ -0,0 +1,30 @@
+//! # Audit Event
+//!
+//! A job deleting audit events older than the retention period.
+use chrono::{Duration, Utc};
+use diesel::PgConnection;
+use slog::Logger;
+
+use crate::job::{JobError, PurgeAuditEvents};
+use crate::model::audit_event::AuditEvent;
+
+pub fn purge_audit_events(
+    p: &PurgeAuditEvents,
+    db_conn: &PgConnection,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    if p.retention_days < 1 {
+        return Err(JobError::Permanent(format!(
+            "invalid retention_days: {}",
+            p.retention_days
+        )));
+    }
+
+    let time = (Utc::now() - Duration::days(p.retention_days)).naive_utc();
+    let count = AuditEvent::delete_all_before(time, db_conn, logger)
+        .map_err(|e| JobError::Transient(e.to_string()))?;
+    info!(logger, "purged: {}", count);
+    Ok(())
+}
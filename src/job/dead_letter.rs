@@ -0,0 +1,125 @@
+//! # Dead Letter
+//!
+//! Jobs which can't succeed (or have exhausted their attempts) are kept in a
+//! list so that they can be inspected and replayed (or deleted) later.
+use redis::{Commands, Connection, RedisResult};
+
+use crate::job::{FailedJob, UndecodableJob};
+
+pub const DEAD_LETTER_KEY: &str = "job:dead";
+
+/// Entry is either a job which has failed or a value which can't be decoded.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Entry {
+    Failed(FailedJob),
+    Undecodable(UndecodableJob),
+}
+
+impl Entry {
+    pub fn id(&self) -> &str {
+        match self {
+            Entry::Failed(e) => &e.id,
+            Entry::Undecodable(e) => &e.id,
+        }
+    }
+}
+
+pub fn push(entry: &FailedJob, conn: &mut Connection) -> RedisResult<()> {
+    let value = serde_json::to_string(entry).unwrap();
+    conn.lpush(DEAD_LETTER_KEY, value)
+}
+
+pub fn push_undecodable(
+    entry: &UndecodableJob,
+    conn: &mut Connection,
+) -> RedisResult<()> {
+    let value = serde_json::to_string(entry).unwrap();
+    conn.lpush(DEAD_LETTER_KEY, value)
+}
+
+pub fn len(conn: &mut Connection) -> RedisResult<usize> {
+    conn.llen(DEAD_LETTER_KEY)
+}
+
+/// Returns entries of failed jobs in the range (the newest one comes first).
+/// Other entries are skipped.
+pub fn lrange(
+    start: isize,
+    stop: isize,
+    conn: &mut Connection,
+) -> RedisResult<Vec<FailedJob>> {
+    let values: Vec<String> = conn.lrange(DEAD_LETTER_KEY, start, stop)?;
+    Ok(values
+        .iter()
+        .filter_map(|v| serde_json::from_str(v).ok())
+        .collect())
+}
+
+/// Returns all kinds of entries in the range (the newest one comes first).
+pub fn entries(
+    start: isize,
+    stop: isize,
+    conn: &mut Connection,
+) -> RedisResult<Vec<Entry>> {
+    let values: Vec<String> = conn.lrange(DEAD_LETTER_KEY, start, stop)?;
+    Ok(values
+        .iter()
+        .filter_map(|v| serde_json::from_str(v).ok())
+        .collect())
+}
+
+// Returns the entry and its raw value in the list
+fn find(
+    id: &str,
+    conn: &mut Connection,
+) -> RedisResult<Option<(Entry, String)>> {
+    let values: Vec<String> = conn.lrange(DEAD_LETTER_KEY, 0, -1)?;
+    Ok(values.into_iter().find_map(|v| {
+        serde_json::from_str::<Entry>(&v)
+            .ok()
+            .filter(|entry| entry.id() == id)
+            .map(|entry| (entry, v))
+    }))
+}
+
+/// Removes the entry from the list, and enqueues its job again (with a fresh
+/// attempts count) into the original queue. A value which couldn't be decoded
+/// is enqueued as it is (a newer worker may decode it), but the one taken from
+/// the retry set or delayed ones can't be replayed. Returns false if it's not
+/// found (or can't be replayed).
+pub fn replay(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    let (entry, value) = match find(id, conn)? {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+    // the queue name and the value to enqueue (as fourche does)
+    let (name, payload) = match entry {
+        Entry::Failed(entry) => {
+            let mut job = entry.job;
+            job.attempts = 0;
+            (entry.queue, serde_json::to_string(&job).unwrap())
+        },
+        Entry::Undecodable(UndecodableJob {
+            queue: Some(queue),
+            payload,
+            ..
+        }) => (queue, payload),
+        Entry::Undecodable(_) => return Ok(false),
+    };
+    let removed: usize = conn.lrem(DEAD_LETTER_KEY, 1, value)?;
+    if removed != 1 {
+        return Ok(false);
+    }
+    conn.lpush(name, payload).map(|_: usize| true)
+}
+
+/// Removes the entry from the list. Returns false if it's not found.
+pub fn del(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    let (_, value) = match find(id, conn)? {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+    let removed: usize = conn.lrem(DEAD_LETTER_KEY, 1, value)?;
+    Ok(removed == 1)
+}
@@ -0,0 +1,84 @@
+//! # Delay
+//!
+//! Jobs to run at a given time wait in a sorted set (scored by the timestamp)
+//! until they are enqueued into their queue.
+use fourche::queue::Queue;
+use redis::{Commands, Connection, RedisResult};
+
+use crate::job::Job;
+use crate::util::generate_random_hash;
+
+pub const DELAYED_KEY: &str = "job:delayed";
+
+const DELAYED_JOB_ID_LENGTH: i32 = 16;
+const DELAYED_JOB_ID_SOURCE: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DelayedJob {
+    pub id: String,
+    pub queue: String,
+    pub job: Job,
+    pub run_at: i64,
+}
+
+/// Puts the job to be enqueued at `run_at` into its queue, and returns the
+/// entry.
+pub fn enqueue_at(
+    job: Job,
+    run_at: i64,
+    conn: &mut Connection,
+) -> RedisResult<DelayedJob> {
+    let entry = DelayedJob {
+        id: generate_random_hash(DELAYED_JOB_ID_SOURCE, DELAYED_JOB_ID_LENGTH),
+        queue: job.queue().to_string(),
+        job,
+        run_at,
+    };
+    let value = serde_json::to_string(&entry).unwrap();
+    let _: usize = conn.zadd(DELAYED_KEY, value, run_at)?;
+    Ok(entry)
+}
+
+pub fn len(conn: &mut Connection) -> RedisResult<usize> {
+    conn.zcard(DELAYED_KEY)
+}
+
+/// Returns entries in the range (the earliest one comes first).
+pub fn range(
+    start: isize,
+    stop: isize,
+    conn: &mut Connection,
+) -> RedisResult<Vec<DelayedJob>> {
+    let values: Vec<String> = conn.zrange(DELAYED_KEY, start, stop)?;
+    Ok(values
+        .iter()
+        .filter_map(|v| serde_json::from_str(v).ok())
+        .collect())
+}
+
+/// Enqueues the job of the entry into its queue before `run_at`. Returns
+/// false if it's not found.
+pub fn run_now(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    let entry =
+        match super::take(DELAYED_KEY, id, conn, |e: &DelayedJob| &e.id)? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+    let mut queue = Queue::new(&entry.queue, conn);
+    queue.enqueue::<Job>(entry.job)?;
+    Ok(true)
+}
+
+/// Removes the entry (the job won't run). Returns false if it's not found.
+pub fn del(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    super::take(DELAYED_KEY, id, conn, |e: &DelayedJob| &e.id)
+        .map(|v| v.is_some())
+}
+
+/// Enqueues jobs which are due at `now` into their queue, and returns the
+/// number of them.
+pub fn enqueue_due(now: i64, conn: &mut Connection) -> RedisResult<usize> {
+    super::enqueue_due(DELAYED_KEY, now, conn, |entry: DelayedJob| {
+        (entry.queue, entry.job)
+    })
+}
@@ -0,0 +1,123 @@
+//! # Export
+//!
+//! A job exporting messages into a file to download.
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::{self, SystemTime};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::PgConnection;
+use slog::Logger;
+
+use crate::config::Config;
+use crate::export::{self, ExportFormat, Exporter};
+use crate::job::{ExportMessages, JobError, PurgeMessageExports, not_found};
+use crate::model::message::{LogLevel, Message, MessageFilter};
+use crate::model::token::{Claims, TokenData, VerificationClaims};
+use crate::model::user::User;
+use crate::mailer::user::UserMailer;
+
+// The number of rows fetched through the cursor at once
+const EXPORT_BATCH_SIZE: i64 = 1_000;
+
+// Writes messages through a cursor into a file, and then emails a signed link
+// to download it. The file is removed if the export fails (it will be written
+// again by a retry).
+pub fn export_messages(
+    p: &ExportMessages,
+    db_conn: &PgConnection,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    let user =
+        User::find_by_id(p.user_id, db_conn, logger).ok_or_else(not_found)?;
+
+    let format = ExportFormat::from(p.format.clone());
+    let filter = MessageFilter {
+        stream_id: p.stream_id,
+        level: p.level.clone().map(LogLevel::from),
+        code: p.code.clone(),
+        since: NaiveDateTime::from_timestamp(p.since, 0),
+        until: NaiveDateTime::from_timestamp(p.until, 0),
+    };
+
+    let name = export::file_name(&format);
+    let path = export::file_path(&config.export_directory, &user.uuid, &name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let result = db_conn
+        .build_transaction()
+        .read_only()
+        .run::<_, JobError, _>(|| {
+            let file = BufWriter::new(File::create(&path)?);
+            let mut exporter = Exporter::new(file, format.clone())?;
+
+            Message::declare_cursor(&filter, db_conn, logger)?;
+            loop {
+                let rows =
+                    Message::fetch_cursor(EXPORT_BATCH_SIZE, db_conn, logger)?;
+                if rows.is_empty() {
+                    break;
+                }
+                for row in &rows {
+                    exporter.write(row)?;
+                }
+            }
+            Message::close_cursor(db_conn, logger)?;
+
+            let (mut file, count) = exporter.finish()?;
+            file.flush()?;
+            Ok(count)
+        });
+    let count = match result {
+        Err(e) => {
+            if let Err(err) = fs::remove_file(&path) {
+                error!(logger, "err: {}", err);
+            }
+            return Err(e);
+        },
+        Ok(count) => count,
+    };
+    info!(logger, "exported: {} ({} rows)", path.display(), count);
+
+    let now = Utc::now();
+    let data = TokenData {
+        value: export::subject(&user.uuid, &name),
+        granted_at: now.timestamp(),
+        expires_at: (now + Duration::hours(export::LINK_DURATION)).timestamp(),
+    };
+    let token = VerificationClaims::encode(
+        data,
+        &config.verification_token_issuer,
+        &config.verification_token_key_id,
+        &config.verification_token_secret,
+    );
+
+    let email = user.email.as_ref();
+    let user_name = user.name.unwrap_or_default();
+    let mut mailer = UserMailer::new(config, logger);
+    mailer
+        .to((email, &user_name))
+        .send_message_export_email(&name, &token, count)
+        .map_err(JobError::from)
+}
+
+// Removes exported files of which download links have expired.
+pub fn purge_message_exports(
+    p: &PurgeMessageExports,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    let duration =
+        time::Duration::from_secs(export::LINK_DURATION as u64 * 3600);
+    let time = SystemTime::now() - duration;
+    let count = export::purge(&config.export_directory, time)?;
+    info!(logger, "purged: {}", count);
+    Ok(())
+}
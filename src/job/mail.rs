@@ -0,0 +1,115 @@
+//! # Mail
+//!
+//! Jobs sending emails to users about their accounts.
+use diesel::PgConnection;
+use slog::Logger;
+
+use crate::config::Config;
+use crate::job::{
+    JobError, PasswordResetEmail, UserActivationEmail, UserDeregistrationEmail,
+    UserEmailVerificationEmail, not_found,
+};
+use crate::model::user::User;
+use crate::model::user_email::UserEmail;
+use crate::mailer::user::UserMailer;
+
+pub fn send_user_activation_email(
+    p: &UserActivationEmail,
+    db_conn: &PgConnection,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    db_conn
+        .build_transaction()
+        .read_only()
+        .run::<_, JobError, _>(|| {
+            let user_email =
+                UserEmail::find_by_id(p.user_email_id, db_conn, &logger)
+                    .ok_or_else(not_found)?;
+            let email = user_email.email.as_ref().ok_or_else(not_found)?;
+            info!(logger, "user_email.email: {}", email);
+
+            let user =
+                User::find_by_primary_email_in_pending(email, db_conn, logger)
+                    .ok_or_else(not_found)?;
+
+            let name = user.name.unwrap_or_default();
+            let mut mailer = UserMailer::new(config, logger);
+            mailer
+                .to((email, &name))
+                .send_user_activation_email(&p.session_id, &p.token)
+                .map_err(JobError::from)
+        })
+}
+
+pub fn send_password_reset_email(
+    p: &PasswordResetEmail,
+    db_conn: &PgConnection,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    db_conn
+        .build_transaction()
+        .read_only()
+        .run::<_, JobError, _>(|| {
+            let user = User::find_by_id(p.user_id, db_conn, &logger)
+                .ok_or_else(not_found)?;
+            let email = user.email.as_ref();
+            info!(logger, "user.email: {}", email);
+
+            let name = user.name.unwrap_or_default();
+            let mut mailer = UserMailer::new(config, logger);
+            mailer
+                .to((email, &name))
+                .send_password_reset_email(&p.session_id, &p.token)
+                .map_err(JobError::from)
+        })
+}
+
+pub fn send_user_email_verification_email(
+    p: &UserEmailVerificationEmail,
+    db_conn: &PgConnection,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    db_conn
+        .build_transaction()
+        .read_only()
+        .run::<_, JobError, _>(|| {
+            let user_email =
+                UserEmail::find_by_id(p.user_email_id, db_conn, &logger)
+                    .ok_or_else(not_found)?;
+            let email = user_email.email.as_ref().ok_or_else(not_found)?;
+            info!(logger, "user_email.email: {}", email);
+
+            let user = User::find_by_id(user_email.user_id, db_conn, logger)
+                .ok_or_else(not_found)?;
+
+            let name = user.name.unwrap_or_default();
+            let mut mailer = UserMailer::new(config, logger);
+            mailer
+                .to((email, &name))
+                .send_user_email_verification_email(&p.session_id, &p.token)
+                .map_err(JobError::from)
+        })
+}
+
+pub fn send_user_deregistration_email(
+    p: &UserDeregistrationEmail,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    let mut mailer = UserMailer::new(config, logger);
+    mailer
+        .to((&p.email, &p.name))
+        .send_user_deregistration_email()
+        .map_err(JobError::from)
+}
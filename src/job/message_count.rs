@@ -0,0 +1,50 @@
+//! # Message Count
+//!
+//! A job counting messages per hour into the rollup table.
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::PgConnection;
+use diesel::result::Error;
+use slog::Logger;
+
+use crate::job::{JobError, RollupMessageCounts};
+use crate::model::message_count::{MessageCount, truncate_to_hour};
+
+// The number of hours re-counted by a rollup (for late messages)
+const ROLLUP_HOURS: i64 = 2;
+
+pub fn rollup_message_counts(
+    p: &RollupMessageCounts,
+    db_conn: &PgConnection,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(logger, "payload: {:?}", p);
+
+    let hours = p.hours.unwrap_or(ROLLUP_HOURS);
+    if hours < 0 {
+        return Err(JobError::Permanent(format!("invalid hours: {}", hours)));
+    }
+    let unavailable = |e: Error| JobError::Transient(e.to_string());
+
+    let until = truncate_to_hour(Utc::now().naive_utc());
+    let mut since = until - Duration::hours(hours);
+    if let Some(t) = p.since {
+        since =
+            since.min(truncate_to_hour(NaiveDateTime::from_timestamp(t, 0)));
+    }
+    match MessageCount::watermark(db_conn, logger).map_err(unavailable)? {
+        Some(t) => since = since.min(t),
+        None => {
+            match MessageCount::earliest(db_conn, logger)
+                .map_err(unavailable)?
+            {
+                Some(t) => since = since.min(truncate_to_hour(t)),
+                None => return Ok(()), // no message
+            }
+        },
+    }
+
+    let count = MessageCount::rollup(since, until, db_conn, logger)
+        .map_err(unavailable)?;
+    info!(logger, "rolled up: {} ({} - {})", count, since, until);
+    Ok(())
+}
@@ -0,0 +1,631 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+use chrono::Utc;
+use diesel::PgConnection;
+use diesel::result::Error;
+use fourche::queue::Queue;
+use redis::{Commands, Connection, RedisResult};
+use serde::de::DeserializeOwned;
+use slog::Logger;
+
+use crate::config::Config;
+use crate::mailer::MailerError;
+use crate::util::generate_random_hash;
+use crate::webhook::WebhookError;
+
+pub mod alert;
+pub mod audit_event;
+pub mod dead_letter;
+pub mod delay;
+pub mod export;
+pub mod mail;
+pub mod message_count;
+pub mod payload;
+pub mod pending;
+pub mod postpone;
+pub mod retry;
+pub mod scheduler;
+pub mod webhook;
+
+pub use self::payload::{
+    EvaluateAlertRules, ExportMessages, JobKind, PasswordResetEmail, Payload,
+    PurgeAuditEvents, PurgeMessageExports, RollupMessageCounts,
+    UserActivationEmail, UserDeregistrationEmail, UserEmailVerificationEmail,
+    WebhookEvent,
+};
+
+/// The current version of the job format (1 is the one with positional args).
+pub const VERSION: u32 = 2;
+
+pub const MAIL_QUEUE: &str = "mail";
+pub const MAINTENANCE_QUEUE: &str = "maintenance";
+pub const ALERT_QUEUE: &str = "alert";
+pub const WEBHOOK_QUEUE: &str = "webhook";
+pub const EXPORT_QUEUE: &str = "export";
+
+/// The number of attempts before a job is moved to the dead letter list.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+const FAILED_JOB_ID_LENGTH: i32 = 16;
+const FAILED_JOB_ID_SOURCE: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// JobError tells the worker whether the job is worth retrying.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobError {
+    /// e.g. SMTP 4xx reply, connection failure, database is unavailable
+    Transient(String),
+    /// e.g. SMTP 5xx reply, invalid args, record not found
+    Permanent(String),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::Transient(ref e) => write!(f, "transient: {}", e),
+            JobError::Permanent(ref e) => write!(f, "permanent: {}", e),
+        }
+    }
+}
+
+impl From<MailerError> for JobError {
+    fn from(e: MailerError) -> Self {
+        match e {
+            MailerError::Permanent(_) => JobError::Permanent(e.to_string()),
+            _ => JobError::Transient(e.to_string()),
+        }
+    }
+}
+
+impl From<WebhookError> for JobError {
+    fn from(e: WebhookError) -> Self {
+        if e.is_transient() {
+            JobError::Transient(e.to_string())
+        } else {
+            JobError::Permanent(e.to_string())
+        }
+    }
+}
+
+impl From<io::Error> for JobError {
+    fn from(e: io::Error) -> Self {
+        JobError::Transient(e.to_string())
+    }
+}
+
+impl From<Error> for JobError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::NotFound | Error::RollbackTransaction => {
+                JobError::Permanent(e.to_string())
+            },
+            _ => JobError::Transient(e.to_string()),
+        }
+    }
+}
+
+impl JobError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, JobError::Transient(_))
+    }
+}
+
+/// Job is what is enqueued. `version` tells the format of the payload, jobs
+/// enqueued by a newer version (during a rolling deploy) are postponed without
+/// counting an attempt so that a worker of the version can take them (see
+/// `dequeue`). `request_id` is the id of the request which has enqueued the
+/// job (if any) to correlate logs.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "RawJob")]
+pub struct Job {
+    pub version: u32,
+    pub payload: Payload,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+// Accepts both the current format and the legacy one (positional args)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawJob {
+    Current {
+        version: u32,
+        payload: Payload,
+        #[serde(default)]
+        attempts: u32,
+        #[serde(default)]
+        request_id: Option<String>,
+    },
+    Legacy {
+        kind: JobKind,
+        args: Vec<String>,
+        #[serde(default)]
+        attempts: u32,
+    },
+}
+
+// The version and the kind of a job in the current format, to tell whether
+// the job is a newer one before decoding its payload.
+#[derive(Deserialize)]
+struct Envelope {
+    version: u32,
+    payload: EnvelopePayload,
+}
+
+#[derive(Deserialize)]
+struct EnvelopePayload {
+    kind: String,
+}
+
+impl Envelope {
+    // A job of a newer version, or of an unknown kind (added by a newer
+    // version without changing the format)
+    fn is_newer(&self) -> bool {
+        self.version > VERSION ||
+            serde_json::from_value::<JobKind>(serde_json::Value::String(
+                self.payload.kind.clone(),
+            ))
+            .is_err()
+    }
+}
+
+impl TryFrom<RawJob> for Job {
+    type Error = String;
+
+    fn try_from(raw: RawJob) -> Result<Self, Self::Error> {
+        match raw {
+            RawJob::Current {
+                version,
+                payload,
+                attempts,
+                request_id,
+            } => {
+                Ok(Self {
+                    version,
+                    payload,
+                    attempts,
+                    request_id,
+                })
+            },
+            RawJob::Legacy {
+                kind,
+                args,
+                attempts,
+            } => {
+                Ok(Self {
+                    version: 1,
+                    payload: Payload::from_args(kind, args)?,
+                    attempts,
+                    request_id: None,
+                })
+            },
+        }
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<Job {kind} v{version}>",
+            kind = self.payload.kind(),
+            version = self.version
+        )
+    }
+}
+
+/// FailedJob is an entry of the retry set and the dead letter list. It keeps
+/// the queue name so that the job can be enqueued again into the same one.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FailedJob {
+    pub id: String,
+    pub queue: String,
+    pub job: Job,
+    pub error: String,
+    pub failed_at: i64,
+}
+
+impl FailedJob {
+    pub fn new(queue: &str, job: Job, err: &JobError) -> Self {
+        Self {
+            id: generate_random_hash(
+                FAILED_JOB_ID_SOURCE,
+                FAILED_JOB_ID_LENGTH,
+            ),
+            queue: queue.to_string(),
+            job,
+            error: err.to_string(),
+            failed_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+/// UndecodableJob is an entry of the dead letter list for a value which can't
+/// be decoded (e.g. a job of an unknown kind). The value is kept as it is.
+/// `queue` is none if it has been taken from the retry set or delayed ones.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UndecodableJob {
+    pub id: String,
+    pub queue: Option<String>,
+    pub payload: String,
+    pub error: String,
+    pub failed_at: i64,
+}
+
+impl UndecodableJob {
+    pub fn new(queue: Option<&str>, payload: &[u8], err: &str) -> Self {
+        Self {
+            id: generate_random_hash(
+                FAILED_JOB_ID_SOURCE,
+                FAILED_JOB_ID_LENGTH,
+            ),
+            queue: queue.map(|q| q.to_string()),
+            payload: String::from_utf8_lossy(payload).into_owned(),
+            error: err.to_string(),
+            failed_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Returns the key of the list which keeps jobs taken from the queue by the
+/// worker until they are done.
+pub fn forked_key(queue: &str, worker: &str) -> String {
+    format!("{}:forked:{}", queue, worker)
+}
+
+/// Dequeues a job from the queue (in the same way as fourche does), and
+/// returns it with its value. The value is kept in the forked list of the
+/// worker until `ack` is called. A job enqueued by a newer version is
+/// postponed as it is, and a value which can't be decoded otherwise goes to
+/// the dead letter list with the reason. None is returned for them (or if it
+/// has been unblocked).
+pub fn dequeue(
+    queue: &str,
+    worker: &str,
+    conn: &mut Connection,
+    logger: &Logger,
+) -> RedisResult<Option<(Job, Vec<u8>)>> {
+    let value: Option<Vec<u8>> =
+        conn.brpoplpush(queue, &forked_key(queue, worker), 0)?;
+    let value = match value {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let newer = serde_json::from_slice::<Envelope>(&value)
+        .map(|e| e.is_newer())
+        .unwrap_or(false);
+    if newer {
+        let run_at = Utc::now().timestamp() + postpone::POSTPONE_DELAY;
+        let entry = postpone::schedule(queue, &value, run_at, conn)?;
+        info!(logger, "postponed id: {}, run_at: {}", entry.id, run_at);
+        ack(queue, worker, &value, conn)?;
+        return Ok(None);
+    }
+    match serde_json::from_slice::<Job>(&value) {
+        Ok(job) => Ok(Some((job, value))),
+        Err(e) => {
+            let entry =
+                UndecodableJob::new(Some(queue), &value, &e.to_string());
+            error!(logger, "dead id: {}, err: {}", entry.id, entry.error);
+            dead_letter::push_undecodable(&entry, conn)?;
+            ack(queue, worker, &value, conn)?;
+            Ok(None)
+        },
+    }
+}
+
+/// Removes the value of a job which is done (or has been retried or
+/// dead-lettered) from the forked list of the worker.
+pub fn ack(
+    queue: &str,
+    worker: &str,
+    value: &[u8],
+    conn: &mut Connection,
+) -> RedisResult<()> {
+    conn.lrem(forked_key(queue, worker), 1, value)
+}
+
+/// Puts jobs taken by workers which are not `alive` back into their queue,
+/// and returns the number of them. A worker which has stopped without
+/// finishing its jobs (e.g. crashed) leaves them in its forked lists.
+pub fn recover(alive: &[String], conn: &mut Connection) -> RedisResult<usize> {
+    let keys: Vec<String> = conn.keys("*:forked:*")?;
+    let mut count = 0;
+    for key in keys {
+        let (queue, worker) = match key.find(":forked:") {
+            Some(i) => (&key[..i], &key[i + ":forked:".len()..]),
+            None => continue,
+        };
+        if alive.iter().any(|id| id == worker) {
+            continue;
+        }
+        loop {
+            let value: Option<Vec<u8>> = conn.rpoplpush(key.as_str(), queue)?;
+            if value.is_none() {
+                break;
+            }
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Handles a job which has failed. It will be retried later with backoff if
+/// the error is transient, otherwise (or if it has reached MAX_ATTEMPTS) it
+/// goes to the dead letter list.
+pub fn fail(
+    queue: &str,
+    mut job: Job,
+    err: &JobError,
+    conn: &mut Connection,
+    logger: &Logger,
+) -> RedisResult<()> {
+    job.attempts += 1;
+    let attempts = job.attempts;
+    let entry = FailedJob::new(queue, job, err);
+
+    if err.is_transient() && attempts < MAX_ATTEMPTS {
+        let run_at = entry.failed_at + retry::backoff(attempts);
+        info!(
+            logger,
+            "retry id: {}, attempts: {}, run_at: {}",
+            entry.id,
+            attempts,
+            run_at
+        );
+        retry::schedule(&entry, run_at, conn)
+    } else {
+        error!(
+            logger,
+            "dead id: {}, attempts: {}, err: {}", entry.id, attempts, err
+        );
+        dead_letter::push(&entry, conn)
+    }
+}
+
+// Moves entries which are due at `now` in the sorted set into their queue.
+// `split` returns the queue name and the job of an entry.
+fn enqueue_due<T, F>(
+    key: &str,
+    now: i64,
+    conn: &mut Connection,
+    split: F,
+) -> RedisResult<usize>
+where
+    T: DeserializeOwned,
+    F: Fn(T) -> (String, Job),
+{
+    let values: Vec<String> = conn.zrangebyscore(key, "-inf", now)?;
+
+    let mut count = 0;
+    for value in values {
+        // another process may have taken it
+        let removed: usize = conn.zrem(key, &value)?;
+        if removed != 1 {
+            continue;
+        }
+        let (name, job) = match serde_json::from_str::<T>(&value) {
+            Ok(entry) => split(entry),
+            Err(e) => {
+                // keep it instead of dropping
+                let err = format!("{}: {}", key, e);
+                let entry = UndecodableJob::new(None, value.as_bytes(), &err);
+                dead_letter::push_undecodable(&entry, conn)?;
+                continue;
+            },
+        };
+        let mut queue = Queue::new(&name, conn);
+        queue.enqueue::<Job>(job)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Removes the entry of the id from the sorted set, and returns it.
+// `id_of` returns the id of an entry.
+fn take<T, F>(
+    key: &str,
+    id: &str,
+    conn: &mut Connection,
+    id_of: F,
+) -> RedisResult<Option<T>>
+where
+    T: DeserializeOwned,
+    F: Fn(&T) -> &str,
+{
+    let values: Vec<String> = conn.zrange(key, 0, -1)?;
+    let found = values.into_iter().find_map(|v| {
+        serde_json::from_str::<T>(&v)
+            .ok()
+            .filter(|entry| id_of(entry) == id)
+            .map(|entry| (entry, v))
+    });
+    match found {
+        None => Ok(None),
+        Some((entry, value)) => {
+            // another process may have taken it
+            let removed: usize = conn.zrem(key, value)?;
+            Ok(if removed == 1 { Some(entry) } else { None })
+        },
+    }
+}
+
+impl Job {
+    pub fn new(payload: Payload) -> Self {
+        Self {
+            version: VERSION,
+            payload,
+            attempts: 0,
+            request_id: None,
+        }
+    }
+
+    /// Sets the id of the request which enqueues the job.
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    pub fn kind(&self) -> JobKind {
+        self.payload.kind()
+    }
+
+    /// Returns the name of the queue which the job should be enqueued into.
+    pub fn queue(&self) -> &'static str {
+        match self.payload {
+            Payload::SendUserActivationEmail(_) |
+            Payload::SendPasswordResetEmail(_) |
+            Payload::SendUserDeregistrationEmail(_) |
+            Payload::SendUserEmailVerificationEmail(_) => MAIL_QUEUE,
+            Payload::PurgeAuditEvents(_) |
+            Payload::RollupMessageCounts(_) |
+            Payload::PurgeMessageExports(_) => MAINTENANCE_QUEUE,
+            Payload::EvaluateAlertRules(_) => ALERT_QUEUE,
+            Payload::DeliverWebhook(_) => WEBHOOK_QUEUE,
+            Payload::ExportMessages(_) => EXPORT_QUEUE,
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) -> Result<(), JobError> {
+        if self.version > VERSION {
+            return Err(JobError::Transient(format!(
+                "unsupported version: {}",
+                self.version
+            )));
+        }
+
+        match self.payload {
+            Payload::SendUserActivationEmail(ref p) => {
+                mail::send_user_activation_email(p, db_conn, config, logger)
+            },
+            Payload::SendPasswordResetEmail(ref p) => {
+                mail::send_password_reset_email(p, db_conn, config, logger)
+            },
+            Payload::SendUserDeregistrationEmail(ref p) => {
+                mail::send_user_deregistration_email(p, config, logger)
+            },
+            Payload::SendUserEmailVerificationEmail(ref p) => {
+                mail::send_user_email_verification_email(
+                    p, db_conn, config, logger,
+                )
+            },
+            Payload::PurgeAuditEvents(ref p) => {
+                audit_event::purge_audit_events(p, db_conn, logger)
+            },
+            Payload::EvaluateAlertRules(ref p) => {
+                alert::evaluate_alert_rules(p, db_conn, config, logger)
+            },
+            Payload::DeliverWebhook(ref p) => {
+                webhook::deliver_webhook(
+                    p,
+                    self.attempts + 1,
+                    db_conn,
+                    config,
+                    logger,
+                )
+            },
+            Payload::ExportMessages(ref p) => {
+                export::export_messages(p, db_conn, config, logger)
+            },
+            Payload::RollupMessageCounts(ref p) => {
+                message_count::rollup_message_counts(p, db_conn, logger)
+            },
+            Payload::PurgeMessageExports(ref p) => {
+                export::purge_message_exports(p, config, logger)
+            },
+        }
+    }
+}
+
+fn not_found() -> JobError {
+    JobError::Permanent("not found :'(".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use lettre::smtp::response::{Category, Code, Detail, Response, Severity};
+
+    fn build_mailer_error(severity: Severity) -> MailerError {
+        let code = Code::new(severity, Category::MailSystem, Detail::Zero);
+        Response::new(code, vec![]).into()
+    }
+
+    #[test]
+    fn test_job_error_from_mailer_error() {
+        let e = build_mailer_error(Severity::TransientNegativeCompletion);
+        assert!(JobError::from(e).is_transient());
+
+        let e = build_mailer_error(Severity::PermanentNegativeCompletion);
+        assert!(!JobError::from(e).is_transient());
+    }
+
+    #[test]
+    fn test_job_error_from_diesel_error() {
+        assert!(!JobError::from(Error::NotFound).is_transient());
+        assert!(JobError::from(Error::AlreadyInTransaction).is_transient());
+    }
+
+    #[test]
+    fn test_job_serialization() {
+        let job =
+            Job::new(Payload::SendPasswordResetEmail(PasswordResetEmail {
+                user_id: 1,
+                session_id: "s".to_string(),
+                token: "t".to_string(),
+            }));
+        let s = serde_json::to_string(&job).unwrap();
+        assert!(!s.contains("request_id"));
+        assert_eq!(serde_json::from_str::<Job>(&s).unwrap(), job);
+
+        let job = job.with_request_id("req-1");
+        let s = serde_json::to_string(&job).unwrap();
+        let result = serde_json::from_str::<Job>(&s).unwrap();
+        assert_eq!(result.request_id, Some("req-1".to_string()));
+    }
+
+    #[test]
+    fn test_job_deserialization_of_legacy_format() {
+        let job: Job = serde_json::from_str(
+            r#"{"kind":"SendPasswordResetEmail","args":["1","s","t"]}"#,
+        )
+        .unwrap();
+        assert_eq!(job.version, 1);
+        assert_eq!(job.attempts, 0);
+        assert_eq!(job.kind(), JobKind::SendPasswordResetEmail);
+
+        // malformed
+        let result = serde_json::from_str::<Job>(
+            r#"{"kind":"SendPasswordResetEmail","args":["a","s","t"]}"#,
+        );
+        assert!(result.is_err());
+        let result = serde_json::from_str::<Job>(
+            r#"{"kind":"SendPasswordResetEmail","args":[]}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invoke_newer_version() {
+        crate::model::test::run(|conn, config, logger| {
+            let mut job = Job::new(Payload::SendUserDeregistrationEmail(
+                UserDeregistrationEmail {
+                    email: "oswald@example.org".to_string(),
+                    name: "Oswald".to_string(),
+                },
+            ));
+            job.version = VERSION + 1;
+
+            let result = job.invoke(conn, config, logger);
+            assert!(matches!(result, Err(JobError::Transient(_))));
+        })
+    }
+}
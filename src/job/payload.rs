@@ -0,0 +1,254 @@
+//! # Payload
+//!
+//! Each kind of job has its own payload struct. In the queue, it's encoded as
+//! an object tagged with `kind` (e.g. `{"kind":"SendPasswordResetEmail",
+//! "user_id":1,...}`).
+use std::fmt;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum JobKind {
+    SendUserActivationEmail,
+    SendPasswordResetEmail,
+    SendUserDeregistrationEmail,
+    SendUserEmailVerificationEmail,
+    PurgeAuditEvents,
+    EvaluateAlertRules,
+    DeliverWebhook,
+    ExportMessages,
+    RollupMessageCounts,
+    PurgeMessageExports,
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserActivationEmail {
+    pub user_email_id: i64,
+    pub session_id: String,
+    pub token: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PasswordResetEmail {
+    pub user_id: i64,
+    pub session_id: String,
+    pub token: String,
+}
+
+// The user has already been anonymized. The email address and name are
+// passed instead of user id.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserDeregistrationEmail {
+    pub email: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserEmailVerificationEmail {
+    pub user_email_id: i64,
+    pub session_id: String,
+    pub token: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PurgeAuditEvents {
+    pub retention_days: i64,
+}
+
+// Rules on all streams are evaluated if stream_id is not given (e.g. by a
+// schedule, for absence conditions).
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct EvaluateAlertRules {
+    #[serde(default)]
+    pub stream_id: Option<i64>,
+}
+
+// The body is built when the event happens, so that retries (with the same
+// delivery id) send the same one.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WebhookEvent {
+    pub webhook_id: i64,
+    pub delivery_id: String,
+    pub event: String,
+    pub body: String,
+}
+
+// The time range is given in timestamps (UTC), see MessageFilter.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportMessages {
+    pub user_id: i64,
+    pub stream_id: i64,
+    pub format: String,
+    pub level: Option<String>,
+    pub code: Option<String>,
+    pub since: i64,
+    pub until: i64,
+}
+
+// Counts are rolled up from the watermark (or the oldest message at first)
+// until the current hour. The last `hours` are always re-counted for late
+// messages, and `since` (a timestamp) re-counts older ones (e.g. imported).
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RollupMessageCounts {
+    #[serde(default)]
+    pub hours: Option<i64>,
+    #[serde(default)]
+    pub since: Option<i64>,
+}
+
+// Exported files are removed after their download links have expired.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PurgeMessageExports {}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Payload {
+    SendUserActivationEmail(UserActivationEmail),
+    SendPasswordResetEmail(PasswordResetEmail),
+    SendUserDeregistrationEmail(UserDeregistrationEmail),
+    SendUserEmailVerificationEmail(UserEmailVerificationEmail),
+    PurgeAuditEvents(PurgeAuditEvents),
+    EvaluateAlertRules(EvaluateAlertRules),
+    DeliverWebhook(WebhookEvent),
+    ExportMessages(ExportMessages),
+    RollupMessageCounts(RollupMessageCounts),
+    PurgeMessageExports(PurgeMessageExports),
+}
+
+impl Payload {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            Payload::SendUserActivationEmail(_) => {
+                JobKind::SendUserActivationEmail
+            },
+            Payload::SendPasswordResetEmail(_) => {
+                JobKind::SendPasswordResetEmail
+            },
+            Payload::SendUserDeregistrationEmail(_) => {
+                JobKind::SendUserDeregistrationEmail
+            },
+            Payload::SendUserEmailVerificationEmail(_) => {
+                JobKind::SendUserEmailVerificationEmail
+            },
+            Payload::PurgeAuditEvents(_) => JobKind::PurgeAuditEvents,
+            Payload::EvaluateAlertRules(_) => JobKind::EvaluateAlertRules,
+            Payload::DeliverWebhook(_) => JobKind::DeliverWebhook,
+            Payload::ExportMessages(_) => JobKind::ExportMessages,
+            Payload::RollupMessageCounts(_) => JobKind::RollupMessageCounts,
+            Payload::PurgeMessageExports(_) => JobKind::PurgeMessageExports,
+        }
+    }
+
+    /// Builds a payload from positional args of a job enqueued by an older
+    /// version (`{"kind":"...","args":[...]}`).
+    pub fn from_args(kind: JobKind, args: Vec<String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let mut next =
+            |name: &str| args.next().ok_or_else(|| format!("missing {}", name));
+        let id = |v: String, name: &str| {
+            v.parse::<i64>()
+                .map_err(|e| format!("invalid {}: {}", name, e))
+        };
+
+        let payload = match kind {
+            JobKind::SendUserActivationEmail => {
+                Payload::SendUserActivationEmail(UserActivationEmail {
+                    user_email_id: id(next("user_email_id")?, "user_email_id")?,
+                    session_id: next("session_id")?,
+                    token: next("token")?,
+                })
+            },
+            JobKind::SendPasswordResetEmail => {
+                Payload::SendPasswordResetEmail(PasswordResetEmail {
+                    user_id: id(next("user_id")?, "user_id")?,
+                    session_id: next("session_id")?,
+                    token: next("token")?,
+                })
+            },
+            JobKind::SendUserDeregistrationEmail => {
+                Payload::SendUserDeregistrationEmail(UserDeregistrationEmail {
+                    email: next("email")?,
+                    name: next("name")?,
+                })
+            },
+            JobKind::SendUserEmailVerificationEmail => {
+                Payload::SendUserEmailVerificationEmail(
+                    UserEmailVerificationEmail {
+                        user_email_id: id(
+                            next("user_email_id")?,
+                            "user_email_id",
+                        )?,
+                        session_id: next("session_id")?,
+                        token: next("token")?,
+                    },
+                )
+            },
+            // introduced after the legacy format
+            JobKind::PurgeAuditEvents |
+            JobKind::EvaluateAlertRules |
+            JobKind::DeliverWebhook |
+            JobKind::ExportMessages |
+            JobKind::RollupMessageCounts |
+            JobKind::PurgeMessageExports => {
+                return Err(format!("unsupported kind: {}", kind));
+            },
+        };
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_args() {
+        let args = vec!["1".to_string(), "s".to_string(), "t".to_string()];
+        let payload = Payload::from_args(JobKind::SendPasswordResetEmail, args);
+        assert_eq!(
+            payload,
+            Ok(Payload::SendPasswordResetEmail(PasswordResetEmail {
+                user_id: 1,
+                session_id: "s".to_string(),
+                token: "t".to_string(),
+            }))
+        );
+
+        let args = vec!["a".to_string(), "s".to_string(), "t".to_string()];
+        let payload =
+            Payload::from_args(JobKind::SendUserActivationEmail, args);
+        assert!(payload.is_err());
+
+        let args = vec!["1".to_string(), "s".to_string()];
+        let payload =
+            Payload::from_args(JobKind::SendUserEmailVerificationEmail, args);
+        assert_eq!(payload, Err("missing token".to_string()));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let payload =
+            Payload::SendUserDeregistrationEmail(UserDeregistrationEmail {
+                email: "oswald@example.org".to_string(),
+                name: "Oswald".to_string(),
+            });
+        assert_eq!(
+            serde_json::to_string(&payload).unwrap(),
+            r#"{"kind":"SendUserDeregistrationEmail","email":"oswald@example.org","name":"Oswald"}"#
+        );
+        assert_eq!(payload.kind(), JobKind::SendUserDeregistrationEmail);
+    }
+
+    #[test]
+    fn test_deserialize_without_fields() {
+        let payload = serde_json::from_str::<Payload>(
+            r#"{"kind":"PurgeMessageExports"}"#,
+        )
+        .unwrap();
+        assert_eq!(payload.kind(), JobKind::PurgeMessageExports);
+    }
+}
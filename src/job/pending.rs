@@ -0,0 +1,42 @@
+//! # Pending
+//!
+//! Jobs waiting in a queue. A queue of fourche is a Redis list named after
+//! the queue, so they can be inspected (or removed) without dequeuing them.
+use redis::{Commands, Connection, RedisResult};
+
+// The key of the list for the queue
+fn key(queue: &str) -> &str {
+    queue
+}
+
+pub fn len(queue: &str, conn: &mut Connection) -> RedisResult<usize> {
+    conn.llen(key(queue))
+}
+
+/// Returns raw values in the range of the list. They are returned as they
+/// are, because jobs which can't be decoded are also worth inspecting.
+pub fn lrange(
+    queue: &str,
+    start: isize,
+    stop: isize,
+    conn: &mut Connection,
+) -> RedisResult<Vec<String>> {
+    conn.lrange(key(queue), start, stop)
+}
+
+/// Removes the job at the index. Returns false if it's not found.
+pub fn del(
+    queue: &str,
+    index: isize,
+    conn: &mut Connection,
+) -> RedisResult<bool> {
+    let value: Option<String> = conn.lindex(key(queue), index)?;
+    match value {
+        None => Ok(false),
+        Some(v) => {
+            // an identical job may be removed instead, but it doesn't matter
+            let removed: usize = conn.lrem(key(queue), 1, v)?;
+            Ok(removed == 1)
+        },
+    }
+}
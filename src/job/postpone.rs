@@ -0,0 +1,81 @@
+//! # Postpone
+//!
+//! Jobs which the worker can't take (enqueued by a newer version during a
+//! rolling deploy) wait in a sorted set as they are, and are enqueued again
+//! into their queue after a while, so that a worker of the version can take
+//! them. It doesn't count as an attempt.
+use redis::{Commands, Connection, RedisResult};
+
+use crate::job::{UndecodableJob, dead_letter};
+use crate::util::generate_random_hash;
+
+pub const POSTPONED_KEY: &str = "job:postponed";
+
+/// The delay (in seconds) before a postponed job is enqueued again.
+pub const POSTPONE_DELAY: i64 = 60;
+
+const POSTPONED_JOB_ID_LENGTH: i32 = 16;
+const POSTPONED_JOB_ID_SOURCE: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// PostponedJob keeps the value of the job without decoding it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PostponedJob {
+    pub id: String,
+    pub queue: String,
+    pub value: String,
+    pub run_at: i64,
+}
+
+/// Puts the value to be enqueued again at `run_at` into the queue, and
+/// returns the entry.
+pub fn schedule(
+    queue: &str,
+    value: &[u8],
+    run_at: i64,
+    conn: &mut Connection,
+) -> RedisResult<PostponedJob> {
+    let entry = PostponedJob {
+        id: generate_random_hash(
+            POSTPONED_JOB_ID_SOURCE,
+            POSTPONED_JOB_ID_LENGTH,
+        ),
+        queue: queue.to_string(),
+        value: String::from_utf8_lossy(value).into_owned(),
+        run_at,
+    };
+    let value = serde_json::to_string(&entry).unwrap();
+    let _: usize = conn.zadd(POSTPONED_KEY, value, run_at)?;
+    Ok(entry)
+}
+
+pub fn len(conn: &mut Connection) -> RedisResult<usize> {
+    conn.zcard(POSTPONED_KEY)
+}
+
+/// Enqueues values which are due at `now` into their queue (as they were),
+/// and returns the number of them.
+pub fn enqueue_due(now: i64, conn: &mut Connection) -> RedisResult<usize> {
+    let values: Vec<String> = conn.zrangebyscore(POSTPONED_KEY, "-inf", now)?;
+
+    let mut count = 0;
+    for value in values {
+        // another process may have taken it
+        let removed: usize = conn.zrem(POSTPONED_KEY, &value)?;
+        if removed != 1 {
+            continue;
+        }
+        let entry = match serde_json::from_str::<PostponedJob>(&value) {
+            Ok(v) => v,
+            Err(e) => {
+                // keep it instead of dropping
+                let err = format!("{}: {}", POSTPONED_KEY, e);
+                let entry = UndecodableJob::new(None, value.as_bytes(), &err);
+                dead_letter::push_undecodable(&entry, conn)?;
+                continue;
+            },
+        };
+        let _: usize = conn.lpush(&entry.queue, entry.value)?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,93 @@
+//! # Retry
+//!
+//! Jobs failed with a transient error wait in a sorted set (scored by the
+//! timestamp to run at) until they are enqueued again into their queue.
+use fourche::queue::Queue;
+use redis::{Commands, Connection, RedisResult};
+
+use crate::job::{FailedJob, Job};
+
+pub const RETRY_KEY: &str = "job:retry";
+
+const BACKOFF_BASE: i64 = 10; // seconds
+const BACKOFF_MAX: i64 = 3600; // seconds
+
+/// Returns the delay (in seconds) before the next attempt. It grows
+/// exponentially (10s, 20s, 40s, ...) up to an hour.
+pub fn backoff(attempts: u32) -> i64 {
+    if attempts < 1 {
+        return BACKOFF_BASE;
+    }
+    2_i64
+        .checked_pow(attempts - 1)
+        .and_then(|n| n.checked_mul(BACKOFF_BASE))
+        .map_or(BACKOFF_MAX, |n| n.min(BACKOFF_MAX))
+}
+
+pub fn schedule(
+    entry: &FailedJob,
+    run_at: i64,
+    conn: &mut Connection,
+) -> RedisResult<()> {
+    let value = serde_json::to_string(entry).unwrap();
+    conn.zadd(RETRY_KEY, value, run_at)
+}
+
+pub fn len(conn: &mut Connection) -> RedisResult<usize> {
+    conn.zcard(RETRY_KEY)
+}
+
+/// Returns entries in the range (the earliest one comes first).
+pub fn range(
+    start: isize,
+    stop: isize,
+    conn: &mut Connection,
+) -> RedisResult<Vec<FailedJob>> {
+    let values: Vec<String> = conn.zrange(RETRY_KEY, start, stop)?;
+    Ok(values
+        .iter()
+        .filter_map(|v| serde_json::from_str(v).ok())
+        .collect())
+}
+
+/// Enqueues the job of the entry into its queue without waiting for the
+/// backoff. Returns false if it's not found.
+pub fn run_now(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    let entry = match super::take(RETRY_KEY, id, conn, |e: &FailedJob| &e.id)? {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+    let mut queue = Queue::new(&entry.queue, conn);
+    queue.enqueue::<Job>(entry.job)?;
+    Ok(true)
+}
+
+/// Removes the entry (the job won't be retried). Returns false if it's not
+/// found.
+pub fn del(id: &str, conn: &mut Connection) -> RedisResult<bool> {
+    super::take(RETRY_KEY, id, conn, |e: &FailedJob| &e.id).map(|v| v.is_some())
+}
+
+/// Enqueues jobs which are due at `now` into their queue, and returns the
+/// number of them.
+pub fn enqueue_due(now: i64, conn: &mut Connection) -> RedisResult<usize> {
+    super::enqueue_due(RETRY_KEY, now, conn, |entry: FailedJob| {
+        (entry.queue, entry.job)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(0), 10);
+        assert_eq!(backoff(1), 10);
+        assert_eq!(backoff(2), 20);
+        assert_eq!(backoff(3), 40);
+        assert_eq!(backoff(9), 2560);
+        assert_eq!(backoff(10), 3600);
+        assert_eq!(backoff(100), 3600);
+    }
+}
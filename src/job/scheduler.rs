@@ -0,0 +1,205 @@
+//! # Scheduler
+//!
+//! Recurring jobs are declared with cron expressions in config (see
+//! `Config::worker_schedules`). Only one worker (the leader) enqueues them,
+//! the leadership is held by a lock with TTL in Redis.
+use std::str::FromStr;
+
+use chrono::{TimeZone, Utc};
+use fourche::queue::Queue;
+use redis::{Commands, Connection, RedisResult, Script};
+
+use crate::job::{Job, Payload};
+
+pub const LEADER_KEY: &str = "scheduler:leader";
+pub const LAST_RUN_KEY: &str = "scheduler:last_run";
+
+const LEADER_TTL: usize = 10_000; // milliseconds
+
+/// Schedule is a job with a cron expression. It's declared as a string like
+/// `0 4 * * * {"kind":"PurgeAuditEvents","retention_days":365}` (the seconds
+/// field of the expression is optional).
+pub struct Schedule {
+    pub spec: String,
+    pub job: Job,
+    cron: cron::Schedule,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        let i = spec
+            .find('{')
+            .ok_or_else(|| format!("{}: payload is missing", spec))?;
+        let (expr, payload) = spec.split_at(i);
+
+        // the standard one has 5 fields (without seconds)
+        let expr = expr.trim();
+        let expr = if expr.split_whitespace().count() == 5 {
+            format!("0 {}", expr)
+        } else {
+            expr.to_string()
+        };
+        let cron = cron::Schedule::from_str(&expr)
+            .map_err(|e| format!("{}: {}", spec, e))?;
+        let payload = serde_json::from_str::<Payload>(payload)
+            .map_err(|e| format!("{}: {}", spec, e))?;
+
+        Ok(Self {
+            spec: spec.to_string(),
+            job: Job::new(payload),
+            cron,
+        })
+    }
+}
+
+impl Schedule {
+    /// Returns the next time (timestamp) after `t`.
+    pub fn next_after(&self, t: i64) -> Option<i64> {
+        self.cron
+            .after(&Utc.timestamp(t, 0))
+            .next()
+            .map(|dt| dt.timestamp())
+    }
+}
+
+/// Parses all schedules, or returns the errors.
+pub fn parse_schedules(specs: &[String]) -> Result<Vec<Schedule>, Vec<String>> {
+    let (schedules, errors): (Vec<_>, Vec<_>) = specs
+        .iter()
+        .map(|s| Schedule::from_str(s))
+        .partition(Result::is_ok);
+    if !errors.is_empty() {
+        return Err(errors.into_iter().filter_map(Result::err).collect());
+    }
+    Ok(schedules.into_iter().filter_map(Result::ok).collect())
+}
+
+/// Takes (or extends) the leadership, and returns true if the worker `id` is
+/// the leader.
+pub fn acquire_leadership(
+    id: &str,
+    conn: &mut Connection,
+) -> RedisResult<bool> {
+    let script = Script::new(
+        r#"
+        if redis.call("GET", KEYS[1]) == ARGV[1] then
+          return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+        end
+        if redis.call("SET", KEYS[1], ARGV[1], "NX", "PX", ARGV[2]) then
+          return 1
+        end
+        return 0
+        "#,
+    );
+    let result: i64 = script
+        .key(LEADER_KEY)
+        .arg(id)
+        .arg(LEADER_TTL)
+        .invoke(conn)?;
+    Ok(result == 1)
+}
+
+/// Gives up the leadership if the worker `id` has it.
+pub fn release_leadership(id: &str, conn: &mut Connection) -> RedisResult<()> {
+    let script = Script::new(
+        r#"
+        if redis.call("GET", KEYS[1]) == ARGV[1] then
+          redis.call("DEL", KEYS[1])
+        end
+        return 0
+        "#,
+    );
+    let _: i64 = script.key(LEADER_KEY).arg(id).invoke(conn)?;
+    Ok(())
+}
+
+pub fn leader(conn: &mut Connection) -> RedisResult<Option<String>> {
+    conn.get(LEADER_KEY)
+}
+
+/// Enqueues jobs of schedules which are due at `now`, and returns the number
+/// of them. It must be called only by the leader.
+///
+/// The last run is kept in Redis, so that the next leader takes it over. Runs
+/// missed while there is no leader are caught up just once.
+pub fn tick(
+    schedules: &[Schedule],
+    now: i64,
+    conn: &mut Connection,
+) -> RedisResult<usize> {
+    let mut count = 0;
+    for schedule in schedules {
+        let last_run: Option<i64> = conn.hget(LAST_RUN_KEY, &schedule.spec)?;
+        let last_run = match last_run {
+            Some(t) => t,
+            // first time
+            None => {
+                let _: usize = conn.hset(LAST_RUN_KEY, &schedule.spec, now)?;
+                continue;
+            },
+        };
+
+        match schedule.next_after(last_run) {
+            Some(t) if t <= now => {
+                let job = schedule.job.clone();
+                let mut queue = Queue::new(job.queue(), conn);
+                queue.enqueue::<Job>(job)?;
+                let _: usize = conn.hset(LAST_RUN_KEY, &schedule.spec, now)?;
+                count += 1;
+            },
+            _ => (),
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::job::{JobKind, MAINTENANCE_QUEUE};
+
+    #[test]
+    fn test_schedule_from_str() {
+        let s = Schedule::from_str(
+            r#"0 4 * * * {"kind":"PurgeAuditEvents","retention_days":365}"#,
+        )
+        .unwrap();
+        assert_eq!(s.job.kind(), JobKind::PurgeAuditEvents);
+        assert_eq!(s.job.queue(), MAINTENANCE_QUEUE);
+
+        // 2021-01-01T00:00:00Z
+        let t = 1_609_459_200;
+        assert_eq!(s.next_after(t), Some(t + 4 * 3600));
+        assert_eq!(s.next_after(t + 4 * 3600), Some(t + 28 * 3600));
+
+        // with seconds
+        let s = Schedule::from_str(
+            r#"30 */5 * * * * {"kind":"PurgeAuditEvents","retention_days":1}"#,
+        )
+        .unwrap();
+        assert_eq!(s.next_after(t), Some(t + 30));
+
+        assert!(Schedule::from_str("0 4 * * *").is_err());
+        assert!(Schedule::from_str(r#"0 4 * * * {"kind":"Unknown"}"#).is_err());
+        assert!(Schedule::from_str(
+            r#"0 25 * * * {"kind":"PurgeAuditEvents","retention_days":1}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_schedules() {
+        let specs = vec![
+            r#"0 4 * * * {"kind":"PurgeAuditEvents","retention_days":1}"#
+                .to_string(),
+        ];
+        assert_eq!(parse_schedules(&specs).map(|v| v.len()), Ok(1));
+
+        let specs = vec!["invalid".to_string(), "".to_string()];
+        assert_eq!(parse_schedules(&specs).err().map(|v| v.len()), Some(2));
+    }
+}
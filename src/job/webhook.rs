@@ -0,0 +1,73 @@
+//! # Webhook
+//!
+//! A job delivering an event to a webhook.
+use chrono::Utc;
+use diesel::PgConnection;
+use slog::Logger;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::job::{JobError, WebhookEvent, not_found};
+use crate::model::webhook::Webhook;
+use crate::model::webhook_delivery::{NewWebhookDelivery, WebhookDelivery};
+use crate::webhook;
+
+// Delivers the event to the webhook, and logs the attempt. A failed delivery
+// is retried by the worker with the same delivery id.
+pub fn deliver_webhook(
+    p: &WebhookEvent,
+    attempt: u32,
+    db_conn: &PgConnection,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), JobError> {
+    info!(
+        logger,
+        "webhook_id: {}, delivery_id: {}, event: {}, attempt: {}",
+        p.webhook_id,
+        p.delivery_id,
+        p.event,
+        attempt
+    );
+
+    let uuid = Uuid::parse_str(&p.delivery_id).map_err(|e| {
+        JobError::Permanent(format!("invalid delivery_id: {}", e))
+    })?;
+    let hook = Webhook::find_by_id(p.webhook_id, db_conn, logger)
+        .ok_or_else(not_found)?;
+    // a test event is sent even if it's disabled
+    if !hook.enabled && p.event != webhook::PING_EVENT {
+        info!(logger, "disabled: {}", hook);
+        return Ok(());
+    }
+
+    let delivery = webhook::Delivery {
+        id: &p.delivery_id,
+        event: &p.event,
+        timestamp: Utc::now().timestamp(),
+        body: &p.body,
+    };
+    let result = webhook::deliver(
+        &hook.url,
+        &hook.secret,
+        &delivery,
+        config.webhook_allow_private_hosts,
+    );
+
+    let d = NewWebhookDelivery {
+        uuid,
+        webhook_id: hook.id,
+        event: p.event.clone(),
+        attempt: attempt as i32,
+        status_code: match result {
+            Ok(status) => Some(i32::from(status)),
+            Err(ref e) => e.status().map(i32::from),
+        },
+        error: result.as_ref().err().map(|e| e.to_string()),
+        succeeded: result.is_ok(),
+    };
+    if WebhookDelivery::insert(&d, db_conn, logger).is_none() {
+        error!(logger, "err: failed to log the delivery {}", p.delivery_id);
+    }
+    result.map(|_| ()).map_err(JobError::from)
+}
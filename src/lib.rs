@@ -13,10 +13,9 @@ extern crate rocket;
 extern crate rocket_contrib;
 #[macro_use]
 extern crate serde_derive;
-#[macro_use(error, info, warn)]
+#[macro_use(error, info, o, warn)]
 extern crate slog;
 
-#[cfg(test)]
 #[macro_use]
 extern crate lazy_static;
 #[cfg(test)]
@@ -29,21 +28,29 @@ use std::collections::HashMap;
 
 mod response;
 mod validation;
-mod service;
 mod schema;
-mod util;
+mod totp;
 
 pub mod db;
 pub mod mq;
 pub mod ss;
 
 pub mod config;
+pub mod export;
+pub mod health;
+pub mod import;
 pub mod job;
 pub mod logger;
 pub mod mailer;
+pub mod metrics;
 pub mod model;
+pub mod redaction;
 pub mod request;
 pub mod route;
+pub mod service;
+pub mod util;
+pub mod webhook;
+pub mod worker;
 
 // macros
 
@@ -78,10 +85,14 @@ pub fn routes() -> Vec<(&'static str, Vec<rocket::Route>)> {
                 route::activation::preflight::activate,
                 route::activation::activate,
                 route::authentication::preflight::login,
+                route::authentication::preflight::login_totp,
                 route::authentication::preflight::logout,
                 route::authentication::preignition::login,
                 route::authentication::login,
+                route::authentication::login_totp,
                 route::authentication::logout,
+                route::email::preflight::verify,
+                route::email::verify,
                 route::password_reset::preflight::request,
                 route::password_reset::preflight::verify_update,
                 route::password_reset::preignition::request,
@@ -95,6 +106,9 @@ pub fn routes() -> Vec<(&'static str, Vec<rocket::Route>)> {
                 route::registration::deregister,
                 route::registration::register,
                 route::health::check,
+                route::health::live,
+                route::health::ready,
+                route::metrics::metrics,
             ],
         ),
         (
@@ -110,17 +124,104 @@ pub fn routes() -> Vec<(&'static str, Vec<rocket::Route>)> {
                 route::access_token::hset_state,
                 route::access_token::append,
                 route::access_token::lrange,
+                route::alert::preflight::ack,
+                route::alert::preflight::del,
+                route::alert::preflight::hgetall,
+                route::alert::preflight::hset,
+                route::alert::preflight::lrange,
+                route::alert::preflight::mute,
+                route::alert::ack,
+                route::alert::del,
+                route::alert::hgetall,
+                route::alert::hset,
+                route::alert::lrange,
+                route::alert::mute,
+                route::annotation::preflight::append,
+                route::annotation::preflight::del,
+                route::annotation::preflight::hgetall,
+                route::annotation::preflight::hset,
+                route::annotation::append,
+                route::annotation::del,
+                route::annotation::hgetall,
+                route::annotation::hset,
+                route::audit::preflight::lrange,
+                route::audit::preflight::lrange_namespace,
+                route::audit::lrange,
+                route::audit::lrange_namespace,
+                route::email::preflight::append,
+                route::email::preflight::del,
+                route::email::preflight::hgetall,
+                route::email::preflight::hset_primary,
+                route::email::append,
+                route::email::del,
+                route::email::hgetall,
+                route::email::hset_primary,
+                route::issue::preflight::hset_state,
+                route::issue::preflight::lrange,
+                route::issue::hset_state,
+                route::issue::lrange,
                 route::message::preflight::append,
+                route::message::preflight::del,
+                route::message::preflight::hget,
+                route::message::preflight::hset,
                 route::message::preflight::lrange,
+                route::message::preflight::search,
                 route::message::append,
+                route::message::del,
+                route::message::hget,
+                route::message::hset,
                 route::message::lrange,
+                route::message::search,
+                route::message_export::preflight::download,
+                route::message_export::preflight::export,
+                route::message_export::download,
+                route::message_export::export,
+                route::message_import::preflight::import,
+                route::message_import::import,
+                route::message_stats::preflight::stats,
+                route::message_stats::stats,
+                route::message_view::preflight::del,
+                route::message_view::preflight::hgetall,
+                route::message_view::preflight::hset,
+                route::message_view::preflight::lrange,
+                route::message_view::preflight::update,
+                route::message_view::del,
+                route::message_view::hgetall,
+                route::message_view::hset,
+                route::message_view::lrange,
+                route::message_view::update,
                 route::namespace::preflight::hget,
                 route::namespace::preflight::hgetall,
                 route::namespace::preflight::hset,
                 route::namespace::hget,
                 route::namespace::hgetall,
                 route::namespace::hset,
+                route::redaction::preflight::del,
+                route::redaction::preflight::hgetall,
+                route::redaction::preflight::hset,
+                route::redaction::del,
+                route::redaction::hgetall,
+                route::redaction::hset,
+                route::totp::preflight::append,
+                route::totp::preflight::confirm,
+                route::totp::preflight::del,
+                route::totp::append,
+                route::totp::confirm,
+                route::totp::del,
+                route::webhook::preflight::del,
+                route::webhook::preflight::hgetall,
+                route::webhook::preflight::hset,
+                route::webhook::preflight::hset_state,
+                route::webhook::preflight::lrange,
+                route::webhook::preflight::ping,
+                route::webhook::del,
+                route::webhook::hgetall,
+                route::webhook::hset,
+                route::webhook::hset_state,
+                route::webhook::lrange,
+                route::webhook::ping,
                 route::health::check,
+                route::health::live,
             ],
         ),
     ];
@@ -133,10 +234,14 @@ pub fn server() -> rocket::Rocket {
     rocket::ignite()
         .mount("/_", r["/_"].clone())
         .mount("/v1", r["/v1"].clone())
+        .attach(metrics::MetricsFairing)
+        .attach(request::request_id::RequestIdFairing)
         .register(catchers![
             route::error::bad_request,
+            route::error::forbidden,
             route::error::internal_server_error,
             route::error::not_found,
+            route::error::service_unavailable,
             route::error::unauthorized,
             route::error::unprocessable_entity,
         ])
@@ -1,3 +1,7 @@
+use std::io;
+use std::str::FromStr;
+
+use slog::{Drain, Level};
 use sloggers::{
     Build,
     terminal::{TerminalLoggerBuilder, Destination},
@@ -8,27 +12,67 @@ use crate::config::Config;
 
 pub type Logger = slog::Logger;
 
-pub fn get_logger(config: &Config) -> Logger {
-    let mut builder = TerminalLoggerBuilder::new();
+/// LogFormat is the output format of records. Json writes a record as an
+/// object per line (with its key-values) for log collectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
 
-    let level = match config.env_name {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("invalid log format: {}", s)),
+        }
+    }
+}
+
+pub fn get_logger(config: &Config) -> Logger {
+    let severity = match config.env_name {
         "development" => Severity::Debug,
         "production" => Severity::Error,
         "testing" => Severity::Warning,
         _ => Severity::Trace,
     };
 
-    builder.level(level);
-    builder.destination(Destination::Stdout);
-    builder.build().unwrap()
+    match config.log_format {
+        LogFormat::Text => {
+            let mut builder = TerminalLoggerBuilder::new();
+            builder.level(severity);
+            builder.destination(Destination::Stdout);
+            builder.build().unwrap()
+        },
+        LogFormat::Json => build_json_logger(severity.as_level()),
+    }
+}
+
+fn build_json_logger(level: Level) -> Logger {
+    let drain = slog_json::Json::new(io::stdout())
+        .add_default_keys()
+        .build()
+        .fuse();
+    let drain = slog_async::Async::new(drain)
+        .build()
+        .filter_level(level)
+        .fuse();
+    slog::Logger::root(drain, o!())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    use slog::Drain;
-
     use crate::model::test::run;
 
     #[test]
@@ -63,6 +107,25 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_get_logger_json() {
+        run(|_, config, _| {
+            let mut c = config.clone();
+            c.log_format = LogFormat::Json;
+            let logger = get_logger(&c);
+
+            assert!(logger.is_warning_enabled());
+            assert!(!logger.is_info_enabled());
+        })
+    }
+
+    #[test]
+    fn test_log_format_from_str() {
+        assert_eq!("text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
     #[test]
     fn test_get_logger_testing() {
         run(|_, config, _| {
@@ -0,0 +1,179 @@
+//! AlertMailer
+
+use lettre_email::Email;
+use slog::Logger;
+
+use crate::config::Config;
+use crate::mailer::{Client, Header, Mailer, MailerError};
+use crate::model::alert::Alert;
+use crate::model::alert_rule::{AlertCondition, AlertRule};
+
+/// AlertMailer is a wrapper handles notifications of alerts.
+pub struct AlertMailer<'a> {
+    /// Config object.
+    config: &'a Config,
+    /// Email Header object.
+    header: Header<'a>,
+    /// Mailer is the actual mailer holds client.
+    mailer: Mailer<'a>,
+}
+
+// e.g. "2 or more error (or higher) messages with code E1 in 300 seconds"
+fn describe(rule: &AlertRule) -> String {
+    let level = rule
+        .level
+        .as_ref()
+        .map_or("".to_string(), |l| format!("{} (or higher) ", l));
+    let code = rule
+        .code
+        .as_ref()
+        .map_or("".to_string(), |c| format!(" with code {}", c));
+    match rule.condition {
+        AlertCondition::Match | AlertCondition::Threshold => {
+            format!(
+                "{} or more {}messages{} in {} seconds",
+                rule.threshold, level, code, rule.window_seconds
+            )
+        },
+        AlertCondition::Absence => {
+            format!(
+                "no {}messages{} in {} seconds",
+                level, code, rule.window_seconds
+            )
+        },
+    }
+}
+
+impl<'a> AlertMailer<'a> {
+    /// Creates a new AlertMailer.
+    pub fn new(config: &'a Config, logger: &'a Logger) -> Self {
+        let header = Header {
+            from: (&config.mailer_from_email, &config.mailer_from_alias),
+
+            ..Default::default()
+        };
+        let mailer = Mailer::new(config, logger);
+
+        Self {
+            config,
+            header,
+            mailer,
+        }
+    }
+
+    /// Sets to and returns mailer itself.
+    pub fn to(&mut self, to: (&'a str, &'a str)) -> &mut Self {
+        self.header.to = to;
+        self
+    }
+
+    pub fn inject(&mut self, client: Option<Client<'a>>) {
+        self.mailer.client = client;
+    }
+
+    /// Builds an alert message and send it via actual mailer.
+    pub fn send_alert_email(
+        &mut self,
+        rule: &AlertRule,
+        alert: &Alert,
+    ) -> Result<(), MailerError> {
+        let url = self.config.application_url.to_string();
+
+        let subject = format!("[Alert] {}", rule.name);
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+The alert rule "{}" has fired at {} (UTC).
+It watches: {}.
+
+You won't be notified again until the alert is resolved.
+
+--
+Eloquentlog
+{}
+"#,
+            rule.name,
+            alert.created_at.format("%Y-%m-%d %H:%M:%S"),
+            describe(rule),
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use lettre::smtp::response::{Category, Code, Detail, Severity};
+
+    use crate::model::alert::AlertState;
+    use crate::model::alert_rule::data::ALERT_RULES;
+    use crate::model::message::LogLevel;
+    use crate::model::test::run;
+
+    include!("./mock_transport.rs");
+
+    #[test]
+    fn test_describe() {
+        let mut rule = ALERT_RULES.get("oswald's errors").unwrap().clone();
+        assert_eq!(
+            describe(&rule),
+            "2 or more error (or higher) messages in 300 seconds"
+        );
+
+        rule.condition = AlertCondition::Absence;
+        rule.level = None;
+        rule.code = Some("E1".to_string());
+        assert_eq!(describe(&rule), "no messages with code E1 in 300 seconds");
+
+        rule.level = Some(LogLevel::Critical);
+        assert_eq!(
+            describe(&rule),
+            "no critical (or higher) messages with code E1 in 300 seconds"
+        );
+    }
+
+    #[test]
+    fn test_send_alert_email() {
+        run(|_, config, logger| {
+            let rule = ALERT_RULES.get("oswald's errors").unwrap().clone();
+            let alert = Alert {
+                id: 1,
+                uuid: uuid::Uuid::new_v4(),
+                alert_rule_id: rule.id,
+                state: AlertState::Firing,
+                occurrences: 1,
+                notified_at: None,
+                acknowledged_by: None,
+                acknowledged_at: None,
+                resolved_at: None,
+                created_at: rule.created_at,
+                updated_at: rule.created_at,
+            };
+
+            let code = Code::new(
+                Severity::PositiveCompletion,
+                Category::MailSystem,
+                Detail::Zero,
+            );
+            let transport = MockTransport::new(code, vec![]);
+
+            let mut mailer = AlertMailer::new(config, logger);
+            mailer.inject(Some(Box::new(transport)));
+            let result = mailer
+                .to((&rule.target, ""))
+                .send_alert_email(&rule, &alert);
+            assert!(result.is_ok());
+        })
+    }
+}
@@ -1,5 +1,6 @@
 //! Mailer sends email.
 
+pub mod alert;
 pub mod user;
 
 use lettre::{
@@ -15,6 +16,8 @@ use slog::Logger;
 
 use crate::config::Config;
 
+pub use lettre::smtp::error::Error as MailerError;
+
 struct Header<'a> {
     from: (&'a str, &'a str),
     to: (&'a str, &'a str),
@@ -84,7 +87,9 @@ impl<'a> Mailer<'a> {
     /// Transports an email.
     ///
     /// `lettre_email::Email` implements Into<lettre::SenderableEmail>.
-    pub fn send(&mut self, email: SendableEmail) -> bool {
+    /// A transient error (e.g. 4xx reply, connection failure) may succeed if
+    /// it's sent again later, but a permanent one (5xx reply) won't.
+    pub fn send(&mut self, email: SendableEmail) -> Result<(), MailerError> {
         let result;
         if let Some(ref mut c) = self.client {
             result = c.send(email);
@@ -95,7 +100,7 @@ impl<'a> Mailer<'a> {
         if let Err(ref e) = result {
             error!(self.logger, "err: {}", e);
         }
-        result.is_ok()
+        result.map(|_| ())
     }
 }
 
@@ -137,7 +142,8 @@ mod test {
                 "id".to_string(),
                 b"Hello, world!".to_vec(),
             );
-            assert!(!mailer.send(email));
+            let result = mailer.send(email);
+            assert!(matches!(result, Err(MailerError::Transient(_))));
         })
     }
 
@@ -167,7 +173,7 @@ mod test {
                 "id".to_string(),
                 b"Hello, world!".to_vec(),
             );
-            assert!(mailer.send(email));
+            assert!(mailer.send(email).is_ok());
         })
     }
 }
@@ -5,7 +5,8 @@ use lettre_email::Email;
 use slog::Logger;
 
 use crate::config::Config;
-use crate::mailer::{Client, Header, Mailer};
+use crate::export::LINK_DURATION;
+use crate::mailer::{Client, Header, Mailer, MailerError};
 
 /// UserMailer is a wrapper handles email to user.
 ///
@@ -43,7 +44,7 @@ use crate::mailer::{Client, Header, Mailer};
 /// let result = mailer
 ///     .to(("postmaster@eloquentlog.com", "Name"))
 ///     .send_user_activation_email(s, t);
-/// assert!(result);
+/// assert!(result.is_ok());
 /// #
 /// # }
 /// ```
@@ -84,7 +85,11 @@ impl<'a> UserMailer<'a> {
     }
 
     /// Builds an user activation message and send it via actual mailer.
-    pub fn send_user_activation_email(&mut self, s: &str, t: &str) -> bool {
+    pub fn send_user_activation_email(
+        &mut self,
+        s: &str,
+        t: &str,
+    ) -> Result<(), MailerError> {
         let url = self.config.application_url.to_string();
         // TODO: build it with rocket::http::uri::Origin?
         let activation_url = format!("{}/user/activate?s={}&t={}", url, s, t);
@@ -119,7 +124,11 @@ Eloquentlog
     }
 
     /// Builds a password reset message and send it via actual mailer.
-    pub fn send_password_reset_email(&mut self, s: &str, t: &str) -> bool {
+    pub fn send_password_reset_email(
+        &mut self,
+        s: &str,
+        t: &str,
+    ) -> Result<(), MailerError> {
         let url = self.config.application_url.to_string();
         // TODO: build it with rocket::http::uri::Origin?
         let reset_url = format!("{}/password/reset?s={}&t={}", url, s, t);
@@ -154,4 +163,126 @@ Eloquentlog
             .unwrap();
         self.mailer.send(email.into())
     }
+
+    /// Builds an email verification message for an additional address and
+    /// send it via actual mailer.
+    pub fn send_user_email_verification_email(
+        &mut self,
+        s: &str,
+        t: &str,
+    ) -> Result<(), MailerError> {
+        let url = self.config.application_url.to_string();
+        // TODO: build it with rocket::http::uri::Origin?
+        let verification_url = format!("{}/email/verify?s={}&t={}", url, s, t);
+
+        let subject = "Verify your email address";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+This email address has been added to an Eloquentlog account.
+To verify it, just follow the link below
+
+{}
+
+If you did not add this address, disregard this email and no action will be taken.
+
+Happy logging !-)
+
+--
+Eloquentlog
+{}
+"#,
+            verification_url, url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a message to confirm the account deletion and send it via
+    /// actual mailer.
+    pub fn send_user_deregistration_email(
+        &mut self,
+    ) -> Result<(), MailerError> {
+        let url = self.config.application_url.to_string();
+
+        let subject = "Your account has been deleted";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+Your Eloquentlog account has been deleted as requested.
+All of your access tokens have been revoked, and you have left all the namespaces.
+
+If you did not request this, please contact us by replying to this email.
+
+Thank you for using Eloquentlog.
+
+--
+Eloquentlog
+{}
+"#,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a message with a link to download exported messages and send
+    /// it via actual mailer.
+    pub fn send_message_export_email(
+        &mut self,
+        name: &str,
+        t: &str,
+        count: usize,
+    ) -> Result<(), MailerError> {
+        let url = self.config.application_url.to_string();
+        // TODO: build it with rocket::http::uri::Origin?
+        let download_url =
+            format!("{}/message/export/download/{}?t={}", url, name, t);
+
+        let subject = "Your export is ready";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+The export you have requested is ready ({} messages).
+To download it, just follow the link below
+
+{}
+
+The link expires in {} hours.
+
+Happy logging !-)
+
+--
+Eloquentlog
+{}
+"#,
+            count, download_url, LINK_DURATION, url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
 }
@@ -0,0 +1,206 @@
+//! # Metrics
+//!
+//! Metrics of the server and the worker in Prometheus text format. They are
+//! registered into the default registry of the process, so that each process
+//! exposes only its own ones (the server on the metrics route and the worker
+//! on `Config::worker_metrics_address`).
+use std::time::{Duration, Instant};
+
+use prometheus::{
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, TextEncoder,
+    register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge_vec,
+};
+use rocket::{Data, Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+// The route label of requests which don't match any route (e.g. 404). Paths
+// are not used as labels to keep the number of series small.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+lazy_static! {
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "eloquentlog_http_requests_total",
+        "The number of HTTP requests by route, method and status.",
+        &["route", "method", "status"]
+    )
+    .unwrap();
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "eloquentlog_http_request_duration_seconds",
+        "The latency of HTTP requests by route and method.",
+        &["route", "method"]
+    )
+    .unwrap();
+    static ref POOL_CONNECTIONS: IntGaugeVec = register_int_gauge_vec!(
+        "eloquentlog_pool_connections",
+        "The number of connections in the pool by state (idle, in_use or max).",
+        &["pool", "state"]
+    )
+    .unwrap();
+    static ref QUEUE_LENGTH: IntGaugeVec = register_int_gauge_vec!(
+        "eloquentlog_job_queue_length",
+        "The number of jobs waiting in the queue (including retry, delayed, \
+         postponed and dead ones).",
+        &["queue"]
+    )
+    .unwrap();
+    // Not labeled by stream, as the number of streams is unbounded (see
+    // STREAM_MESSAGES for the counts per stream)
+    static ref MESSAGES_INGESTED: IntCounter = register_int_counter!(
+        "eloquentlog_messages_ingested_total",
+        "The number of messages saved (appended or imported)."
+    )
+    .unwrap();
+    // Only the top streams are labeled, and set on scrape from the database
+    static ref STREAM_MESSAGES: IntGaugeVec = register_int_gauge_vec!(
+        "eloquentlog_stream_messages_ingested_last_hour",
+        "The number of messages saved in the last hour on the most active \
+         streams.",
+        &["stream"]
+    )
+    .unwrap();
+    static ref JOBS_PROCESSED: IntCounterVec = register_int_counter_vec!(
+        "eloquentlog_jobs_processed_total",
+        "The number of jobs processed by queue and kind.",
+        &["queue", "kind"]
+    )
+    .unwrap();
+    static ref JOBS_FAILED: IntCounterVec = register_int_counter_vec!(
+        "eloquentlog_jobs_failed_total",
+        "The number of jobs failed by queue and kind.",
+        &["queue", "kind"]
+    )
+    .unwrap();
+    static ref JOB_DURATION: HistogramVec = register_histogram_vec!(
+        "eloquentlog_job_duration_seconds",
+        "The duration of jobs by queue and kind.",
+        &["queue", "kind"]
+    )
+    .unwrap();
+}
+
+/// Sets the utilization of the connection pool.
+pub fn set_pool_connections(
+    pool: &str,
+    connections: u32,
+    idle_connections: u32,
+    max_size: u32,
+) {
+    let in_use = connections.saturating_sub(idle_connections);
+    for (state, v) in &[
+        ("idle", idle_connections),
+        ("in_use", in_use),
+        ("max", max_size),
+    ] {
+        POOL_CONNECTIONS
+            .with_label_values(&[pool, state])
+            .set(i64::from(*v));
+    }
+}
+
+pub fn set_queue_length(queue: &str, length: usize) {
+    QUEUE_LENGTH.with_label_values(&[queue]).set(length as i64);
+}
+
+pub fn inc_messages_ingested(count: u64) {
+    MESSAGES_INGESTED.inc_by(count);
+}
+
+/// Replaces the counts of messages per stream. Streams which are not given
+/// anymore are removed, so that the number of series stays bounded.
+pub fn set_stream_messages(counts: &[(&str, i64)]) {
+    STREAM_MESSAGES.reset();
+    for (stream, count) in counts {
+        STREAM_MESSAGES.with_label_values(&[stream]).set(*count);
+    }
+}
+
+/// Records a job which has been processed (or failed) in the duration.
+pub fn observe_job(queue: &str, kind: &str, failed: bool, duration: Duration) {
+    JOBS_PROCESSED.with_label_values(&[queue, kind]).inc();
+    if failed {
+        JOBS_FAILED.with_label_values(&[queue, kind]).inc();
+    }
+    JOB_DURATION
+        .with_label_values(&[queue, kind])
+        .observe(duration.as_secs_f64());
+}
+
+/// Returns all the metrics in the text format.
+pub fn gather() -> String {
+    let mut buf = vec![];
+    let encoder = TextEncoder::new();
+    if encoder.encode(&prometheus::gather(), &mut buf).is_err() {
+        return "".to_string();
+    }
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+// The time when the request has arrived
+struct RequestStart(Option<Instant>);
+
+/// MetricsFairing records the number of requests and their latency per
+/// route.
+pub struct MetricsFairing;
+
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, req: &mut Request, _: &Data) {
+        let _ = req.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    fn on_response(&self, req: &Request, res: &mut Response) {
+        let route = req
+            .route()
+            .map(|r| r.uri.path().to_string())
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let method = req.method().as_str();
+        let status = res.status().code.to_string();
+
+        HTTP_REQUESTS
+            .with_label_values(&[&route, method, &status])
+            .inc();
+        if let Some(start) = req.local_cache(|| RequestStart(None)).0 {
+            HTTP_REQUEST_DURATION
+                .with_label_values(&[&route, method])
+                .observe(start.elapsed().as_secs_f64());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gather() {
+        set_pool_connections("test", 3, 1, 4);
+        inc_messages_ingested(2);
+        observe_job("test", "Test", true, Duration::from_secs(1));
+        set_stream_messages(&[("a", 3), ("b", 1)]);
+        set_stream_messages(&[("b", 2)]);
+
+        let s = gather();
+        assert!(s.contains(
+            "eloquentlog_pool_connections{pool=\"test\",state=\"in_use\"} 2"
+        ));
+        assert!(s.contains("eloquentlog_messages_ingested_total "));
+        assert!(s.contains(
+            "eloquentlog_stream_messages_ingested_last_hour{stream=\"b\"} 2"
+        ));
+        assert!(!s.contains(
+            "eloquentlog_stream_messages_ingested_last_hour{stream=\"a\"}"
+        ));
+        assert!(s.contains(
+            "eloquentlog_jobs_failed_total{kind=\"Test\",queue=\"test\"} 1"
+        ));
+    }
+}
@@ -16,6 +16,9 @@ pub use crate::model::token::Claims;
 pub use crate::schema::access_tokens;
 
 use crate::logger::Logger;
+use crate::model::access_token_state_history::{
+    AccessTokenStateHistory, NewAccessTokenStateHistory,
+};
 use crate::model::user::User;
 use crate::util::generate_random_hash;
 
@@ -52,6 +55,35 @@ impl<'a> From<&'a User> for NewAccessToken {
     }
 }
 
+/// AccessTokenStateError
+#[derive(Debug, PartialEq)]
+pub enum AccessTokenStateError {
+    NotFound,
+    Revoked,
+    InvalidTransition,
+    Unexpected,
+}
+
+impl fmt::Display for AccessTokenStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::NotFound => write!(f, "not found"),
+            Self::Revoked => write!(f, "revoked"),
+            Self::InvalidTransition => write!(f, "invalid transition"),
+            Self::Unexpected => write!(f, "unexpected error"),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for AccessTokenStateError {
+    fn from(e: diesel::result::Error) -> Self {
+        match e {
+            diesel::result::Error::NotFound => Self::NotFound,
+            _ => Self::Unexpected,
+        }
+    }
+}
+
 type AllColumns = (
     access_tokens::id,
     access_tokens::uuid,
@@ -202,6 +234,27 @@ impl AccessToken {
         }
     }
 
+    /// Returns an access token owned by the user even if it's revoked.
+    pub fn owned_by_uuid_including_revoked(
+        user: &User,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let with_uuid = Self::with_uuid(&uuid);
+        let q = Self::by_user(user).filter(with_uuid).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn generate_token() -> String {
         generate_random_hash(HASH_SOURCE, HASH_LENGTH)
     }
@@ -249,8 +302,64 @@ impl AccessToken {
         }
     }
 
+    /// Changes the state with a check of the transition, and records the
+    /// change by the user into histories.
+    pub fn change_state(
+        &self,
+        state: AccessTokenState,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<AccessTokenState, AccessTokenStateError> {
+        if self.revoked_at.is_some() {
+            return Err(AccessTokenStateError::Revoked);
+        }
+
+        if !self.state.can_transition_to(&state) {
+            return Err(AccessTokenStateError::InvalidTransition);
+        }
+
+        // the state might have been changed since it was loaded
+        let q = diesel::update(
+            access_tokens::table
+                .filter(access_tokens::id.eq(self.id))
+                .filter(access_tokens::state.eq(&self.state))
+                .filter(access_tokens::revoked_at.is_null()),
+        )
+        .set(access_tokens::state.eq(&state));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let access_token = match q.get_result::<Self>(conn) {
+            Err(diesel::result::Error::NotFound) => {
+                error!(logger, "err: state of {} has been changed", self);
+                return Err(AccessTokenStateError::InvalidTransition);
+            },
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                return Err(AccessTokenStateError::Unexpected);
+            },
+            Ok(t) => t,
+        };
+
+        let h = NewAccessTokenStateHistory {
+            access_token_id: self.id,
+            user_id: user.id,
+            from_state: self.state.clone(),
+            to_state: access_token.state.clone(),
+            revoked: false,
+        };
+        match AccessTokenStateHistory::insert(&h, conn, logger) {
+            None => Err(AccessTokenStateError::Unexpected),
+            Some(_) => Ok(access_token.state),
+        }
+    }
+
+    /// Revokes the access token, and records the revocation by the user into
+    /// histories.
     pub fn revoke(
         &self,
+        user: &User,
         conn: &PgConnection,
         logger: &Logger,
     ) -> Result<Self, &'static str> {
@@ -273,15 +382,87 @@ impl AccessToken {
 
         info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
 
-        match q.get_result::<Self>(conn) {
+        let access_token = match q.get_result::<Self>(conn) {
             Err(e) => {
                 error!(logger, "err: {}", e);
-                Err("failed to change state")
+                return Err("failed to change state");
             },
-            Ok(access_token) => Ok(access_token),
+            Ok(access_token) => access_token,
+        };
+
+        let h = NewAccessTokenStateHistory {
+            access_token_id: self.id,
+            user_id: user.id,
+            from_state: self.state.clone(),
+            to_state: access_token.state.clone(),
+            revoked: true,
+        };
+        match AccessTokenStateHistory::insert(&h, conn, logger) {
+            None => Err("failed to record revocation"),
+            Some(_) => Ok(access_token),
         }
     }
 
+    /// Revokes all the (not revoked yet) access tokens of the user at once,
+    /// and records each revocation into histories.
+    pub fn revoke_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let q = access_tokens::table
+            .select((access_tokens::id, access_tokens::state))
+            .filter(Self::with_type(AgentType::Person))
+            .filter(Self::with_user(user))
+            .filter(Self::visible());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let targets = match q.load::<(i64, AccessTokenState)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                return Err("failed to revoke");
+            },
+            Ok(v) => v,
+        };
+
+        let q = diesel::update(
+            access_tokens::table
+                .filter(Self::with_type(AgentType::Person))
+                .filter(Self::with_user(user))
+                .filter(Self::visible()),
+        )
+        .set((
+            access_tokens::state.eq(AccessTokenState::Disabled),
+            access_tokens::token.eq(None::<Vec<u8>>),
+            access_tokens::revoked_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let n = match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                return Err("failed to revoke");
+            },
+            Ok(n) => n,
+        };
+
+        for (id, state) in targets {
+            let h = NewAccessTokenStateHistory {
+                access_token_id: id,
+                user_id: user.id,
+                from_state: state,
+                to_state: AccessTokenState::Disabled,
+                revoked: true,
+            };
+            if AccessTokenStateHistory::insert(&h, conn, logger).is_none() {
+                return Err("failed to record revocation");
+            }
+        }
+        Ok(n)
+    }
+
     pub fn visible() -> Visible {
         access_tokens::revoked_at.is_null()
     }
@@ -475,6 +656,208 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_owned_by_uuid_including_revoked() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let access_token = diesel::insert_into(access_tokens::table)
+                .values((
+                    access_tokens::uuid.eq(Uuid::new_v4()),
+                    access_tokens::agent_id.eq(user.id),
+                    access_tokens::agent_type.eq(AgentType::Person),
+                    access_tokens::name.eq("name"),
+                    access_tokens::state.eq(AccessTokenState::Disabled),
+                    access_tokens::revoked_at.eq(Utc::now().naive_utc()),
+                ))
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let uuid = access_token.uuid.to_string();
+
+            let result = AccessToken::owned_by_uuid(&user, &uuid, conn, logger);
+            assert!(result.is_none());
+
+            let result = AccessToken::owned_by_uuid_including_revoked(
+                &user, &uuid, conn, logger,
+            );
+            assert_eq!(Some(access_token), result);
+        });
+    }
+
+    #[test]
+    fn test_change_state() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let access_token = diesel::insert_into(access_tokens::table)
+                .values((
+                    access_tokens::uuid.eq(Uuid::new_v4()),
+                    access_tokens::agent_id.eq(user.id),
+                    access_tokens::agent_type.eq(AgentType::Person),
+                    access_tokens::name.eq("name"),
+                    access_tokens::state.eq(AccessTokenState::Disabled),
+                ))
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = access_token.change_state(
+                AccessTokenState::Enabled,
+                &user,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Ok(AccessTokenState::Enabled));
+
+            let histories = AccessTokenStateHistory::find_all_by_access_token(
+                &access_token,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(histories.len(), 1);
+            assert_eq!(histories[0].user_id, user.id);
+            assert_eq!(histories[0].from_state, AccessTokenState::Disabled);
+            assert_eq!(histories[0].to_state, AccessTokenState::Enabled);
+
+            // stale state (it has already been enabled)
+            let result = access_token.change_state(
+                AccessTokenState::Enabled,
+                &user,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Err(AccessTokenStateError::InvalidTransition));
+        });
+    }
+
+    #[test]
+    fn test_change_state_of_revoked_access_token() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let access_token = diesel::insert_into(access_tokens::table)
+                .values((
+                    access_tokens::uuid.eq(Uuid::new_v4()),
+                    access_tokens::agent_id.eq(user.id),
+                    access_tokens::agent_type.eq(AgentType::Person),
+                    access_tokens::name.eq("name"),
+                    access_tokens::state.eq(AccessTokenState::Disabled),
+                    access_tokens::revoked_at.eq(Utc::now().naive_utc()),
+                ))
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = access_token.change_state(
+                AccessTokenState::Enabled,
+                &user,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Err(AccessTokenStateError::Revoked));
+
+            let state = access_tokens::table
+                .select(access_tokens::state)
+                .filter(access_tokens::id.eq(access_token.id))
+                .first::<AccessTokenState>(conn)
+                .expect("Failed to get a record");
+            assert_eq!(state, AccessTokenState::Disabled);
+        });
+    }
+
+    #[test]
+    fn test_revoke() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let access_token = diesel::insert_into(access_tokens::table)
+                .values((
+                    access_tokens::uuid.eq(Uuid::new_v4()),
+                    access_tokens::agent_id.eq(user.id),
+                    access_tokens::agent_type.eq(AgentType::Person),
+                    access_tokens::name.eq("name"),
+                    access_tokens::state.eq(AccessTokenState::Enabled),
+                ))
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = access_token.revoke(&user, conn, logger);
+            assert!(result.is_ok());
+
+            let revoked = result.unwrap();
+            assert!(revoked.revoked_at.is_some());
+            assert_eq!(revoked.state, AccessTokenState::Disabled);
+
+            let histories = AccessTokenStateHistory::find_all_by_access_token(
+                &access_token,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(histories.len(), 1);
+            assert_eq!(histories[0].user_id, user.id);
+            assert_eq!(histories[0].from_state, AccessTokenState::Enabled);
+            assert_eq!(histories[0].to_state, AccessTokenState::Disabled);
+            assert!(histories[0].revoked);
+        });
+    }
+
+    #[test]
+    fn test_revoke_all_by_user() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let access_token = diesel::insert_into(access_tokens::table)
+                .values((
+                    access_tokens::uuid.eq(Uuid::new_v4()),
+                    access_tokens::agent_id.eq(user.id),
+                    access_tokens::agent_type.eq(AgentType::Person),
+                    access_tokens::name.eq("name"),
+                    access_tokens::state.eq(AccessTokenState::Enabled),
+                ))
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = AccessToken::revoke_all_by_user(&user, conn, logger);
+            assert_eq!(result, Ok(1));
+
+            let histories = AccessTokenStateHistory::find_all_by_access_token(
+                &access_token,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(histories.len(), 1);
+            assert_eq!(histories[0].from_state, AccessTokenState::Enabled);
+            assert_eq!(histories[0].to_state, AccessTokenState::Disabled);
+            assert!(histories[0].revoked);
+
+            // already revoked ones are not recorded again
+            let result = AccessToken::revoke_all_by_user(&user, conn, logger);
+            assert_eq!(result, Ok(0));
+        });
+    }
+
     #[test]
     fn test_insert() {
         run(|conn, _, logger| {
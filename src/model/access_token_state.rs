@@ -90,6 +90,17 @@ impl AccessTokenState {
     pub fn is_enabled(&self) -> bool {
         self == &AccessTokenState::Enabled
     }
+
+    /// Checks whether the state can be changed into the given state.
+    ///
+    /// Only a change between disabled and enabled is allowed. A change into
+    /// the same state is not a transition.
+    pub fn can_transition_to(&self, state: &AccessTokenState) -> bool {
+        matches!(
+            (self, state),
+            (Self::Disabled, Self::Enabled) | (Self::Enabled, Self::Disabled)
+        )
+    }
 }
 
 #[cfg(test)]
@@ -128,4 +139,17 @@ mod test {
             AccessTokenState::as_vec()
         )
     }
+
+    #[test]
+    fn test_can_transition_to() {
+        assert!(AccessTokenState::Disabled
+            .can_transition_to(&AccessTokenState::Enabled));
+        assert!(AccessTokenState::Enabled
+            .can_transition_to(&AccessTokenState::Disabled));
+
+        assert!(!AccessTokenState::Disabled
+            .can_transition_to(&AccessTokenState::Disabled));
+        assert!(!AccessTokenState::Enabled
+            .can_transition_to(&AccessTokenState::Enabled));
+    }
 }
@@ -0,0 +1,155 @@
+//! # Access Token State History
+//!
+//! AccessTokenStateHistory records who changed the state of an AccessToken
+//! (or revoked it) and when.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+
+pub use crate::model::access_token_state::*;
+pub use crate::schema::access_token_state_histories;
+
+use crate::logger::Logger;
+use crate::model::access_token::AccessToken;
+
+/// NewAccessTokenStateHistory
+#[derive(Debug)]
+pub struct NewAccessTokenStateHistory {
+    pub access_token_id: i64,
+    pub user_id: i64,
+    pub from_state: AccessTokenState,
+    pub to_state: AccessTokenState,
+    pub revoked: bool,
+}
+
+/// AccessTokenStateHistory
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(AccessToken)]
+#[table_name = "access_token_state_histories"]
+pub struct AccessTokenStateHistory {
+    pub id: i64,
+    pub access_token_id: i64,
+    pub user_id: i64,
+    pub from_state: AccessTokenState,
+    pub to_state: AccessTokenState,
+    pub revoked: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl fmt::Display for AccessTokenStateHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<AccessTokenStateHistory {from} -> {to}{revoked}>",
+            from = &self.from_state,
+            to = &self.to_state,
+            revoked = if self.revoked { " (revoked)" } else { "" },
+        )
+    }
+}
+
+impl AccessTokenStateHistory {
+    pub fn find_all_by_access_token(
+        access_token: &AccessToken,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::belonging_to(access_token)
+            .order(access_token_state_histories::created_at.desc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        history: &NewAccessTokenStateHistory,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(access_token_state_histories::table)
+            .values((
+                access_token_state_histories::access_token_id
+                    .eq(history.access_token_id),
+                access_token_state_histories::user_id.eq(history.user_id),
+                access_token_state_histories::from_state
+                    .eq(&history.from_state),
+                access_token_state_histories::to_state.eq(&history.to_state),
+                access_token_state_histories::revoked.eq(history.revoked),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(h) => Some(h),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::access_token::{AgentType, access_tokens};
+    use crate::model::user::{User, users};
+
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_insert() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let access_token = diesel::insert_into(access_tokens::table)
+                .values((
+                    access_tokens::agent_id.eq(user.id),
+                    access_tokens::agent_type.eq(AgentType::Person),
+                    access_tokens::name.eq("name"),
+                    access_tokens::state.eq(AccessTokenState::Disabled),
+                ))
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let h = NewAccessTokenStateHistory {
+                access_token_id: access_token.id,
+                user_id: user.id,
+                from_state: AccessTokenState::Disabled,
+                to_state: AccessTokenState::Enabled,
+                revoked: false,
+            };
+            let result = AccessTokenStateHistory::insert(&h, conn, logger);
+            assert!(result.is_some());
+
+            let histories = AccessTokenStateHistory::find_all_by_access_token(
+                &access_token,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(histories.len(), 1);
+
+            let history = &histories[0];
+            assert_eq!(history.user_id, user.id);
+            assert_eq!(history.from_state, AccessTokenState::Disabled);
+            assert_eq!(history.to_state, AccessTokenState::Enabled);
+            assert!(!history.revoked);
+        })
+    }
+}
@@ -0,0 +1,368 @@
+//! # Alert
+//!
+//! Alert is opened when an AlertRule fires, and it stays open (firing or
+//! acknowledged) until the rule stops firing. While it's open, further
+//! firings are only counted as occurrences, so that it's notified just once.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use uuid::Uuid;
+
+pub use crate::model::alert_state::*;
+pub use crate::schema::alerts;
+
+use crate::logger::Logger;
+use crate::model::alert_rule::{AlertRule, alert_rules};
+use crate::model::stream::streams;
+use crate::model::user::User;
+
+/// Alert
+#[derive(Associations, Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[belongs_to(AlertRule)]
+#[table_name = "alerts"]
+pub struct Alert {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub alert_rule_id: i64,
+    pub state: AlertState,
+    pub occurrences: i32,
+    pub notified_at: Option<NaiveDateTime>,
+    pub acknowledged_by: Option<i64>,
+    pub acknowledged_at: Option<NaiveDateTime>,
+    pub resolved_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Alert {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl Alert {
+    pub fn is_open(&self) -> bool {
+        self.state != AlertState::Resolved
+    }
+
+    pub fn find_open_by_rule(
+        rule: &AlertRule,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::belonging_to(rule)
+            .filter(alerts::state.ne(AlertState::Resolved))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = alerts::table
+            .inner_join(alert_rules::table.inner_join(streams::table))
+            .select(alerts::all_columns)
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(alerts::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns alerts in the namespace (the newest one comes first) together
+    /// with their rule.
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, AlertRule)>> {
+        if namespace_id < 1 {
+            return None;
+        }
+
+        let q = alerts::table
+            .inner_join(alert_rules::table.inner_join(streams::table))
+            .select((alerts::all_columns, alert_rules::all_columns))
+            .filter(streams::namespace_id.eq(namespace_id))
+            .order(alerts::id.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, AlertRule)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns firing alerts which have not been notified yet (on the stream
+    /// if it's given) together with their rule.
+    pub fn find_all_to_notify(
+        stream_id: Option<i64>,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, AlertRule)>> {
+        let mut q = alerts::table
+            .inner_join(alert_rules::table)
+            .select((alerts::all_columns, alert_rules::all_columns))
+            .filter(alerts::state.eq(AlertState::Firing))
+            .filter(alerts::notified_at.is_null())
+            .into_boxed();
+        if let Some(id) = stream_id {
+            q = q.filter(alert_rules::stream_id.eq(id));
+        }
+        let q = q.order(alerts::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, AlertRule)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Opens a new alert for the rule, or counts an occurrence on the open
+    /// one. Returns the alert and whether it's new.
+    ///
+    /// This should be called within a transaction.
+    pub fn fire(
+        rule: &AlertRule,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(Self, bool), &'static str> {
+        if let Some(alert) = Self::find_open_by_rule(rule, conn, logger) {
+            let q = diesel::update(&alert).set((
+                alerts::occurrences.eq(alerts::occurrences + 1),
+                alerts::updated_at.eq(now),
+            ));
+
+            info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+            return q.get_result::<Self>(conn).map(|a| (a, false)).map_err(
+                |e| {
+                    error!(logger, "err: {}", e);
+                    "failed to update"
+                },
+            );
+        }
+
+        let q = diesel::insert_into(alerts::table).values((
+            alerts::alert_rule_id.eq(rule.id),
+            alerts::state.eq(AlertState::Firing),
+            alerts::created_at.eq(now),
+            alerts::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map(|a| (a, true)).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to insert"
+        })
+    }
+
+    /// Resolves the open alert of the rule (if any), and returns it.
+    pub fn resolve(
+        rule: &AlertRule,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Option<Self>, &'static str> {
+        let q = diesel::update(
+            Self::belonging_to(rule)
+                .filter(alerts::state.ne(AlertState::Resolved)),
+        )
+        .set((
+            alerts::state.eq(AlertState::Resolved),
+            alerts::resolved_at.eq(now),
+            alerts::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).optional().map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to resolve"
+        })
+    }
+
+    /// Acknowledges the firing alert. It won't be notified (if it has not
+    /// been yet), but it stays open until the rule stops firing.
+    pub fn acknowledge(
+        &self,
+        user: &User,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if self.state != AlertState::Firing {
+            return Err("not firing");
+        }
+
+        let q = diesel::update(self).set((
+            alerts::state.eq(AlertState::Acknowledged),
+            alerts::acknowledged_by.eq(user.id),
+            alerts::acknowledged_at.eq(now),
+            alerts::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to acknowledge"
+        })
+    }
+
+    pub fn mark_as_notified(
+        &self,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self)
+            .set((alerts::notified_at.eq(now), alerts::updated_at.eq(now)));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update"
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Utc;
+
+    use crate::model::alert_rule::NewAlertRule;
+    use crate::model::message::LogLevel;
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::Stream;
+    use crate::model::user::users;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+    use crate::model::user::data::USERS;
+
+    fn insert_rule(conn: &PgConnection, logger: &Logger) -> AlertRule {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        let stream = diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let r = NewAlertRule {
+            stream_id: stream.id,
+            name: "errors".to_string(),
+            level: Some(LogLevel::Error),
+            target: "oswald@example.org".to_string(),
+
+            ..Default::default()
+        };
+        AlertRule::insert(&r, conn, logger).unwrap()
+    }
+
+    #[test]
+    fn test_fire_and_resolve() {
+        run(|conn, _, logger| {
+            let rule = insert_rule(conn, logger);
+            let now = Utc::now().naive_utc();
+
+            let (alert, opened) =
+                Alert::fire(&rule, now, conn, logger).unwrap();
+            assert!(opened);
+            assert_eq!(alert.state, AlertState::Firing);
+
+            // de-duplicated
+            let (a, opened) = Alert::fire(&rule, now, conn, logger).unwrap();
+            assert!(!opened);
+            assert_eq!(a.id, alert.id);
+            assert_eq!(a.occurrences, 2);
+
+            let alerts = Alert::find_all_to_notify(None, conn, logger).unwrap();
+            assert_eq!(alerts.len(), 1);
+
+            let resolved = Alert::resolve(&rule, now, conn, logger).unwrap();
+            assert_eq!(resolved.map(|a| a.state), Some(AlertState::Resolved));
+            assert!(Alert::resolve(&rule, now, conn, logger)
+                .unwrap()
+                .is_none());
+            assert!(Alert::find_open_by_rule(&rule, conn, logger).is_none());
+
+            // opens another one
+            let (a, opened) = Alert::fire(&rule, now, conn, logger).unwrap();
+            assert!(opened);
+            assert_ne!(a.id, alert.id);
+        })
+    }
+
+    #[test]
+    fn test_acknowledge() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let rule = insert_rule(conn, logger);
+            let now = Utc::now().naive_utc();
+
+            let (alert, _) = Alert::fire(&rule, now, conn, logger).unwrap();
+            let alert = alert.acknowledge(&user, now, conn, logger).unwrap();
+            assert_eq!(alert.state, AlertState::Acknowledged);
+            assert_eq!(alert.acknowledged_by, Some(user.id));
+            assert!(alert.is_open());
+
+            // only once
+            assert!(alert.acknowledge(&user, now, conn, logger).is_err());
+
+            let alerts = Alert::find_all_to_notify(None, conn, logger).unwrap();
+            assert!(alerts.is_empty());
+        })
+    }
+}
@@ -0,0 +1,110 @@
+//! # A type AlertChannel for AlertRule in alert_rule.rs
+//!
+//! EAlertChannel represents SQL type value `e_alert_channel` and AlertChannel
+//! is an Enum contains all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_alert_channel")]
+pub struct EAlertChannel;
+
+#[derive(
+    AsExpression, Clone, Debug, Deserialize, FromSqlRow, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "EAlertChannel"]
+pub enum AlertChannel {
+    Email,
+    Webhook,
+}
+
+const ALERT_CHANNELS: [AlertChannel; 2] =
+    [AlertChannel::Email, AlertChannel::Webhook];
+
+impl fmt::Display for AlertChannel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl AsRef<str> for AlertChannel {
+    fn as_ref(&self) -> &str {
+        match *self {
+            Self::Email => "email",
+            Self::Webhook => "webhook",
+        }
+    }
+}
+
+impl From<String> for AlertChannel {
+    fn from(s: String) -> Self {
+        let s = s.to_ascii_lowercase();
+        match Self::iter().find(|v| v.as_ref() == s) {
+            Some(v) => v.clone(),
+            None => AlertChannel::Email,
+        }
+    }
+}
+
+impl ToSql<EAlertChannel, Pg> for AlertChannel {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let s: &str = self.as_ref();
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EAlertChannel, Pg> for AlertChannel {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let b = not_none!(bytes);
+        match Self::iter().find(|v| v.as_ref().as_bytes() == b) {
+            Some(v) => Ok(v.clone()),
+            None => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl AlertChannel {
+    pub fn iter() -> Iter<'static, AlertChannel> {
+        ALERT_CHANNELS.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("email", format!("{}", AlertChannel::Email));
+        assert_eq!("webhook", format!("{}", AlertChannel::Webhook));
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(
+            AlertChannel::Email,
+            AlertChannel::from("email".to_string())
+        );
+        assert_eq!(
+            AlertChannel::Webhook,
+            AlertChannel::from("webhook".to_string())
+        );
+        assert_eq!(
+            AlertChannel::Email,
+            AlertChannel::from("unknown".to_string())
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! # A type AlertCondition for AlertRule in alert_rule.rs
+//!
+//! EAlertCondition represents SQL type value `e_alert_condition` and
+//! AlertCondition is an Enum contains all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_alert_condition")]
+pub struct EAlertCondition;
+
+#[derive(
+    AsExpression, Clone, Debug, Deserialize, FromSqlRow, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "EAlertCondition"]
+pub enum AlertCondition {
+    Match,
+    Threshold,
+    Absence,
+}
+
+const ALERT_CONDITIONS: [AlertCondition; 3] = [
+    AlertCondition::Match,
+    AlertCondition::Threshold,
+    AlertCondition::Absence,
+];
+
+impl fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl AsRef<str> for AlertCondition {
+    fn as_ref(&self) -> &str {
+        match *self {
+            Self::Match => "match",
+            Self::Threshold => "threshold",
+            Self::Absence => "absence",
+        }
+    }
+}
+
+impl From<String> for AlertCondition {
+    fn from(s: String) -> Self {
+        let s = s.to_ascii_lowercase();
+        match Self::iter().find(|v| v.as_ref() == s) {
+            Some(v) => v.clone(),
+            None => AlertCondition::Match,
+        }
+    }
+}
+
+impl ToSql<EAlertCondition, Pg> for AlertCondition {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let s: &str = self.as_ref();
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EAlertCondition, Pg> for AlertCondition {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let b = not_none!(bytes);
+        match Self::iter().find(|v| v.as_ref().as_bytes() == b) {
+            Some(v) => Ok(v.clone()),
+            None => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl AlertCondition {
+    pub fn iter() -> Iter<'static, AlertCondition> {
+        ALERT_CONDITIONS.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("match", format!("{}", AlertCondition::Match));
+        assert_eq!("threshold", format!("{}", AlertCondition::Threshold));
+        assert_eq!("absence", format!("{}", AlertCondition::Absence));
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(
+            AlertCondition::Match,
+            AlertCondition::from("match".to_string())
+        );
+        assert_eq!(
+            AlertCondition::Threshold,
+            AlertCondition::from("threshold".to_string())
+        );
+        assert_eq!(
+            AlertCondition::Absence,
+            AlertCondition::from("absence".to_string())
+        );
+        assert_eq!(
+            AlertCondition::Match,
+            AlertCondition::from("unknown".to_string())
+        );
+    }
+}
@@ -0,0 +1,472 @@
+//! # Alert Rule
+//!
+//! AlertRule watches messages on a stream. Messages in the window are
+//! filtered by the (minimum) level and the code, and the rule fires if the
+//! number of them reaches the threshold (`match` and `threshold`), or if
+//! there is none (`absence`). See also Alert in alert.rs.
+use std::fmt;
+
+use chrono::{Duration, NaiveDateTime};
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use uuid::Uuid;
+
+pub use crate::model::alert_channel::*;
+pub use crate::model::alert_condition::*;
+pub use crate::schema::alert_rules;
+
+use crate::logger::Logger;
+use crate::model::message::{LogLevel, NewMessage, messages};
+use crate::model::stream::{Stream, streams};
+use crate::request::alert_rule::AlertRule as RequestData;
+
+/// NewAlertRule
+#[derive(Debug)]
+pub struct NewAlertRule {
+    pub stream_id: i64,
+    pub name: String,
+    pub condition: AlertCondition,
+    pub level: Option<LogLevel>,
+    pub code: Option<String>,
+    pub threshold: i32,
+    pub window_seconds: i32,
+    pub channel: AlertChannel,
+    pub target: String,
+}
+
+impl Default for NewAlertRule {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            stream_id: -1,
+            name: "".to_string(),
+            condition: AlertCondition::Match,
+            level: None,
+            code: None,
+            threshold: 1,
+            window_seconds: 300,
+            channel: AlertChannel::Email,
+            target: "".to_string(),
+        }
+    }
+}
+
+impl From<RequestData> for NewAlertRule {
+    fn from(data: RequestData) -> Self {
+        let d = Self::default();
+        Self {
+            name: data.name.unwrap_or_default(),
+            condition: data
+                .condition
+                .map(AlertCondition::from)
+                .unwrap_or(d.condition),
+            level: data.level.map(LogLevel::from),
+            code: data.code,
+            threshold: data.threshold.unwrap_or(d.threshold),
+            window_seconds: data.window_seconds.unwrap_or(d.window_seconds),
+            channel: data.channel.map(AlertChannel::from).unwrap_or(d.channel),
+            target: data.target.unwrap_or_default(),
+
+            ..d
+        }
+    }
+}
+
+/// AlertRule
+#[derive(Associations, Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[belongs_to(Stream)]
+#[table_name = "alert_rules"]
+pub struct AlertRule {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub stream_id: i64,
+    pub name: String,
+    pub condition: AlertCondition,
+    pub level: Option<LogLevel>,
+    pub code: Option<String>,
+    pub threshold: i32,
+    pub window_seconds: i32,
+    pub channel: AlertChannel,
+    pub target: String,
+    pub muted_until: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<AlertRule {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl AlertRule {
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = alert_rules::table
+            .inner_join(streams::table)
+            .select(alert_rules::all_columns)
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(alert_rules::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns rules on streams in the namespace together with the uuid of
+    /// the stream.
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, Uuid)>> {
+        if namespace_id < 1 {
+            return None;
+        }
+
+        let q = alert_rules::table
+            .inner_join(streams::table)
+            .select((alert_rules::all_columns, streams::uuid))
+            .filter(streams::namespace_id.eq(namespace_id))
+            .order(alert_rules::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, Uuid)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns rules on the stream, or all the rules if it's not given.
+    pub fn find_all(
+        stream_id: Option<i64>,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let mut q = alert_rules::table.into_boxed();
+        if let Some(id) = stream_id {
+            q = q.filter(alert_rules::stream_id.eq(id));
+        }
+        let q = q.order(alert_rules::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        rule: &NewAlertRule,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(alert_rules::table).values((
+            alert_rules::stream_id.eq(rule.stream_id),
+            alert_rules::name.eq(&rule.name),
+            alert_rules::condition.eq(&rule.condition),
+            alert_rules::level.eq(&rule.level),
+            alert_rules::code.eq(&rule.code),
+            alert_rules::threshold.eq(rule.threshold),
+            alert_rules::window_seconds.eq(rule.window_seconds),
+            alert_rules::channel.eq(&rule.channel),
+            alert_rules::target.eq(&rule.target),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(r) => Some(r),
+        }
+    }
+
+    /// Deletes the rule. Its alerts are deleted together.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Suppresses notifications until the time (None unmutes it). Alerts are
+    /// still recorded while it's muted.
+    pub fn mute(
+        &self,
+        until: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            alert_rules::muted_until.eq(until),
+            alert_rules::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to mute"
+        })
+    }
+
+    pub fn is_muted(&self, now: NaiveDateTime) -> bool {
+        self.muted_until.map_or(false, |t| t > now)
+    }
+
+    // Returns levels which are equal to or higher than the level of the rule
+    fn levels(&self) -> Vec<LogLevel> {
+        match self.level {
+            None => LogLevel::as_vec(),
+            Some(ref level) => level.and_higher(),
+        }
+    }
+
+    /// Checks whether the message passes through the filter of the rule.
+    pub fn matches(&self, message: &NewMessage) -> bool {
+        message.stream_id == self.stream_id &&
+            self.levels().contains(&message.level) &&
+            self.code
+                .as_ref()
+                .map_or(true, |c| message.code.as_ref() == Some(c))
+    }
+
+    /// Tells whether the rule fires with the number of messages which have
+    /// passed through the filter in the window.
+    pub fn fires(&self, count: i64) -> bool {
+        match self.condition {
+            AlertCondition::Match | AlertCondition::Threshold => {
+                count >= i64::from(self.threshold)
+            },
+            AlertCondition::Absence => count == 0,
+        }
+    }
+
+    /// Counts messages which have passed through the filter in the window
+    /// until `now`, and tells whether the rule fires.
+    pub fn evaluate(
+        &self,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<bool> {
+        let since = now - Duration::seconds(i64::from(self.window_seconds));
+        let mut q = messages::table
+            .filter(messages::stream_id.eq(self.stream_id))
+            .filter(messages::created_at.gt(since))
+            .filter(messages::created_at.le(now))
+            .filter(messages::level.eq_any(self.levels()))
+            .into_boxed();
+        if let Some(ref code) = self.code {
+            q = q.filter(messages::code.eq(code));
+        }
+        let q = q.count();
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<i64>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(count) => Some(self.fires(count)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod data {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+    use fnv::FnvHashMap;
+
+    use crate::fnvhashmap;
+    use crate::model::stream::data::STREAMS;
+
+    type AlertRuleFixture = FnvHashMap<&'static str, AlertRule>;
+
+    lazy_static! {
+        pub static ref ALERT_RULES: AlertRuleFixture = fnvhashmap! {
+            "oswald's errors" => AlertRule {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                stream_id: STREAMS.get("oswald's stream").unwrap().id,
+                name: "oswald's errors".to_string(),
+                condition: AlertCondition::Threshold,
+                level: Some(LogLevel::Error),
+                code: None,
+                threshold: 2,
+                window_seconds: 300,
+                channel: AlertChannel::Email,
+                target: "oswald@example.org".to_string(),
+                muted_until: None,
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Utc;
+
+    use crate::model::test::run;
+    use crate::model::alert_rule::data::ALERT_RULES;
+    use crate::model::message::{Message, NewMessage};
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn build_message(level: LogLevel, code: Option<&str>) -> NewMessage {
+        NewMessage {
+            stream_id: STREAMS.get("oswald's stream").unwrap().id,
+            agent_id: 1,
+            level,
+            code: code.map(str::to_string),
+            title: Some("title".to_string()),
+
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut rule = ALERT_RULES.get("oswald's errors").unwrap().clone();
+
+        assert!(!rule.matches(&build_message(LogLevel::Warning, None)));
+        assert!(rule.matches(&build_message(LogLevel::Error, None)));
+        assert!(rule.matches(&build_message(LogLevel::Critical, Some("E1"))));
+
+        rule.code = Some("E1".to_string());
+        assert!(!rule.matches(&build_message(LogLevel::Error, None)));
+        assert!(rule.matches(&build_message(LogLevel::Error, Some("E1"))));
+        assert!(!rule.matches(&build_message(LogLevel::Error, Some("E2"))));
+
+        let mut m = build_message(LogLevel::Error, Some("E1"));
+        m.stream_id = 2;
+        assert!(!rule.matches(&m));
+    }
+
+    #[test]
+    fn test_fires() {
+        let mut rule = ALERT_RULES.get("oswald's errors").unwrap().clone();
+
+        assert!(!rule.fires(0));
+        assert!(!rule.fires(1));
+        assert!(rule.fires(2));
+
+        rule.condition = AlertCondition::Absence;
+        assert!(rule.fires(0));
+        assert!(!rule.fires(1));
+    }
+
+    #[test]
+    fn test_is_muted() {
+        let mut rule = ALERT_RULES.get("oswald's errors").unwrap().clone();
+        let now = Utc::now().naive_utc();
+
+        assert!(!rule.is_muted(now));
+        rule.muted_until = Some(now + Duration::minutes(1));
+        assert!(rule.is_muted(now));
+        rule.muted_until = Some(now - Duration::minutes(1));
+        assert!(!rule.is_muted(now));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let _ = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let s = STREAMS.get("oswald's stream").unwrap();
+            let stream = diesel::insert_into(streams::table)
+                .values(s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let r = NewAlertRule {
+                stream_id: stream.id,
+                name: "errors".to_string(),
+                condition: AlertCondition::Threshold,
+                level: Some(LogLevel::Error),
+                threshold: 2,
+                target: "oswald@example.org".to_string(),
+
+                ..Default::default()
+            };
+            let rule = AlertRule::insert(&r, conn, logger).unwrap();
+
+            let _ = Message::insert(
+                &build_message(LogLevel::Error, None),
+                conn,
+                logger,
+            );
+            let _ = Message::insert(
+                &build_message(LogLevel::Warning, None),
+                conn,
+                logger,
+            );
+
+            let now = Utc::now().naive_utc();
+            assert_eq!(rule.evaluate(now, conn, logger), Some(false));
+
+            let _ = Message::insert(
+                &build_message(LogLevel::Critical, None),
+                conn,
+                logger,
+            );
+
+            let now = Utc::now().naive_utc();
+            assert_eq!(rule.evaluate(now, conn, logger), Some(true));
+
+            // out of the window
+            let later = now + Duration::seconds(301);
+            assert_eq!(rule.evaluate(later, conn, logger), Some(false));
+
+            let rules = AlertRule::find_all(Some(stream.id), conn, logger);
+            assert_eq!(rules, Some(vec![rule]));
+        })
+    }
+}
@@ -0,0 +1,114 @@
+//! # A type AlertState for Alert in alert.rs
+//!
+//! EAlertState represents SQL type value `e_alert_state` and AlertState is an
+//! Enum contains all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_alert_state")]
+pub struct EAlertState;
+
+#[derive(
+    AsExpression, Clone, Debug, Deserialize, FromSqlRow, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "EAlertState"]
+pub enum AlertState {
+    Firing,
+    Acknowledged,
+    Resolved,
+}
+
+const ALERT_STATES: [AlertState; 3] = [
+    AlertState::Firing,
+    AlertState::Acknowledged,
+    AlertState::Resolved,
+];
+
+impl fmt::Display for AlertState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl AsRef<str> for AlertState {
+    fn as_ref(&self) -> &str {
+        match *self {
+            Self::Firing => "firing",
+            Self::Acknowledged => "acknowledged",
+            Self::Resolved => "resolved",
+        }
+    }
+}
+
+impl From<String> for AlertState {
+    fn from(s: String) -> Self {
+        let s = s.to_ascii_lowercase();
+        match Self::iter().find(|v| v.as_ref() == s) {
+            Some(v) => v.clone(),
+            None => AlertState::Firing,
+        }
+    }
+}
+
+impl ToSql<EAlertState, Pg> for AlertState {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let s: &str = self.as_ref();
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EAlertState, Pg> for AlertState {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let b = not_none!(bytes);
+        match Self::iter().find(|v| v.as_ref().as_bytes() == b) {
+            Some(v) => Ok(v.clone()),
+            None => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl AlertState {
+    pub fn iter() -> Iter<'static, AlertState> {
+        ALERT_STATES.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("firing", format!("{}", AlertState::Firing));
+        assert_eq!("acknowledged", format!("{}", AlertState::Acknowledged));
+        assert_eq!("resolved", format!("{}", AlertState::Resolved));
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(AlertState::Firing, AlertState::from("firing".to_string()));
+        assert_eq!(
+            AlertState::Acknowledged,
+            AlertState::from("acknowledged".to_string())
+        );
+        assert_eq!(
+            AlertState::Resolved,
+            AlertState::from("resolved".to_string())
+        );
+        assert_eq!(AlertState::Firing, AlertState::from("unknown".to_string()));
+    }
+}
@@ -0,0 +1,313 @@
+//! # Annotation
+//!
+//! Annotation is a note on a message by a member of the namespace, for
+//! collaboration on an incident. It has a label (e.g. investigating), a body
+//! and a link to a ticket, at least one of them.
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Identifiable, Queryable, debug_query, dsl, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use diesel::sql_types::BigInt;
+use uuid::Uuid;
+
+pub use crate::schema::annotations;
+
+use crate::logger::Logger;
+use crate::model::message::messages;
+use crate::model::stream::streams;
+use crate::model::user::users;
+use crate::request::annotation::Annotation as RequestData;
+
+/// NewAnnotation
+#[derive(Debug, Default, Insertable)]
+#[table_name = "annotations"]
+pub struct NewAnnotation {
+    pub message_id: i64,
+    pub user_id: i64,
+    pub label: Option<String>,
+    pub body: Option<String>,
+    pub ticket_url: Option<String>,
+}
+
+impl From<RequestData> for NewAnnotation {
+    fn from(data: RequestData) -> Self {
+        Self {
+            label: data.label,
+            body: data.body,
+            ticket_url: data.ticket_url,
+
+            ..Default::default()
+        }
+    }
+}
+
+/// Annotation
+#[derive(Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[table_name = "annotations"]
+pub struct Annotation {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub message_id: i64,
+    pub user_id: i64,
+    pub label: Option<String>,
+    pub body: Option<String>,
+    pub ticket_url: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Annotation {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl Annotation {
+    /// Returns the annotation on a message in the namespace.
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = annotations::table
+            .inner_join(messages::table.inner_join(streams::table))
+            .select(annotations::all_columns)
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(annotations::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns annotations on the message together with the uuid of the
+    /// user (the oldest one comes first).
+    pub fn find_all_by_message_id(
+        message_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, Uuid)>> {
+        let q = annotations::table
+            .inner_join(users::table)
+            .select((annotations::all_columns, users::uuid))
+            .filter(annotations::message_id.eq(message_id))
+            .order(annotations::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, Uuid)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns the number of annotations by message. Messages having none
+    /// are not included.
+    pub fn count_by_message_ids(
+        message_ids: &[i64],
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<HashMap<i64, i64>> {
+        let q = annotations::table
+            .select((annotations::message_id, dsl::sql::<BigInt>("COUNT(*)")))
+            .filter(annotations::message_id.eq_any(message_ids))
+            .group_by(annotations::message_id);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(i64, i64)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v.into_iter().collect()),
+        }
+    }
+
+    pub fn insert(
+        annotation: &NewAnnotation,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(annotations::table).values(annotation);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(a) => Some(a),
+        }
+    }
+
+    /// Replaces the label, the body and the ticket_url.
+    pub fn update(
+        &self,
+        annotation: &NewAnnotation,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::update(self).set((
+            annotations::label.eq(&annotation.label),
+            annotations::body.eq(&annotation.body),
+            annotations::ticket_url.eq(&annotation.ticket_url),
+            annotations::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(a) => Some(a),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::message::{Message, NewMessage};
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::Stream;
+    use crate::model::user::User;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+    use crate::model::user::data::USERS;
+
+    fn insert_message(conn: &PgConnection, logger: &Logger) -> (User, i64) {
+        let u = USERS.get("oswald").unwrap();
+        let user = diesel::insert_into(users::table)
+            .values(u)
+            .get_result::<User>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        let stream = diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let m = NewMessage {
+            agent_id: user.id,
+            stream_id: stream.id,
+            title: Some("connection refused".to_string()),
+
+            ..Default::default()
+        };
+        let id = Message::insert(&m, conn, logger).unwrap();
+        (user, id)
+    }
+
+    #[test]
+    fn test_find_all_by_message_id() {
+        run(|conn, _, logger| {
+            let (user, message_id) = insert_message(conn, logger);
+
+            for label in &["investigating", "identified"] {
+                let a = NewAnnotation {
+                    message_id,
+                    user_id: user.id,
+                    label: Some(label.to_string()),
+
+                    ..Default::default()
+                };
+                assert!(Annotation::insert(&a, conn, logger).is_some());
+            }
+
+            let annotations =
+                Annotation::find_all_by_message_id(message_id, conn, logger)
+                    .unwrap();
+            assert_eq!(annotations.len(), 2);
+            assert_eq!(
+                annotations[0].0.label,
+                Some("investigating".to_string())
+            );
+            assert_eq!(annotations[0].1, user.uuid);
+
+            let counts = Annotation::count_by_message_ids(
+                &[message_id, message_id + 1],
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(counts.get(&message_id), Some(&2));
+            assert_eq!(counts.get(&(message_id + 1)), None);
+        })
+    }
+
+    #[test]
+    fn test_update() {
+        run(|conn, _, logger| {
+            let (user, message_id) = insert_message(conn, logger);
+
+            let a = NewAnnotation {
+                message_id,
+                user_id: user.id,
+                label: Some("investigating".to_string()),
+
+                ..Default::default()
+            };
+            let annotation = Annotation::insert(&a, conn, logger).unwrap();
+
+            let a = NewAnnotation {
+                label: Some("resolved".to_string()),
+                ticket_url: Some("https://example.org/issues/1".to_string()),
+
+                ..Default::default()
+            };
+            let annotation = annotation.update(&a, conn, logger).unwrap();
+            assert_eq!(annotation.label, Some("resolved".to_string()));
+            assert_eq!(annotation.body, None);
+            assert_eq!(
+                annotation.ticket_url,
+                Some("https://example.org/issues/1".to_string())
+            );
+        })
+    }
+}
@@ -0,0 +1,439 @@
+//! # Audit Event
+//!
+//! AuditEvent records a security-relevant action done by (or against) a user,
+//! optionally within a namespace. Rows are append-only, old ones are purged
+//! by a maintenance job (see `AuditEvent::delete_all_before`).
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::model::audit_event_action::*;
+pub use crate::schema::audit_events;
+
+use crate::logger::Logger;
+use crate::model::user::{User, users};
+
+/// NewAuditEvent
+#[derive(Debug)]
+pub struct NewAuditEvent {
+    pub user_id: Option<i64>,
+    pub namespace_id: Option<i64>,
+    pub action: AuditEventAction,
+    pub target: Option<String>,
+}
+
+impl Default for NewAuditEvent {
+    fn default() -> Self {
+        Self {
+            user_id: None,
+            namespace_id: None,
+            action: AuditEventAction::Login,
+            target: None,
+        }
+    }
+}
+
+impl<'a> From<&'a User> for NewAuditEvent {
+    fn from(user: &'a User) -> Self {
+        Self {
+            user_id: Some(user.id),
+
+            ..Default::default()
+        }
+    }
+}
+
+type AllColumns = (
+    audit_events::id,
+    audit_events::user_id,
+    audit_events::namespace_id,
+    audit_events::action,
+    audit_events::target,
+    audit_events::created_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    audit_events::id,
+    audit_events::user_id,
+    audit_events::namespace_id,
+    audit_events::action,
+    audit_events::target,
+    audit_events::created_at,
+);
+
+/// AuditEvent
+#[derive(Debug, Identifiable, PartialEq, Queryable, Serialize)]
+#[table_name = "audit_events"]
+pub struct AuditEvent {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(skip)]
+    pub user_id: Option<i64>,
+    #[serde(skip)]
+    pub namespace_id: Option<i64>,
+    pub action: AuditEventAction,
+    pub target: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<AuditEvent {action}>", action = &self.action)
+    }
+}
+
+type All = dsl::Select<audit_events::table, AllColumns>;
+type WithUser = dsl::Eq<audit_events::user_id, i64>;
+type WithNamespace = dsl::Eq<audit_events::namespace_id, i64>;
+
+impl AuditEvent {
+    pub fn all() -> All {
+        audit_events::table.select(ALL_COLUMNS)
+    }
+
+    pub fn find_all_by_user(
+        user: &User,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = Self::all()
+            .filter(Self::with_user(user))
+            .order(audit_events::created_at.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns events in the namespace together with the uuid of the actor
+    /// (if any).
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, Option<Uuid>)>> {
+        if namespace_id < 1 {
+            return None;
+        }
+
+        let q = audit_events::table
+            .left_join(users::table)
+            .select((ALL_COLUMNS, users::uuid.nullable()))
+            .filter(Self::with_namespace_id(namespace_id))
+            .order(audit_events::created_at.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, Option<Uuid>)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        event: &NewAuditEvent,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(audit_events::table).values((
+            audit_events::user_id.eq(event.user_id),
+            audit_events::namespace_id.eq(event.namespace_id),
+            audit_events::action.eq(&event.action),
+            audit_events::target.eq(&event.target),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(e) => Some(e),
+        }
+    }
+
+    /// Deletes events recorded before the time, and returns the number of
+    /// them.
+    pub fn delete_all_before(
+        time: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let q = diesel::delete(
+            audit_events::table.filter(audit_events::created_at.lt(time)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to delete"
+        })
+    }
+
+    /// Clears targets of the user's events about email addresses, which
+    /// were recorded as raw addresses before (for the deregistration).
+    /// Returns the number of them.
+    pub fn anonymize_email_targets_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let q = diesel::update(
+            audit_events::table.filter(Self::with_user(user)).filter(
+                audit_events::action.eq_any(vec![
+                    AuditEventAction::EmailAddition,
+                    AuditEventAction::EmailVerification,
+                    AuditEventAction::PrimaryEmailChange,
+                    AuditEventAction::EmailRemoval,
+                ]),
+            ),
+        )
+        .set(audit_events::target.eq(None::<String>));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to anonymize"
+        })
+    }
+
+    pub fn with_user(user: &User) -> WithUser {
+        audit_events::user_id.eq(user.id)
+    }
+
+    pub fn with_namespace_id(namespace_id: i64) -> WithNamespace {
+        audit_events::namespace_id.eq(namespace_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Duration;
+
+    use crate::model::namespace::{Namespace, namespaces};
+
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_new_audit_event_from_user() {
+        let user = USERS.get("oswald").unwrap();
+        let e = NewAuditEvent::from(user);
+
+        assert_eq!(e.user_id, Some(user.id));
+        assert_eq!(e.namespace_id, None);
+        assert_eq!(e.target, None);
+    }
+
+    #[test]
+    fn test_insert() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::Logout,
+
+                ..NewAuditEvent::from(&user)
+            };
+            let result = AuditEvent::insert(&e, conn, logger);
+            assert!(result.is_some());
+
+            let event = result.unwrap();
+            assert_eq!(event.user_id, Some(user.id));
+            assert_eq!(event.action, AuditEventAction::Logout);
+
+            // without user
+            let e = NewAuditEvent {
+                action: AuditEventAction::LoginFailure,
+                target: Some("unknown@example.org".to_string()),
+
+                ..Default::default()
+            };
+            let result = AuditEvent::insert(&e, conn, logger);
+            assert!(result.is_some());
+            assert_eq!(result.unwrap().user_id, None);
+        })
+    }
+
+    #[test]
+    fn test_delete_all_before() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let e = NewAuditEvent::from(&user);
+            let event = AuditEvent::insert(&e, conn, logger).unwrap();
+
+            let time = event.created_at - Duration::days(1);
+            let result = AuditEvent::delete_all_before(time, conn, logger);
+            assert_eq!(result, Ok(0));
+
+            let time = event.created_at + Duration::seconds(1);
+            let result = AuditEvent::delete_all_before(time, conn, logger);
+            assert_eq!(result, Ok(1));
+        })
+    }
+
+    #[test]
+    fn test_anonymize_email_targets_by_user() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for action in &[
+                AuditEventAction::EmailAddition,
+                AuditEventAction::AccessTokenDump,
+            ] {
+                let e = NewAuditEvent {
+                    action: action.clone(),
+                    target: Some("oswald.new@example.org".to_string()),
+
+                    ..NewAuditEvent::from(&user)
+                };
+                let _ = AuditEvent::insert(&e, conn, logger).unwrap();
+            }
+
+            let result = AuditEvent::anonymize_email_targets_by_user(
+                &user, conn, logger,
+            );
+            assert_eq!(result, Ok(1));
+
+            let events =
+                AuditEvent::find_all_by_user(&user, 0, 10, conn, logger)
+                    .unwrap();
+            let targets: Vec<_> = events
+                .iter()
+                .map(|e| (e.action.clone(), e.target.is_some()))
+                .collect();
+            assert!(targets.contains(&(AuditEventAction::EmailAddition, false)));
+            assert!(
+                targets.contains(&(AuditEventAction::AccessTokenDump, true))
+            );
+        })
+    }
+
+    #[test]
+    fn test_find_all_by_user() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("weenie").unwrap();
+            let another_user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for action in &[AuditEventAction::Login, AuditEventAction::Logout] {
+                let e = NewAuditEvent {
+                    action: action.clone(),
+
+                    ..NewAuditEvent::from(&user)
+                };
+                let _ = AuditEvent::insert(&e, conn, logger);
+            }
+            let e = NewAuditEvent::from(&another_user);
+            let _ = AuditEvent::insert(&e, conn, logger);
+
+            let events =
+                AuditEvent::find_all_by_user(&user, 0, 10, conn, logger)
+                    .unwrap();
+            assert_eq!(events.len(), 2);
+            assert!(events.iter().all(|e| e.user_id == Some(user.id)));
+
+            let events =
+                AuditEvent::find_all_by_user(&user, 1, 10, conn, logger)
+                    .unwrap();
+            assert_eq!(events.len(), 1);
+        })
+    }
+
+    #[test]
+    fn test_find_all_by_namespace_id() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let e = NewAuditEvent {
+                namespace_id: Some(namespace.id),
+                action: AuditEventAction::NamespaceCreation,
+                target: Some(namespace.uuid.to_string()),
+
+                ..NewAuditEvent::from(&user)
+            };
+            let _ = AuditEvent::insert(&e, conn, logger);
+
+            // not in the namespace
+            let e = NewAuditEvent::from(&user);
+            let _ = AuditEvent::insert(&e, conn, logger);
+
+            let events = AuditEvent::find_all_by_namespace_id(
+                namespace.id,
+                0,
+                10,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(events.len(), 1);
+
+            let (event, actor) = &events[0];
+            assert_eq!(event.action, AuditEventAction::NamespaceCreation);
+            assert_eq!(actor, &Some(user.uuid));
+        })
+    }
+}
@@ -0,0 +1,154 @@
+//! # A type AuditEventAction for AuditEvent in audit_event.rs
+//!
+//! EAuditEventAction represents SQL type value
+//! `e_audit_event_action` and AuditEventAction is an
+//! Enum contains all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_audit_event_action")]
+pub struct EAuditEventAction;
+
+#[derive(
+    AsExpression, Clone, Debug, Deserialize, FromSqlRow, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "EAuditEventAction"]
+pub enum AuditEventAction {
+    Login,
+    LoginFailure,
+    Logout,
+    AccountActivation,
+    PasswordResetRequest,
+    PasswordReset,
+    AccessTokenDump,
+    AccessTokenRevocation,
+    AccessTokenStateChange,
+    NamespaceCreation,
+    MembershipCreation,
+    TotpActivation,
+    TotpDeactivation,
+    AccountDeregistration,
+    EmailAddition,
+    EmailVerification,
+    PrimaryEmailChange,
+    EmailRemoval,
+}
+
+const AUDIT_EVENT_ACTIONS: [AuditEventAction; 18] = [
+    AuditEventAction::Login,
+    AuditEventAction::LoginFailure,
+    AuditEventAction::Logout,
+    AuditEventAction::AccountActivation,
+    AuditEventAction::PasswordResetRequest,
+    AuditEventAction::PasswordReset,
+    AuditEventAction::AccessTokenDump,
+    AuditEventAction::AccessTokenRevocation,
+    AuditEventAction::AccessTokenStateChange,
+    AuditEventAction::NamespaceCreation,
+    AuditEventAction::MembershipCreation,
+    AuditEventAction::TotpActivation,
+    AuditEventAction::TotpDeactivation,
+    AuditEventAction::AccountDeregistration,
+    AuditEventAction::EmailAddition,
+    AuditEventAction::EmailVerification,
+    AuditEventAction::PrimaryEmailChange,
+    AuditEventAction::EmailRemoval,
+];
+
+impl fmt::Display for AuditEventAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl AsRef<str> for AuditEventAction {
+    fn as_ref(&self) -> &str {
+        match *self {
+            Self::Login => "login",
+            Self::LoginFailure => "login_failure",
+            Self::Logout => "logout",
+            Self::AccountActivation => "account_activation",
+            Self::PasswordResetRequest => "password_reset_request",
+            Self::PasswordReset => "password_reset",
+            Self::AccessTokenDump => "access_token_dump",
+            Self::AccessTokenRevocation => "access_token_revocation",
+            Self::AccessTokenStateChange => "access_token_state_change",
+            Self::NamespaceCreation => "namespace_creation",
+            Self::MembershipCreation => "membership_creation",
+            Self::TotpActivation => "totp_activation",
+            Self::TotpDeactivation => "totp_deactivation",
+            Self::AccountDeregistration => "account_deregistration",
+            Self::EmailAddition => "email_addition",
+            Self::EmailVerification => "email_verification",
+            Self::PrimaryEmailChange => "primary_email_change",
+            Self::EmailRemoval => "email_removal",
+        }
+    }
+}
+
+impl ToSql<EAuditEventAction, Pg> for AuditEventAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let s: &str = self.as_ref();
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EAuditEventAction, Pg> for AuditEventAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let b = not_none!(bytes);
+        match Self::iter().find(|a| a.as_ref().as_bytes() == b) {
+            Some(a) => Ok(a.clone()),
+            None => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl AuditEventAction {
+    pub fn iter() -> Iter<'static, AuditEventAction> {
+        AUDIT_EVENT_ACTIONS.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("login", format!("{}", AuditEventAction::Login));
+        assert_eq!(
+            "login_failure",
+            format!("{}", AuditEventAction::LoginFailure)
+        );
+        assert_eq!(
+            "access_token_state_change",
+            format!("{}", AuditEventAction::AccessTokenStateChange)
+        );
+        assert_eq!(
+            "membership_creation",
+            format!("{}", AuditEventAction::MembershipCreation)
+        );
+    }
+
+    #[test]
+    fn test_as_vec() {
+        let actions = AuditEventAction::as_vec();
+        assert_eq!(actions.len(), 18);
+        assert_eq!(actions[0], AuditEventAction::Login);
+        assert_eq!(actions[17], AuditEventAction::EmailRemoval);
+    }
+}
@@ -0,0 +1,593 @@
+//! # Issue
+//!
+//! Issue groups recurring messages on a stream. Each message is fingerprinted
+//! from its level, code and normalized title (numbers, UUIDs and hex values
+//! are replaced with placeholders), and occurrences are counted on the issue
+//! having the fingerprint. A resolved issue is reopened when it recurs after
+//! the resolution, but an ignored one stays ignored.
+//!
+//! Messages don't refer to issues. An edit which changes the fingerprint moves
+//! the occurrence to another issue (see `Issue::regroup`), and a deletion
+//! takes it from the issue (see `Issue::discount`).
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl::sql;
+use diesel::pg::{Pg, PgConnection};
+use diesel::sql_types::{Nullable, Timestamp};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub use crate::model::issue_state::*;
+pub use crate::schema::issues;
+
+use crate::logger::Logger;
+use crate::model::message::{LogLevel, Message, NewMessage};
+use crate::model::stream::streams;
+
+lazy_static! {
+    static ref UUID: Regex = Regex::new(
+        r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b"
+    )
+    .unwrap();
+    static ref HEX: Regex =
+        Regex::new(r"(?i)\b(0x[0-9a-f]+|[0-9a-f]{8,})\b").unwrap();
+    static ref NUMBER: Regex = Regex::new(r"\d+(\.\d+)?").unwrap();
+    static ref SPACES: Regex = Regex::new(r"\s+").unwrap();
+}
+
+/// Replaces variable parts in the title with placeholders.
+///
+/// e.g. "user 42 not found (0x1f)" -> "user <n> not found (<hex>)"
+pub fn normalize(title: &str) -> String {
+    let s = UUID.replace_all(title, "<uuid>");
+    let s = HEX.replace_all(&s, "<hex>");
+    let s = NUMBER.replace_all(&s, "<n>");
+    SPACES.replace_all(s.trim(), " ").to_string()
+}
+
+/// Returns the fingerprint (a SHA-256 hex digest) of the message.
+pub fn fingerprint(m: &NewMessage) -> String {
+    digest(
+        &m.level,
+        m.code.as_deref(),
+        m.title.as_deref().unwrap_or_default(),
+    )
+}
+
+fn digest(level: &LogLevel, code: Option<&str>, title: &str) -> String {
+    let source = format!(
+        "{}\n{}\n{}",
+        level,
+        code.unwrap_or_default(),
+        normalize(title),
+    );
+    format!("{:x}", Sha256::digest(source.as_bytes()))
+}
+
+/// NewIssue
+#[derive(Debug, Insertable)]
+#[table_name = "issues"]
+pub struct NewIssue {
+    pub stream_id: i64,
+    pub fingerprint: String,
+    pub level: LogLevel,
+    pub code: Option<String>,
+    pub title: String,
+    pub first_seen_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+}
+
+impl From<&NewMessage> for NewIssue {
+    fn from(m: &NewMessage) -> Self {
+        let seen_at = m.created_at.unwrap_or_else(|| Utc::now().naive_utc());
+        Self {
+            stream_id: m.stream_id,
+            fingerprint: fingerprint(m),
+            level: m.level.clone(),
+            code: m.code.clone(),
+            title: m.title.clone().unwrap_or_default(),
+            first_seen_at: seen_at,
+            last_seen_at: seen_at,
+        }
+    }
+}
+
+impl From<&Message> for NewIssue {
+    fn from(m: &Message) -> Self {
+        Self {
+            stream_id: m.stream_id,
+            fingerprint: digest(&m.level, m.code.as_deref(), &m.title),
+            level: m.level.clone(),
+            code: m.code.clone(),
+            title: m.title.clone(),
+            first_seen_at: m.created_at,
+            last_seen_at: m.created_at,
+        }
+    }
+}
+
+/// Issue
+#[derive(Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[table_name = "issues"]
+pub struct Issue {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub stream_id: i64,
+    pub fingerprint: String,
+    pub level: LogLevel,
+    pub code: Option<String>,
+    pub title: String,
+    pub state: IssueState,
+    pub count: i64,
+    pub first_seen_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Issue {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl Issue {
+    /// Records an occurrence of the message on the issue having the same
+    /// fingerprint (or a new one), and returns the issue.
+    pub fn record(
+        m: &NewMessage,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        Self::upsert(&NewIssue::from(m), conn, logger)
+    }
+
+    /// Moves the occurrence of the edited message to the issue having its new
+    /// fingerprint, if the edit has changed it. It returns the issue which
+    /// the message belongs to.
+    pub fn regroup(
+        old: &Message,
+        new: &Message,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Option<Self>, &'static str> {
+        let issue = NewIssue::from(new);
+        if NewIssue::from(old).fingerprint == issue.fingerprint {
+            return Ok(None);
+        }
+        Self::discount(old, conn, logger)?;
+        Self::upsert(&issue, conn, logger)
+            .map(Some)
+            .ok_or("failed to record")
+    }
+
+    /// Takes the occurrence of the message from the issue having the same
+    /// fingerprint. The issue is deleted when no occurrence remains (the
+    /// seen times are left as they were).
+    pub fn discount(
+        m: &Message,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let issue = NewIssue::from(m);
+        let target = issues::table
+            .filter(issues::stream_id.eq(issue.stream_id))
+            .filter(issues::fingerprint.eq(&issue.fingerprint));
+
+        let q = diesel::update(target.filter(issues::count.gt(1))).set((
+            issues::count.eq(issues::count - 1),
+            issues::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let n = q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update"
+        })?;
+        if n > 0 {
+            return Ok(n);
+        }
+
+        let q = diesel::delete(target);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to delete"
+        })
+    }
+
+    fn upsert(
+        issue: &NewIssue,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let now = Utc::now().naive_utc();
+
+        // values in expressions are the ones before the update
+        let q = diesel::insert_into(issues::table)
+            .values(issue)
+            .on_conflict((issues::stream_id, issues::fingerprint))
+            .do_update()
+            .set((
+                issues::count.eq(issues::count + 1),
+                issues::first_seen_at.eq(sql::<Timestamp>(
+                    "LEAST(issues.first_seen_at, excluded.first_seen_at)",
+                )),
+                issues::last_seen_at.eq(sql::<Timestamp>(
+                    "GREATEST(issues.last_seen_at, excluded.last_seen_at)",
+                )),
+                issues::state.eq(sql::<EIssueState>(
+                    "CASE WHEN issues.state = 'resolved' AND \
+                     excluded.last_seen_at > issues.resolved_at THEN \
+                     'open'::e_issue_state ELSE issues.state END",
+                )),
+                issues::resolved_at.eq(sql::<Nullable<Timestamp>>(
+                    "CASE WHEN issues.state = 'resolved' AND \
+                     excluded.last_seen_at > issues.resolved_at THEN NULL \
+                     ELSE issues.resolved_at END",
+                )),
+                issues::updated_at.eq(now),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = issues::table
+            .inner_join(streams::table)
+            .select(issues::all_columns)
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(issues::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns issues on the stream (the most recently seen one comes first).
+    pub fn find_all_by_stream_id(
+        stream_id: i64,
+        state: Option<IssueState>,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if stream_id < 1 {
+            return None;
+        }
+
+        let mut q = issues::table
+            .filter(issues::stream_id.eq(stream_id))
+            .into_boxed();
+        if let Some(s) = state {
+            q = q.filter(issues::state.eq(s));
+        }
+        let q = q
+            .order((issues::last_seen_at.desc(), issues::id.desc()))
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Changes the state. resolved_at is set only for the resolved state.
+    pub fn update_state(
+        &self,
+        state: IssueState,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let resolved_at = if state == IssueState::Resolved {
+            Some(now)
+        } else {
+            None
+        };
+        let q = diesel::update(self).set((
+            issues::state.eq(state),
+            issues::resolved_at.eq(resolved_at),
+            issues::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update"
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Duration;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::Stream;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    fn build_message(stream: &Stream, title: &str) -> NewMessage {
+        NewMessage {
+            stream_id: stream.id,
+            level: LogLevel::Error,
+            code: Some("E1".to_string()),
+            title: Some(title.to_string()),
+
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("user 42 not found"), "user <n> not found");
+        assert_eq!(normalize("took 1.25s"), "took <n>s");
+        assert_eq!(
+            normalize("request 0b8a4ab4-1d6f-4c3e-9e0b-2f6a1c3d5e7f failed"),
+            "request <uuid> failed"
+        );
+        assert_eq!(
+            normalize("segfault at 0x7ffd5fbff8a0 (deadbeef12)"),
+            "segfault at <hex> (<hex>)"
+        );
+        assert_eq!(normalize("  a \t b\n"), "a b");
+        assert_eq!(normalize("cafe"), "cafe");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let m = NewMessage {
+            title: Some("user 1 not found".to_string()),
+
+            ..Default::default()
+        };
+        let other = NewMessage {
+            title: Some("user 2 not found".to_string()),
+
+            ..Default::default()
+        };
+        assert_eq!(fingerprint(&m).len(), 64);
+        assert_eq!(fingerprint(&m), fingerprint(&other));
+
+        let other = NewMessage {
+            level: LogLevel::Error,
+            title: Some("user 2 not found".to_string()),
+
+            ..Default::default()
+        };
+        assert_ne!(fingerprint(&m), fingerprint(&other));
+
+        let other = NewMessage {
+            code: Some("E1".to_string()),
+            title: Some("user 2 not found".to_string()),
+
+            ..Default::default()
+        };
+        assert_ne!(fingerprint(&m), fingerprint(&other));
+    }
+
+    #[test]
+    fn test_record() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let issue =
+                Issue::record(&build_message(&stream, "id 1"), conn, logger)
+                    .unwrap();
+            assert_eq!(issue.count, 1);
+            assert_eq!(issue.title, "id 1");
+            assert_eq!(issue.state, IssueState::Open);
+
+            let recurred =
+                Issue::record(&build_message(&stream, "id 2"), conn, logger)
+                    .unwrap();
+            assert_eq!(recurred.id, issue.id);
+            assert_eq!(recurred.count, 2);
+            assert_eq!(recurred.title, "id 1");
+            assert!(recurred.last_seen_at >= issue.last_seen_at);
+
+            let other =
+                Issue::record(&build_message(&stream, "other"), conn, logger)
+                    .unwrap();
+            assert_ne!(other.id, issue.id);
+
+            let issues = Issue::find_all_by_stream_id(
+                stream.id, None, 0, 10, conn, logger,
+            )
+            .unwrap();
+            assert_eq!(issues.len(), 2);
+            assert_eq!(issues[0].id, other.id);
+        })
+    }
+
+    #[test]
+    fn test_record_reopens_resolved_issue() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            // without fractional seconds (see the precision of timestamp)
+            let now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+            let issue =
+                Issue::record(&build_message(&stream, "id 1"), conn, logger)
+                    .unwrap();
+            let issue = issue
+                .update_state(IssueState::Resolved, now, conn, logger)
+                .unwrap();
+            assert_eq!(issue.resolved_at, Some(now));
+
+            // an imported (older) one doesn't reopen it
+            let mut m = build_message(&stream, "id 2");
+            m.created_at = Some(now - Duration::days(1));
+            let issue = Issue::record(&m, conn, logger).unwrap();
+            assert_eq!(issue.state, IssueState::Resolved);
+            assert_eq!(issue.first_seen_at, now - Duration::days(1));
+
+            let mut m = build_message(&stream, "id 3");
+            m.created_at = Some(now + Duration::seconds(1));
+            let issue = Issue::record(&m, conn, logger).unwrap();
+            assert_eq!(issue.state, IssueState::Open);
+            assert_eq!(issue.resolved_at, None);
+            assert_eq!(issue.count, 3);
+
+            issue
+                .update_state(IssueState::Ignored, now, conn, logger)
+                .unwrap();
+            let mut m = build_message(&stream, "id 4");
+            m.created_at = Some(now + Duration::seconds(2));
+            let issue = Issue::record(&m, conn, logger).unwrap();
+            assert_eq!(issue.state, IssueState::Ignored);
+
+            let issues = Issue::find_all_by_stream_id(
+                stream.id,
+                Some(IssueState::Open),
+                0,
+                10,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert!(issues.is_empty());
+        })
+    }
+
+    fn insert_message(
+        stream: &Stream,
+        title: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Message {
+        let m = NewMessage {
+            agent_id: 1,
+
+            ..build_message(stream, title)
+        };
+        let id = Message::insert(&m, conn, logger).unwrap();
+        let _ = Issue::record(&m, conn, logger).unwrap();
+        Message::first_by_stream_id(id, stream.id, conn, logger).unwrap()
+    }
+
+    #[test]
+    fn test_regroup() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let message = insert_message(&stream, "id 1", conn, logger);
+            let _ = insert_message(&stream, "id 2", conn, logger);
+
+            // the title is normalized into the same fingerprint
+            let edited = Message {
+                title: "id 3".to_string(),
+
+                ..message.clone()
+            };
+            assert_eq!(
+                Issue::regroup(&message, &edited, conn, logger).unwrap(),
+                None
+            );
+
+            let edited = Message {
+                level: LogLevel::Warning,
+
+                ..message.clone()
+            };
+            let issue = Issue::regroup(&message, &edited, conn, logger)
+                .unwrap()
+                .unwrap();
+            assert_eq!(issue.level, LogLevel::Warning);
+            assert_eq!(issue.count, 1);
+
+            let issues = Issue::find_all_by_stream_id(
+                stream.id, None, 0, 10, conn, logger,
+            )
+            .unwrap();
+            let counts: Vec<(LogLevel, i64)> =
+                issues.into_iter().map(|i| (i.level, i.count)).collect();
+            assert_eq!(counts.len(), 2);
+            assert!(counts.contains(&(LogLevel::Error, 1)));
+            assert!(counts.contains(&(LogLevel::Warning, 1)));
+        })
+    }
+
+    #[test]
+    fn test_discount() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let message = insert_message(&stream, "id 1", conn, logger);
+            let _ = insert_message(&stream, "id 2", conn, logger);
+
+            assert_eq!(Issue::discount(&message, conn, logger), Ok(1));
+            let issues = Issue::find_all_by_stream_id(
+                stream.id, None, 0, 10, conn, logger,
+            )
+            .unwrap();
+            assert_eq!(issues.len(), 1);
+            assert_eq!(issues[0].count, 1);
+
+            // the last occurrence deletes the issue
+            assert_eq!(Issue::discount(&message, conn, logger), Ok(1));
+            let issues = Issue::find_all_by_stream_id(
+                stream.id, None, 0, 10, conn, logger,
+            )
+            .unwrap();
+            assert!(issues.is_empty());
+
+            assert_eq!(Issue::discount(&message, conn, logger), Ok(0));
+        })
+    }
+}
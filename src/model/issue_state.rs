@@ -0,0 +1,111 @@
+//! # A type IssueState for Issue in issue.rs
+//!
+//! EIssueState represents SQL type value `e_issue_state` and IssueState is an
+//! Enum contains all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_issue_state")]
+pub struct EIssueState;
+
+#[derive(
+    AsExpression, Clone, Debug, Deserialize, FromSqlRow, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "EIssueState"]
+pub enum IssueState {
+    Open,
+    Resolved,
+    Ignored,
+}
+
+const ISSUE_STATES: [IssueState; 3] =
+    [IssueState::Open, IssueState::Resolved, IssueState::Ignored];
+
+impl fmt::Display for IssueState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl AsRef<str> for IssueState {
+    fn as_ref(&self) -> &str {
+        match *self {
+            Self::Open => "open",
+            Self::Resolved => "resolved",
+            Self::Ignored => "ignored",
+        }
+    }
+}
+
+impl From<String> for IssueState {
+    fn from(s: String) -> Self {
+        let s = s.to_ascii_lowercase();
+        match Self::iter().find(|v| v.as_ref() == s) {
+            Some(v) => v.clone(),
+            None => IssueState::Open,
+        }
+    }
+}
+
+impl ToSql<EIssueState, Pg> for IssueState {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let s: &str = self.as_ref();
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EIssueState, Pg> for IssueState {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let b = not_none!(bytes);
+        match Self::iter().find(|v| v.as_ref().as_bytes() == b) {
+            Some(v) => Ok(v.clone()),
+            None => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl IssueState {
+    pub fn iter() -> Iter<'static, IssueState> {
+        ISSUE_STATES.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("open", format!("{}", IssueState::Open));
+        assert_eq!("resolved", format!("{}", IssueState::Resolved));
+        assert_eq!("ignored", format!("{}", IssueState::Ignored));
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(IssueState::Open, IssueState::from("open".to_string()));
+        assert_eq!(
+            IssueState::Resolved,
+            IssueState::from("resolved".to_string())
+        );
+        assert_eq!(
+            IssueState::Ignored,
+            IssueState::from("ignored".to_string())
+        );
+        assert_eq!(IssueState::Open, IssueState::from("unknown".to_string()));
+    }
+}
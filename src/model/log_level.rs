@@ -95,12 +95,30 @@ impl LogLevel {
     pub fn as_vec() -> Vec<LogLevel> {
         LogLevel::iter().cloned().collect()
     }
+
+    /// Returns levels which are equal to or higher than the level.
+    pub fn and_higher(&self) -> Vec<LogLevel> {
+        LogLevel::iter()
+            .skip_while(|l| *l != self)
+            .cloned()
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_and_higher() {
+        assert_eq!(LogLevel::Debug.and_higher(), LogLevel::as_vec());
+        assert_eq!(
+            LogLevel::Error.and_higher(),
+            vec![LogLevel::Error, LogLevel::Critical]
+        );
+        assert_eq!(LogLevel::Critical.and_higher(), vec![LogLevel::Critical]);
+    }
+
     #[allow(clippy::cognitive_complexity)]
     #[test]
     fn test_from() {
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
 use diesel::dsl;
 use diesel::pg::{Pg, PgConnection};
@@ -85,6 +85,33 @@ impl Membership {
         }
     }
 
+    pub fn find_by_namespace_and_user(
+        namespace: &Namespace,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if namespace.id < 1 || user.id < 1 {
+            return None;
+        }
+
+        let q = memberships::table
+            .filter(memberships::namespace_id.eq(namespace.id))
+            .filter(Self::with_user(user))
+            .filter(memberships::revoked_at.is_null())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Membership>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn insert(
         membership: &NewMembership,
         conn: &PgConnection,
@@ -107,6 +134,34 @@ impl Membership {
         }
     }
 
+    /// Revokes all the (not revoked yet) memberships of the user at once.
+    pub fn revoke_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let now = Utc::now().naive_utc();
+        let q = diesel::update(
+            memberships::table
+                .filter(Self::with_user(user))
+                .filter(memberships::revoked_at.is_null()),
+        )
+        .set((
+            memberships::revoked_at.eq(now),
+            memberships::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to revoke")
+            },
+            Ok(n) => Ok(n),
+        }
+    }
+
     pub fn with_user(user: &User) -> WithUser {
         memberships::user_id.eq(user.id)
     }
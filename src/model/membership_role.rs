@@ -83,6 +83,10 @@ impl MembershipRole {
     pub fn as_vec() -> Vec<Self> {
         Self::iter().cloned().collect()
     }
+
+    pub fn is_owner(&self) -> bool {
+        matches!(*self, Self::PrimaryOwner | Self::Owner)
+    }
 }
 
 #[cfg(test)]
@@ -134,4 +138,11 @@ mod test {
             MembershipRole::as_vec()
         )
     }
+
+    #[test]
+    fn test_is_owner() {
+        assert!(MembershipRole::PrimaryOwner.is_owner());
+        assert!(MembershipRole::Owner.is_owner());
+        assert!(!MembershipRole::Member.is_owner());
+    }
 }
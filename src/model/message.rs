@@ -12,6 +12,8 @@ use diesel::{self, Insertable, prelude::*};
 use diesel::debug_query;
 use diesel::dsl;
 use diesel::pg::{Pg, PgConnection};
+use diesel::sql_query;
+use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamp, Varchar};
 use serde::Serialize;
 
 use crate::logger::Logger;
@@ -20,6 +22,7 @@ use crate::request::message::Message as RequestData;
 pub use crate::model::agent_type::*;
 pub use crate::model::log_level::*;
 pub use crate::model::log_format::*;
+use crate::model::message_deletion::{MessageDeletion, NewMessageDeletion};
 pub use crate::model::stream::{Stream, streams};
 use crate::model::user::User;
 pub use crate::schema::messages;
@@ -37,6 +40,8 @@ pub struct NewMessage {
     pub format: LogFormat,
     pub title: Option<String>,
     pub content: Option<String>,
+    pub created_at: Option<NaiveDateTime>, // it's filled if none (e.g. import)
+    pub redactions_count: i32,
 }
 
 impl fmt::Display for NewMessage {
@@ -61,6 +66,8 @@ impl Default for NewMessage {
             format: LogFormat::TOML,
             title: None,
             content: None,
+            created_at: None,
+            redactions_count: 0,
         }
     }
 }
@@ -84,6 +91,8 @@ impl From<RequestData> for NewMessage {
             ),
             title: data.title,
             content: data.content,
+            created_at: None,
+            redactions_count: 0,
         }
     }
 }
@@ -92,6 +101,7 @@ type AllColumns = (
     messages::id,
     messages::agent_id,
     messages::agent_type,
+    messages::stream_id,
     messages::code,
     messages::lang,
     messages::level,
@@ -100,12 +110,14 @@ type AllColumns = (
     messages::content,
     messages::created_at,
     messages::updated_at,
+    messages::redactions_count,
 );
 
 const ALL_COLUMNS: AllColumns = (
     messages::id,
     messages::agent_id,
     messages::agent_type,
+    messages::stream_id,
     messages::code,
     messages::lang,
     messages::level,
@@ -114,6 +126,7 @@ const ALL_COLUMNS: AllColumns = (
     messages::content,
     messages::created_at,
     messages::updated_at,
+    messages::redactions_count,
 );
 
 /// Message
@@ -140,6 +153,7 @@ pub struct Message {
     pub content: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub redactions_count: i32,
 }
 
 impl Clone for Message {
@@ -170,6 +184,53 @@ impl fmt::Display for Message {
     }
 }
 
+/// MessageFilter narrows messages on a stream down (e.g. for an export). The
+/// time range is [since, until).
+#[derive(Clone, Debug)]
+pub struct MessageFilter {
+    pub stream_id: i64,
+    pub level: Option<LogLevel>, // and higher
+    pub code: Option<String>,
+    pub since: NaiveDateTime,
+    pub until: NaiveDateTime,
+}
+
+/// MessageQuery finds messages in the namespace (e.g. for a saved view). The
+/// stream is any of the namespace if none, and levels are any if empty.
+#[derive(Clone, Debug, Default)]
+pub struct MessageQuery {
+    pub namespace_id: i64,
+    pub stream_id: Option<i64>,
+    pub levels: Vec<LogLevel>,
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub since: Option<NaiveDateTime>,
+}
+
+/// MessageRow is a message read through a cursor. The values of enum types
+/// are read as text.
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct MessageRow {
+    #[sql_type = "BigInt"]
+    pub id: i64,
+    #[sql_type = "Nullable<Varchar>"]
+    pub code: Option<String>,
+    #[sql_type = "Varchar"]
+    pub lang: String,
+    #[sql_type = "Text"]
+    pub level: String,
+    #[sql_type = "Text"]
+    pub format: String,
+    #[sql_type = "Varchar"]
+    pub title: String,
+    #[sql_type = "Nullable<Text>"]
+    pub content: Option<String>,
+    #[sql_type = "Timestamp"]
+    pub created_at: NaiveDateTime,
+}
+
+const CURSOR_NAME: &str = "messages_cursor";
+
 type All = dsl::Select<messages::table, AllColumns>;
 type WithType = dsl::Eq<messages::agent_type, AgentType>;
 type WithUser = dsl::And<
@@ -189,32 +250,74 @@ impl Message {
         Self::all().filter(Self::with_user(user))
     }
 
-    pub fn fetch_by_stream_slug(
-        stream_slug: String,
+    /// Returns messages on the stream (the newest one comes first).
+    pub fn fetch_by_stream_id(
+        stream_id: i64,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::all()
+            .filter(messages::stream_id.eq(stream_id))
+            .order(messages::created_at.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns messages which match the query (the newest one comes first).
+    pub fn search(
+        query: &MessageQuery,
         offset: i64,
         limit: i64,
         conn: &PgConnection,
         logger: &Logger,
     ) -> Option<Vec<Self>> {
-        if stream_slug.is_empty() {
+        if query.namespace_id < 1 {
             return None;
         }
 
-        // TODO: Fix clause id = slug
-        let stream_id = 1;
-        let q = messages::table
+        let mut q = messages::table
             .inner_join(streams::table)
-            .filter(streams::id.eq(stream_id))
-            .order(messages::created_at.desc())
+            .select(ALL_COLUMNS)
+            .filter(streams::namespace_id.eq(query.namespace_id))
+            .into_boxed();
+        if let Some(stream_id) = query.stream_id {
+            q = q.filter(messages::stream_id.eq(stream_id));
+        }
+        if !query.levels.is_empty() {
+            q = q.filter(messages::level.eq_any(query.levels.clone()));
+        }
+        if let Some(code) = &query.code {
+            q = q.filter(messages::code.eq(code));
+        }
+        if let Some(lang) = &query.lang {
+            q = q.filter(messages::lang.eq(lang));
+        }
+        if let Some(since) = query.since {
+            q = q.filter(messages::created_at.ge(since));
+        }
+        let q = q
+            .order((messages::created_at.desc(), messages::id.desc()))
             .offset(offset)
             .limit(limit);
 
         info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
 
-        match q.load::<(Self, Stream)>(conn) {
-            Ok(r) => Some(r.into_iter().map(|(m, _)| m).collect::<Vec<Self>>()),
+        match q.load::<Self>(conn) {
+            Ok(v) => Some(v),
             Err(e) => {
-                println!("err: {}", e);
+                error!(logger, "err: {}", e);
                 None
             },
         }
@@ -263,6 +366,22 @@ impl Message {
         }
     }
 
+    /// Save new messages at once (a multi-row insert), and returns the number
+    /// of them.
+    pub fn insert_all(
+        messages: &[NewMessage],
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let q = diesel::insert_into(messages::table).values(messages);
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to insert"
+        })
+    }
+
     /// Update a message.
     pub fn update(
         message: &mut Message,
@@ -285,6 +404,108 @@ impl Message {
         }
     }
 
+    /// Deletes the message, and logs the deletion by the user in the same
+    /// transaction.
+    pub fn delete(
+        &self,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<MessageDeletion, &'static str> {
+        conn.transaction(|| {
+            let q = diesel::delete(self);
+
+            info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+            q.execute(conn)?;
+
+            let d = NewMessageDeletion {
+                stream_id: self.stream_id,
+                message_id: self.id,
+                user_id: user.id,
+                title: self.title.clone(),
+            };
+            MessageDeletion::insert(&d, conn, logger)
+        })
+        .map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to delete"
+        })
+    }
+
+    /// Declares a server-side cursor over messages which pass through the
+    /// filter (in order of id). It must be called in a transaction. See also
+    /// `close_cursor`.
+    pub fn declare_cursor(
+        filter: &MessageFilter,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<()> {
+        let levels: Vec<String> = filter
+            .level
+            .as_ref()
+            .map_or_else(LogLevel::as_vec, |l| l.and_higher())
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        let q = sql_query(format!(
+            r#"DECLARE {} NO SCROLL CURSOR FOR
+SELECT id, code, lang, level::text AS level, format::text AS format, title,
+  content, created_at
+FROM messages
+WHERE stream_id = $1 AND created_at >= $2 AND created_at < $3
+  AND level::text = ANY($4) AND ($5::text IS NULL OR code = $5)
+ORDER BY id ASC"#,
+            CURSOR_NAME
+        ))
+        .bind::<BigInt, _>(filter.stream_id)
+        .bind::<Timestamp, _>(filter.since)
+        .bind::<Timestamp, _>(filter.until)
+        .bind::<Array<Text>, _>(levels)
+        .bind::<Nullable<Text>, _>(filter.code.clone());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map(|_| ()).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
+    /// Fetches next rows (at most the count) through the cursor declared by
+    /// `declare_cursor`. It returns an empty vec at the end.
+    pub fn fetch_cursor(
+        count: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Vec<MessageRow>> {
+        let q =
+            sql_query(format!("FETCH FORWARD {} FROM {}", count, CURSOR_NAME));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.load::<MessageRow>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
+    /// Closes the cursor declared by `declare_cursor`. (A cursor is kept
+    /// until the end of the transaction otherwise, even if it's a nested one)
+    pub fn close_cursor(
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<()> {
+        let q = sql_query(format!("CLOSE {}", CURSOR_NAME));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map(|_| ()).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
     // FIXME: scope
     pub fn visible() -> Visible {
         messages::content.is_not_null()
@@ -333,6 +554,7 @@ mod data {
                 content: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
                 updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                redactions_count: 0,
             }
         };
     }
@@ -348,6 +570,8 @@ mod test {
     use crate::model::stream::{Stream, streams};
     use crate::model::stream::data::STREAMS;
     use crate::model::test::run;
+    use crate::model::user::users;
+    use crate::model::user::data::USERS;
 
     #[test]
     fn test_insert() {
@@ -375,6 +599,8 @@ mod test {
                 format: LogFormat::TOML,
                 title: Some("title".to_string()),
                 content: None,
+                created_at: None,
+                redactions_count: 0,
             };
             let result = Message::insert(&m, conn, logger);
             assert!(result.is_some());
@@ -429,4 +655,249 @@ mod test {
             assert_eq!(title, "updated");
         })
     }
+
+    #[test]
+    fn test_delete() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let ns = NAMESPACES.get("ball").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("weenie's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut m = MESSAGES.get("blank message").unwrap().clone();
+            m.stream_id = stream.id;
+            let message = diesel::insert_into(messages::table)
+                .values(m)
+                .get_result::<Message>(conn)
+                .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+            let deletion = message.delete(&user, conn, logger).unwrap();
+            assert_eq!(deletion.message_id, message.id);
+            assert_eq!(deletion.user_id, user.id);
+            assert_eq!(deletion.title, "title");
+
+            assert!(Message::first_by_stream_id(
+                message.id, stream.id, conn, logger
+            )
+            .is_none());
+        })
+    }
+
+    #[test]
+    fn test_declare_and_fetch_cursor() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("ball").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("weenie's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for (i, level) in LogLevel::iter().enumerate() {
+                let m = NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    code: Some(format!("E{}", i % 2)),
+                    level: level.clone(),
+                    title: Some(format!("title {}", i)),
+
+                    ..Default::default()
+                };
+                assert!(Message::insert(&m, conn, logger).is_some());
+            }
+
+            let now = Utc::now().naive_utc();
+            let filter = MessageFilter {
+                stream_id: stream.id,
+                level: Some(LogLevel::Warning),
+                code: None,
+                since: now - chrono::Duration::hours(1),
+                until: now + chrono::Duration::hours(1),
+            };
+
+            let rows = conn
+                .transaction::<_, diesel::result::Error, _>(|| {
+                    Message::declare_cursor(&filter, conn, logger)?;
+
+                    let mut rows = vec![];
+                    loop {
+                        let batch = Message::fetch_cursor(2, conn, logger)?;
+                        if batch.is_empty() {
+                            break;
+                        }
+                        assert!(batch.len() <= 2);
+                        rows.extend(batch);
+                    }
+                    Message::close_cursor(conn, logger)?;
+                    Ok(rows)
+                })
+                .unwrap();
+            let titles: Vec<&str> =
+                rows.iter().map(|r| r.title.as_str()).collect();
+            assert_eq!(titles, vec!["title 2", "title 3", "title 4"]);
+            assert_eq!(rows[0].level, "warning");
+
+            let filter = MessageFilter {
+                code: Some("E1".to_string()),
+                ..filter
+            };
+            let rows = conn
+                .transaction::<_, diesel::result::Error, _>(|| {
+                    Message::declare_cursor(&filter, conn, logger)?;
+                    let rows = Message::fetch_cursor(10, conn, logger)?;
+                    Message::close_cursor(conn, logger)?;
+                    Ok(rows)
+                })
+                .unwrap();
+            assert_eq!(rows.len(), 1);
+            assert_eq!(rows[0].title, "title 3");
+        })
+    }
+
+    #[test]
+    fn test_insert_all() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("ball").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("weenie's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let created_at = MESSAGES.get("blank message").unwrap().created_at;
+            let messages = vec![
+                NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    title: Some("old".to_string()),
+                    created_at: Some(created_at),
+
+                    ..Default::default()
+                },
+                NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    title: Some("new".to_string()),
+
+                    ..Default::default()
+                },
+            ];
+            let result = Message::insert_all(&messages, conn, logger);
+            assert_eq!(result, Ok(2));
+
+            let times = messages::table
+                .select(messages::created_at)
+                .order(messages::id.asc())
+                .load::<NaiveDateTime>(conn)
+                .expect("Failed to load");
+            assert_eq!(times[0], created_at);
+            assert!(times[1] > created_at);
+        })
+    }
+
+    #[test]
+    fn test_search() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("ball").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("weenie's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let created_at = MESSAGES.get("blank message").unwrap().created_at;
+            let messages = vec![
+                NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    level: LogLevel::Error,
+                    code: Some("E1".to_string()),
+                    title: Some("old".to_string()),
+                    created_at: Some(created_at),
+
+                    ..Default::default()
+                },
+                NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    level: LogLevel::Critical,
+                    code: Some("E1".to_string()),
+                    title: Some("new".to_string()),
+
+                    ..Default::default()
+                },
+                NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    level: LogLevel::Debug,
+                    title: Some("debug".to_string()),
+
+                    ..Default::default()
+                },
+            ];
+            let _ = Message::insert_all(&messages, conn, logger).unwrap();
+
+            let query = MessageQuery {
+                namespace_id: namespace.id,
+                levels: vec![LogLevel::Error, LogLevel::Critical],
+                code: Some("E1".to_string()),
+
+                ..Default::default()
+            };
+            let result = Message::search(&query, 0, 10, conn, logger).unwrap();
+            let titles: Vec<&str> =
+                result.iter().map(|m| m.title.as_str()).collect();
+            assert_eq!(titles, vec!["new", "old"]);
+
+            let query = MessageQuery {
+                since: Some(created_at + chrono::Duration::seconds(1)),
+
+                ..query
+            };
+            let result = Message::search(&query, 0, 10, conn, logger).unwrap();
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].title, "new");
+
+            let query = MessageQuery {
+                namespace_id: namespace.id,
+                stream_id: Some(stream.id + 1),
+
+                ..Default::default()
+            };
+            let result = Message::search(&query, 0, 10, conn, logger).unwrap();
+            assert!(result.is_empty());
+        })
+    }
 }
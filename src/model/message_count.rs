@@ -0,0 +1,492 @@
+//! # MessageCount model for aggregation
+//!
+//! Messages are counted by level (and code) in time buckets with
+//! `date_trunc`. Hourly counts are rolled up into the message_counts table by
+//! the worker, and aggregation over a long range reads them until the
+//! watermark (the end of the rolled up hours) instead of scanning messages.
+use std::fmt;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+use diesel::{self, prelude::*};
+use diesel::debug_query;
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Nullable, Text, Timestamp, Varchar};
+use serde::Serialize;
+
+use crate::logger::Logger;
+
+pub use crate::model::log_level::*;
+pub use crate::schema::message_counts;
+use crate::schema::messages;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Bucket {
+    Minute,
+    Hour, // default
+    Day,
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Bucket::Minute => write!(f, "minute"),
+            Bucket::Hour => write!(f, "hour"),
+            Bucket::Day => write!(f, "day"),
+        }
+    }
+}
+
+impl From<String> for Bucket {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "minute" => Bucket::Minute,
+            "day" => Bucket::Day,
+            _ => Bucket::Hour,
+        }
+    }
+}
+
+impl Bucket {
+    pub fn as_vec() -> Vec<Bucket> {
+        vec![Bucket::Minute, Bucket::Hour, Bucket::Day]
+    }
+
+    pub fn duration(&self) -> Duration {
+        match *self {
+            Bucket::Minute => Duration::minutes(1),
+            Bucket::Hour => Duration::hours(1),
+            Bucket::Day => Duration::days(1),
+        }
+    }
+}
+
+/// Returns the start of the hour.
+pub fn truncate_to_hour(t: NaiveDateTime) -> NaiveDateTime {
+    t.date().and_hms(t.hour(), 0, 0)
+}
+
+/// CountFilter narrows messages down to ones on the stream (or on any stream
+/// in the namespace) in [since, until).
+///
+/// If boundary is given, counts in [since, boundary) are read from rollups.
+/// Then since must be on the hour, and boundary must be before the watermark.
+#[derive(Clone, Debug)]
+pub struct CountFilter {
+    pub namespace_id: i64,
+    pub stream_id: Option<i64>,
+    pub since: NaiveDateTime,
+    pub until: NaiveDateTime,
+    pub boundary: Option<NaiveDateTime>,
+}
+
+/// LevelCount is the number of messages at the level in the bucket. The
+/// level is read as text.
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct LevelCount {
+    #[sql_type = "Timestamp"]
+    pub bucket: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub level: String,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
+
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct CodeCount {
+    #[sql_type = "Varchar"]
+    pub code: String,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
+
+/// StreamCount is the number of messages on the stream. The stream is
+/// identified by its uuid (as text).
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct StreamCount {
+    #[sql_type = "Text"]
+    pub stream: String,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
+
+/// MessageCount is a rolled up count of messages in an hour.
+#[derive(Debug, Queryable)]
+pub struct MessageCount {
+    pub stream_id: i64,
+    pub bucket_at: NaiveDateTime,
+    pub level: LogLevel,
+    pub code: String,
+    pub count: i64,
+}
+
+impl MessageCount {
+    /// Returns the end of the rolled up hours, if any.
+    pub fn watermark(
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Option<NaiveDateTime>> {
+        let q =
+            message_counts::table.select(dsl::max(message_counts::bucket_at));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.first::<Option<NaiveDateTime>>(conn)
+            .map(|t| t.map(|t| t + Duration::hours(1)))
+            .map_err(|e| {
+                error!(logger, "err: {}", e);
+                e
+            })
+    }
+
+    /// Returns the time of the oldest message, it's where the first rollup
+    /// starts from.
+    pub fn earliest(
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Option<NaiveDateTime>> {
+        let q = messages::table.select(dsl::min(messages::created_at));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.first::<Option<NaiveDateTime>>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
+    /// Re-counts messages in [since, until) by hour. Both must be on the
+    /// hour. It returns the number of rows rolled up.
+    pub fn rollup(
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<usize> {
+        conn.transaction(|| {
+            let q = diesel::delete(
+                message_counts::table
+                    .filter(message_counts::bucket_at.ge(since))
+                    .filter(message_counts::bucket_at.lt(until)),
+            );
+
+            info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+            q.execute(conn)?;
+
+            let q = sql_query(
+                r#"INSERT INTO message_counts
+  (stream_id, bucket_at, level, code, count)
+SELECT stream_id, date_trunc('hour', created_at), level, COALESCE(code, ''),
+  COUNT(*)
+FROM messages
+WHERE created_at >= $1 AND created_at < $2
+GROUP BY 1, 2, 3, 4"#,
+            )
+            .bind::<Timestamp, _>(since)
+            .bind::<Timestamp, _>(until);
+
+            info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+            q.execute(conn)
+        })
+        .map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
+    /// Counts messages by level in each bucket (in order of bucket and
+    /// level). Empty buckets are omitted.
+    pub fn count_by_level(
+        filter: &CountFilter,
+        bucket: &Bucket,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Vec<LevelCount>> {
+        let q = sql_query(
+            r#"SELECT date_trunc($1, t.bucket_at) AS bucket, t.level,
+  SUM(t.count)::bigint AS count
+FROM (
+  SELECT bucket_at, level::text AS level, count
+  FROM message_counts
+  WHERE stream_id IN (SELECT id FROM streams WHERE namespace_id = $2)
+    AND ($3::bigint IS NULL OR stream_id = $3)
+    AND bucket_at >= $4 AND bucket_at < $6
+  UNION ALL
+  SELECT created_at, level::text, 1
+  FROM messages
+  WHERE stream_id IN (SELECT id FROM streams WHERE namespace_id = $2)
+    AND ($3::bigint IS NULL OR stream_id = $3)
+    AND created_at >= GREATEST($4, $6) AND created_at < $5
+) t
+GROUP BY 1, 2
+ORDER BY 1, 2"#,
+        )
+        .bind::<Text, _>(bucket.to_string())
+        .bind::<BigInt, _>(filter.namespace_id)
+        .bind::<Nullable<BigInt>, _>(filter.stream_id)
+        .bind::<Timestamp, _>(filter.since)
+        .bind::<Timestamp, _>(filter.until)
+        .bind::<Nullable<Timestamp>, _>(filter.boundary);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.load::<LevelCount>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
+    /// Returns the streams which have received the most messages since the
+    /// time (at most the limit). It's for metrics, and reads messages
+    /// directly as the latest hour is not rolled up yet.
+    pub fn top_streams(
+        since: NaiveDateTime,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Vec<StreamCount>> {
+        let q = sql_query(
+            r#"SELECT s.uuid::text AS stream, t.count
+FROM (
+  SELECT stream_id, COUNT(*) AS count
+  FROM messages
+  WHERE created_at >= $1
+  GROUP BY 1
+  ORDER BY 2 DESC, 1
+  LIMIT $2
+) t
+INNER JOIN streams s ON s.id = t.stream_id
+ORDER BY 2 DESC, 1"#,
+        )
+        .bind::<Timestamp, _>(since)
+        .bind::<BigInt, _>(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.load::<StreamCount>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+
+    /// Returns the most frequent codes (at most the limit). Messages without
+    /// code are not counted.
+    pub fn top_codes(
+        filter: &CountFilter,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Vec<CodeCount>> {
+        let q = sql_query(
+            r#"SELECT t.code, SUM(t.count)::bigint AS count
+FROM (
+  SELECT code, count
+  FROM message_counts
+  WHERE stream_id IN (SELECT id FROM streams WHERE namespace_id = $1)
+    AND ($2::bigint IS NULL OR stream_id = $2)
+    AND bucket_at >= $3 AND bucket_at < $5 AND code <> ''
+  UNION ALL
+  SELECT code, 1
+  FROM messages
+  WHERE stream_id IN (SELECT id FROM streams WHERE namespace_id = $1)
+    AND ($2::bigint IS NULL OR stream_id = $2)
+    AND created_at >= GREATEST($3, $5) AND created_at < $4
+    AND code IS NOT NULL
+) t
+GROUP BY 1
+ORDER BY 2 DESC, 1
+LIMIT $6"#,
+        )
+        .bind::<BigInt, _>(filter.namespace_id)
+        .bind::<Nullable<BigInt>, _>(filter.stream_id)
+        .bind::<Timestamp, _>(filter.since)
+        .bind::<Timestamp, _>(filter.until)
+        .bind::<Nullable<Timestamp>, _>(filter.boundary)
+        .bind::<BigInt, _>(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.load::<CodeCount>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            e
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{NaiveDate, Utc};
+
+    use crate::model::message::{Message, NewMessage};
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::{Stream, streams};
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2021, 1, 1).and_hms(h, m, 0)
+    }
+
+    fn insert_messages(stream: &Stream, conn: &PgConnection, logger: &Logger) {
+        let messages: Vec<NewMessage> = vec![
+            (at(0, 10), LogLevel::Error, Some("E1")),
+            (at(0, 20), LogLevel::Error, Some("E1")),
+            (at(0, 30), LogLevel::Warning, None),
+            (at(1, 10), LogLevel::Error, Some("E2")),
+            (at(2, 10), LogLevel::Error, Some("E1")),
+        ]
+        .into_iter()
+        .map(|(created_at, level, code)| {
+            NewMessage {
+                agent_id: 1,
+                stream_id: stream.id,
+                level,
+                code: code.map(|c| c.to_string()),
+                title: Some("title".to_string()),
+                created_at: Some(created_at),
+
+                ..Default::default()
+            }
+        })
+        .collect();
+        let _ = Message::insert_all(&messages, conn, logger).unwrap();
+    }
+
+    #[test]
+    fn test_count_by_level() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+            insert_messages(&stream, conn, logger);
+
+            let filter = CountFilter {
+                namespace_id: stream.namespace_id,
+                stream_id: None,
+                since: at(0, 0),
+                until: at(2, 0),
+                boundary: None,
+            };
+            let counts = MessageCount::count_by_level(
+                &filter,
+                &Bucket::Hour,
+                conn,
+                logger,
+            )
+            .unwrap();
+            let counts: Vec<(NaiveDateTime, &str, i64)> = counts
+                .iter()
+                .map(|c| (c.bucket, c.level.as_str(), c.count))
+                .collect();
+            assert_eq!(
+                vec![
+                    (at(0, 0), "error", 2),
+                    (at(0, 0), "warning", 1),
+                    (at(1, 0), "error", 1),
+                ],
+                counts
+            );
+
+            let codes =
+                MessageCount::top_codes(&filter, 1, conn, logger).unwrap();
+            assert_eq!(codes.len(), 1);
+            assert_eq!(codes[0].code, "E1");
+            assert_eq!(codes[0].count, 2);
+        })
+    }
+
+    #[test]
+    fn test_top_streams() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+            insert_messages(&stream, conn, logger);
+
+            let counts =
+                MessageCount::top_streams(at(1, 0), 10, conn, logger).unwrap();
+            let counts: Vec<(&str, i64)> = counts
+                .iter()
+                .map(|c| (c.stream.as_str(), c.count))
+                .collect();
+            assert_eq!(vec![(stream.uuid.to_string().as_str(), 2)], counts);
+
+            let counts =
+                MessageCount::top_streams(at(3, 0), 10, conn, logger).unwrap();
+            assert!(counts.is_empty());
+
+            let counts =
+                MessageCount::top_streams(at(0, 0), 0, conn, logger).unwrap();
+            assert!(counts.is_empty());
+        })
+    }
+
+    #[test]
+    fn test_rollup() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+            insert_messages(&stream, conn, logger);
+
+            assert_eq!(MessageCount::watermark(conn, logger).unwrap(), None);
+            assert_eq!(
+                MessageCount::earliest(conn, logger).unwrap(),
+                Some(at(0, 10))
+            );
+
+            let rows =
+                MessageCount::rollup(at(0, 0), at(2, 0), conn, logger).unwrap();
+            assert_eq!(rows, 3);
+            assert_eq!(
+                MessageCount::watermark(conn, logger).unwrap(),
+                Some(at(2, 0))
+            );
+
+            // rolled up again without duplication
+            let rows =
+                MessageCount::rollup(at(0, 0), at(2, 0), conn, logger).unwrap();
+            assert_eq!(rows, 3);
+
+            // rollups until the boundary, and messages after it
+            let filter = CountFilter {
+                namespace_id: stream.namespace_id,
+                stream_id: Some(stream.id),
+                since: at(0, 0),
+                until: Utc::now().naive_utc(),
+                boundary: Some(at(2, 0)),
+            };
+            let counts = MessageCount::count_by_level(
+                &filter,
+                &Bucket::Day,
+                conn,
+                logger,
+            )
+            .unwrap();
+            let counts: Vec<(&str, i64)> =
+                counts.iter().map(|c| (c.level.as_str(), c.count)).collect();
+            assert_eq!(vec![("error", 4), ("warning", 1)], counts);
+
+            let codes =
+                MessageCount::top_codes(&filter, 10, conn, logger).unwrap();
+            let codes: Vec<(&str, i64)> =
+                codes.iter().map(|c| (c.code.as_str(), c.count)).collect();
+            assert_eq!(vec![("E1", 3), ("E2", 1)], codes);
+        })
+    }
+}
@@ -0,0 +1,80 @@
+//! # Message Deletion
+//!
+//! MessageDeletion is a log of a message deleted by a user. It's written in
+//! the same transaction as the deletion (see `Message::delete`).
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+
+pub use crate::schema::message_deletions;
+
+use crate::logger::Logger;
+
+/// NewMessageDeletion
+#[derive(Debug, Insertable)]
+#[table_name = "message_deletions"]
+pub struct NewMessageDeletion {
+    pub stream_id: i64,
+    pub message_id: i64,
+    pub user_id: i64,
+    pub title: String,
+}
+
+/// MessageDeletion
+#[derive(Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[table_name = "message_deletions"]
+pub struct MessageDeletion {
+    pub id: i64,
+    pub stream_id: i64,
+    pub message_id: i64,
+    pub user_id: i64,
+    pub title: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl fmt::Display for MessageDeletion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<MessageDeletion {id}>", id = &self.message_id)
+    }
+}
+
+impl MessageDeletion {
+    /// Returns deletions on the stream (the newest one comes first).
+    pub fn find_all_by_stream_id(
+        stream_id: i64,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = message_deletions::table
+            .filter(message_deletions::stream_id.eq(stream_id))
+            .order(message_deletions::id.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        deletion: &NewMessageDeletion,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> QueryResult<Self> {
+        let q = diesel::insert_into(message_deletions::table).values(deletion);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn)
+    }
+}
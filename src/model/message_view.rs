@@ -0,0 +1,374 @@
+//! # Message View
+//!
+//! MessageView is a saved query for messages in the namespace. It's private
+//! to the user who made it, or shared with all the members of the namespace.
+//! Running it returns the same messages as the search by the same query.
+use std::fmt;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use uuid::Uuid;
+
+pub use crate::schema::message_views;
+
+use crate::logger::Logger;
+use crate::model::message::{LogLevel, MessageQuery};
+use crate::model::stream::streams;
+use crate::request::message_view::MessageView as RequestData;
+
+/// NewMessageView
+#[derive(Debug, Insertable)]
+#[table_name = "message_views"]
+pub struct NewMessageView {
+    pub namespace_id: i64,
+    pub user_id: i64,
+    pub stream_id: Option<i64>,
+    pub name: String,
+    pub shared: bool,
+    pub levels: Vec<String>,
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub time_window: Option<i32>,
+}
+
+impl Default for NewMessageView {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            namespace_id: -1,
+            user_id: -1,
+            stream_id: None,
+            name: "".to_string(),
+            shared: false,
+            levels: vec![],
+            code: None,
+            lang: None,
+            time_window: None,
+        }
+    }
+}
+
+impl From<RequestData> for NewMessageView {
+    // The stream is given as uuid, so it must be set by the caller
+    fn from(data: RequestData) -> Self {
+        Self {
+            name: data.name.unwrap_or_default(),
+            shared: data.shared.unwrap_or(false),
+            levels: data
+                .levels
+                .unwrap_or_default()
+                .iter()
+                .map(|l| l.to_ascii_lowercase())
+                .collect(),
+            code: data.code,
+            lang: data.lang,
+            time_window: data.time_window.map(|t| t as i32),
+
+            ..Default::default()
+        }
+    }
+}
+
+impl NewMessageView {
+    /// Returns the query without saving it (e.g. for a search). The time
+    /// window ends at now.
+    pub fn to_query(&self, now: NaiveDateTime) -> MessageQuery {
+        to_query(
+            self.namespace_id,
+            self.stream_id,
+            &self.levels,
+            &self.code,
+            &self.lang,
+            self.time_window,
+            now,
+        )
+    }
+}
+
+fn to_query(
+    namespace_id: i64,
+    stream_id: Option<i64>,
+    levels: &[String],
+    code: &Option<String>,
+    lang: &Option<String>,
+    time_window: Option<i32>,
+    now: NaiveDateTime,
+) -> MessageQuery {
+    MessageQuery {
+        namespace_id,
+        stream_id,
+        levels: levels
+            .iter()
+            .map(|l| LogLevel::from(l.to_string()))
+            .collect(),
+        code: code.clone(),
+        lang: lang.clone(),
+        since: time_window.map(|t| now - Duration::seconds(i64::from(t))),
+    }
+}
+
+/// MessageView
+#[derive(Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[table_name = "message_views"]
+pub struct MessageView {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub namespace_id: i64,
+    pub user_id: i64,
+    pub stream_id: Option<i64>,
+    pub name: String,
+    pub shared: bool,
+    pub levels: Vec<String>,
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub time_window: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for MessageView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<MessageView {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl MessageView {
+    /// Returns the view visible to the user (a shared one or the user's own
+    /// one) in the namespace with the uuid of the stream.
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        user_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<(Self, Option<Uuid>)> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = message_views::table
+            .left_join(streams::table)
+            .select((message_views::all_columns, streams::uuid.nullable()))
+            .filter(message_views::namespace_id.eq(namespace_id))
+            .filter(
+                message_views::shared
+                    .eq(true)
+                    .or(message_views::user_id.eq(user_id)),
+            )
+            .filter(message_views::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<(Self, Option<Uuid>)>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns views visible to the user in the namespace with the uuid of
+    /// the stream (shared ones come first).
+    pub fn find_all_by_namespace_id_and_user_id(
+        namespace_id: i64,
+        user_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, Option<Uuid>)>> {
+        if namespace_id < 1 || user_id < 1 {
+            return None;
+        }
+
+        let q = message_views::table
+            .left_join(streams::table)
+            .select((message_views::all_columns, streams::uuid.nullable()))
+            .filter(message_views::namespace_id.eq(namespace_id))
+            .filter(
+                message_views::shared
+                    .eq(true)
+                    .or(message_views::user_id.eq(user_id)),
+            )
+            .order((message_views::shared.desc(), message_views::id.asc()));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, Option<Uuid>)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        view: &NewMessageView,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(message_views::table).values(view);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Replaces the name, the sharing and the query. The owner of the view
+    /// doesn't change.
+    pub fn update(
+        &self,
+        view: &NewMessageView,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::update(self).set((
+            message_views::stream_id.eq(view.stream_id),
+            message_views::name.eq(&view.name),
+            message_views::shared.eq(view.shared),
+            message_views::levels.eq(&view.levels),
+            message_views::code.eq(&view.code),
+            message_views::lang.eq(&view.lang),
+            message_views::time_window.eq(view.time_window),
+            message_views::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Returns the query of the view. The time window ends at now.
+    pub fn to_query(&self, now: NaiveDateTime) -> MessageQuery {
+        to_query(
+            self.namespace_id,
+            self.stream_id,
+            &self.levels,
+            &self.code,
+            &self.lang,
+            self.time_window,
+            now,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::user::{User, users};
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_find_all_by_namespace_id_and_user_id() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for (name, shared) in &[("mine", false), ("ours", true)] {
+                let v = NewMessageView {
+                    namespace_id: namespace.id,
+                    user_id: user.id,
+                    name: name.to_string(),
+                    shared: *shared,
+                    levels: vec!["error".to_string()],
+
+                    ..Default::default()
+                };
+                assert!(MessageView::insert(&v, conn, logger).is_some());
+            }
+
+            let views = MessageView::find_all_by_namespace_id_and_user_id(
+                namespace.id,
+                user.id,
+                conn,
+                logger,
+            )
+            .unwrap();
+            let names: Vec<&str> =
+                views.iter().map(|(v, _)| v.name.as_str()).collect();
+            assert_eq!(names, vec!["ours", "mine"]);
+            assert_eq!(views[0].1, None);
+
+            // others can see only the shared one
+            let views = MessageView::find_all_by_namespace_id_and_user_id(
+                namespace.id,
+                user.id + 1,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(views.len(), 1);
+            assert_eq!(views[0].0.name, "ours");
+        })
+    }
+
+    #[test]
+    fn test_to_query() {
+        let now = Utc::now().naive_utc();
+        let view = MessageView {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            namespace_id: 1,
+            user_id: 1,
+            stream_id: Some(2),
+            name: "errors".to_string(),
+            shared: false,
+            levels: vec!["error".to_string(), "critical".to_string()],
+            code: Some("E1".to_string()),
+            lang: None,
+            time_window: Some(3600),
+            created_at: now,
+            updated_at: now,
+        };
+
+        let query = view.to_query(now);
+        assert_eq!(query.namespace_id, 1);
+        assert_eq!(query.stream_id, Some(2));
+        assert_eq!(query.levels, vec![LogLevel::Error, LogLevel::Critical]);
+        assert_eq!(query.code, Some("E1".to_string()));
+        assert_eq!(query.since, Some(now - Duration::hours(1)));
+    }
+}
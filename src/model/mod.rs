@@ -5,9 +5,15 @@
 // sql types
 mod access_token_state;
 mod agent_type;
+mod alert_channel;
+mod alert_condition;
+mod alert_state;
+mod audit_event_action;
+mod issue_state;
 mod log_level;
 mod log_format;
 mod membership_role;
+mod redaction_detector;
 mod user_email_identification_state;
 mod user_email_role;
 mod user_reset_password_state;
@@ -18,12 +24,26 @@ pub mod token;
 
 // models
 pub mod access_token;
+pub mod access_token_state_history;
+pub mod alert;
+pub mod alert_rule;
+pub mod annotation;
+pub mod audit_event;
+pub mod issue;
 pub mod message;
+pub mod message_count;
+pub mod message_deletion;
+pub mod message_view;
 pub mod membership;
 pub mod namespace;
+pub mod redaction_rule;
 pub mod stream;
 pub mod user;
 pub mod user_email;
+pub mod user_recovery_code;
+pub mod user_totp;
+pub mod webhook;
+pub mod webhook_delivery;
 
 use diesel::pg::PgConnection;
 
@@ -149,10 +169,24 @@ pub mod test {
         let tables = [
             "users",
             "user_emails",
+            "user_recovery_codes",
+            "user_totps",
             "access_tokens",
+            "access_token_state_histories",
+            "alert_rules",
+            "alerts",
+            "annotations",
+            "audit_events",
+            "issues",
             "messages",
+            "message_counts",
+            "message_deletions",
+            "message_views",
             "namespaces",
+            "redaction_rules",
             "streams",
+            "webhooks",
+            "webhook_deliveries",
         ]
         .join(", ");
         let q = format!("TRUNCATE TABLE {} RESTART IDENTITY CASCADE;", tables);
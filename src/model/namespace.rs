@@ -2,7 +2,7 @@
 use std::fmt;
 use std::str;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{Identifiable, Insertable, Queryable, debug_query, prelude::*};
 use diesel::dsl;
 use diesel::pg::{Pg, PgConnection};
@@ -11,7 +11,7 @@ use uuid::Uuid;
 
 use crate::logger::Logger;
 use crate::request::namespace::Namespace as RequestData;
-use crate::model::membership::{Membership, memberships};
+use crate::model::membership::{Membership, MembershipRole, memberships};
 use crate::model::user::User;
 
 pub use crate::schema::namespaces;
@@ -189,6 +189,71 @@ impl Namespace {
         }
     }
 
+    /// Returns namespaces which the user owns as a primary owner, and which
+    /// have other members (the user can't leave them as they are).
+    pub fn find_all_shared_by_primary_owner(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let owned = memberships::table
+            .select(memberships::namespace_id)
+            .filter(Membership::with_user(user))
+            .filter(memberships::role.eq(MembershipRole::PrimaryOwner))
+            .filter(memberships::revoked_at.is_null());
+        let shared = memberships::table
+            .select(memberships::namespace_id)
+            .filter(memberships::user_id.ne(user.id))
+            .filter(memberships::revoked_at.is_null());
+        let q = Self::all()
+            .filter(namespaces::id.eq_any(owned))
+            .filter(namespaces::id.eq_any(shared))
+            .filter(Self::visible());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Archives all the namespaces which the user owns as a primary owner.
+    pub fn archive_all_by_primary_owner(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let owned = memberships::table
+            .select(memberships::namespace_id)
+            .filter(Membership::with_user(user))
+            .filter(memberships::role.eq(MembershipRole::PrimaryOwner))
+            .filter(memberships::revoked_at.is_null());
+        let q = diesel::update(
+            namespaces::table
+                .filter(namespaces::id.eq_any(owned))
+                .filter(Self::visible()),
+        )
+        .set(namespaces::archived_at.eq(Utc::now().naive_utc()));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to archive")
+            },
+            Ok(n) => Ok(n),
+        }
+    }
+
     pub fn insert(
         namespace: &NewNamespace,
         conn: &PgConnection,
@@ -279,7 +344,7 @@ pub mod data {
 mod test {
     use super::*;
 
-    use crate::model::membership::{Membership, memberships};
+    use crate::model::membership::{Membership, MembershipRole, memberships};
     use crate::model::user::{User, users};
 
     use crate::model::membership::data::MEMBERSHIPS;
@@ -402,4 +467,94 @@ mod test {
             assert_eq!(result.streams_count, 0);
         })
     }
+
+    #[test]
+    fn test_find_all_shared_by_primary_owner() {
+        run(|conn, _, logger| {
+            let n = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(n)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("oswald").unwrap();
+            let oswald = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("weenie").unwrap();
+            let weenie = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let m = MEMBERSHIPS.get("oswald as a primary owner").unwrap();
+            let _ = diesel::insert_into(memberships::table)
+                .values(m)
+                .get_result::<Membership>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = Namespace::find_all_shared_by_primary_owner(
+                &oswald, conn, logger,
+            );
+            assert_eq!(result, Some(vec![]));
+
+            let _ = diesel::insert_into(memberships::table)
+                .values((
+                    memberships::id.eq(2),
+                    memberships::namespace_id.eq(namespace.id),
+                    memberships::user_id.eq(weenie.id),
+                    memberships::role.eq(MembershipRole::Member),
+                ))
+                .get_result::<Membership>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = Namespace::find_all_shared_by_primary_owner(
+                &oswald, conn, logger,
+            );
+            assert_eq!(result, Some(vec![namespace]));
+
+            // weenie is not a primary owner
+            let result = Namespace::find_all_shared_by_primary_owner(
+                &weenie, conn, logger,
+            );
+            assert_eq!(result, Some(vec![]));
+        })
+    }
+
+    #[test]
+    fn test_archive_all_by_primary_owner() {
+        run(|conn, _, logger| {
+            let n = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(n)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let m = MEMBERSHIPS.get("oswald as a primary owner").unwrap();
+            let _ = diesel::insert_into(memberships::table)
+                .values(m)
+                .get_result::<Membership>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result =
+                Namespace::archive_all_by_primary_owner(&user, conn, logger);
+            assert_eq!(result, Ok(1));
+
+            let result = Namespace::find_by_uuid(
+                &namespace.uuid.to_string(),
+                &user,
+                conn,
+                logger,
+            );
+            assert_eq!(result, None);
+        })
+    }
 }
@@ -0,0 +1,147 @@
+//! # A type RedactionDetector for RedactionRule in redaction_rule.rs
+//!
+//! ERedactionDetector represents SQL type value `e_redaction_detector` and
+//! RedactionDetector is an Enum contains all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_redaction_detector")]
+pub struct ERedactionDetector;
+
+#[derive(
+    AsExpression, Clone, Debug, Deserialize, FromSqlRow, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "ERedactionDetector"]
+pub enum RedactionDetector {
+    Email,
+    CreditCard,
+    Jwt,
+    BearerToken,
+    IpAddress,
+    Custom,
+}
+
+const REDACTION_DETECTORS: [RedactionDetector; 6] = [
+    RedactionDetector::Email,
+    RedactionDetector::CreditCard,
+    RedactionDetector::Jwt,
+    RedactionDetector::BearerToken,
+    RedactionDetector::IpAddress,
+    RedactionDetector::Custom,
+];
+
+impl fmt::Display for RedactionDetector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = self.as_ref();
+        write!(f, "{}", s)
+    }
+}
+
+impl AsRef<str> for RedactionDetector {
+    fn as_ref(&self) -> &str {
+        match *self {
+            Self::Email => "email",
+            Self::CreditCard => "credit_card",
+            Self::Jwt => "jwt",
+            Self::BearerToken => "bearer_token",
+            Self::IpAddress => "ip_address",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+impl From<String> for RedactionDetector {
+    fn from(s: String) -> Self {
+        let s = s.to_ascii_lowercase();
+        match Self::iter().find(|v| v.as_ref() == s) {
+            Some(v) => v.clone(),
+            None => RedactionDetector::Custom,
+        }
+    }
+}
+
+impl ToSql<ERedactionDetector, Pg> for RedactionDetector {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let s: &str = self.as_ref();
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<ERedactionDetector, Pg> for RedactionDetector {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let b = not_none!(bytes);
+        match Self::iter().find(|v| v.as_ref().as_bytes() == b) {
+            Some(v) => Ok(v.clone()),
+            None => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl RedactionDetector {
+    pub fn iter() -> Iter<'static, RedactionDetector> {
+        REDACTION_DETECTORS.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("email", format!("{}", RedactionDetector::Email));
+        assert_eq!("credit_card", format!("{}", RedactionDetector::CreditCard));
+        assert_eq!("jwt", format!("{}", RedactionDetector::Jwt));
+        assert_eq!(
+            "bearer_token",
+            format!("{}", RedactionDetector::BearerToken)
+        );
+        assert_eq!("ip_address", format!("{}", RedactionDetector::IpAddress));
+        assert_eq!("custom", format!("{}", RedactionDetector::Custom));
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(
+            RedactionDetector::Email,
+            RedactionDetector::from("email".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::CreditCard,
+            RedactionDetector::from("credit_card".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::Jwt,
+            RedactionDetector::from("jwt".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::BearerToken,
+            RedactionDetector::from("bearer_token".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::IpAddress,
+            RedactionDetector::from("ip_address".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::Custom,
+            RedactionDetector::from("custom".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::Custom,
+            RedactionDetector::from("unknown".to_string())
+        );
+    }
+}
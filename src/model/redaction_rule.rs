@@ -0,0 +1,245 @@
+//! # Redaction Rule
+//!
+//! RedactionRule enables a detector of sensitive data (e.g. email addresses)
+//! in messages on the namespace, or a custom one by a regular expression.
+//! Matches are replaced before messages are saved. See redaction.rs.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use uuid::Uuid;
+
+pub use crate::model::redaction_detector::*;
+pub use crate::schema::redaction_rules;
+
+use crate::logger::Logger;
+use crate::request::redaction_rule::RedactionRule as RequestData;
+
+/// NewRedactionRule
+#[derive(Debug, Insertable)]
+#[table_name = "redaction_rules"]
+pub struct NewRedactionRule {
+    pub namespace_id: i64,
+    pub detector: RedactionDetector,
+    pub name: String,
+    pub pattern: Option<String>,
+}
+
+impl Default for NewRedactionRule {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            namespace_id: -1,
+            detector: RedactionDetector::Custom,
+            name: "".to_string(),
+            pattern: None,
+        }
+    }
+}
+
+impl From<RequestData> for NewRedactionRule {
+    // The name of a built-in detector defaults to the detector itself
+    fn from(data: RequestData) -> Self {
+        let detector = data
+            .detector
+            .map(RedactionDetector::from)
+            .unwrap_or(RedactionDetector::Custom);
+        let name = match data.name {
+            Some(name) => name,
+            None if detector != RedactionDetector::Custom => {
+                detector.to_string()
+            },
+            None => "".to_string(),
+        };
+        let pattern = if detector == RedactionDetector::Custom {
+            data.pattern
+        } else {
+            None
+        };
+        Self {
+            detector,
+            name,
+            pattern,
+
+            ..Default::default()
+        }
+    }
+}
+
+/// RedactionRule
+#[derive(Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[table_name = "redaction_rules"]
+pub struct RedactionRule {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub namespace_id: i64,
+    pub detector: RedactionDetector,
+    pub name: String,
+    pub pattern: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for RedactionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<RedactionRule {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl RedactionRule {
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = redaction_rules::table
+            .filter(redaction_rules::namespace_id.eq(namespace_id))
+            .filter(redaction_rules::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if namespace_id < 1 {
+            return None;
+        }
+
+        let q = redaction_rules::table
+            .filter(redaction_rules::namespace_id.eq(namespace_id))
+            .order(redaction_rules::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        rule: &NewRedactionRule,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(redaction_rules::table).values(rule);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(r) => Some(r),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::{Namespace, namespaces};
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+
+    #[test]
+    fn test_new_redaction_rule_from() {
+        let r = NewRedactionRule::from(RequestData {
+            detector: Some("email".to_string()),
+            name: None,
+            pattern: Some("ignored".to_string()),
+        });
+        assert_eq!(r.detector, RedactionDetector::Email);
+        assert_eq!(r.name, "email");
+        assert_eq!(r.pattern, None);
+
+        let r = NewRedactionRule::from(RequestData {
+            detector: Some("custom".to_string()),
+            name: Some("api_key".to_string()),
+            pattern: Some("sk_[0-9a-z]+".to_string()),
+        });
+        assert_eq!(r.detector, RedactionDetector::Custom);
+        assert_eq!(r.name, "api_key");
+        assert_eq!(r.pattern, Some("sk_[0-9a-z]+".to_string()));
+    }
+
+    #[test]
+    fn test_find_all_by_namespace_id() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for detector in &[RedactionDetector::Email, RedactionDetector::Jwt]
+            {
+                let r = NewRedactionRule {
+                    namespace_id: namespace.id,
+                    detector: detector.clone(),
+                    name: detector.to_string(),
+                    pattern: None,
+                };
+                assert!(RedactionRule::insert(&r, conn, logger).is_some());
+            }
+
+            let rules = RedactionRule::find_all_by_namespace_id(
+                namespace.id,
+                conn,
+                logger,
+            )
+            .unwrap();
+            let names: Vec<&str> =
+                rules.iter().map(|r| r.name.as_str()).collect();
+            assert_eq!(names, vec!["email", "jwt"]);
+
+            assert!(rules[0].delete(conn, logger).is_ok());
+            assert!(RedactionRule::find_by_uuid(
+                &rules[0].uuid.to_string(),
+                namespace.id,
+                conn,
+                logger
+            )
+            .is_none());
+        })
+    }
+}
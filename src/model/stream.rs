@@ -110,6 +110,24 @@ impl Stream {
         Self::all().filter(Self::with_uuid(uuid))
     }
 
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::all().filter(streams::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn find_by_uuid(
         uuid: &str,
         conn: &PgConnection,
@@ -33,6 +33,9 @@ const BCRYPT_COST: u32 = 12;
 const RESET_PASSWORD_HASH_LENGTH: i32 = 128;
 const RESET_PASSWORD_HASH_SOURCE: &[u8] =
     b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const DELETED_USERNAME_PREFIX: &str = "deleted-";
+const DELETED_USERNAME_HASH_LENGTH: i32 = 24; // username is VARCHAR(32)
+const DELETED_EMAIL_DOMAIN: &str = "deleted.invalid";
 
 /// Returns encrypted password hash as bytes using bcrypt.
 pub fn encrypt_password(password: &str) -> Option<Vec<u8>> {
@@ -395,6 +398,57 @@ impl User {
             Ok(user) => Ok(user.reset_password_token.unwrap()),
         }
     }
+
+    /// Anonymizes the user as deleted. The row itself is kept because other
+    /// records (e.g. audit events) still refer to it, but nothing in it can
+    /// identify the person nor be used to log in anymore.
+    pub fn deregister(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let username = format!(
+            "{}{}",
+            DELETED_USERNAME_PREFIX,
+            generate_random_hash(
+                RESET_PASSWORD_HASH_SOURCE,
+                DELETED_USERNAME_HASH_LENGTH,
+            ),
+        );
+        let email = format!("{}@{}", self.uuid, DELETED_EMAIL_DOMAIN);
+        let password = encrypt_password(&generate_random_hash(
+            RESET_PASSWORD_HASH_SOURCE,
+            RESET_PASSWORD_HASH_LENGTH,
+        ))
+        .ok_or("failed to encrypt password")?;
+
+        let q = diesel::update(
+            users::table
+                .filter(users::id.eq(self.id))
+                .filter(users::state.ne(UserState::Deleted)),
+        )
+        .set((
+            users::name.eq(None::<String>),
+            users::username.eq(username),
+            users::email.eq(email),
+            users::password.eq(password),
+            users::state.eq(UserState::Deleted),
+            users::reset_password_state.eq(UserResetPasswordState::Never),
+            users::reset_password_token.eq(None::<String>),
+            users::reset_password_token_expires_at.eq(None::<NaiveDateTime>),
+            users::reset_password_token_granted_at.eq(None::<NaiveDateTime>),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to deregister")
+            },
+            Ok(user) => Ok(user),
+        }
+    }
 }
 
 impl Activatable for User {
@@ -1082,4 +1136,38 @@ mod test {
             assert_eq!(1, rows_count);
         })
     }
+
+    #[test]
+    fn test_deregister() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = user.deregister(conn, logger);
+            assert!(result.is_ok());
+
+            let deleted = result.unwrap();
+            assert_eq!(deleted.id, user.id);
+            assert_eq!(deleted.uuid, user.uuid);
+            assert_eq!(deleted.state, UserState::Deleted);
+            assert_eq!(deleted.name, None);
+            assert_ne!(deleted.username, user.username);
+            assert!(deleted.username.starts_with(DELETED_USERNAME_PREFIX));
+            assert_eq!(
+                deleted.email,
+                format!("{}@{}", user.uuid, DELETED_EMAIL_DOMAIN)
+            );
+            assert_ne!(deleted.password, user.password);
+
+            assert!(User::find_by_email(&user.email, conn, logger).is_none());
+            assert!(User::find_by_uuid(&user.uuid.to_string(), conn, logger)
+                .is_none());
+
+            // only once
+            assert!(user.deregister(conn, logger).is_err());
+        })
+    }
 }
@@ -11,7 +11,7 @@ pub use crate::schema::user_emails;
 
 use crate::logger::Logger;
 use crate::model::Activatable;
-use crate::model::user::User;
+use crate::model::user::{User, users};
 use crate::util::generate_random_hash;
 
 const VERIFICATION_HASH_LENGTH: i32 = 128;
@@ -88,6 +88,73 @@ impl Clone for UserEmail {
 }
 
 impl UserEmail {
+    /// Returns true if no verified address is the same as `email`. Unverified
+    /// ones don't reserve the address.
+    pub fn check_email_uniqueness(
+        email: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> bool {
+        let q = user_emails::table
+            .select(user_emails::id)
+            .filter(user_emails::email.eq(email))
+            .filter(
+                user_emails::identification_state
+                    .eq(UserEmailIdentificationState::Done),
+            )
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        matches!(q.load::<i64>(conn), Ok(ref v) if v.is_empty())
+    }
+
+    pub fn find_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = Self::belonging_to(user).order(user_emails::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn owned_by_id(
+        user: &User,
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if user.id < 1 || id < 1 {
+            return None;
+        }
+
+        let q = Self::belonging_to(user)
+            .filter(user_emails::id.eq(id))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn find_by_id(
         id: i64,
         conn: &PgConnection,
@@ -164,7 +231,7 @@ impl UserEmail {
         let q = diesel::insert_into(user_emails::table).values((
             user_emails::user_id.eq(&user_email.user_id),
             Some(user_emails::email.eq(&user_email.email)),
-            user_emails::role.eq(UserEmailRole::Primary),
+            user_emails::role.eq(&user_email.role),
             user_emails::identification_state
                 .eq(UserEmailIdentificationState::Pending),
         ));
@@ -212,6 +279,130 @@ impl UserEmail {
         }
     }
 
+    /// Makes this (verified) address primary instead of the current one, and
+    /// copies it into users.email which is used for login.
+    ///
+    /// This should be called within a transaction.
+    pub fn promote(
+        &self,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<User, &'static str> {
+        if self.user_id != user.id ||
+            self.is_primary() ||
+            self.identification_state != UserEmailIdentificationState::Done
+        {
+            return Err("not promotable");
+        }
+        let email = self.email.as_ref().ok_or("not promotable")?;
+
+        let q = diesel::update(
+            Self::belonging_to(user)
+                .filter(user_emails::role.eq(UserEmailRole::Primary)),
+        )
+        .set(user_emails::role.eq(UserEmailRole::General));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        if let Err(e) = q.execute(conn) {
+            error!(logger, "err: {}", e);
+            return Err("failed to demote");
+        }
+
+        let q = diesel::update(self)
+            .set(user_emails::role.eq(UserEmailRole::Primary));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        if let Err(e) = q.execute(conn) {
+            error!(logger, "err: {}", e);
+            return Err("failed to promote");
+        }
+
+        let q = diesel::update(user).set(users::email.eq(email));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<User>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to update user")
+            },
+            Ok(u) => Ok(u),
+        }
+    }
+
+    /// Deletes this address. The primary one can't be deleted.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(
+            user_emails::table
+                .filter(user_emails::id.eq(self.id))
+                .filter(user_emails::role.eq(UserEmailRole::General)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(0) => Err("not found"),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Deletes unverified rows of other users having the same address as this
+    /// one. This is called after this address has been verified.
+    pub fn delete_pending_duplicates(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let q = diesel::delete(
+            user_emails::table
+                .filter(user_emails::email.eq(&self.email))
+                .filter(user_emails::id.ne(self.id))
+                .filter(
+                    user_emails::identification_state
+                        .eq(UserEmailIdentificationState::Pending),
+                ),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(n) => Ok(n),
+        }
+    }
+
+    pub fn delete_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let q = diesel::delete(Self::belonging_to(user));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(n) => Ok(n),
+        }
+    }
+
     pub fn is_primary(&self) -> bool {
         self.role == UserEmailRole::Primary
     }
@@ -655,4 +846,123 @@ mod test {
             );
         });
     }
+
+    #[test]
+    fn test_promote() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let primary =
+                UserEmail::insert(&NewUserEmail::from(&user), conn, logger)
+                    .unwrap();
+
+            let ue = NewUserEmail {
+                user_id: user.id,
+                email: "oswald.new@example.org".to_string(),
+
+                ..Default::default()
+            };
+            let secondary = UserEmail::insert(&ue, conn, logger).unwrap();
+            assert!(!secondary.is_primary());
+
+            // not verified yet
+            assert!(secondary.promote(&user, conn, logger).is_err());
+
+            let _ = secondary.activate(conn, logger);
+            let secondary =
+                UserEmail::find_by_id(secondary.id, conn, logger).unwrap();
+
+            let result = secondary.promote(&user, conn, logger);
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap().email, "oswald.new@example.org");
+
+            let primary = UserEmail::find_by_id(primary.id, conn, logger);
+            assert!(!primary.unwrap().is_primary());
+            let secondary = UserEmail::find_by_id(secondary.id, conn, logger);
+            assert!(secondary.unwrap().is_primary());
+        })
+    }
+
+    #[test]
+    fn test_delete() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let primary =
+                UserEmail::insert(&NewUserEmail::from(&user), conn, logger)
+                    .unwrap();
+
+            let ue = NewUserEmail {
+                user_id: user.id,
+                email: "oswald.new@example.org".to_string(),
+
+                ..Default::default()
+            };
+            let secondary = UserEmail::insert(&ue, conn, logger).unwrap();
+
+            // the primary one can't be deleted
+            assert!(primary.delete(conn, logger).is_err());
+            assert!(secondary.delete(conn, logger).is_ok());
+
+            let result = UserEmail::find_all_by_user(&user, conn, logger);
+            assert_eq!(result.map(|v| v.len()), Some(1));
+        })
+    }
+
+    #[test]
+    fn test_delete_pending_duplicates() {
+        run(|conn, _, logger| {
+            let users = ["oswald", "weenie", "hennry"]
+                .iter()
+                .map(|k| {
+                    diesel::insert_into(users::table)
+                        .values(USERS.get(k).unwrap())
+                        .get_result::<User>(conn)
+                        .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+                })
+                .collect::<Vec<User>>();
+
+            let emails = users
+                .iter()
+                .map(|u| {
+                    let ue = NewUserEmail {
+                        user_id: u.id,
+                        email: "shared@example.org".to_string(),
+
+                        ..Default::default()
+                    };
+                    UserEmail::insert(&ue, conn, logger).unwrap()
+                })
+                .collect::<Vec<UserEmail>>();
+
+            // unverified ones don't reserve the address
+            assert!(UserEmail::check_email_uniqueness(
+                "shared@example.org",
+                conn,
+                logger
+            ));
+
+            let _ = emails[0].activate(conn, logger);
+            assert!(!UserEmail::check_email_uniqueness(
+                "shared@example.org",
+                conn,
+                logger
+            ));
+
+            let result = emails[0].delete_pending_duplicates(conn, logger);
+            assert_eq!(result, Ok(2));
+
+            assert!(UserEmail::find_by_id(emails[0].id, conn, logger).is_some());
+            assert!(UserEmail::find_by_id(emails[1].id, conn, logger).is_none());
+            assert!(UserEmail::find_by_id(emails[2].id, conn, logger).is_none());
+        })
+    }
 }
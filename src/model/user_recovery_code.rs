@@ -0,0 +1,213 @@
+//! # User Recovery Code
+//!
+//! UserRecoveryCode is a single-use code which can be used instead of a TOTP
+//! code. Only its SHA-256 digest is stored, the plain codes are shown to the
+//! user just once.
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use sha2::{Digest, Sha256};
+
+pub use crate::schema::user_recovery_codes;
+
+use crate::logger::Logger;
+use crate::model::user::User;
+use crate::util::generate_random_hash;
+
+const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: i32 = 10;
+// without characters confusing each other (e.g. 0, o, 1, l)
+const RECOVERY_CODE_SOURCE: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+/// UserRecoveryCode
+#[derive(Associations, Debug, Identifiable, PartialEq, Queryable)]
+#[belongs_to(User)]
+#[table_name = "user_recovery_codes"]
+pub struct UserRecoveryCode {
+    pub id: i64,
+    pub user_id: i64,
+    pub code: Vec<u8>,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl fmt::Display for UserRecoveryCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<UserRecoveryCode {id}>", id = &self.id)
+    }
+}
+
+// e.g. "abcde-fghij" -> sha256("abcdefghij")
+fn digest(code: &str) -> Vec<u8> {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Sha256::digest(normalized.as_bytes()).to_vec()
+}
+
+fn generate_code() -> String {
+    let s = generate_random_hash(RECOVERY_CODE_SOURCE, RECOVERY_CODE_LENGTH);
+    let (a, b) = s.split_at((RECOVERY_CODE_LENGTH / 2) as usize);
+    format!("{}-{}", a, b)
+}
+
+impl UserRecoveryCode {
+    /// Replaces all the codes of the user with new ones, and returns them
+    /// as plain text.
+    pub fn generate_for(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<String>> {
+        Self::delete_by_user(user, conn, logger).ok()?;
+
+        let codes: Vec<String> =
+            (0..RECOVERY_CODES_COUNT).map(|_| generate_code()).collect();
+        let values: Vec<_> = codes
+            .iter()
+            .map(|c| {
+                (
+                    user_recovery_codes::user_id.eq(user.id),
+                    user_recovery_codes::code.eq(digest(c)),
+                )
+            })
+            .collect();
+        let q = diesel::insert_into(user_recovery_codes::table).values(&values);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(_) => Some(codes),
+        }
+    }
+
+    /// Marks the code as used. Returns false if the code is unknown or has
+    /// already been used.
+    pub fn consume(
+        user: &User,
+        code: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> bool {
+        if user.id < 1 || code.is_empty() {
+            return false;
+        }
+
+        let q = diesel::update(
+            Self::belonging_to(user)
+                .filter(user_recovery_codes::code.eq(digest(code)))
+                .filter(user_recovery_codes::used_at.is_null()),
+        )
+        .set(user_recovery_codes::used_at.eq(Utc::now().naive_utc()));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                false
+            },
+            Ok(n) => n == 1,
+        }
+    }
+
+    pub fn delete_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(Self::belonging_to(user));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::user::users;
+
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(digest("abcde-fghij"), digest("ABCDEFGHIJ"));
+        assert_ne!(digest("abcde-fghij"), digest("abcde-fghik"));
+    }
+
+    #[test]
+    fn test_generate_code() {
+        let code = generate_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(code.chars().nth(5), Some('-'));
+    }
+
+    #[test]
+    fn test_generate_for() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let codes =
+                UserRecoveryCode::generate_for(&user, conn, logger).unwrap();
+            assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+
+            // replaces old ones
+            let _ = UserRecoveryCode::generate_for(&user, conn, logger);
+
+            let rows_count: i64 = user_recovery_codes::table
+                .count()
+                .first(conn)
+                .expect("Failed to count rows");
+            assert_eq!(RECOVERY_CODES_COUNT as i64, rows_count);
+            assert!(!UserRecoveryCode::consume(&user, &codes[0], conn, logger));
+        })
+    }
+
+    #[test]
+    fn test_consume() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let codes =
+                UserRecoveryCode::generate_for(&user, conn, logger).unwrap();
+
+            assert!(!UserRecoveryCode::consume(&user, "", conn, logger));
+            assert!(!UserRecoveryCode::consume(
+                &user,
+                "xxxxx-xxxxx",
+                conn,
+                logger
+            ));
+
+            assert!(UserRecoveryCode::consume(&user, &codes[0], conn, logger));
+            // only once
+            assert!(!UserRecoveryCode::consume(&user, &codes[0], conn, logger));
+        })
+    }
+}
@@ -0,0 +1,275 @@
+//! # User TOTP
+//!
+//! UserTotp holds the shared secret of TOTP, which is required as a second
+//! factor at login once it has been confirmed.
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+
+pub use crate::schema::user_totps;
+
+use crate::logger::Logger;
+use crate::model::user::User;
+use crate::totp;
+
+/// UserTotp
+#[derive(Associations, Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[belongs_to(User)]
+#[table_name = "user_totps"]
+pub struct UserTotp {
+    pub id: i64,
+    pub user_id: i64,
+    pub secret: Vec<u8>,
+    pub last_used_step: i64,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for UserTotp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<UserTotp {id}>", id = &self.id)
+    }
+}
+
+impl UserTotp {
+    pub fn find_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = Self::belonging_to(user).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Saves a new secret for the user. An existing (unconfirmed) one will be
+    /// replaced.
+    pub fn insert(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let secret = totp::generate_secret();
+        let q = diesel::insert_into(user_totps::table)
+            .values((
+                user_totps::user_id.eq(user.id),
+                user_totps::secret.eq(&secret),
+            ))
+            .on_conflict(user_totps::user_id)
+            .do_update()
+            .set((
+                user_totps::secret.eq(&secret),
+                user_totps::last_used_step.eq(0),
+                user_totps::confirmed_at.eq(None::<NaiveDateTime>),
+                user_totps::updated_at.eq(Utc::now().naive_utc()),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(t) => Some(t),
+        }
+    }
+
+    pub fn delete_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(Self::belonging_to(user));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+
+    pub fn encoded_secret(&self) -> String {
+        totp::encode_secret(&self.secret)
+    }
+
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        totp::provisioning_uri(&self.secret, account)
+    }
+
+    pub fn confirm(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let now = Utc::now().naive_utc();
+        let q = diesel::update(
+            user_totps::table
+                .filter(user_totps::id.eq(self.id))
+                .filter(user_totps::confirmed_at.is_null()),
+        )
+        .set((
+            user_totps::confirmed_at.eq(now),
+            user_totps::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to confirm")
+            },
+            Ok(t) => Ok(t),
+        }
+    }
+
+    /// Verifies the code, and marks its step as used so that the same code
+    /// can't be used twice.
+    pub fn verify(
+        &self,
+        code: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> bool {
+        let step =
+            match totp::verify(&self.secret, code, Utc::now().timestamp()) {
+                Some(s) => s,
+                None => return false,
+            };
+
+        let q = diesel::update(
+            user_totps::table
+                .filter(user_totps::id.eq(self.id))
+                .filter(user_totps::last_used_step.lt(step)),
+        )
+        .set(user_totps::last_used_step.eq(step));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                false
+            },
+            Ok(n) => n == 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::user::users;
+
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_insert() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let t = UserTotp::insert(&user, conn, logger).unwrap();
+            assert_eq!(t.user_id, user.id);
+            assert!(!t.is_confirmed());
+
+            // replaces the secret
+            let result = UserTotp::insert(&user, conn, logger).unwrap();
+            assert_eq!(result.id, t.id);
+            assert_ne!(result.secret, t.secret);
+
+            let rows_count: i64 = user_totps::table
+                .count()
+                .first(conn)
+                .expect("Failed to count rows");
+            assert_eq!(1, rows_count);
+        })
+    }
+
+    #[test]
+    fn test_confirm() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let t = UserTotp::insert(&user, conn, logger).unwrap();
+            let result = t.confirm(conn, logger);
+            assert!(result.is_ok());
+            assert!(result.unwrap().is_confirmed());
+
+            // only once
+            assert!(t.confirm(conn, logger).is_err());
+
+            let t = UserTotp::find_by_user(&user, conn, logger).unwrap();
+            assert!(t.is_confirmed());
+        })
+    }
+
+    #[test]
+    fn test_verify() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let t = UserTotp::insert(&user, conn, logger).unwrap();
+            assert!(!t.verify("000000x", conn, logger));
+
+            let step = Utc::now().timestamp() / 30;
+            let code = totp::generate(&t.secret, step);
+            assert!(t.verify(&code, conn, logger));
+
+            // the same code can't be used twice
+            assert!(!t.verify(&code, conn, logger));
+        })
+    }
+
+    #[test]
+    fn test_delete_by_user() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let _ = UserTotp::insert(&user, conn, logger).unwrap();
+            assert!(UserTotp::delete_by_user(&user, conn, logger).is_ok());
+            assert!(UserTotp::find_by_user(&user, conn, logger).is_none());
+        })
+    }
+}
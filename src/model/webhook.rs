@@ -0,0 +1,370 @@
+//! # Webhook
+//!
+//! Webhook subscribes to messages in a namespace, or only ones on a stream of
+//! it. Messages which pass through the filter (the minimum level and the
+//! code) are delivered to the URL with a signature made by the secret. See
+//! also WebhookDelivery in webhook_delivery.rs.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use uuid::Uuid;
+
+pub use crate::schema::webhooks;
+
+use crate::logger::Logger;
+use crate::model::message::{LogLevel, NewMessage};
+use crate::model::namespace::Namespace;
+use crate::model::stream::Stream;
+use crate::request::webhook::Webhook as RequestData;
+use crate::util::generate_random_hash;
+
+const SECRET_LENGTH: i32 = 64;
+const SECRET_SOURCE: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// NewWebhook
+#[derive(Debug)]
+pub struct NewWebhook {
+    pub namespace_id: i64,
+    pub stream_id: Option<i64>,
+    pub url: String,
+    pub secret: String,
+    pub level: Option<LogLevel>,
+    pub code: Option<String>,
+}
+
+impl Default for NewWebhook {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            namespace_id: -1,
+            stream_id: None,
+            url: "".to_string(),
+            secret: "".to_string(),
+            level: None,
+            code: None,
+        }
+    }
+}
+
+impl From<RequestData> for NewWebhook {
+    fn from(data: RequestData) -> Self {
+        Self {
+            url: data.url.unwrap_or_default(),
+            level: data.level.map(LogLevel::from),
+            code: data.code,
+
+            ..Default::default()
+        }
+    }
+}
+
+/// Webhook
+#[derive(Associations, Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[belongs_to(Namespace)]
+#[table_name = "webhooks"]
+pub struct Webhook {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub namespace_id: i64,
+    pub stream_id: Option<i64>,
+    pub url: String,
+    pub secret: String,
+    pub level: Option<LogLevel>,
+    pub code: Option<String>,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Webhook {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl Webhook {
+    pub fn generate_secret() -> String {
+        generate_random_hash(SECRET_SOURCE, SECRET_LENGTH)
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = webhooks::table.filter(webhooks::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_uuid(
+        uuid: &str,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        let q = webhooks::table
+            .filter(webhooks::namespace_id.eq(namespace_id))
+            .filter(webhooks::uuid.eq(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if namespace_id < 1 {
+            return None;
+        }
+
+        let q = webhooks::table
+            .filter(webhooks::namespace_id.eq(namespace_id))
+            .order(webhooks::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns enabled webhooks which subscribe to messages on the stream.
+    pub fn find_all_enabled_by_stream(
+        stream: &Stream,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = webhooks::table
+            .filter(webhooks::namespace_id.eq(stream.namespace_id))
+            .filter(
+                webhooks::stream_id
+                    .is_null()
+                    .or(webhooks::stream_id.eq(stream.id)),
+            )
+            .filter(webhooks::enabled.eq(true))
+            .order(webhooks::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        webhook: &NewWebhook,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(webhooks::table).values((
+            webhooks::namespace_id.eq(webhook.namespace_id),
+            webhooks::stream_id.eq(webhook.stream_id),
+            webhooks::url.eq(&webhook.url),
+            webhooks::secret.eq(&webhook.secret),
+            webhooks::level.eq(&webhook.level),
+            webhooks::code.eq(&webhook.code),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(w) => Some(w),
+        }
+    }
+
+    /// Deletes the webhook. Its delivery log is deleted together.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn set_enabled(
+        &self,
+        enabled: bool,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self)
+            .set((webhooks::enabled.eq(enabled), webhooks::updated_at.eq(now)));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update"
+        })
+    }
+
+    /// Checks whether the message passes through the filter of the webhook.
+    pub fn matches(&self, message: &NewMessage) -> bool {
+        self.stream_id.map_or(true, |id| message.stream_id == id) &&
+            self.level
+                .as_ref()
+                .map_or(true, |l| l.and_higher().contains(&message.level)) &&
+            self.code
+                .as_ref()
+                .map_or(true, |c| message.code.as_ref() == Some(c))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Utc;
+
+    use crate::model::namespace::namespaces;
+    use crate::model::stream::streams;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = Webhook::generate_secret();
+        assert_eq!(secret.len(), SECRET_LENGTH as usize);
+        assert_ne!(secret, Webhook::generate_secret());
+    }
+
+    #[test]
+    fn test_matches() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let w = NewWebhook {
+                namespace_id: stream.namespace_id,
+                url: "https://example.org/hook".to_string(),
+                secret: Webhook::generate_secret(),
+                level: Some(LogLevel::Warning),
+
+                ..Default::default()
+            };
+            let mut webhook = Webhook::insert(&w, conn, logger).unwrap();
+
+            let mut m = NewMessage {
+                stream_id: stream.id,
+                level: LogLevel::Information,
+
+                ..Default::default()
+            };
+            assert!(!webhook.matches(&m));
+
+            m.level = LogLevel::Error;
+            assert!(webhook.matches(&m));
+
+            webhook.code = Some("E1".to_string());
+            assert!(!webhook.matches(&m));
+
+            m.code = Some("E1".to_string());
+            assert!(webhook.matches(&m));
+
+            webhook.stream_id = Some(stream.id + 1);
+            assert!(!webhook.matches(&m));
+        })
+    }
+
+    #[test]
+    fn test_find_all_enabled_by_stream() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let w = NewWebhook {
+                namespace_id: stream.namespace_id,
+                url: "https://example.org/hook".to_string(),
+                secret: Webhook::generate_secret(),
+
+                ..Default::default()
+            };
+            let webhook = Webhook::insert(&w, conn, logger).unwrap();
+            let _ = Webhook::insert(
+                &NewWebhook {
+                    stream_id: Some(stream.id),
+                    ..w
+                },
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            let webhooks =
+                Webhook::find_all_enabled_by_stream(&stream, conn, logger)
+                    .unwrap();
+            assert_eq!(webhooks.len(), 2);
+
+            let now = Utc::now().naive_utc();
+            let webhook =
+                webhook.set_enabled(false, now, conn, logger).unwrap();
+            assert!(!webhook.enabled);
+
+            let webhooks =
+                Webhook::find_all_enabled_by_stream(&stream, conn, logger)
+                    .unwrap();
+            assert_eq!(webhooks.len(), 1);
+            assert_eq!(webhooks[0].stream_id, Some(stream.id));
+        })
+    }
+}
@@ -0,0 +1,150 @@
+//! # Webhook Delivery
+//!
+//! WebhookDelivery is a log of an attempt to deliver an event to a webhook.
+//! Retries of the same delivery share the uuid.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use uuid::Uuid;
+
+pub use crate::schema::webhook_deliveries;
+
+use crate::logger::Logger;
+use crate::model::webhook::Webhook;
+
+/// NewWebhookDelivery
+#[derive(Debug, Insertable)]
+#[table_name = "webhook_deliveries"]
+pub struct NewWebhookDelivery {
+    pub uuid: Uuid,
+    pub webhook_id: i64,
+    pub event: String,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub succeeded: bool,
+}
+
+/// WebhookDelivery
+#[derive(Associations, Clone, Debug, Identifiable, PartialEq, Queryable)]
+#[belongs_to(Webhook)]
+#[table_name = "webhook_deliveries"]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub webhook_id: i64,
+    pub event: String,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub succeeded: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl fmt::Display for WebhookDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<WebhookDelivery {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+impl WebhookDelivery {
+    /// Returns attempts of deliveries to the webhook (the newest one comes
+    /// first).
+    pub fn find_all_by_webhook(
+        webhook: &Webhook,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::belonging_to(webhook)
+            .order(webhook_deliveries::id.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn insert(
+        delivery: &NewWebhookDelivery,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(webhook_deliveries::table).values(delivery);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(d) => Some(d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::webhook::NewWebhook;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+
+    #[test]
+    fn test_find_all_by_webhook() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let w = NewWebhook {
+                namespace_id: namespace.id,
+                url: "https://example.org/hook".to_string(),
+                secret: Webhook::generate_secret(),
+
+                ..Default::default()
+            };
+            let webhook = Webhook::insert(&w, conn, logger).unwrap();
+
+            let uuid = Uuid::new_v4();
+            for attempt in 1..=2 {
+                let d = NewWebhookDelivery {
+                    uuid,
+                    webhook_id: webhook.id,
+                    event: "ping".to_string(),
+                    attempt,
+                    status_code: Some(if attempt == 1 { 502 } else { 200 }),
+                    error: None,
+                    succeeded: attempt == 2,
+                };
+                assert!(WebhookDelivery::insert(&d, conn, logger).is_some());
+            }
+
+            let deliveries = WebhookDelivery::find_all_by_webhook(
+                &webhook, 0, 10, conn, logger,
+            )
+            .unwrap();
+            assert_eq!(deliveries.len(), 2);
+            assert_eq!(deliveries[0].attempt, 2);
+            assert_eq!(deliveries[0].status_code, Some(200));
+            assert!(deliveries[0].succeeded);
+            assert_eq!(deliveries[1].uuid, uuid);
+        })
+    }
+}
@@ -23,6 +23,16 @@ impl MqPoolHolder {
     pub fn get(&self) -> Option<MqPooledConn> {
         self.pool.get().ok()
     }
+
+    /// Returns the number of connections (and idle ones) with the max size.
+    pub fn state(&self) -> (u32, u32, u32) {
+        let state = self.pool.state();
+        (
+            state.connections,
+            state.idle_connections,
+            self.pool.max_size(),
+        )
+    }
 }
 
 impl<'a, 'r> FromRequest<'a, 'r> for MqConn {
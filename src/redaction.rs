@@ -0,0 +1,422 @@
+//! Redaction replaces sensitive data in messages with tagged placeholders
+//! (e.g. `[REDACTED:EMAIL]`), before they are saved.
+//!
+//! Detectors are enabled per namespace by its redaction rules. Built-in ones
+//! are email addresses, credit card numbers (with the Luhn check), JWTs,
+//! bearer tokens and IP addresses, and custom ones are regular expressions.
+//! Only `title` and `content` are redacted, and the number of replaced
+//! matches is saved as `redactions_count` of the message.
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use diesel::PgConnection;
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::logger::Logger;
+use crate::model::message::NewMessage;
+use crate::model::redaction_rule::{RedactionDetector, RedactionRule};
+use crate::model::stream::Stream;
+
+/// The max size of a compiled custom pattern (bytes).
+pub const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+// varchar(255)
+const TITLE_LENGTH_MAX: usize = 255;
+
+lazy_static! {
+    static ref EMAIL: Regex = Regex::new(
+        r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b"
+    )
+    .unwrap();
+
+    // 13-19 digits, which may be separated by a space or a hyphen
+    static ref CREDIT_CARD: Regex =
+        Regex::new(r"\b[0-9](?:[ -]?[0-9]){12,18}\b").unwrap();
+
+    // both of the header and the payload are JSON objects (`{"`)
+    static ref JWT: Regex = Regex::new(
+        r"\beyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*"
+    )
+    .unwrap();
+
+    static ref BEARER_TOKEN: Regex =
+        Regex::new(r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/-]+=*").unwrap();
+
+    static ref IPV4: Regex =
+        Regex::new(r"\b[0-9]{1,3}(?:\.[0-9]{1,3}){3}\b").unwrap();
+
+    // candidates, which are checked by parsing
+    static ref IPV6: Regex = Regex::new(r"(?i)[0-9a-f:]*:[0-9a-f:.]*").unwrap();
+}
+
+/// Compiles a pattern of a custom rule.
+pub fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+}
+
+/// Returns true if the digits pass the Luhn check.
+pub fn luhn(digits: &str) -> bool {
+    let mut sum = 0;
+    for (i, c) in digits.chars().rev().enumerate() {
+        let mut d = match c.to_digit(10) {
+            None => return false,
+            Some(d) => d,
+        };
+        if i % 2 == 1 {
+            d *= 2;
+            if d > 9 {
+                d -= 9;
+            }
+        }
+        sum += d;
+    }
+    !digits.is_empty() && sum % 10 == 0
+}
+
+fn placeholder(tag: &str) -> String {
+    format!("[REDACTED:{}]", tag)
+}
+
+// A match must not be a part of a word (e.g. `std::fmt` is not an address)
+fn is_isolated(s: &str, start: usize, end: usize) -> bool {
+    let word = |c: char| c.is_alphanumeric() || c == '_';
+    !s[..start].chars().last().map_or(false, word) &&
+        !s[end..].chars().next().map_or(false, word)
+}
+
+enum Matcher {
+    BuiltIn(RedactionDetector),
+    Custom(Regex),
+}
+
+/// Redactor holds detectors enabled by rules.
+pub struct Redactor {
+    matchers: Vec<(String, Matcher)>, // (tag, matcher)
+}
+
+impl Redactor {
+    /// Creates a redactor by the rules. Built-in detectors run first (in
+    /// order of RedactionDetector), and a custom rule having an invalid
+    /// pattern is skipped.
+    pub fn new(rules: &[RedactionRule]) -> Self {
+        let mut rules: Vec<&RedactionRule> = rules.iter().collect();
+        rules.sort_by_key(|r| {
+            let i = RedactionDetector::iter()
+                .position(|d| *d == r.detector)
+                .unwrap_or_default();
+            (i, r.id)
+        });
+
+        let mut matchers = vec![];
+        for r in rules {
+            if r.detector != RedactionDetector::Custom {
+                let tag = r.detector.to_string().to_ascii_uppercase();
+                matchers.push((tag, Matcher::BuiltIn(r.detector.clone())));
+                continue;
+            }
+            if let Some(re) = r.pattern.as_ref().and_then(|p| compile(p).ok()) {
+                let tag = r.name.to_ascii_uppercase();
+                matchers.push((tag, Matcher::Custom(re)));
+            }
+        }
+        Self { matchers }
+    }
+
+    /// Creates a redactor by the rules on the namespace of the stream.
+    pub fn for_stream(
+        stream_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Self {
+        let rules = Stream::find_by_id(stream_id, conn, logger)
+            .and_then(|s| {
+                RedactionRule::find_all_by_namespace_id(
+                    s.namespace_id,
+                    conn,
+                    logger,
+                )
+            })
+            .unwrap_or_default();
+        Self::new(&rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    /// Redacts the text, and returns it with the number of replaced matches.
+    pub fn redact_str(&self, s: &str) -> (String, i32) {
+        let mut count = 0;
+        let mut s = s.to_string();
+        for (tag, matcher) in &self.matchers {
+            let text = s.clone();
+            s = match matcher {
+                Matcher::Custom(re) => {
+                    re.replace_all(&text, |_: &Captures| {
+                        count += 1;
+                        placeholder(tag)
+                    })
+                    .to_string()
+                },
+                Matcher::BuiltIn(d) => redact_by(d, tag, &text, &mut count),
+            };
+        }
+        (s, count)
+    }
+
+    /// Redacts the title and the content of the message, and returns the
+    /// number of replaced matches (it's added to `redactions_count`).
+    pub fn redact(&self, m: &mut NewMessage) -> i32 {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let mut count = 0;
+        if let Some(title) = &m.title {
+            let (s, n) = self.redact_str(title);
+            if n > 0 {
+                m.title = Some(s.chars().take(TITLE_LENGTH_MAX).collect());
+                count += n;
+            }
+        }
+        if let Some(content) = &m.content {
+            let (s, n) = self.redact_str(content);
+            if n > 0 {
+                m.content = Some(s);
+                count += n;
+            }
+        }
+        m.redactions_count += count;
+        count
+    }
+}
+
+fn redact_by(
+    detector: &RedactionDetector,
+    tag: &str,
+    text: &str,
+    count: &mut i32,
+) -> String {
+    let mut replace = |caps: &Captures, valid: bool| {
+        if valid {
+            *count += 1;
+            placeholder(tag)
+        } else {
+            caps[0].to_string()
+        }
+    };
+    match detector {
+        RedactionDetector::Email => {
+            EMAIL.replace_all(text, |c: &Captures| replace(c, true))
+        },
+        RedactionDetector::CreditCard => {
+            CREDIT_CARD.replace_all(text, |c: &Captures| {
+                let digits =
+                    c[0].replace(|ch: char| ch == ' ' || ch == '-', "");
+                replace(c, luhn(&digits))
+            })
+        },
+        RedactionDetector::Jwt => {
+            JWT.replace_all(text, |c: &Captures| replace(c, true))
+        },
+        RedactionDetector::BearerToken => {
+            BEARER_TOKEN.replace_all(text, |c: &Captures| {
+                let s = replace(c, true);
+                format!("{} {}", &c[1], s)
+            })
+        },
+        RedactionDetector::IpAddress => {
+            let s = IPV6
+                .replace_all(text, |c: &Captures| {
+                    let m = c.get(0).unwrap();
+                    let s = m.as_str().trim_end_matches('.');
+                    let valid = s.matches(':').count() >= 2 &&
+                        s.chars().any(|c| c.is_ascii_hexdigit()) &&
+                        is_isolated(text, m.start(), m.start() + s.len()) &&
+                        s.parse::<Ipv6Addr>().is_ok();
+                    if valid {
+                        format!(
+                            "{}{}",
+                            replace(c, true),
+                            &m.as_str()[s.len()..]
+                        )
+                    } else {
+                        replace(c, false)
+                    }
+                })
+                .to_string();
+            return IPV4
+                .replace_all(&s, |c: &Captures| {
+                    replace(c, c[0].parse::<Ipv4Addr>().is_ok())
+                })
+                .to_string();
+        },
+        RedactionDetector::Custom => return text.to_string(),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn rule(
+        detector: RedactionDetector,
+        pattern: Option<&str>,
+    ) -> RedactionRule {
+        let now = Utc::now().naive_utc();
+        RedactionRule {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            namespace_id: 1,
+            name: match pattern {
+                Some(_) => "api_key".to_string(),
+                None => detector.to_string(),
+            },
+            detector,
+            pattern: pattern.map(|p| p.to_string()),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn redact(detector: RedactionDetector, s: &str) -> (String, i32) {
+        Redactor::new(&[rule(detector, None)]).redact_str(s)
+    }
+
+    #[test]
+    fn test_luhn() {
+        assert!(luhn("4111111111111111"));
+        assert!(luhn("79927398713"));
+        assert!(!luhn("4111111111111112"));
+        assert!(!luhn("4111-1111"));
+        assert!(!luhn(""));
+    }
+
+    #[test]
+    fn test_redact_email() {
+        assert_eq!(
+            redact(RedactionDetector::Email, "sent to oswald@example.org"),
+            ("sent to [REDACTED:EMAIL]".to_string(), 1)
+        );
+        assert_eq!(
+            redact(RedactionDetector::Email, "@oswald is not an email"),
+            ("@oswald is not an email".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn test_redact_credit_card() {
+        assert_eq!(
+            redact(
+                RedactionDetector::CreditCard,
+                "card 4111 1111 1111 1111, order 1234567890123"
+            ),
+            (
+                "card [REDACTED:CREDIT_CARD], order 1234567890123".to_string(),
+                1
+            )
+        );
+        assert_eq!(
+            redact(RedactionDetector::CreditCard, "card 4111-1111-1111-1111"),
+            ("card [REDACTED:CREDIT_CARD]".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn test_redact_jwt() {
+        let token = "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.\
+                     dBjftJeZ4CVPmB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert_eq!(
+            redact(RedactionDetector::Jwt, &format!("token={}", token)),
+            ("token=[REDACTED:JWT]".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn test_redact_bearer_token() {
+        assert_eq!(
+            redact(
+                RedactionDetector::BearerToken,
+                "Authorization: Bearer abc.DEF-123=="
+            ),
+            (
+                "Authorization: Bearer [REDACTED:BEARER_TOKEN]".to_string(),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn test_redact_ip_address() {
+        assert_eq!(
+            redact(
+                RedactionDetector::IpAddress,
+                "from 192.168.0.1 and 2001:db8::1, not 999.1.1.1"
+            ),
+            (
+                "from [REDACTED:IP_ADDRESS] and [REDACTED:IP_ADDRESS], not \
+                 999.1.1.1"
+                    .to_string(),
+                2
+            )
+        );
+        assert_eq!(
+            redact(
+                RedactionDetector::IpAddress,
+                "std::fmt::Display at 12:30:45."
+            ),
+            ("std::fmt::Display at 12:30:45.".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn test_redact_custom() {
+        let redactor = Redactor::new(&[
+            rule(RedactionDetector::Custom, Some("sk_[0-9a-z]{8}")),
+            rule(RedactionDetector::Custom, Some("(invalid")),
+        ]);
+        assert_eq!(
+            redactor.redact_str("key sk_0123abcd, sk_4567efgh"),
+            ("key [REDACTED:API_KEY], [REDACTED:API_KEY]".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        let redactor = Redactor::new(&[
+            rule(RedactionDetector::Email, None),
+            rule(RedactionDetector::BearerToken, None),
+        ]);
+
+        let mut m = NewMessage {
+            title: Some("failed for oswald@example.org".to_string()),
+            content: Some(
+                "Authorization: Bearer s3cr3t\nTo: oswald@example.org"
+                    .to_string(),
+            ),
+
+            ..Default::default()
+        };
+        assert_eq!(redactor.redact(&mut m), 3);
+        assert_eq!(m.redactions_count, 3);
+        assert_eq!(m.title.unwrap(), "failed for [REDACTED:EMAIL]");
+        assert_eq!(
+            m.content.unwrap(),
+            "Authorization: Bearer [REDACTED:BEARER_TOKEN]\nTo: \
+             [REDACTED:EMAIL]"
+        );
+
+        let mut m = NewMessage {
+            title: Some("nothing to redact".to_string()),
+
+            ..Default::default()
+        };
+        assert_eq!(Redactor::new(&[]).redact(&mut m), 0);
+        assert_eq!(m.title.unwrap(), "nothing to redact");
+    }
+}
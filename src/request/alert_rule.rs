@@ -0,0 +1,35 @@
+/// AlertRule
+#[derive(Clone, Deserialize)]
+pub struct AlertRule {
+    pub stream: Option<String>, // uuid
+    pub name: Option<String>,
+    pub condition: Option<String>,
+    pub level: Option<String>,
+    pub code: Option<String>,
+    pub threshold: Option<i32>,
+    pub window_seconds: Option<i32>,
+    pub channel: Option<String>,
+    pub target: Option<String>,
+}
+
+impl Default for AlertRule {
+    fn default() -> Self {
+        Self {
+            stream: None,
+            name: None,
+            condition: None,
+            level: None,
+            code: None,
+            threshold: None,
+            window_seconds: None,
+            channel: None,
+            target: None,
+        }
+    }
+}
+
+/// AlertRuleMute
+#[derive(Clone, Deserialize)]
+pub struct AlertRuleMute {
+    pub minutes: i64, // 0 unmutes it
+}
@@ -0,0 +1,7 @@
+/// Annotation
+#[derive(Clone, Default, Deserialize)]
+pub struct Annotation {
+    pub label: Option<String>,
+    pub body: Option<String>,
+    pub ticket_url: Option<String>,
+}
@@ -0,0 +1,5 @@
+/// IssueState
+#[derive(Clone, Default, Deserialize)]
+pub struct IssueState {
+    pub state: Option<String>,
+}
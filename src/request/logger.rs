@@ -0,0 +1,82 @@
+use std::ops::Deref;
+use std::sync::RwLock;
+
+use rocket::{Request, State};
+use rocket::request::{self, FromRequest};
+use rocket_slog::SyncLogger;
+use uuid::Uuid;
+
+use crate::logger::Logger;
+use crate::request::request_id::RequestId;
+
+// The user is known only after the authentication by the User guard
+#[derive(Default)]
+struct RequestUser(RwLock<Option<String>>);
+
+impl RequestUser {
+    fn of<'a>(req: &'a Request) -> &'a Self {
+        req.local_cache(Self::default)
+    }
+}
+
+/// Remembers the authenticated user of the request for RequestLogger.
+pub fn set_user(req: &Request, uuid: &Uuid) {
+    if let Ok(mut user) = RequestUser::of(req).0.write() {
+        *user = Some(uuid.to_string());
+    }
+}
+
+/// RequestLogger is a child logger of the request. Its records carry the
+/// request id, the route and the user (if the User guard has been run before
+/// it in the handler).
+#[derive(Clone)]
+pub struct RequestLogger {
+    logger: Logger,
+    request_id: String,
+}
+
+impl RequestLogger {
+    pub fn new(logger: &Logger, req: &Request) -> Self {
+        let request_id = RequestId::of(req).0.clone();
+        let route = req
+            .route()
+            .map(|r| format!("{} {}", r.method, r.uri))
+            .unwrap_or_default();
+        let user = RequestUser::of(req)
+            .0
+            .read()
+            .ok()
+            .and_then(|u| u.clone())
+            .unwrap_or_default();
+        Self {
+            logger: logger.new(o!(
+                "request_id" => request_id.clone(),
+                "route" => route,
+                "user" => user,
+            )),
+            request_id,
+        }
+    }
+
+    /// Returns the id to pass to jobs enqueued in the request.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
+impl Deref for RequestLogger {
+    type Target = Logger;
+
+    fn deref(&self) -> &Self::Target {
+        &self.logger
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RequestLogger {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let base = req.guard::<State<SyncLogger>>()?;
+        request::Outcome::Success(Self::new(&base, req))
+    }
+}
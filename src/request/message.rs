@@ -27,3 +27,16 @@ impl Default for Message {
         }
     }
 }
+
+/// MessageUpdate
+///
+/// Only given fields are changed.
+#[derive(Clone, Default, Deserialize)]
+pub struct MessageUpdate {
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub level: Option<String>,
+    pub format: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+}
@@ -0,0 +1,23 @@
+/// MessageExport
+#[derive(Clone, Deserialize)]
+pub struct MessageExport {
+    pub stream: Option<String>, // uuid
+    pub format: Option<String>,
+    pub level: Option<String>,
+    pub code: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+impl Default for MessageExport {
+    fn default() -> Self {
+        Self {
+            stream: None,
+            format: None,
+            level: None,
+            code: None,
+            since: None,
+            until: None,
+        }
+    }
+}
@@ -0,0 +1,9 @@
+/// MessageStats (query)
+#[derive(Clone, Default, FromForm)]
+pub struct MessageStats {
+    pub stream: Option<String>, // uuid (or all streams in the namespace)
+    pub bucket: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub top: Option<String>, // the number of codes
+}
@@ -0,0 +1,43 @@
+/// MessageView
+#[derive(Clone, Default, Deserialize)]
+pub struct MessageView {
+    pub name: Option<String>,
+    pub shared: Option<bool>,
+    pub stream: Option<String>, // uuid (or all streams in the namespace)
+    pub levels: Option<Vec<String>>,
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub time_window: Option<i64>, // seconds until now
+}
+
+/// MessageSearch (query)
+///
+/// It's the same query as the view, but levels are separated by comma (e.g.
+/// `?levels=error,critical`).
+#[derive(Clone, Default, FromForm)]
+pub struct MessageSearch {
+    pub stream: Option<String>,
+    pub levels: Option<String>,
+    pub code: Option<String>,
+    pub lang: Option<String>,
+    pub time_window: Option<String>,
+}
+
+impl From<MessageSearch> for MessageView {
+    fn from(search: MessageSearch) -> Self {
+        Self {
+            stream: search.stream,
+            levels: search
+                .levels
+                .map(|l| l.split(',').map(|v| v.trim().to_string()).collect()),
+            code: search.code,
+            lang: search.lang,
+            // an invalid value fails on validation
+            time_window: search
+                .time_window
+                .map(|t| t.parse::<i64>().unwrap_or(-1)),
+
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use rocket::{Request, State, request};
+use rocket::request::FromRequest;
+
+use crate::config::Config;
+use crate::totp::constant_time_eq;
+
+const PREFIX: &str = "Bearer ";
+
+/// MetricsToken allows the request to the metrics route. It's given as a
+/// bearer token which matches `Config::metrics_token`. The request forwards
+/// (as not found) if the token is not configured or doesn't match, so that
+/// the route is not visible on the public mount.
+pub struct MetricsToken;
+
+impl<'a, 'r> FromRequest<'a, 'r> for MetricsToken {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let config = req.guard::<State<Config>>().unwrap();
+        let expected = match config.metrics_token {
+            Some(ref v) => v,
+            None => return request::Outcome::Forward(()),
+        };
+        match req.headers().get_one("Authorization") {
+            Some(v)
+                if v.starts_with(PREFIX) &&
+                    constant_time_eq(
+                        v[PREFIX.len()..].as_bytes(),
+                        expected.as_bytes(),
+                    ) =>
+            {
+                request::Outcome::Success(MetricsToken)
+            },
+            _ => request::Outcome::Forward(()),
+        }
+    }
+}
@@ -1,10 +1,22 @@
 pub mod access_token;
 pub mod agent_type;
+pub mod alert_rule;
+pub mod annotation;
+pub mod issue;
+pub mod logger;
 pub mod message;
+pub mod message_export;
+pub mod message_stats;
+pub mod message_view;
+pub mod metrics;
 pub mod namespace;
 pub mod password_reset;
+pub mod redaction_rule;
+pub mod request_id;
 pub mod token;
+pub mod totp;
 pub mod user;
+pub mod webhook;
 
 #[macro_export]
 macro_rules! bad_request_by {
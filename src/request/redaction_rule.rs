@@ -0,0 +1,7 @@
+/// RedactionRule
+#[derive(Clone, Default, Deserialize)]
+pub struct RedactionRule {
+    pub detector: Option<String>,
+    pub name: Option<String>,
+    pub pattern: Option<String>, // only for custom
+}
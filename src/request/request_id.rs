@@ -0,0 +1,83 @@
+use rocket::{Data, Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{self, FromRequest};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+const MAX_LENGTH: usize = 64;
+
+/// RequestId identifies a request in responses (and logs). It's taken from
+/// the X-Request-Id header if it's valid, or generated otherwise. The value
+/// is the same in a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn of<'a>(req: &'a Request) -> &'a Self {
+        req.local_cache(|| {
+            match req.headers().get_one(REQUEST_ID_HEADER) {
+                Some(v) if is_valid(v) => Self(v.to_string()),
+                _ => Self(Uuid::new_v4().to_string()),
+            }
+        })
+    }
+}
+
+// Accepts only characters which are safe in a header and logs
+fn is_valid(value: &str) -> bool {
+    !value.is_empty() &&
+        value.len() <= MAX_LENGTH &&
+        value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RequestId {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        request::Outcome::Success(Self::of(req).clone())
+    }
+}
+
+/// RequestIdFairing assigns (or accepts) the id at the beginning of every
+/// request, and returns it in the X-Request-Id header of every response
+/// including raw ones and those of preflight requests.
+pub struct RequestIdFairing;
+
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, req: &mut Request, _: &Data) {
+        let _ = RequestId::of(req);
+    }
+
+    fn on_response(&self, req: &Request, res: &mut Response) {
+        if !res.headers().contains(REQUEST_ID_HEADER) {
+            let id = RequestId::of(req).0.clone();
+            res.set_header(Header::new(REQUEST_ID_HEADER, id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0"));
+        assert!(is_valid("req_1"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("a b"));
+        assert!(!is_valid("a\r\nSet-Cookie: x"));
+        assert!(!is_valid(&"a".repeat(65)));
+    }
+}
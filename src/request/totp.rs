@@ -0,0 +1,19 @@
+/// TotpAuthentication (the second step of login)
+#[derive(Clone, Deserialize)]
+pub struct TotpAuthentication {
+    pub session_id: String,
+    pub code: String, // TOTP code or recovery code
+}
+
+/// TotpConfirmation
+#[derive(Clone, Deserialize)]
+pub struct TotpConfirmation {
+    pub code: String,
+}
+
+/// TotpDeactivation
+#[derive(Clone, Deserialize)]
+pub struct TotpDeactivation {
+    pub password: String,
+    pub code: String, // TOTP code or recovery code
+}
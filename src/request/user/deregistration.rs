@@ -0,0 +1,13 @@
+/// UserDeregistration (account deletion)
+#[derive(Clone, Deserialize)]
+pub struct UserDeregistration {
+    pub password: String,
+}
+
+impl Default for UserDeregistration {
+    fn default() -> Self {
+        Self {
+            password: "".to_string(),
+        }
+    }
+}
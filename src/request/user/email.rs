@@ -0,0 +1,13 @@
+/// UserEmailAddition (a secondary email address)
+#[derive(Clone, Deserialize)]
+pub struct UserEmailAddition {
+    pub email: String,
+}
+
+impl Default for UserEmailAddition {
+    fn default() -> Self {
+        Self {
+            email: "".to_string(),
+        }
+    }
+}
@@ -1,7 +1,10 @@
 pub mod authentication;
+pub mod deregistration;
+pub mod email;
 pub mod registration;
 
 use rocket::{Request, State, request};
+use rocket::http::Status;
 use rocket::request::FromRequest;
 use rocket_slog::SyncLogger;
 
@@ -9,6 +12,7 @@ use crate::config::Config;
 use crate::db::DbConn;
 use crate::model::token::{BrowserCookieTokenClaims, PersonalAccessTokenClaims};
 use crate::model::user::User;
+use crate::request::logger::set_user;
 use crate::request::token::TokenType;
 use crate::request::token::authentication::AuthenticationToken;
 
@@ -17,9 +21,11 @@ impl<'a, 'r> FromRequest<'a, 'r> for &'a User {
     type Error = ();
 
     fn from_request(req: &'a Request<'r>) -> request::Outcome<&'a User, ()> {
-        let token_type = req
-            .guard::<TokenType>()
-            .failure_then(|_| request::Outcome::Forward(()))?;
+        // A missing or unknown token fails as unauthorized (not as not found
+        // by forwarding)
+        let token_type = req.guard::<TokenType>().failure_then(|_| {
+            request::Outcome::Failure((Status::Unauthorized, ()))
+        })?;
 
         let authentication_token = req
             .guard::<AuthenticationToken>()
@@ -52,8 +58,9 @@ impl<'a, 'r> FromRequest<'a, 'r> for &'a User {
             }
         });
         if let Some(ref user) = login {
+            set_user(req, &user.uuid);
             return request::Outcome::Success(user);
         }
-        request::Outcome::Forward(())
+        request::Outcome::Failure((Status::Unauthorized, ()))
     }
 }
@@ -0,0 +1,25 @@
+/// Webhook
+#[derive(Clone, Deserialize)]
+pub struct Webhook {
+    pub stream: Option<String>, // uuid (all streams in the namespace if none)
+    pub url: Option<String>,
+    pub level: Option<String>,
+    pub code: Option<String>,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            stream: None,
+            url: None,
+            level: None,
+            code: None,
+        }
+    }
+}
+
+/// WebhookState
+#[derive(Clone, Deserialize)]
+pub struct WebhookState {
+    pub enabled: bool,
+}
@@ -3,20 +3,28 @@ use std::io::Cursor;
 use rocket::State;
 use rocket::http::{Cookies, ContentType, Status};
 use rocket::request::Request;
-use rocket::response::Responder;
+use rocket::response::{ResponseBuilder, Responder};
 use rocket::response::Response as RawResponse;
 use rocket_contrib::json::JsonValue;
+use serde::Serialize;
+use serde_json::Value;
 
 use crate::config::Config;
+use crate::request::request_id::{REQUEST_ID_HEADER, RequestId};
 
 const MAX_AGE: &str = "10800"; // 3 hours
 const VARY: &str = "Accept-Encoding,Origin";
 
+/// The version of the error envelope. It's incremented only on an
+/// incompatible change of its shape.
+pub const ERROR_VERSION: i32 = 1;
+
 #[derive(Debug)]
 pub struct Response<'a> {
     pub cookies: Cookies<'a>,
     pub status: Status,
     pub data: JsonValue,
+    pub error: Option<Error>,
 }
 
 impl<'a> Default for Response<'a> {
@@ -25,6 +33,7 @@ impl<'a> Default for Response<'a> {
             cookies: Cookies::empty(),
             status: Status::Ok,
             data: json!(null),
+            error: None,
         }
     }
 }
@@ -45,29 +54,206 @@ impl<'a> Response<'a> {
         self.data = data;
         self
     }
+
+    /// Responds with the error (and its status) instead of the data.
+    pub fn error(mut self, e: Error) -> Response<'a> {
+        self.status = e.status;
+        self.error = Some(e);
+        self
+    }
+}
+
+// Sets headers for CORS and the body
+fn finish<'r>(
+    mut builder: ResponseBuilder<'r>,
+    body: String,
+    req: &Request,
+) -> Result<RawResponse<'r>, Status> {
+    builder.header(ContentType::JSON);
+
+    let config = req.guard::<State<Config>>().unwrap();
+    builder
+        .raw_header(
+            "Access-Control-Allow-Origin",
+            config.application_url.to_owned(),
+        )
+        .raw_header("Access-Control-Allow-Credentials", "true")
+        .raw_header("Vary", VARY);
+
+    builder.sized_body(Cursor::new(body)).ok()
+}
+
+fn is_error(status: Status) -> bool {
+    status.class().is_client_error() || status.class().is_server_error()
 }
 
 impl<'r> Responder<'r> for Response<'r> {
+    // A response with an error status is sent as Error (the default one of
+    // the status if it's not given)
     fn respond_to(self, req: &Request) -> Result<RawResponse<'r>, Status> {
         let mut builder = RawResponse::build();
 
         builder.status(self.status);
-        builder.header(ContentType::JSON);
         self.cookies.iter().for_each(|c| {
             builder.header(c);
         });
 
-        let config = req.guard::<State<Config>>().unwrap();
-        builder
-            .raw_header(
-                "Access-Control-Allow-Origin",
-                config.application_url.to_owned(),
-            )
-            .raw_header("Access-Control-Allow-Credentials", "true")
-            .raw_header("Vary", VARY);
+        let status = self.status;
+        if is_error(status) {
+            let e = self.error.unwrap_or_else(|| Error::new(status));
+            let request_id = RequestId::of(req).0.clone();
+            builder.raw_header(REQUEST_ID_HEADER, request_id.clone());
+            return finish(builder, e.to_json(&request_id).to_string(), req);
+        }
+        finish(builder, self.data.to_string(), req)
+    }
+}
+
+/// ErrorCode is the stable, machine-readable code of an error. Each status
+/// has its own one, and some failures have more specific ones.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    Gone,
+    PayloadTooLarge,
+    UnprocessableEntity,
+    TooManyRequests,
+    InternalServerError,
+    ServiceUnavailable,
+    Unknown,
+
+    // more specific ones
+    InvalidFields, // field errors (validation)
+    InvalidCredentials,
+    InvalidCsrfToken,
+    InvalidToken,
+    SessionExpired,
+}
 
-        let body = self.data.to_string();
-        builder.sized_body(Cursor::new(body)).ok()
+impl From<Status> for ErrorCode {
+    fn from(status: Status) -> Self {
+        match status.code {
+            400 => Self::BadRequest,
+            401 => Self::Unauthorized,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            409 => Self::Conflict,
+            410 => Self::Gone,
+            413 => Self::PayloadTooLarge,
+            422 => Self::UnprocessableEntity,
+            429 => Self::TooManyRequests,
+            500 => Self::InternalServerError,
+            503 => Self::ServiceUnavailable,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Error is the body for all failures of routes and catchers. The code is a
+/// stable, machine-readable one (e.g. `not_found`), and the message is for
+/// humans. Details are data of the failure (e.g. uuids of resources in a
+/// conflict).
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "error": {
+///     "code": "invalid_fields",
+///     "message": "The input is invalid",
+///     "errors": [{"field": "name", "messages": ["..."]}],
+///     "details": null,
+///     "request_id": "..."
+///   }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Error {
+    pub status: Status,
+    pub code: ErrorCode,
+    pub message: String,
+    pub errors: Option<Value>, // field errors
+    pub details: Option<Value>,
+}
+
+impl Error {
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            code: ErrorCode::from(status),
+            message: default_message(status),
+            errors: None,
+            details: None,
+        }
+    }
+
+    pub fn code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn message<T: ToString>(mut self, message: T) -> Self {
+        self.message = message.to_string();
+        self
+    }
+
+    pub fn errors<T: Serialize>(mut self, errors: T) -> Self {
+        self.code = ErrorCode::InvalidFields;
+        self.errors = serde_json::to_value(errors).ok();
+        self
+    }
+
+    pub fn details(mut self, details: JsonValue) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    /// Makes an error for field errors by validators.
+    pub fn invalid<T: Serialize>(errors: T) -> Self {
+        Self::new(Status::UnprocessableEntity).errors(errors)
+    }
+
+    pub fn to_json(&self, request_id: &str) -> JsonValue {
+        json!({
+            "version": ERROR_VERSION,
+            "error": {
+                "code": self.code,
+                "message": self.message,
+                "errors": self.errors,
+                "details": self.details,
+                "request_id": request_id,
+            }
+        })
+    }
+}
+
+fn default_message(status: Status) -> String {
+    match status.code {
+        400 => "The request header/body is invalid",
+        401 => "The request is not allowed",
+        403 => "The request is prohibited",
+        404 => "The resource is not found",
+        422 => "The input is invalid",
+        500 => "Internal server error occured",
+        503 => "The service is temporarily unavailable",
+        _ => status.reason,
+    }
+    .to_string()
+}
+
+impl<'r> Responder<'r> for Error {
+    fn respond_to(self, req: &Request) -> Result<RawResponse<'r>, Status> {
+        let mut builder = RawResponse::build();
+
+        builder.status(self.status);
+
+        let request_id = RequestId::of(req).0.clone();
+        builder.raw_header(REQUEST_ID_HEADER, request_id.clone());
+        finish(builder, self.to_json(&request_id).to_string(), req)
     }
 }
 
@@ -94,3 +280,54 @@ pub fn no_content_for<'a>(methods: &str, config: &Config) -> RawResponse<'a> {
     res.set_status(Status::NoContent);
     res
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_new() {
+        let e = Error::new(Status::UnprocessableEntity);
+        assert_eq!(e.code, ErrorCode::UnprocessableEntity);
+        assert_eq!(e.message, "The input is invalid");
+        assert_eq!(e.errors, None);
+
+        let e = Error::new(Status::Forbidden);
+        assert_eq!(e.code, ErrorCode::Forbidden);
+        assert_eq!(e.message, "The request is prohibited");
+
+        let e = Error::new(Status::ImATeapot);
+        assert_eq!(e.code, ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn test_error_invalid() {
+        let e = Error::invalid(vec![json!({
+            "field": "name",
+            "messages": ["Must exist"],
+        })]);
+        assert_eq!(e.status, Status::UnprocessableEntity);
+        assert_eq!(e.code, ErrorCode::InvalidFields);
+        assert_eq!(e.errors.unwrap()[0]["field"], "name");
+    }
+
+    #[test]
+    fn test_error_to_json() {
+        let e = Error::new(Status::Conflict)
+            .code(ErrorCode::InvalidToken)
+            .message("You are not allowed")
+            .details(json!({"namespaces": ["a"]}));
+
+        let json = e.to_json("req-1");
+        assert_eq!(json["version"], ERROR_VERSION);
+        assert_eq!(json["error"]["code"], "invalid_token");
+        assert_eq!(json["error"]["message"], "You are not allowed");
+        assert_eq!(json["error"]["details"]["namespaces"][0], "a");
+        assert_eq!(json["error"]["request_id"], "req-1");
+
+        let json = Error::new(Status::NotFound).to_json("req-2");
+        assert_eq!(json["error"]["code"], "not_found");
+        assert_eq!(json["error"]["message"], "The resource is not found");
+        assert_eq!(json["error"]["details"], Value::Null);
+    }
+}
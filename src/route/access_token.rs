@@ -1,31 +1,34 @@
 use diesel::result::Error;
 use rocket::State;
 use rocket::http::Status;
-use rocket_slog::SyncLogger;
 use serde_json::Value;
 
 use crate::config::Config;
 use crate::db::DbConn;
-use crate::model::access_token::{AccessToken, AgentType};
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
+use crate::model::access_token::{
+    AccessToken, AccessTokenState, AccessTokenStateError, AgentType,
+};
 use crate::model::token::{AuthenticationClaims, Claims, TokenData};
 use crate::model::user::User;
 use crate::request::access_token::AccessTokenData as RequestData;
-use crate::response::Response;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error as ResponseError, Response};
 
 pub mod preflight {
     use rocket::State;
     use rocket::response::Response as RawResponse;
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
     use crate::model::access_token::AgentType;
+    use crate::request::logger::RequestLogger;
     use crate::response::no_content_for;
 
     #[options("/access_token/del/<uuid>", rank = 2)]
     pub fn del<'a>(
         uuid: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "uuid: {}", uuid);
         no_content_for("PATCH", &config)
@@ -35,7 +38,7 @@ pub mod preflight {
     pub fn dump<'a>(
         uuid: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "uuid: {}", uuid);
         no_content_for("PATCH", &config)
@@ -45,7 +48,7 @@ pub mod preflight {
     pub fn hset_state<'a>(
         uuid: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "uuid: {}", uuid);
         no_content_for("PATCH", &config)
@@ -55,7 +58,7 @@ pub mod preflight {
     pub fn append<'a>(
         agent_type: AgentType,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "agent_type: {}", agent_type);
         no_content_for("PUT", &config)
@@ -67,7 +70,7 @@ pub mod preflight {
         start: i64,
         stop: i64,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(
             logger,
@@ -83,12 +86,13 @@ pub fn dump<'a>(
     user: &User,
     conn: DbConn,
     config: State<Config>,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response<'a> {
     info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
 
     let res: Response = Default::default();
 
+    let mut status = Status::InternalServerError;
     let result: Result<AccessToken, Error> = conn
         .build_transaction()
         .serializable()
@@ -121,22 +125,43 @@ pub fn dump<'a>(
                                 &config.authentication_token_secret,
                             );
                             t.token = Some(value.into_bytes());
+
+                            let e = NewAuditEvent {
+                                action: AuditEventAction::AccessTokenDump,
+                                target: Some(t.uuid.to_string()),
+
+                                ..NewAuditEvent::from(user)
+                            };
+                            AuditEvent::insert(&e, &conn, &logger)
+                                .ok_or(Error::RollbackTransaction)?;
+
                             Ok(t)
                         },
                     }
                 },
-                _ => {
+                Some(_) => {
+                    error!(logger, "err: already dumped {}", uuid);
+                    status = Status::Conflict;
+                    Err(Error::RollbackTransaction)
+                },
+                None => {
                     error!(logger, "err: not found {}", uuid);
+                    status = Status::NotFound;
                     Err(Error::RollbackTransaction)
                 },
             }
         });
 
-    if result.is_err() {
-        return res.status(Status::NotFound);
-    }
-
-    let t = result.unwrap();
+    let t = match result {
+        Ok(t) => t,
+        Err(_) if status == Status::Conflict => {
+            return res.error(
+                ResponseError::new(status)
+                    .message("The access token has already been dumped."),
+            );
+        },
+        Err(_) => return res.status(status),
+    };
     let token = String::from_utf8(t.token.unwrap()).unwrap();
     res.format(json!({
         "access_token": {
@@ -157,12 +182,13 @@ pub fn del<'a>(
     uuid: String,
     user: &User,
     conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response<'a> {
     info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
 
     let res: Response = Default::default();
 
+    let mut status = Status::InternalServerError;
     let result: Result<(), Error> = conn
         .build_transaction()
         .serializable()
@@ -172,22 +198,33 @@ pub fn del<'a>(
             match AccessToken::owned_by_uuid(&user, &uuid, &conn, &logger) {
                 None => {
                     error!(logger, "err: not found {}", uuid);
+                    status = Status::NotFound;
                     Err(Error::RollbackTransaction)
                 },
                 Some(t) => {
-                    match t.revoke(&conn, &logger) {
+                    match t.revoke(&user, &conn, &logger) {
                         Err(e) => {
                             error!(logger, "err: {}", e);
                             Err(Error::RollbackTransaction)
                         },
-                        Ok(_) => Ok(()),
+                        Ok(_) => {
+                            let e = NewAuditEvent {
+                                action: AuditEventAction::AccessTokenRevocation,
+                                target: Some(t.uuid.to_string()),
+
+                                ..NewAuditEvent::from(user)
+                            };
+                            AuditEvent::insert(&e, &conn, &logger)
+                                .ok_or(Error::RollbackTransaction)?;
+                            Ok(())
+                        },
                     }
                 },
             }
         });
 
     if result.is_err() {
-        return res.status(Status::NotFound);
+        return res.status(status);
     }
 
     res.format(json!({
@@ -206,50 +243,76 @@ pub fn hset_state<'a>(
     data: RequestData,
     user: &User,
     conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response<'a> {
     info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
 
     let res: Response = Default::default();
 
-    let result: Result<(), Error> = conn
+    let state = data.access_token.state;
+    let result: Result<AccessTokenState, AccessTokenStateError> = conn
         .build_transaction()
         .serializable()
         .deferrable()
         .read_write()
-        .run::<(), diesel::result::Error, _>(|| {
-            match AccessToken::owned_by_uuid(&user, &uuid, &conn, &logger) {
+        .run::<AccessTokenState, AccessTokenStateError, _>(|| {
+            // a revoked token must be distinguished from an unknown one
+            match AccessToken::owned_by_uuid_including_revoked(
+                &user, &uuid, &conn, &logger,
+            ) {
                 None => {
                     error!(logger, "err: not found {}", uuid);
-                    Err(Error::RollbackTransaction)
+                    Err(AccessTokenStateError::NotFound)
                 },
                 Some(t) => {
-                    // this does not check old state
-                    match t.mark_as(data.access_token.state, &conn, &logger) {
-                        Err(e) => {
-                            error!(logger, "err: {}", e);
-                            Err(Error::RollbackTransaction)
-                        },
-                        Ok(_) => Ok(()),
-                    }
+                    let s = t
+                        .change_state(state, &user, &conn, &logger)
+                        .map_err(|e| {
+                            error!(logger, "err: {} {}", e, uuid);
+                            e
+                        })?;
+
+                    let e = NewAuditEvent {
+                        action: AuditEventAction::AccessTokenStateChange,
+                        target: Some(t.uuid.to_string()),
+
+                        ..NewAuditEvent::from(user)
+                    };
+                    AuditEvent::insert(&e, &conn, &logger)
+                        .ok_or(AccessTokenStateError::Unexpected)?;
+                    Ok(s)
                 },
             }
         });
 
-    if result.is_err() {
-        return res.status(Status::NotFound);
+    match result {
+        Ok(_) => {
+            res.format(json!({
+                "access_token": 1,
+            }))
+        },
+        Err(AccessTokenStateError::Revoked) => {
+            res.error(
+                ResponseError::new(Status::Gone)
+                    .message("The access token has been revoked."),
+            )
+        },
+        Err(AccessTokenStateError::InvalidTransition) => {
+            res.error(
+                ResponseError::new(Status::Conflict)
+                    .message("The state of the access token can't be changed."),
+            )
+        },
+        Err(AccessTokenStateError::NotFound) => res.status(Status::NotFound),
+        Err(_) => res.status(Status::InternalServerError),
     }
-
-    res.format(json!({
-        "access_token": 1,
-    }))
 }
 
 #[put("/access_token/append/<agent_type>", rank = 1)]
 pub fn append<'a>(
     user: &User,
     agent_type: AgentType,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response<'a> {
     info!(logger, "user: {}, agent_type: {}", user.uuid, agent_type);
 
@@ -265,7 +328,7 @@ pub fn lrange<'a>(
     stop: i64,
     user: &User,
     conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response<'a> {
     info!(
         logger,
@@ -1,28 +1,28 @@
 use rocket::State;
 use rocket::http::Status;
-use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
 use crate::model::user::User;
 use crate::model::user_email::UserEmail;
+use crate::request::logger::RequestLogger;
 use crate::request::token::verification::VerificationToken;
-use crate::response::Response;
+use crate::response::{Error, ErrorCode, Response};
 use crate::service::account_activator::AccountActivator;
 
 pub mod preflight {
     use rocket::State;
     use rocket::response::Response as RawResponse;
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::no_content_for;
 
     #[options("/activate/<session_id>", rank = 2)]
     pub fn activate<'a>(
         session_id: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "session_id: {}", session_id);
         no_content_for("PATCH", &config)
@@ -34,7 +34,7 @@ pub fn activate(
     session_id: String,
     token: VerificationToken,
     db_conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
     config: State<Config>,
 ) -> Response {
     info!(logger, "session_id: {}", session_id);
@@ -52,7 +52,9 @@ pub fn activate(
         return res.status(Status::Ok);
     }
 
-    res.status(Status::BadRequest).format(json!({
-        "message": "The activation link has been expired or is invalid"
-    }))
+    res.error(
+        Error::new(Status::BadRequest)
+            .code(ErrorCode::InvalidToken)
+            .message("The activation link has been expired or is invalid"),
+    )
 }
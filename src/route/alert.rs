@@ -0,0 +1,421 @@
+use chrono::{Duration, Utc};
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::model::alert::Alert;
+use crate::model::alert_rule::{AlertRule, NewAlertRule};
+use crate::model::stream::Stream;
+use crate::model::user::User;
+use crate::request::alert_rule::{
+    AlertRule as RequestData, AlertRuleMute as MuteData,
+};
+use crate::request::logger::RequestLogger;
+use crate::response::{Error, Response};
+use crate::route::{find_namespace, offset_and_limit};
+use crate::validation::alert_rule::Validator;
+
+// the number of alerts returned per request at most
+const ALERTS_LIMIT: i64 = 100;
+
+// a week
+const MUTE_MINUTES_MAX: i64 = 10_080;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/alert/<namespace_key>/rule/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/alert/<namespace_key>/rule/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/alert/<namespace_key>/rule/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/alert/<namespace_key>/rule/mute/<uuid>", rank = 2)]
+    pub fn mute<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/alert/<namespace_key>/lrange/<start>/<stop>", rank = 2)]
+    pub fn lrange<'a>(
+        namespace_key: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, start: {}, stop: {}",
+            namespace_key,
+            start,
+            stop,
+        );
+        no_content_for("GET", &config)
+    }
+
+    #[options("/alert/<namespace_key>/ack/<uuid>", rank = 2)]
+    pub fn ack<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+}
+
+fn format_alert_rule(rule: &AlertRule, stream_uuid: &Uuid) -> JsonValue {
+    json!({
+        "uuid": rule.uuid.to_string(),
+        "stream": stream_uuid.to_string(),
+        "name": rule.name,
+        "condition": rule.condition,
+        "level": rule.level.as_ref().map(|l| l.to_string()),
+        "code": rule.code,
+        "threshold": rule.threshold,
+        "window_seconds": rule.window_seconds,
+        "channel": rule.channel,
+        "target": rule.target,
+        "muted_until": rule.muted_until,
+        "created_at": rule.created_at,
+    })
+}
+
+fn format_alert(alert: &Alert, rule: &AlertRule) -> JsonValue {
+    json!({
+        "uuid": alert.uuid.to_string(),
+        "rule": rule.uuid.to_string(),
+        "name": rule.name,
+        "state": alert.state,
+        "occurrences": alert.occurrences,
+        "notified_at": alert.notified_at,
+        "acknowledged_at": alert.acknowledged_at,
+        "resolved_at": alert.resolved_at,
+        "created_at": alert.created_at,
+    })
+}
+
+#[get("/alert/<namespace_key>/rule/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_key: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let data =
+        match AlertRule::find_all_by_namespace_id(namespace.id, &conn, &logger)
+        {
+            None => {
+                error!(
+                    logger,
+                    "err: not found namespace.id {}", namespace.uuid
+                );
+                vec![]
+            },
+            Some(a) => {
+                a.iter()
+                    .map(|(r, s)| {
+                        json!({ "alert_rule": format_alert_rule(r, s) })
+                    })
+                    .collect()
+            },
+        };
+    res.format(json!(data))
+}
+
+#[post(
+    "/alert/<namespace_key>/rule/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_key: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    // only owners can manage the rules
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => {
+                return res.error(Error::new(status).message(
+                    "You are not allowed to manage the alert rules.",
+                ));
+            },
+            Ok(n) => n,
+        };
+
+    let v = Validator::new(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    // the stream has been validated
+    let stream = data
+        .stream
+        .as_ref()
+        .and_then(|uuid| Stream::find_by_uuid(uuid, &conn, &logger))
+        .unwrap();
+    let r = NewAlertRule {
+        stream_id: stream.id,
+
+        ..NewAlertRule::from(data.0.clone())
+    };
+    match AlertRule::insert(&r, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(rule) => {
+            info!(logger, "alert_rule: {}", rule.uuid);
+            res.format(json!({
+                "alert_rule": format_alert_rule(&rule, &stream.uuid),
+            }))
+        },
+    }
+}
+
+#[patch("/alert/<namespace_key>/rule/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let rule =
+        match AlertRule::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(r) => r,
+        };
+    match rule.delete(&conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(_) => res,
+    }
+}
+
+// Suppresses notifications of the rule for the minutes (0 unmutes it).
+#[patch(
+    "/alert/<namespace_key>/rule/mute/<uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn mute<'a>(
+    namespace_key: String,
+    uuid: String,
+    data: Json<MuteData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let minutes = data.minutes;
+    if !(0..=MUTE_MINUTES_MAX).contains(&minutes) {
+        return res.error(Error::invalid(json!([{
+        "field": "minutes",
+        "messages": [format!("Must be between 0 and {}", MUTE_MINUTES_MAX)],
+        }])));
+    }
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let rule =
+        match AlertRule::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(r) => r,
+        };
+
+    let now = Utc::now().naive_utc();
+    let until = if minutes == 0 {
+        None
+    } else {
+        Some(now + Duration::minutes(minutes))
+    };
+    match rule.mute(until, now, &conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(r) => {
+            res.format(json!({
+                "alert_rule": {
+                    "uuid": r.uuid.to_string(),
+                    "muted_until": r.muted_until,
+                }
+            }))
+        },
+    }
+}
+
+#[get("/alert/<namespace_key>/lrange/<start>/<stop>", rank = 1)]
+pub fn lrange<'a>(
+    namespace_key: String,
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_key,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let (offset, limit) = offset_and_limit(start, stop, ALERTS_LIMIT);
+    let data = match Alert::find_all_by_namespace_id(
+        namespace.id,
+        offset,
+        limit,
+        &conn,
+        &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found namespace.id {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|(a, r)| json!({ "alert": format_alert(a, r) }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Acknowledges the firing alert. It's not notified anymore.
+#[patch("/alert/<namespace_key>/ack/<uuid>", rank = 1)]
+pub fn ack<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let alert = match Alert::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(a) => a,
+    };
+
+    let now = Utc::now().naive_utc();
+    match alert.acknowledge(user, now, &conn, &logger) {
+        Err(e) => {
+            error!(logger, "err: {}", e);
+            res.error(
+                Error::new(Status::Conflict)
+                    .message("The alert is not firing."),
+            )
+        },
+        Ok(a) => {
+            res.format(json!({
+                "alert": {
+                    "uuid": a.uuid.to_string(),
+                    "state": a.state,
+                    "acknowledged_at": a.acknowledged_at,
+                }
+            }))
+        },
+    }
+}
@@ -0,0 +1,318 @@
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::model::annotation::{Annotation, NewAnnotation};
+use crate::model::membership::Membership;
+use crate::model::user::User;
+use crate::request::annotation::Annotation as RequestData;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error, Response};
+use crate::route::{find_message, find_namespace};
+use crate::validation::annotation::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options(
+        "/annotation/<namespace_key>/hgetall/<stream_uuid>/<message_id>",
+        rank = 2
+    )]
+    pub fn hgetall<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        message_id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, stream_uuid: {}, message_id: {}",
+            namespace_key,
+            stream_uuid,
+            message_id
+        );
+        no_content_for("GET", &config)
+    }
+
+    #[options(
+        "/annotation/<namespace_key>/append/<stream_uuid>/<message_id>",
+        rank = 2
+    )]
+    pub fn append<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        message_id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, stream_uuid: {}, message_id: {}",
+            namespace_key,
+            stream_uuid,
+            message_id
+        );
+        no_content_for("POST", &config)
+    }
+
+    #[options("/annotation/<namespace_key>/hset/<uuid>", rank = 2)]
+    pub fn hset<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/annotation/<namespace_key>/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+}
+
+fn format_annotation(annotation: &Annotation, user_uuid: &Uuid) -> JsonValue {
+    json!({
+        "uuid": annotation.uuid.to_string(),
+        "message": annotation.message_id,
+        "user": user_uuid.to_string(),
+        "label": annotation.label,
+        "body": annotation.body,
+        "ticket_url": annotation.ticket_url,
+        "created_at": annotation.created_at,
+        "updated_at": annotation.updated_at,
+    })
+}
+
+#[get(
+    "/annotation/<namespace_key>/hgetall/<stream_uuid>/<message_id>",
+    rank = 1
+)]
+pub fn hgetall<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    message_id: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, stream_uuid: {}, message_id: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid,
+        message_id
+    );
+
+    let res: Response = Default::default();
+
+    let (message, _) = match find_message(
+        &namespace_key,
+        &stream_uuid,
+        message_id,
+        false,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(v) => v,
+    };
+
+    let data =
+        match Annotation::find_all_by_message_id(message.id, &conn, &logger) {
+            None => {
+                error!(logger, "err: not found message.id {}", message.id);
+                vec![]
+            },
+            Some(a) => {
+                a.iter()
+                    .map(|(a, u)| {
+                        json!({ "annotation": format_annotation(a, u) })
+                    })
+                    .collect()
+            },
+        };
+    res.format(json!(data))
+}
+
+// Annotates the message. Any member can do it.
+#[post(
+    "/annotation/<namespace_key>/append/<stream_uuid>/<message_id>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn append<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    message_id: i64,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, stream_uuid: {}, message_id: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid,
+        message_id
+    );
+
+    let res: Response = Default::default();
+
+    let (message, _) = match find_message(
+        &namespace_key,
+        &stream_uuid,
+        message_id,
+        false,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(v) => v,
+    };
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    let a = NewAnnotation {
+        message_id: message.id,
+        user_id: user.id,
+
+        ..NewAnnotation::from(data.0.clone())
+    };
+    match Annotation::insert(&a, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(annotation) => {
+            info!(logger, "annotation: {}", annotation.uuid);
+            res.format(json!({
+                "annotation": format_annotation(&annotation, &user.uuid),
+            }))
+        },
+    }
+}
+
+// Edits the annotation. Only the user who wrote it can do it.
+#[patch(
+    "/annotation/<namespace_key>/hset/<uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_key: String,
+    uuid: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let annotation =
+        match Annotation::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(a) => a,
+        };
+    if annotation.user_id != user.id {
+        return res.error(
+            Error::new(Status::Forbidden)
+                .message("You are not allowed to edit the annotation."),
+        );
+    }
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    let a = NewAnnotation::from(data.0.clone());
+    match annotation.update(&a, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(annotation) => {
+            res.format(json!({
+                "annotation": format_annotation(&annotation, &user.uuid),
+            }))
+        },
+    }
+}
+
+// Deletes the annotation. Owners of the namespace can delete others' ones.
+#[patch("/annotation/<namespace_key>/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let annotation =
+        match Annotation::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(a) => a,
+        };
+    if annotation.user_id != user.id {
+        match Membership::find_by_namespace_and_user(
+            &namespace, user, &conn, &logger,
+        ) {
+            Some(ref m) if m.role.is_owner() => (),
+            _ => {
+                return res.error(
+                    Error::new(Status::Forbidden).message(
+                        "You are not allowed to delete the annotation.",
+                    ),
+                );
+            },
+        }
+    }
+
+    match annotation.delete(&conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(_) => res,
+    }
+}
@@ -0,0 +1,151 @@
+use rocket::http::Status;
+
+use crate::db::DbConn;
+use crate::model::audit_event::AuditEvent;
+use crate::model::membership::Membership;
+use crate::model::namespace::Namespace;
+use crate::model::user::User;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error, Response};
+use crate::route::offset_and_limit;
+
+// the number of events returned per request at most
+const AUDIT_EVENTS_LIMIT: i64 = 100;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/audit/lrange/<start>/<stop>", rank = 2)]
+    pub fn lrange<'a>(
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "start: {}, stop: {}", start, stop);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/audit/<namespace_key>/lrange/<start>/<stop>", rank = 2)]
+    pub fn lrange_namespace<'a>(
+        namespace_key: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, start: {}, stop: {}",
+            namespace_key,
+            start,
+            stop,
+        );
+        no_content_for("GET", &config)
+    }
+}
+
+#[get("/audit/lrange/<start>/<stop>", rank = 1)]
+pub fn lrange<'a>(
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, start: {}, stop: {}", user.uuid, start, stop,
+    );
+
+    let res: Response = Default::default();
+
+    let (offset, limit) = offset_and_limit(start, stop, AUDIT_EVENTS_LIMIT);
+    let data = match AuditEvent::find_all_by_user(
+        &user, offset, limit, &conn, &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found user.id {}", user.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|e| json!({ "audit_event": e })).collect(),
+    };
+    res.format(json!(data))
+}
+
+#[get("/audit/<namespace_key>/lrange/<start>/<stop>", rank = 1)]
+pub fn lrange_namespace<'a>(
+    namespace_key: String,
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_key,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_key, &user, &conn, &logger) {
+            None => {
+                error!(logger, "err: no namespace for uuid: {}", namespace_key);
+                return res.status(Status::NotFound);
+            },
+            Some(n) => n,
+        };
+
+    // only owners can read the events in the namespace
+    match Membership::find_by_namespace_and_user(
+        &namespace, &user, &conn, &logger,
+    ) {
+        Some(ref m) if m.role.is_owner() => (),
+        _ => {
+            return res.error(
+                Error::new(Status::Forbidden)
+                    .message("You are not allowed to read the audit events."),
+            );
+        },
+    }
+
+    let (offset, limit) = offset_and_limit(start, stop, AUDIT_EVENTS_LIMIT);
+    let data = match AuditEvent::find_all_by_namespace_id(
+        namespace.id,
+        offset,
+        limit,
+        &conn,
+        &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found namespace.id {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|(e, actor)| {
+                    json!({
+                        "audit_event": {
+                            "action": e.action,
+                            "target": e.target,
+                            "actor": actor.map(|u| u.to_string()),
+                            "created_at": e.created_at,
+                        }
+                    })
+                })
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
@@ -2,17 +2,28 @@ use chrono::Utc;
 use redis::{Commands, RedisError};
 use rocket::State;
 use rocket::http::{Cookie, Cookies, Status};
-use rocket_slog::SyncLogger;
+use rocket_contrib::json::Json;
 
 use crate::config::Config;
 use crate::db::DbConn;
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
 use crate::model::user::User;
+use crate::model::user_recovery_code::UserRecoveryCode;
+use crate::model::user_totp::UserTotp;
 use crate::model::Authenticatable;
 use crate::model::token::{AuthenticationClaims, Claims, TokenData};
+use crate::request::logger::RequestLogger;
+use crate::request::totp::TotpAuthentication;
 use crate::request::user::authentication::UserAuthentication as RequestData;
-use crate::response::Response;
+use crate::response::{Error, ErrorCode, Response};
 use crate::ss::SsConn;
-use crate::util::{split_token, make_cookie};
+use crate::util::{generate_random_hash, split_token, make_cookie};
+
+const TOTP_SESSION_DURATION: usize = 300; // seconds
+const TOTP_SESSION_ID_LENGTH: i32 = 32;
+// failures of the second step are counted per user within the duration
+const TOTP_MAX_ATTEMPTS: i64 = 5;
+const TOTP_LOCKOUT_DURATION: usize = 900; // seconds
 
 pub mod preflight {
     use rocket::State;
@@ -26,6 +37,11 @@ pub mod preflight {
         no_content_for("HEAD,POST", &config)
     }
 
+    #[options("/login/totp", rank = 2)]
+    pub fn login_totp<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("POST", &config)
+    }
+
     #[options("/logout", rank = 2)]
     pub fn logout<'a>(config: State<Config>) -> RawResponse<'a> {
         no_content_for("POST", &config)
@@ -37,9 +53,9 @@ pub mod preignition {
     use redis::{Commands, RedisError};
     use rocket::State;
     use rocket::http::{Cookie, Cookies, SameSite, Status};
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::Response;
     use crate::ss::SsConn;
     use crate::util::generate_random_hash;
@@ -48,7 +64,7 @@ pub mod preignition {
     pub fn login<'a>(
         config: State<Config>,
         mut cookies: Cookies,
-        logger: SyncLogger,
+        logger: RequestLogger,
         mut ss_conn: SsConn,
     ) -> Response<'a> {
         // returns CSRF token
@@ -89,7 +105,7 @@ pub fn login<'a>(
     mut cookies: Cookies<'a>,
     data: RequestData,
     db_conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
     mut ss_conn: SsConn,
 ) -> Response<'a> {
     let res: Response = Default::default();
@@ -97,9 +113,11 @@ pub fn login<'a>(
     let cookie = cookies.get_private("csrf_token").ok_or("");
     if cookie.is_err() {
         info!(logger, "error: missing csrf_token");
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token is required."
-        }));
+        return res.error(
+            Error::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token is required."),
+        );
     }
     let key = cookie.ok().unwrap().value().to_string();
     let result: Result<i64, RedisError> = ss_conn.get(&key).map_err(|e| {
@@ -107,70 +125,287 @@ pub fn login<'a>(
         e
     });
     if result.is_err() {
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token has been expired. Reload the page."
-        }));
+        return res.error(
+            Error::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token has been expired. Reload the page."),
+        );
     }
 
-    match User::find_by_email(&data.username, &db_conn, &logger) {
+    let user = User::find_by_email(&data.username, &db_conn, &logger);
+    match user {
         Some(ref user) if user.verify_password(&data.password) => {
-            // TODO:
-            // set valid expires_at and impl review mechanism (check also
-            // `validate_exp` for Validation struct for JWT)
-            // e.g. let expires_at = (now + Duration::weeks(2)).timestamp();
-            let data = TokenData {
-                value: user.uuid.to_urn().to_string(),
-                granted_at: Utc::now().timestamp(),
-                expires_at: 0,
-            };
-            let authentication_token = AuthenticationClaims::encode(
-                data,
-                &config.authentication_token_issuer,
-                &config.authentication_token_key_id,
-                &config.authentication_token_secret,
-            );
+            // the second step is required if TOTP has been enabled
+            let totp = UserTotp::find_by_user(user, &db_conn, &logger);
+            if totp.map(|t| t.is_confirmed()).unwrap_or(false) {
+                if let Some(session_id) =
+                    start_totp_session(user, &mut ss_conn, &logger)
+                {
+                    return res.status(Status::Accepted).format(json!({
+                        "totp": {
+                            "session_id": session_id,
+                        }
+                    }));
+                }
+                return res.error(
+                    Error::new(Status::InternalServerError)
+                        .message("Something wrong happen, sorry :'("),
+                );
+            }
 
-            // TODO:
-            // * consider about implementation "Are you there?" modal
-            // * consider about extension (re-set it again?)
-            let (token, sign) = match split_token(authentication_token) {
-                Some(result) => result,
-                None => {
-                    return res.status(Status::InternalServerError).format(
-                        json!({
-                         "message": "Something wrong happen, sorry :'("
-                        }),
-                    );
-                },
+            let e = NewAuditEvent {
+                action: AuditEventAction::Login,
+
+                ..NewAuditEvent::from(user)
             };
+            // a session isn't started without its record
+            if AuditEvent::insert(&e, &db_conn, &logger).is_none() {
+                return res.error(
+                    Error::new(Status::InternalServerError)
+                        .message("Something wrong happen, sorry :'("),
+                );
+            }
 
-            let cookie = make_cookie(sign, &config);
-            cookies.add_private(cookie);
-            res.cookies(cookies).format(json!({ "token": token }))
+            authenticate(user, &config, cookies, res)
         },
         _ => {
             warn!(logger, "login failed: username {}", data.username);
 
-            res.status(Status::Unauthorized).format(json!({
-                "message": "The credentials you've entered are incorrect."
-            }))
+            // the username is recorded as target only for an unknown user
+            let e = NewAuditEvent {
+                user_id: user.as_ref().map(|u| u.id),
+                action: AuditEventAction::LoginFailure,
+                target: match user {
+                    Some(_) => None,
+                    None => Some(data.username.chars().take(128).collect()),
+                },
+
+                ..Default::default()
+            };
+            if AuditEvent::insert(&e, &db_conn, &logger).is_none() {
+                error!(logger, "err: failed to record {}", e.action);
+            }
+
+            res.error(
+                Error::new(Status::Unauthorized)
+                    .code(ErrorCode::InvalidCredentials)
+                    .message("The credentials you've entered are incorrect."),
+            )
         },
     }
 }
 
+// Issues an authentication token for the user. The signature part of it is
+// set in a cookie.
+fn authenticate<'a>(
+    user: &User,
+    config: &Config,
+    mut cookies: Cookies<'a>,
+    res: Response<'a>,
+) -> Response<'a> {
+    // TODO:
+    // set valid expires_at and impl review mechanism (check also
+    // `validate_exp` for Validation struct for JWT)
+    // e.g. let expires_at = (now + Duration::weeks(2)).timestamp();
+    let data = TokenData {
+        value: user.uuid.to_urn().to_string(),
+        granted_at: Utc::now().timestamp(),
+        expires_at: 0,
+    };
+    let authentication_token = AuthenticationClaims::encode(
+        data,
+        &config.authentication_token_issuer,
+        &config.authentication_token_key_id,
+        &config.authentication_token_secret,
+    );
+
+    // TODO:
+    // * consider about implementation "Are you there?" modal
+    // * consider about extension (re-set it again?)
+    let (token, sign) = match split_token(authentication_token) {
+        Some(result) => result,
+        None => {
+            return res.error(
+                Error::new(Status::InternalServerError)
+                    .message("Something wrong happen, sorry :'("),
+            );
+        },
+    };
+
+    let cookie = make_cookie(sign, config);
+    cookies.add_private(cookie);
+    res.cookies(cookies).format(json!({ "token": token }))
+}
+
+// Saves the user who has passed the first step of login into session store,
+// and returns the id of the session for the second step.
+fn start_totp_session(
+    user: &User,
+    ss_conn: &mut SsConn,
+    logger: &RequestLogger,
+) -> Option<String> {
+    let session_id =
+        generate_random_hash(Config::CSRF_HASH_SOURCE, TOTP_SESSION_ID_LENGTH);
+    let key = format!("tf-{}", session_id);
+    let result: Result<String, RedisError> = ss_conn
+        .set_ex(&key, user.uuid.to_string(), TOTP_SESSION_DURATION)
+        .map_err(|e| {
+            error!(logger, "error: {}", e);
+            e
+        });
+    result.ok().map(|_| session_id)
+}
+
+#[post("/login/totp", data = "<data>", format = "json", rank = 1)]
+pub fn login_totp<'a>(
+    config: State<Config>,
+    mut cookies: Cookies<'a>,
+    data: Json<TotpAuthentication>,
+    db_conn: DbConn,
+    logger: RequestLogger,
+    mut ss_conn: SsConn,
+) -> Response<'a> {
+    let res: Response = Default::default();
+
+    let cookie = cookies.get_private("csrf_token").ok_or("");
+    if cookie.is_err() {
+        info!(logger, "error: missing csrf_token");
+        return res.error(
+            Error::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token is required."),
+        );
+    }
+    let key = cookie.ok().unwrap().value().to_string();
+    let result: Result<i64, RedisError> = ss_conn.get(&key).map_err(|e| {
+        error!(logger, "error: {}", e);
+        e
+    });
+    if result.is_err() {
+        return res.error(
+            Error::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token has been expired. Reload the page."),
+        );
+    }
+
+    let key = format!("tf-{}", data.session_id);
+
+    let result: Result<String, RedisError> = ss_conn.get(&key);
+    let user = match result
+        .ok()
+        .and_then(|uuid| User::find_by_uuid(&uuid, &db_conn, &logger))
+    {
+        Some(user) => user,
+        None => {
+            return res.error(
+                Error::new(Status::Unauthorized)
+                    .code(ErrorCode::SessionExpired)
+                    .message("The session has been expired. Log in again."),
+            );
+        },
+    };
+
+    // Attempts are counted per user (a new session doesn't reset them), and
+    // before the verification so that concurrent requests can't exceed the
+    // limit. The lockout lasts for the duration since the last attempt.
+    let attempts_key = format!("tfa-{}", user.uuid);
+    let result: Result<(i64,), RedisError> = redis::pipe()
+        .atomic()
+        .incr(&attempts_key, 1)
+        .expire(&attempts_key, TOTP_LOCKOUT_DURATION)
+        .ignore()
+        .query(&mut *ss_conn);
+    let attempts = match result {
+        Ok((n,)) => n,
+        Err(e) => {
+            error!(logger, "error: {}", e);
+            return res.error(
+                Error::new(Status::InternalServerError)
+                    .message("Something wrong happen, sorry :'("),
+            );
+        },
+    };
+    if attempts > TOTP_MAX_ATTEMPTS {
+        warn!(logger, "login locked: totp {}", user.uuid);
+        let _: Result<i64, RedisError> = ss_conn.del(&key);
+        return res.error(
+            Error::new(Status::TooManyRequests)
+                .message("Too many attempts. Try again later."),
+        );
+    }
+
+    // TOTP code or recovery code
+    let verified = match UserTotp::find_by_user(&user, &db_conn, &logger) {
+        Some(ref t) if t.is_confirmed() => {
+            t.verify(&data.code, &db_conn, &logger) ||
+                UserRecoveryCode::consume(
+                    &user, &data.code, &db_conn, &logger,
+                )
+        },
+        _ => false,
+    };
+
+    if !verified {
+        warn!(logger, "login failed: totp {}", user.uuid);
+
+        let e = NewAuditEvent {
+            action: AuditEventAction::LoginFailure,
+
+            ..NewAuditEvent::from(&user)
+        };
+        if AuditEvent::insert(&e, &db_conn, &logger).is_none() {
+            error!(logger, "err: failed to record {}", e.action);
+        }
+
+        return res.error(
+            Error::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCredentials)
+                .message("The code you've entered is incorrect."),
+        );
+    }
+
+    let _: Result<i64, RedisError> = ss_conn.del(&[&key, &attempts_key]);
+
+    let e = NewAuditEvent {
+        action: AuditEventAction::Login,
+
+        ..NewAuditEvent::from(&user)
+    };
+    if AuditEvent::insert(&e, &db_conn, &logger).is_none() {
+        return res.error(
+            Error::new(Status::InternalServerError)
+                .message("Something wrong happen, sorry :'("),
+        );
+    }
+
+    authenticate(&user, &config, cookies, res)
+}
+
 // logout
 //
 // * Remove a cookie
 // * Delete session value in Redis
 #[post("/logout", format = "json", rank = 1)]
 pub fn logout<'a>(
-    mut cookies: Cookies,
+    // the user guard reads cookies, so it must come before `Cookies`
     user: &User,
-    logger: SyncLogger,
+    mut cookies: Cookies,
+    db_conn: DbConn,
+    logger: RequestLogger,
 ) -> Response<'a> {
     let res: Response = Default::default();
     info!(logger, "user: {}", user.uuid);
 
+    let e = NewAuditEvent {
+        action: AuditEventAction::Logout,
+
+        ..NewAuditEvent::from(user)
+    };
+    if AuditEvent::insert(&e, &db_conn, &logger).is_none() {
+        error!(logger, "err: failed to record {}", e.action);
+    }
+
     // TODO: remove_private
     cookies.remove(Cookie::named("sign"));
 
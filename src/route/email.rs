@@ -0,0 +1,419 @@
+use diesel::result::Error;
+use fourche::queue::Queue;
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::job::{Job, Payload, UserEmailVerificationEmail};
+use crate::model::Activatable;
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
+use crate::model::token::VerificationClaims;
+use crate::model::user::{User, UserState};
+use crate::model::user_email::{
+    NewUserEmail, UserEmail, UserEmailIdentificationState, UserEmailRole,
+};
+use crate::mq::MqConn;
+use crate::request::logger::RequestLogger;
+use crate::request::token::verification::VerificationToken;
+use crate::request::user::email::UserEmailAddition;
+use crate::response::{Error as ResponseError, ErrorCode, Response};
+use crate::service::token_issuer::{IssuedToken, TokenIssuer, TokenPurpose};
+use crate::ss::SsConn;
+use crate::validation::user_email::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/email/append", rank = 2)]
+    pub fn append<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PUT", &config)
+    }
+
+    #[options("/email/del/<id>", rank = 2)]
+    pub fn del<'a>(
+        id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "id: {}", id);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/email/hgetall", rank = 2)]
+    pub fn hgetall<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("GET", &config)
+    }
+
+    #[options("/email/hset/<id>/primary", rank = 2)]
+    pub fn hset_primary<'a>(
+        id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "id: {}", id);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/email/verify/<session_id>", rank = 2)]
+    pub fn verify<'a>(
+        session_id: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "session_id: {}", session_id);
+        no_content_for("PATCH", &config)
+    }
+}
+
+fn format_user_email(user_email: &UserEmail) -> JsonValue {
+    json!({
+        "id": user_email.id,
+        "email": user_email.email,
+        "role": user_email.role.to_string(),
+        "identification_state": user_email.identification_state.to_string(),
+        "created_at": user_email.created_at,
+        "updated_at": user_email.updated_at,
+    })
+}
+
+#[get("/email/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    match UserEmail::find_all_by_user(&user, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(v) => {
+            let emails: Vec<JsonValue> =
+                v.iter().map(format_user_email).collect();
+            res.format(json!({ "emails": emails }))
+        },
+    }
+}
+
+// Adds a secondary (general) address, and sends a verification email to it.
+#[put("/email/append", data = "<data>", format = "json", rank = 1)]
+pub fn append<'a>(
+    data: Json<UserEmailAddition>,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    mut ss_conn: SsConn,
+    logger: RequestLogger,
+    config: State<Config>,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let v = Validator::new(&conn, &data, &user, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(ResponseError::invalid(errors));
+    }
+
+    let issuer = TokenIssuer::new(&config, &logger);
+
+    let result: Result<(UserEmail, IssuedToken), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(UserEmail, IssuedToken), Error, _>(|| {
+            let ue = NewUserEmail {
+                user_id: user.id,
+                email: data.email.to_string(),
+                role: UserEmailRole::General,
+
+                ..Default::default()
+            };
+            let user_email = UserEmail::insert(&ue, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            let issued = issuer.grant(&user_email, &conn).map_err(|e| {
+                error!(logger, "error: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::EmailAddition,
+                target: Some(user_email.id.to_string()),
+
+                ..NewAuditEvent::from(user)
+            };
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            Ok((user_email, issued))
+        });
+
+    if let Ok((user_email, issued)) = result {
+        let result = issuer.store(
+            issued,
+            TokenPurpose::UserEmailVerification,
+            &mut *ss_conn,
+        );
+        if let Ok((session_id, token)) = result {
+            let job = Job::new(Payload::SendUserEmailVerificationEmail(
+                UserEmailVerificationEmail {
+                    user_email_id: user_email.id,
+                    session_id,
+                    token,
+                },
+            ))
+            .with_request_id(logger.request_id());
+            let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+            if let Err(err) = queue.enqueue::<Job>(job) {
+                error!(logger, "error: {}", err);
+            } else {
+                return res.format(json!({
+                    "email": format_user_email(&user_email),
+                }));
+            }
+        }
+    }
+    res.error(
+        ResponseError::new(Status::InternalServerError)
+            .message("Something wrong happen, sorry :'("),
+    )
+}
+
+// Verifies a secondary address. The primary address at the registration is
+// verified through the user activation.
+#[patch("/email/verify/<session_id>", rank = 1)]
+pub fn verify(
+    session_id: String,
+    token: VerificationToken,
+    db_conn: DbConn,
+    logger: RequestLogger,
+    config: State<Config>,
+) -> Response {
+    info!(logger, "session_id: {}", session_id);
+
+    let res: Response = Default::default();
+
+    let mut status = Status::BadRequest;
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), Error, _>(|| {
+            let user_email = UserEmail::find_by_token::<VerificationClaims>(
+                &token,
+                &config.verification_token_issuer,
+                &config.verification_token_secret,
+                &db_conn,
+                &logger,
+            )
+            .filter(|ue| !ue.is_primary())
+            .ok_or(Error::RollbackTransaction)?;
+
+            let user = User::find_by_id(user_email.user_id, &db_conn, &logger)
+                .filter(|u| u.state == UserState::Active)
+                .ok_or(Error::RollbackTransaction)?;
+
+            // the address may have been verified by another user since it
+            // was added
+            let email = user_email
+                .email
+                .as_deref()
+                .ok_or(Error::RollbackTransaction)?;
+            if !User::check_email_uniqueness(email, &db_conn, &logger) ||
+                !UserEmail::check_email_uniqueness(email, &db_conn, &logger)
+            {
+                status = Status::Conflict;
+                return Err(Error::RollbackTransaction);
+            }
+
+            status = Status::InternalServerError;
+            user_email
+                .activate(&db_conn, &logger)
+                .map_err(|_| Error::RollbackTransaction)?;
+            user_email
+                .delete_pending_duplicates(&db_conn, &logger)
+                .map_err(|_| Error::RollbackTransaction)?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::EmailVerification,
+                target: Some(user_email.id.to_string()),
+
+                ..NewAuditEvent::from(&user)
+            };
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(())
+        });
+
+    match result {
+        Ok(_) => res.status(Status::Ok),
+        Err(_) if status == Status::Conflict => {
+            res.error(
+                ResponseError::new(status)
+                    .message("The email address is already in use"),
+            )
+        },
+        Err(_) if status == Status::BadRequest => {
+            res.error(
+                ResponseError::new(status)
+                    .code(ErrorCode::InvalidToken)
+                    .message(
+                        "The verification link has been expired or is invalid",
+                    ),
+            )
+        },
+        Err(_) => res.error(ResponseError::new(status)),
+    }
+}
+
+// Makes a verified address primary. users.email follows it.
+#[patch("/email/hset/<id>/primary", rank = 1)]
+pub fn hset_primary<'a>(
+    id: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, id: {}", user.uuid, id);
+
+    let res: Response = Default::default();
+
+    let mut status = Status::InternalServerError;
+    let result: Result<UserEmail, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<UserEmail, Error, _>(|| {
+            let user_email =
+                match UserEmail::owned_by_id(&user, id, &conn, &logger) {
+                    None => {
+                        status = Status::NotFound;
+                        return Err(Error::RollbackTransaction);
+                    },
+                    Some(ref ue) if ue.is_primary() => {
+                        status = Status::Conflict;
+                        return Err(Error::RollbackTransaction);
+                    },
+                    Some(ue) => ue,
+                };
+            if user_email.identification_state !=
+                UserEmailIdentificationState::Done
+            {
+                status = Status::UnprocessableEntity;
+                return Err(Error::RollbackTransaction);
+            }
+
+            user_email
+                .promote(&user, &conn, &logger)
+                .map_err(|_| Error::RollbackTransaction)?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::PrimaryEmailChange,
+                target: Some(user_email.id.to_string()),
+
+                ..NewAuditEvent::from(user)
+            };
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            UserEmail::find_by_id(user_email.id, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)
+        });
+
+    match result {
+        Ok(user_email) => {
+            res.format(json!({
+                "email": format_user_email(&user_email),
+            }))
+        },
+        Err(_) if status == Status::Conflict => {
+            res.error(
+                ResponseError::new(status)
+                    .message("The email address is already primary."),
+            )
+        },
+        Err(_) if status == Status::UnprocessableEntity => {
+            res.error(
+                ResponseError::new(status)
+                    .message("The email address has not been verified yet."),
+            )
+        },
+        Err(_) => res.status(status),
+    }
+}
+
+// Removes a secondary address. The primary one can't be removed.
+#[patch("/email/del/<id>", rank = 1)]
+pub fn del<'a>(
+    id: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, id: {}", user.uuid, id);
+
+    let res: Response = Default::default();
+
+    let mut status = Status::InternalServerError;
+    let result: Result<(), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), Error, _>(|| {
+            let user_email =
+                match UserEmail::owned_by_id(&user, id, &conn, &logger) {
+                    None => {
+                        status = Status::NotFound;
+                        return Err(Error::RollbackTransaction);
+                    },
+                    Some(ref ue) if ue.is_primary() => {
+                        status = Status::Conflict;
+                        return Err(Error::RollbackTransaction);
+                    },
+                    Some(ue) => ue,
+                };
+
+            user_email
+                .delete(&conn, &logger)
+                .map_err(|_| Error::RollbackTransaction)?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::EmailRemoval,
+                target: Some(user_email.id.to_string()),
+
+                ..NewAuditEvent::from(user)
+            };
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            Ok(())
+        });
+
+    match result {
+        Ok(_) => {
+            res.format(json!({
+                "email": 1,
+            }))
+        },
+        Err(_) if status == Status::Conflict => {
+            res.error(
+                ResponseError::new(status)
+                    .message("The primary email address can't be removed."),
+            )
+        },
+        Err(_) => res.status(status),
+    }
+}
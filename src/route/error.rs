@@ -1,82 +1,40 @@
 use rocket::Request;
-use rocket::http::{Cookies, Status};
+use rocket::http::Status;
 
-use crate::response::Response;
+use crate::response::Error;
 
 #[catch(400)]
-pub fn bad_request<'a>(_req: &Request) -> Response<'a> {
-    Response {
-        cookies: Cookies::empty(),
-        status: Status::BadRequest,
-        data: json!({
-            "data": {
-                "message": "The request header/body is invalid".to_string(),
-            }
-        }),
-    }
+pub fn bad_request(_req: &Request) -> Error {
+    Error::new(Status::BadRequest)
 }
 
 #[catch(401)]
-pub fn unauthorized<'a>(_req: &Request) -> Response<'a> {
-    Response {
-        cookies: Cookies::empty(),
-        status: Status::Unauthorized,
-        data: json!({
-            "data": {
-                "message": "The request is not allowed".to_string(),
-            }
-        }),
-    }
+pub fn unauthorized(_req: &Request) -> Error {
+    Error::new(Status::Unauthorized)
 }
 
 #[catch(403)]
-pub fn forbidden<'a>(_req: &Request) -> Response<'a> {
-    Response {
-        cookies: Cookies::empty(),
-        status: Status::Unauthorized,
-        data: json!({
-            "data": {
-                "message": "The request is not prohibited".to_string(),
-            }
-        }),
-    }
+pub fn forbidden(_req: &Request) -> Error {
+    Error::new(Status::Forbidden)
 }
 
 #[catch(404)]
-pub fn not_found<'a>(req: &Request) -> Response<'a> {
-    Response {
-        cookies: Cookies::empty(),
-        status: Status::NotFound,
-        data: json!({
-            "data": {
-                "message": format!("'{path}' is not found", path=req.uri().path()),
-            }
-        }),
-    }
+pub fn not_found(req: &Request) -> Error {
+    Error::new(Status::NotFound)
+        .message(format!("'{path}' is not found", path = req.uri().path()))
 }
 
 #[catch(422)]
-pub fn unprocessable_entity<'a>(_req: &Request) -> Response<'a> {
-    Response {
-        cookies: Cookies::empty(),
-        status: Status::UnprocessableEntity,
-        data: json!({
-            "data": {
-                "message": "The input is invalid".to_string(),
-            }
-        }),
-    }
+pub fn unprocessable_entity(_req: &Request) -> Error {
+    Error::new(Status::UnprocessableEntity)
 }
 
 #[catch(500)]
-pub fn internal_server_error<'a>(_req: &Request) -> Response<'a> {
-    Response {
-        cookies: Cookies::empty(),
-        status: Status::InternalServerError,
-        data: json!({
-            "data": {
-                "message": "Internal server error occured".to_string(),
-            }
-        }),
-    }
+pub fn internal_server_error(_req: &Request) -> Error {
+    Error::new(Status::InternalServerError)
+}
+
+#[catch(503)]
+pub fn service_unavailable(_req: &Request) -> Error {
+    Error::new(Status::ServiceUnavailable)
 }
@@ -1,14 +1,73 @@
 use rocket::State;
 use rocket::http::Status;
-use rocket_slog::SyncLogger;
+use rocket::response::status::Custom;
+use rocket_contrib::json::JsonValue;
 
 use crate::config::Config;
+use crate::db::DbPoolHolder;
+use crate::health;
+use crate::mq::MqPoolHolder;
+use crate::request::logger::RequestLogger;
 use crate::response::Response;
+use crate::ss::SsPoolHolder;
 
 /// Returns just OK status. This route should be mounted both endpoints.
 #[get("/health", rank = 1)]
-pub fn check<'a>(logger: SyncLogger, _state: State<Config>) -> Response<'a> {
+pub fn check<'a>(logger: RequestLogger, _state: State<Config>) -> Response<'a> {
     info!(logger, "");
     let res: Response = Default::default();
     res.status(Status::Ok)
 }
+
+/// Returns OK status while the process is alive, without touching any
+/// dependency (for the liveness probe). This route should be mounted both
+/// endpoints.
+#[get("/health/live", rank = 1)]
+pub fn live<'a>(logger: RequestLogger) -> Response<'a> {
+    info!(logger, "");
+    let res: Response = Default::default();
+    res.status(Status::Ok)
+}
+
+/// Checks dependencies (for the readiness probe), and returns the status and
+/// the latency of each one. It's 503 if any required one is down. The errors
+/// are only logged, as this route is reachable on the "/_" mount.
+#[get("/health/ready", rank = 1)]
+pub fn ready(
+    db_pool_holder: State<DbPoolHolder>,
+    mq_pool_holder: State<MqPoolHolder>,
+    ss_pool_holder: State<SsPoolHolder>,
+    config: State<Config>,
+    logger: RequestLogger,
+) -> Custom<JsonValue> {
+    let checks = health::check_all(
+        &db_pool_holder,
+        &mq_pool_holder,
+        &ss_pool_holder,
+        &config,
+    );
+
+    let ready = checks.iter().all(|c| c.is_ok());
+    for c in checks.iter().filter(|c| !c.is_ok()) {
+        error!(
+            logger,
+            "err: {} {}",
+            c.name,
+            c.message.as_ref().map_or("", |m| m.as_str())
+        );
+    }
+
+    let status = if ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    // not in the error envelope to keep the breakdown
+    Custom(
+        status,
+        json!({
+            "status": if ready { "ready" } else { "unavailable" },
+            "checks": checks,
+        }),
+    )
+}
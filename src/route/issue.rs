@@ -0,0 +1,201 @@
+use chrono::Utc;
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+
+use crate::db::DbConn;
+use crate::model::issue::{Issue, IssueState};
+use crate::model::stream::Stream;
+use crate::model::user::User;
+use crate::request::issue::IssueState as RequestData;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error, Response};
+use crate::route::{find_namespace, offset_and_limit};
+
+// the number of issues returned per request at most
+const ISSUES_LIMIT: i64 = 100;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options(
+        "/issue/<namespace_key>/lrange/<stream_uuid>/<start>/<stop>",
+        rank = 2
+    )]
+    pub fn lrange<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, stream_uuid: {}, start: {}, stop: {}",
+            namespace_key,
+            stream_uuid,
+            start,
+            stop,
+        );
+        no_content_for("GET", &config)
+    }
+
+    #[options("/issue/<namespace_key>/hset_state/<uuid>", rank = 2)]
+    pub fn hset_state<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+}
+
+fn format_issue(issue: &Issue, stream: &Stream) -> JsonValue {
+    json!({
+        "uuid": issue.uuid.to_string(),
+        "stream": stream.uuid.to_string(),
+        "fingerprint": issue.fingerprint,
+        "level": issue.level.to_string(),
+        "code": issue.code,
+        "title": issue.title,
+        "state": issue.state,
+        "count": issue.count,
+        "first_seen_at": issue.first_seen_at,
+        "last_seen_at": issue.last_seen_at,
+        "resolved_at": issue.resolved_at,
+    })
+}
+
+// Returns issues on the stream, the most recently seen one comes first. They
+// can be filtered by state (e.g. `?state=open`).
+#[allow(clippy::too_many_arguments)]
+#[get(
+    "/issue/<namespace_key>/lrange/<stream_uuid>/<start>/<stop>?<state>",
+    rank = 1
+)]
+pub fn lrange<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    start: i64,
+    stop: i64,
+    state: Option<String>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, stream_uuid: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let stream = match Stream::find_by_uuid(&stream_uuid, &conn, &logger)
+        .filter(|s| s.namespace_id == namespace.id)
+    {
+        None => return res.status(Status::NotFound),
+        Some(s) => s,
+    };
+
+    let (offset, limit) = offset_and_limit(start, stop, ISSUES_LIMIT);
+    let data = match Issue::find_all_by_stream_id(
+        stream.id,
+        state.map(IssueState::from),
+        offset,
+        limit,
+        &conn,
+        &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found stream.id {}", stream.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|i| json!({ "issue": format_issue(i, &stream) }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Changes the state of the issue (open, resolved or ignored). A resolved one
+// is reopened when it recurs.
+#[patch(
+    "/issue/<namespace_key>/hset_state/<uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset_state<'a>(
+    namespace_key: String,
+    uuid: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let states: Vec<String> =
+        IssueState::iter().map(|s| s.to_string()).collect();
+    let state = match &data.0.state {
+        Some(s) if states.contains(s) => IssueState::from(s.to_string()),
+        _ => {
+            return res.error(Error::invalid(json!([{
+            "field": "state",
+            "messages": [format!("Must be either {}", states.join(", "))],
+            }])));
+        },
+    };
+
+    let issue = match Issue::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(i) => i,
+    };
+    let stream = match Stream::find_by_id(issue.stream_id, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(s) => s,
+    };
+
+    let now = Utc::now().naive_utc();
+    match issue.update_state(state, now, &conn, &logger) {
+        Err(e) => {
+            error!(logger, "err: {}", e);
+            res.status(Status::InternalServerError)
+        },
+        Ok(i) => {
+            info!(logger, "issue: {}, state: {}", i, i.state);
+            res.format(json!({ "issue": format_issue(&i, &stream) }))
+        },
+    }
+}
@@ -1,60 +1,251 @@
+use std::convert::TryFrom;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::result::Error;
+use fourche::queue::Queue;
 use rocket::http::Status;
-use rocket_contrib::json::Json;
-use rocket_slog::SyncLogger;
+use rocket::request::LenientForm;
+use rocket_contrib::json::{Json, JsonValue};
+
+use uuid::Uuid;
 
 use crate::db::DbConn;
+use crate::job::{
+    EvaluateAlertRules, Job, Payload, RollupMessageCounts, WebhookEvent,
+};
+use crate::metrics;
+use crate::model::alert_rule::{AlertCondition, AlertRule};
+use crate::model::issue::Issue;
 use crate::model::message::{AgentType, Message, NewMessage};
+use crate::model::message_view::NewMessageView;
+use crate::model::stream::Stream;
 use crate::model::user::User;
-use crate::response::Response;
-use crate::request::message::Message as RequestData;
+use crate::model::webhook::Webhook;
+use crate::mq::MqConn;
+use crate::redaction::Redactor;
+use crate::request::logger::RequestLogger;
+use crate::webhook::MESSAGE_EVENT;
+use crate::response::{Error as ResponseError, Response};
+use crate::request::message::{Message as RequestData, MessageUpdate as UpdateData};
+use crate::request::message_view::{
+    MessageSearch as SearchData, MessageView as ViewData,
+};
+use crate::route::{
+    find_message, find_namespace, find_stream, format_messages,
+    offset_and_limit, search_messages,
+};
 use crate::validation::message::Validator;
+use crate::validation::message_view::Validator as SearchValidator;
 
-const MESSAGES_PER_REQUEST: i64 = 100;
+pub const MESSAGES_PER_REQUEST: i64 = 100;
 
 pub mod preflight {
     use rocket::State;
     use rocket::response::Response as RawResponse;
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::no_content_for;
 
-    #[options("/message/<namespace_key>/append/<stream_slug>", rank = 2)]
+    #[options("/message/<namespace_key>/append/<stream_uuid>", rank = 2)]
     pub fn append<'a>(
         namespace_key: String,
-        stream_slug: String,
+        stream_uuid: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(
             logger,
-            "namespace: {}, stream: {}", namespace_key, stream_slug
+            "namespace: {}, stream: {}", namespace_key, stream_uuid
         );
         no_content_for("POST", &config)
     }
 
     #[options(
-        "/message/<namespace_key>/lrange/<stream_slug>/<start>/<stop>",
+        "/message/<namespace_key>/lrange/<stream_uuid>/<start>/<stop>",
         rank = 2
     )]
     pub fn lrange<'a>(
         namespace_key: String,
-        stream_slug: String,
+        stream_uuid: String,
         start: i64,
         stop: i64,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(
             logger,
             "namespace: {}, stream: {}, start: {}, stop: {}",
             namespace_key,
-            stream_slug,
+            stream_uuid,
             start,
             stop
         );
         no_content_for("GET", &config)
     }
+
+    #[options("/message/<namespace_key>/search/<start>/<stop>", rank = 2)]
+    pub fn search<'a>(
+        namespace_key: String,
+        start: u64,
+        stop: u64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, start: {}, stop: {}", namespace_key, start, stop
+        );
+        no_content_for("GET", &config)
+    }
+
+    #[options("/message/<namespace_key>/hget/<stream_uuid>/<id>", rank = 2)]
+    pub fn hget<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, stream: {}, id: {}", namespace_key, stream_uuid, id
+        );
+        no_content_for("GET", &config)
+    }
+
+    #[options("/message/<namespace_key>/hset/<stream_uuid>/<id>", rank = 2)]
+    pub fn hset<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, stream: {}, id: {}", namespace_key, stream_uuid, id
+        );
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/message/<namespace_key>/del/<stream_uuid>/<id>", rank = 2)]
+    pub fn del<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        id: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, stream: {}, id: {}", namespace_key, stream_uuid, id
+        );
+        no_content_for("PATCH", &config)
+    }
+}
+
+// Enqueues an evaluation of alert rules on the stream if the message passes
+// through the filter of any of them. Absence conditions are left to the
+// periodic evaluation.
+fn enqueue_alert_evaluation(
+    m: &NewMessage,
+    stream: &Stream,
+    conn: &DbConn,
+    mq_conn: &mut MqConn,
+    logger: &RequestLogger,
+) {
+    let rules =
+        AlertRule::find_all(Some(stream.id), conn, logger).unwrap_or_default();
+    if !rules
+        .iter()
+        .any(|r| r.condition != AlertCondition::Absence && r.matches(m))
+    {
+        return;
+    }
+
+    let job = Job::new(Payload::EvaluateAlertRules(EvaluateAlertRules {
+        stream_id: Some(stream.id),
+    }))
+    .with_request_id(logger.request_id());
+    let mut queue = Queue::new(job.queue(), &mut **mq_conn);
+    if let Err(err) = queue.enqueue::<Job>(job) {
+        error!(logger, "error: {}", err);
+    }
+}
+
+// Enqueues deliveries of the message to webhooks which subscribe to it.
+fn enqueue_webhook_deliveries(
+    id: i64,
+    m: &NewMessage,
+    stream: &Stream,
+    conn: &DbConn,
+    mq_conn: &mut MqConn,
+    logger: &RequestLogger,
+) {
+    let webhooks = Webhook::find_all_enabled_by_stream(stream, conn, logger)
+        .unwrap_or_default();
+
+    let body = json!({
+        "event": MESSAGE_EVENT,
+        "message": {
+            "id": id,
+            "stream": stream.uuid.to_string(),
+            "code": m.code,
+            "lang": m.lang,
+            "level": m.level.to_string(),
+            "format": m.format.to_string(),
+            "title": m.title,
+            "content": m.content,
+        },
+    })
+    .to_string();
+    for w in webhooks.iter().filter(|w| w.matches(m)) {
+        let job = Job::new(Payload::DeliverWebhook(WebhookEvent {
+            webhook_id: w.id,
+            delivery_id: Uuid::new_v4().to_string(),
+            event: MESSAGE_EVENT.to_string(),
+            body: body.clone(),
+        }))
+        .with_request_id(logger.request_id());
+        let mut queue = Queue::new(job.queue(), &mut **mq_conn);
+        if let Err(err) = queue.enqueue::<Job>(job) {
+            error!(logger, "error: {}", err);
+        }
+    }
+}
+
+// Enqueues a rollup of message counts from the time, as a changed or deleted
+// message may have been counted already.
+fn enqueue_rollup(
+    since: NaiveDateTime,
+    mq_conn: &mut MqConn,
+    logger: &RequestLogger,
+) {
+    let job = Job::new(Payload::RollupMessageCounts(RollupMessageCounts {
+        hours: None,
+        since: Some(since.timestamp()),
+    }))
+    .with_request_id(logger.request_id());
+    let mut queue = Queue::new(job.queue(), &mut **mq_conn);
+    if let Err(err) = queue.enqueue::<Job>(job) {
+        error!(logger, "error: {}", err);
+    }
+}
+
+fn format_message(message: &Message, stream: &Stream) -> JsonValue {
+    json!({
+        "id": message.id,
+        "stream": stream.uuid.to_string(),
+        "code": message.code,
+        "lang": message.lang,
+        "level": message.level.to_string(),
+        "format": message.format.to_string(),
+        "title": message.title,
+        "content": message.content,
+        "created_at": message.created_at,
+        "updated_at": message.updated_at,
+    })
 }
 
 // Save a new log message.
@@ -72,7 +263,7 @@ pub mod preflight {
 // }
 // ```
 #[post(
-    "/message/<namespace_key>/append/<stream_slug>",
+    "/message/<namespace_key>/append/<stream_uuid>",
     format = "json",
     data = "<data>",
     rank = 1
@@ -80,10 +271,11 @@ pub mod preflight {
 pub fn append(
     user: &User,
     namespace_key: String,
-    stream_slug: String,
+    stream_uuid: String,
     data: Json<RequestData>,
     conn: DbConn,
-    logger: SyncLogger,
+    mut mq_conn: MqConn,
+    logger: RequestLogger,
 ) -> Response {
     let res: Response = Default::default();
 
@@ -92,30 +284,65 @@ pub fn append(
         "user: {}, namespace: {}, stream: {}",
         user.uuid,
         namespace_key,
-        stream_slug
+        stream_uuid
     );
 
+    let stream = match find_stream(
+        &namespace_key,
+        &stream_uuid,
+        false,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(s) => s,
+    };
+
     // FIXME
-    // * namespace
-    // * validations for stream_id (slug) and agent_* fields
+    // * validations for agent_* fields
     let v = Validator::new(&data, &logger);
     match v.validate() {
-        Err(errors) => {
-            res.status(Status::UnprocessableEntity).format(json!({
-                "errors": errors,
-            }))
-        },
+        Err(errors) => res.error(ResponseError::invalid(errors)),
         Ok(_) => {
-            // FIXME
-            let stream_id = 1;
             let mut m = NewMessage::from(data.0.clone());
-            m.stream_id = stream_id;
+            m.stream_id = stream.id;
             m.agent_id = user.id;
             m.agent_type = AgentType::Person;
-            if let Some(id) = Message::insert(&m, &conn, &logger) {
+            // redacts before it's stored (or passed to anything else)
+            Redactor::for_stream(stream.id, &conn, &logger).redact(&mut m);
+            // it's recorded on the issue in the same transaction
+            let result = conn
+                .build_transaction()
+                .read_write()
+                .run::<_, Error, _>(|| {
+                    let id = Message::insert(&m, &conn, &logger)
+                        .ok_or(Error::RollbackTransaction)?;
+                    Issue::record(&m, &conn, &logger)
+                        .ok_or(Error::RollbackTransaction)?;
+                    Ok(id)
+                });
+            if let Ok(id) = result {
                 info!(logger, "user: {}", user.uuid);
+                metrics::inc_messages_ingested(1);
+                enqueue_alert_evaluation(
+                    &m,
+                    &stream,
+                    &conn,
+                    &mut mq_conn,
+                    &logger,
+                );
+                enqueue_webhook_deliveries(
+                    id,
+                    &m,
+                    &stream,
+                    &conn,
+                    &mut mq_conn,
+                    &logger,
+                );
                 return res.format(json!({"message": {
                     "id": id,
+                    "redactions_count": m.redactions_count,
                 }}));
             }
             res.status(Status::InternalServerError)
@@ -124,17 +351,17 @@ pub fn append(
 }
 
 #[get(
-    "/message/<namespace_key>/lrange/<stream_slug>/<start>/<stop>",
+    "/message/<namespace_key>/lrange/<stream_uuid>/<start>/<stop>",
     rank = 1
 )]
 pub fn lrange(
     user: &User,
     namespace_key: String,
-    stream_slug: String,
+    stream_uuid: String,
     start: u64,
     stop: u64,
     conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response {
     let res: Response = Default::default();
 
@@ -143,33 +370,282 @@ pub fn lrange(
         "user: {}, namespace: {}, stream: {}, start: {}, stop: {}",
         user.uuid,
         namespace_key,
-        stream_slug,
+        stream_uuid,
         start,
         stop
     );
 
-    // TODO
-    let offset = start as i64;
-    let mut limit = (stop - start + 2) as i64;
-    if limit < 1 {
-        limit = 1;
-    }
-
-    // FIXME
-    // * visible to user (and use namespace_key)
-
-    let data = match Message::fetch_by_stream_slug(
-        stream_slug,
-        offset,
-        limit,
+    let stream = match find_stream(
+        &namespace_key,
+        &stream_uuid,
+        false,
+        user,
         &conn,
         &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(s) => s,
+    };
+
+    let (offset, limit) = offset_and_limit(
+        i64::try_from(start).unwrap_or(i64::MAX),
+        i64::try_from(stop).unwrap_or(i64::MAX),
+        MESSAGES_PER_REQUEST,
+    );
+    let data = match Message::fetch_by_stream_id(
+        stream.id, offset, limit, &conn, &logger,
     ) {
         None => {
             error!(logger, "err: not found user.id {}", user.uuid);
             vec![]
         },
-        Some(a) => a.iter().map(|m| json!({ "message": m })).collect(),
+        Some(a) => format_messages(&a, &conn, &logger),
     };
     res.format(json!(data))
 }
+
+// Returns messages which match the query in the namespace (the newest one
+// comes first). A saved view with the same query returns the same result.
+//
+// e.g. `?stream=<uuid>&levels=error,critical&code=E1&time_window=3600`
+#[get("/message/<namespace_key>/search/<start>/<stop>?<search..>", rank = 1)]
+pub fn search<'a>(
+    namespace_key: String,
+    start: u64,
+    stop: u64,
+    search: LenientForm<SearchData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_key,
+        start,
+        stop
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let data = ViewData::from(search.into_inner());
+    let v = SearchValidator::for_search(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(ResponseError::invalid(errors));
+    }
+
+    // it has been validated
+    let stream = data
+        .stream
+        .as_ref()
+        .and_then(|uuid| Stream::find_by_uuid(uuid, &conn, &logger));
+    let view = NewMessageView {
+        namespace_id: namespace.id,
+        stream_id: stream.map(|s| s.id),
+
+        ..NewMessageView::from(data)
+    };
+    let query = view.to_query(Utc::now().naive_utc());
+    match search_messages(&query, start, stop, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(messages) => res.format(json!(messages)),
+    }
+}
+
+#[get("/message/<namespace_key>/hget/<stream_uuid>/<id>", rank = 1)]
+pub fn hget<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    id: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, stream: {}, id: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid,
+        id
+    );
+
+    let res: Response = Default::default();
+
+    match find_message(
+        &namespace_key,
+        &stream_uuid,
+        id,
+        false,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => res.status(status),
+        Ok((message, stream)) => {
+            res.format(json!({
+                "message": format_message(&message, &stream),
+            }))
+        },
+    }
+}
+
+// Edits the message (e.g. redacts its content). Only given fields are
+// changed.
+#[allow(clippy::too_many_arguments)]
+#[patch(
+    "/message/<namespace_key>/hset/<stream_uuid>/<id>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    id: i64,
+    data: Json<UpdateData>,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, stream: {}, id: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid,
+        id
+    );
+
+    let res: Response = Default::default();
+
+    let (message, stream) = match find_message(
+        &namespace_key,
+        &stream_uuid,
+        id,
+        true,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(v) => v,
+    };
+
+    let d = data.0;
+    let merged = RequestData {
+        agent_id: message.agent_id,
+        agent_type: Some(message.agent_type.to_string()),
+        stream_id: message.stream_id,
+        code: d.code.or_else(|| message.code.clone()),
+        lang: d.lang.or_else(|| Some(message.lang.clone())),
+        level: d.level.or_else(|| Some(message.level.to_string())),
+        format: d.format.or_else(|| Some(message.format.to_string())),
+        title: d.title.or_else(|| Some(message.title.clone())),
+        content: d.content.or_else(|| message.content.clone()),
+    };
+    let merged = Json(merged);
+    let v = Validator::new(&merged, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(ResponseError::invalid(errors));
+    }
+
+    let mut m = NewMessage::from(merged.0);
+    // an edit must not bring back what the rules have redacted on append
+    Redactor::for_stream(stream.id, &conn, &logger).redact(&mut m);
+    let counted = m.level != message.level || m.code != message.code;
+    let old = message.clone();
+    let mut message = Message {
+        code: m.code,
+        lang: m.lang,
+        level: m.level,
+        format: m.format,
+        title: m.title.unwrap_or_default(),
+        content: m.content,
+        redactions_count: message.redactions_count + m.redactions_count,
+
+        ..message
+    };
+    // the occurrence moves to another issue if the fingerprint has changed
+    let result =
+        conn.build_transaction()
+            .read_write()
+            .run::<_, Error, _>(|| {
+                Message::update(&mut message, &conn, &logger)
+                    .ok_or(Error::RollbackTransaction)?;
+                Issue::regroup(&old, &message, &conn, &logger)
+                    .map_err(|_| Error::RollbackTransaction)
+            });
+    if result.is_err() {
+        return res.status(Status::InternalServerError);
+    }
+    if counted {
+        enqueue_rollup(message.created_at, &mut mq_conn, &logger);
+    }
+    res.format(json!({
+        "message": format_message(&message, &stream),
+    }))
+}
+
+// Deletes the message. The deletion is logged with the user, and the
+// occurrence is taken from its issue.
+#[patch("/message/<namespace_key>/del/<stream_uuid>/<id>", rank = 1)]
+pub fn del<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    id: i64,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, stream: {}, id: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid,
+        id
+    );
+
+    let res: Response = Default::default();
+
+    let (message, _) = match find_message(
+        &namespace_key,
+        &stream_uuid,
+        id,
+        true,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(v) => v,
+    };
+
+    let result =
+        conn.build_transaction()
+            .read_write()
+            .run::<_, Error, _>(|| {
+                message
+                    .delete(user, &conn, &logger)
+                    .map_err(|_| Error::RollbackTransaction)?;
+                Issue::discount(&message, &conn, &logger)
+                    .map_err(|_| Error::RollbackTransaction)
+            });
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(_) => {
+            enqueue_rollup(message.created_at, &mut mq_conn, &logger);
+            res
+        },
+    }
+}
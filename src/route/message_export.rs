@@ -0,0 +1,177 @@
+use std::fs::File;
+
+use fourche::queue::Queue;
+use rocket::State;
+use rocket::http::{ContentType, Status};
+use rocket::response::Response as RawResponse;
+use rocket_contrib::json::Json;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::export::{
+    ExportFormat, file_path, is_valid_file_name, parse_time, subject,
+};
+use crate::job::{ExportMessages, Job, Payload};
+use crate::model::stream::Stream;
+use crate::model::token::{Claims, VerificationClaims};
+use crate::model::user::User;
+use crate::mq::MqConn;
+use crate::request::logger::RequestLogger;
+use crate::request::message_export::MessageExport as RequestData;
+use crate::response::{Error, ErrorCode, Response};
+use crate::route::find_namespace;
+use crate::validation::message_export::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/message/<namespace_key>/export", rank = 2)]
+    pub fn export<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/message/export/download/<name>", rank = 2)]
+    pub fn download<'a>(
+        name: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "name: {}", name);
+        no_content_for("GET", &config)
+    }
+}
+
+// Enqueues an export of messages on a stream. A link to download the file is
+// sent to the primary email address of the user.
+#[post(
+    "/message/<namespace_key>/export",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn export<'a>(
+    namespace_key: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let v = Validator::new(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    // they have been validated
+    let d = data.0;
+    let stream = d
+        .stream
+        .as_ref()
+        .and_then(|uuid| Stream::find_by_uuid(uuid, &conn, &logger))
+        .unwrap();
+    let since = d.since.as_ref().and_then(|s| parse_time(s)).unwrap();
+    let until = d.until.as_ref().and_then(|s| parse_time(s)).unwrap();
+    let format = ExportFormat::from(d.format.unwrap_or_default());
+
+    let job = Job::new(Payload::ExportMessages(ExportMessages {
+        user_id: user.id,
+        stream_id: stream.id,
+        format: format.to_string(),
+        level: d.level,
+        code: d.code,
+        since: since.timestamp(),
+        until: until.timestamp(),
+    }))
+    .with_request_id(logger.request_id());
+    let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+    if let Err(err) = queue.enqueue::<Job>(job) {
+        error!(logger, "error: {}", err);
+        return res.status(Status::InternalServerError);
+    }
+    res.status(Status::Accepted).format(json!({
+        "export": {
+            "stream": stream.uuid.to_string(),
+            "format": format.to_string(),
+        }
+    }))
+}
+
+// Sends the exported file. The link (token) must have been issued to the
+// user, and not expired.
+#[get("/message/export/download/<name>?<t>", rank = 1)]
+pub fn download<'a>(
+    name: String,
+    t: String,
+    user: &User,
+    config: State<Config>,
+    logger: RequestLogger,
+) -> Result<RawResponse<'a>, Error> {
+    info!(logger, "user: {}, name: {}", user.uuid, name);
+
+    if !is_valid_file_name(&name) {
+        return Err(Error::new(Status::NotFound));
+    }
+
+    let claims = match VerificationClaims::decode(
+        &t,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+    ) {
+        Err(e) => {
+            info!(logger, "err: {}", e);
+            return Err(Error::new(Status::Forbidden)
+                .code(ErrorCode::InvalidToken)
+                .message("The link is invalid or expired"));
+        },
+        Ok(c) => c,
+    };
+    if claims.get_subject() != subject(&user.uuid, &name) {
+        info!(logger, "err: subject mismatch {}", claims.get_subject());
+        return Err(Error::new(Status::Forbidden)
+            .code(ErrorCode::InvalidToken)
+            .message("The link is invalid or expired"));
+    }
+
+    let path = file_path(&config.export_directory, &user.uuid, &name);
+    let file = File::open(&path).map_err(|e| {
+        error!(logger, "err: {} {}", path.display(), e);
+        Error::new(Status::NotFound)
+    })?;
+
+    let mut res = RawResponse::new();
+    res.set_raw_header(
+        "Access-Control-Allow-Origin",
+        config.application_url.to_owned(),
+    );
+    res.set_raw_header("Access-Control-Allow-Credentials", "true");
+    res.set_header(ContentType::new("application", "gzip"));
+    res.set_raw_header(
+        "Content-Disposition",
+        format!("attachment; filename=\"{}\"", name),
+    );
+    res.set_sized_body(file);
+    Ok(res)
+}
@@ -0,0 +1,139 @@
+use std::io::Read;
+
+use fourche::queue::Queue;
+use rocket::Data;
+use rocket::http::Status;
+
+use crate::db::DbConn;
+use crate::import::{self, ImportFormat};
+use crate::job::{Job, Payload, RollupMessageCounts};
+use crate::metrics;
+use crate::model::user::User;
+use crate::mq::MqConn;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error, Response};
+use crate::route::find_stream;
+
+// The max size of a body (bytes)
+const IMPORT_LIMIT: u64 = 8 * 1024 * 1024;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/message/<namespace_key>/import/<stream_uuid>", rank = 2)]
+    pub fn import<'a>(
+        namespace_key: String,
+        stream_uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, stream_uuid: {}", namespace_key, stream_uuid
+        );
+        no_content_for("POST", &config)
+    }
+}
+
+// Imports historical messages into a stream. The body is NDJSON (default) or
+// TOML, and each record must have its created_at. Records are validated one
+// by one, and the response reports which of them could not be imported.
+// Rollups of message counts are re-counted from the oldest one.
+#[allow(clippy::too_many_arguments)]
+#[post(
+    "/message/<namespace_key>/import/<stream_uuid>?<format>",
+    data = "<data>",
+    rank = 1
+)]
+pub fn import<'a>(
+    namespace_key: String,
+    stream_uuid: String,
+    format: Option<String>,
+    data: Data,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, stream_uuid: {}",
+        user.uuid,
+        namespace_key,
+        stream_uuid
+    );
+
+    let res: Response = Default::default();
+
+    let stream = match find_stream(
+        &namespace_key,
+        &stream_uuid,
+        false,
+        user,
+        &conn,
+        &logger,
+    ) {
+        Err(status) => return res.status(status),
+        Ok(s) => s,
+    };
+
+    let formats: Vec<String> = ImportFormat::as_vec()
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+    if let Some(f) = &format {
+        if !formats.contains(&f.to_ascii_lowercase()) {
+            return res.error(Error::invalid(json!([{
+            "field": "format",
+            "messages": [format!("Must be either {}", formats.join(", "))],
+            }])));
+        }
+    }
+    let format = ImportFormat::from(format.unwrap_or_default());
+
+    let mut body = String::new();
+    if let Err(e) = data.open().take(IMPORT_LIMIT + 1).read_to_string(&mut body)
+    {
+        info!(logger, "err: {}", e);
+        return res.status(Status::BadRequest);
+    }
+    if body.len() as u64 > IMPORT_LIMIT {
+        return res.status(Status::PayloadTooLarge);
+    }
+
+    let records = match import::parse(&format, &body) {
+        Err(e) => {
+            info!(logger, "err: {}", e);
+            return res.error(Error::invalid(json!([{
+                "field": "body",
+                "messages": [e],
+            }])));
+        },
+        Ok(r) => r,
+    };
+
+    let report = import::import(records, user.id, stream.id, &conn, &logger);
+    info!(
+        logger,
+        "imported: {}, failed: {}", report.imported, report.failed
+    );
+    metrics::inc_messages_ingested(report.imported as u64);
+
+    if let Some(oldest) = report.oldest {
+        let job = Job::new(Payload::RollupMessageCounts(RollupMessageCounts {
+            hours: None,
+            since: Some(oldest.timestamp()),
+        }))
+        .with_request_id(logger.request_id());
+        let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+        if let Err(err) = queue.enqueue::<Job>(job) {
+            error!(logger, "error: {}", err);
+        }
+    }
+    res.format(json!({ "import": report }))
+}
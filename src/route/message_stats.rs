@@ -0,0 +1,122 @@
+use chrono::Duration;
+use rocket::http::Status;
+use rocket::request::LenientForm;
+
+use crate::db::DbConn;
+use crate::export::{TIME_FORMAT, parse_time};
+use crate::model::message_count::{
+    Bucket, CountFilter, MessageCount, truncate_to_hour,
+};
+use crate::model::stream::Stream;
+use crate::model::user::User;
+use crate::request::logger::RequestLogger;
+use crate::request::message_stats::MessageStats as RequestData;
+use crate::response::{Error, Response};
+use crate::route::find_namespace;
+use crate::validation::message_stats::Validator;
+
+// A range longer than this (days) is aggregated from rollups until the
+// watermark (except by minute)
+const ROLLUP_THRESHOLD: i64 = 7;
+
+const DEFAULT_TOP: i64 = 10;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/message/<namespace_key>/stats", rank = 2)]
+    pub fn stats<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("GET", &config)
+    }
+}
+
+// Returns counts of messages by level in each time bucket, and the most
+// frequent codes on the stream (or on all streams in the namespace).
+//
+// e.g. `?stream=<uuid>&bucket=hour&since=2021-01-01T00:00:00&until=...`
+#[get("/message/<namespace_key>/stats?<stats..>", rank = 1)]
+pub fn stats<'a>(
+    namespace_key: String,
+    stats: LenientForm<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let v = Validator::new(&conn, &stats, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    // they have been validated
+    let d = stats.into_inner();
+    let stream = d
+        .stream
+        .as_ref()
+        .and_then(|uuid| Stream::find_by_uuid(uuid, &conn, &logger));
+    let bucket = Bucket::from(d.bucket.unwrap_or_default());
+    let mut since = d.since.as_ref().and_then(|s| parse_time(s)).unwrap();
+    let until = d.until.as_ref().and_then(|s| parse_time(s)).unwrap();
+    let top = d.top.map_or(DEFAULT_TOP, |t| t.parse::<i64>().unwrap());
+
+    // rollups are counted by hour, so the range starts on the hour
+    let mut boundary = None;
+    if bucket != Bucket::Minute &&
+        until - since > Duration::days(ROLLUP_THRESHOLD)
+    {
+        if let Ok(Some(watermark)) = MessageCount::watermark(&conn, &logger) {
+            let start = truncate_to_hour(since);
+            if watermark > start {
+                since = start;
+                boundary = Some(watermark.min(until));
+            }
+        }
+    }
+
+    let filter = CountFilter {
+        namespace_id: namespace.id,
+        stream_id: stream.as_ref().map(|s| s.id),
+        since,
+        until,
+        boundary,
+    };
+    let counts = MessageCount::count_by_level(&filter, &bucket, &conn, &logger);
+    let codes = MessageCount::top_codes(&filter, top, &conn, &logger);
+    match (counts, codes) {
+        (Ok(counts), Ok(codes)) => {
+            res.format(json!({
+                "stats": {
+                    "stream": stream.map(|s| s.uuid.to_string()),
+                    "bucket": bucket.to_string(),
+                    "since": since.format(TIME_FORMAT).to_string(),
+                    "until": until.format(TIME_FORMAT).to_string(),
+                    "counts": counts,
+                    "codes": codes,
+                }
+            }))
+        },
+        _ => res.status(Status::InternalServerError),
+    }
+}
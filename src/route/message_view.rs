@@ -0,0 +1,395 @@
+use chrono::Utc;
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::logger::Logger;
+use crate::model::membership::Membership;
+use crate::model::message_view::{MessageView, NewMessageView};
+use crate::model::namespace::Namespace;
+use crate::model::stream::Stream;
+use crate::model::user::User;
+use crate::request::logger::RequestLogger;
+use crate::request::message_view::MessageView as RequestData;
+use crate::response::{Error, Response};
+use crate::route::{find_namespace, search_messages};
+use crate::validation::message_view::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/message/<namespace_key>/view/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/message/<namespace_key>/view/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/message/<namespace_key>/view/hset/<uuid>", rank = 2)]
+    pub fn update<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/message/<namespace_key>/view/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options(
+        "/message/<namespace_key>/view/lrange/<uuid>/<start>/<stop>",
+        rank = 2
+    )]
+    pub fn lrange<'a>(
+        namespace_key: String,
+        uuid: String,
+        start: u64,
+        stop: u64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, uuid: {}, start: {}, stop: {}",
+            namespace_key,
+            uuid,
+            start,
+            stop
+        );
+        no_content_for("GET", &config)
+    }
+}
+
+fn format_view(view: &MessageView, stream_uuid: Option<Uuid>) -> JsonValue {
+    json!({
+        "uuid": view.uuid.to_string(),
+        "name": view.name,
+        "shared": view.shared,
+        "stream": stream_uuid.map(|u| u.to_string()),
+        "levels": view.levels,
+        "code": view.code,
+        "lang": view.lang,
+        "time_window": view.time_window,
+        "created_at": view.created_at,
+        "updated_at": view.updated_at,
+    })
+}
+
+fn is_owner(
+    namespace: &Namespace,
+    user: &User,
+    conn: &DbConn,
+    logger: &Logger,
+) -> bool {
+    Membership::find_by_namespace_and_user(namespace, user, conn, logger)
+        .map_or(false, |m| m.role.is_owner())
+}
+
+// A private view is managed by the user who made it, and a shared one is
+// managed by owners of the namespace.
+fn can_manage(view: &MessageView, user: &User, owner: bool) -> bool {
+    if view.shared {
+        owner
+    } else {
+        view.user_id == user.id
+    }
+}
+
+// Builds a view from the validated data
+fn build_view(
+    data: RequestData,
+    namespace: &Namespace,
+    user: &User,
+    conn: &DbConn,
+    logger: &Logger,
+) -> (NewMessageView, Option<Uuid>) {
+    let stream = data
+        .stream
+        .as_ref()
+        .and_then(|uuid| Stream::find_by_uuid(uuid, conn, logger));
+    let view = NewMessageView {
+        namespace_id: namespace.id,
+        user_id: user.id,
+        stream_id: stream.as_ref().map(|s| s.id),
+
+        ..NewMessageView::from(data)
+    };
+    (view, stream.map(|s| s.uuid))
+}
+
+#[get("/message/<namespace_key>/view/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_key: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let data = match MessageView::find_all_by_namespace_id_and_user_id(
+        namespace.id,
+        user.id,
+        &conn,
+        &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found namespace.id {}", namespace.id);
+            vec![]
+        },
+        Some(v) => {
+            v.iter()
+                .map(|(v, s)| json!({ "message_view": format_view(v, *s) }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Saves a view. Any member can save a private one, but only owners can share
+// it in the namespace.
+#[post(
+    "/message/<namespace_key>/view/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_key: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let v = Validator::new(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    let (view, stream_uuid) =
+        build_view(data.0, &namespace, user, &conn, &logger);
+    if view.shared && !is_owner(&namespace, user, &conn, &logger) {
+        return res.error(
+            Error::new(Status::Forbidden)
+                .message("You are not allowed to share the view."),
+        );
+    }
+
+    match MessageView::insert(&view, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(view) => {
+            info!(logger, "message_view: {}", view.uuid);
+            res.format(json!({
+                "message_view": format_view(&view, stream_uuid),
+            }))
+        },
+    }
+}
+
+#[patch(
+    "/message/<namespace_key>/view/hset/<uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn update<'a>(
+    namespace_key: String,
+    uuid: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let (current, _) = match MessageView::find_by_uuid(
+        &uuid,
+        namespace.id,
+        user.id,
+        &conn,
+        &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(v) => v,
+    };
+
+    let v = Validator::new(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    let (view, stream_uuid) =
+        build_view(data.0, &namespace, user, &conn, &logger);
+    let owner = is_owner(&namespace, user, &conn, &logger);
+    if !can_manage(&current, user, owner) || (view.shared && !owner) {
+        return res.error(
+            Error::new(Status::Forbidden)
+                .message("You are not allowed to edit the view."),
+        );
+    }
+
+    match current.update(&view, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(view) => {
+            res.format(json!({
+                "message_view": format_view(&view, stream_uuid),
+            }))
+        },
+    }
+}
+
+#[patch("/message/<namespace_key>/view/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let (view, _) = match MessageView::find_by_uuid(
+        &uuid,
+        namespace.id,
+        user.id,
+        &conn,
+        &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(v) => v,
+    };
+    let owner = is_owner(&namespace, user, &conn, &logger);
+    if !can_manage(&view, user, owner) {
+        return res.error(
+            Error::new(Status::Forbidden)
+                .message("You are not allowed to delete the view."),
+        );
+    }
+
+    match view.delete(&conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(_) => res,
+    }
+}
+
+// Runs the view. It's the same as the search by the query of the view.
+#[get("/message/<namespace_key>/view/lrange/<uuid>/<start>/<stop>", rank = 1)]
+pub fn lrange<'a>(
+    namespace_key: String,
+    uuid: String,
+    start: u64,
+    stop: u64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_key,
+        uuid,
+        start,
+        stop
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let (view, _) = match MessageView::find_by_uuid(
+        &uuid,
+        namespace.id,
+        user.id,
+        &conn,
+        &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(v) => v,
+    };
+
+    let query = view.to_query(Utc::now().naive_utc());
+    match search_messages(&query, start, stop, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(messages) => res.format(json!(messages)),
+    }
+}
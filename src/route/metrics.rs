@@ -0,0 +1,91 @@
+use std::io::Cursor;
+
+use chrono::{Duration, Utc};
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::Response as RawResponse;
+
+use crate::config::Config;
+use crate::db::DbPoolHolder;
+use crate::job::{dead_letter, delay, pending, postpone, retry};
+use crate::metrics::{self, CONTENT_TYPE};
+use crate::model::message_count::MessageCount;
+use crate::mq::MqPoolHolder;
+use crate::request::logger::RequestLogger;
+use crate::request::metrics::MetricsToken;
+use crate::ss::SsPoolHolder;
+
+const RETRY: &str = "retry";
+const DELAYED: &str = "delayed";
+const POSTPONED: &str = "postponed";
+const DEAD: &str = "dead";
+
+// The number of streams labeled in the metrics of ingested messages
+const TOP_STREAMS: i64 = 20;
+
+/// Returns metrics in Prometheus text format. It's served only with the
+/// metrics token (see `MetricsToken`), and not found otherwise.
+#[get("/metrics", rank = 1)]
+pub fn metrics<'a>(
+    _token: MetricsToken,
+    db_pool_holder: State<DbPoolHolder>,
+    mq_pool_holder: State<MqPoolHolder>,
+    ss_pool_holder: State<SsPoolHolder>,
+    config: State<Config>,
+    logger: RequestLogger,
+) -> RawResponse<'a> {
+    for (name, (connections, idle, max_size)) in &[
+        ("database", db_pool_holder.state()),
+        ("message_queue", mq_pool_holder.state()),
+        ("session_store", ss_pool_holder.state()),
+    ] {
+        metrics::set_pool_connections(name, *connections, *idle, *max_size);
+    }
+
+    // the lengths are left as they were if the queue is not available
+    match mq_pool_holder.get() {
+        None => error!(logger, "err: message queue is not available"),
+        Some(mut conn) => {
+            let conn = &mut *conn;
+            let mut lengths = vec![];
+            for (name, _) in &config.worker_queues {
+                lengths.push((name.as_str(), pending::len(name, conn)));
+            }
+            lengths.push((RETRY, retry::len(conn)));
+            lengths.push((DELAYED, delay::len(conn)));
+            lengths.push((POSTPONED, postpone::len(conn)));
+            lengths.push((DEAD, dead_letter::len(conn)));
+
+            for (name, length) in lengths {
+                match length {
+                    Ok(n) => metrics::set_queue_length(name, n),
+                    Err(e) => error!(logger, "err: {} {}", name, e),
+                }
+            }
+        },
+    }
+
+    // the counts are left as they were if the database is not available
+    match db_pool_holder.get() {
+        None => error!(logger, "err: database is not available"),
+        Some(conn) => {
+            let since = (Utc::now() - Duration::hours(1)).naive_utc();
+            if let Ok(counts) =
+                MessageCount::top_streams(since, TOP_STREAMS, &conn, &logger)
+            {
+                let counts: Vec<(&str, i64)> = counts
+                    .iter()
+                    .map(|c| (c.stream.as_str(), c.count))
+                    .collect();
+                metrics::set_stream_messages(&counts);
+            }
+        },
+    }
+
+    let body = metrics::gather();
+    let mut res = RawResponse::new();
+    res.set_raw_header("Content-Type", CONTENT_TYPE);
+    res.set_status(Status::Ok);
+    res.set_sized_body(Cursor::new(body));
+    res
+}
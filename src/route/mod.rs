@@ -1,9 +1,143 @@
 pub mod access_token;
 pub mod activation;
+pub mod alert;
+pub mod annotation;
+pub mod audit;
 pub mod authentication;
+pub mod email;
 pub mod error;
 pub mod health;
+pub mod issue;
 pub mod message;
+pub mod message_export;
+pub mod message_import;
+pub mod message_stats;
+pub mod message_view;
+pub mod metrics;
 pub mod namespace;
 pub mod password_reset;
+pub mod redaction;
 pub mod registration;
+pub mod totp;
+pub mod webhook;
+
+use std::convert::TryFrom;
+
+use rocket::http::Status;
+use rocket_contrib::json::JsonValue;
+
+use crate::db::DbConn;
+use crate::logger::Logger;
+use crate::model::annotation::Annotation;
+use crate::model::membership::Membership;
+use crate::model::message::{Message, MessageQuery};
+use crate::model::namespace::Namespace;
+use crate::model::stream::Stream;
+use crate::model::user::User;
+
+/// Returns the namespace if the user is a member of it (an owner if
+/// required), or the status for the response.
+pub fn find_namespace(
+    namespace_key: &str,
+    owner: bool,
+    user: &User,
+    conn: &DbConn,
+    logger: &Logger,
+) -> Result<Namespace, Status> {
+    let namespace = Namespace::find_by_uuid(namespace_key, user, conn, logger)
+        .ok_or_else(|| {
+            error!(logger, "err: no namespace for uuid: {}", namespace_key);
+            Status::NotFound
+        })?;
+
+    match Membership::find_by_namespace_and_user(&namespace, user, conn, logger)
+    {
+        Some(ref m) if !owner || m.role.is_owner() => Ok(namespace),
+        _ => Err(Status::Forbidden),
+    }
+}
+
+/// Returns the stream in the namespace, if the user is a member of it (an
+/// owner if required), or the status for the response.
+pub fn find_stream(
+    namespace_key: &str,
+    stream_uuid: &str,
+    owner: bool,
+    user: &User,
+    conn: &DbConn,
+    logger: &Logger,
+) -> Result<Stream, Status> {
+    let namespace = find_namespace(namespace_key, owner, user, conn, logger)?;
+    Stream::find_by_uuid(stream_uuid, conn, logger)
+        .filter(|s| s.namespace_id == namespace.id)
+        .ok_or_else(|| {
+            error!(logger, "err: no stream for uuid: {}", stream_uuid);
+            Status::NotFound
+        })
+}
+
+/// Returns the message on the stream in the namespace with the stream, if
+/// the user is a member of it (an owner if required), or the status for the
+/// response.
+pub fn find_message(
+    namespace_key: &str,
+    stream_uuid: &str,
+    id: i64,
+    owner: bool,
+    user: &User,
+    conn: &DbConn,
+    logger: &Logger,
+) -> Result<(Message, Stream), Status> {
+    let stream =
+        find_stream(namespace_key, stream_uuid, owner, user, conn, logger)?;
+    let message = Message::first_by_stream_id(id, stream.id, conn, logger)
+        .ok_or(Status::NotFound)?;
+    Ok((message, stream))
+}
+
+/// Returns the offset and the limit for the range from start to stop
+/// (inclusive). The limit is at least 1 and at most `max`.
+pub fn offset_and_limit(start: i64, stop: i64, max: i64) -> (i64, i64) {
+    let offset = start.max(0);
+    let limit = stop.saturating_sub(offset).saturating_add(1);
+    (offset, limit.max(1).min(max))
+}
+
+/// Formats messages for a list with the number of annotations on each of
+/// them.
+pub fn format_messages(
+    messages: &[Message],
+    conn: &DbConn,
+    logger: &Logger,
+) -> Vec<JsonValue> {
+    let ids: Vec<i64> = messages.iter().map(|m| m.id).collect();
+    let counts = Annotation::count_by_message_ids(&ids, conn, logger)
+        .unwrap_or_default();
+    messages
+        .iter()
+        .map(|m| {
+            json!({
+                "message": m,
+                "annotations_count": counts.get(&m.id).unwrap_or(&0),
+            })
+        })
+        .collect()
+}
+
+/// Returns messages which match the query from start to stop (inclusive, up
+/// to `MESSAGES_PER_REQUEST`). Both the search and saved views use this.
+pub fn search_messages(
+    query: &MessageQuery,
+    start: u64,
+    stop: u64,
+    conn: &DbConn,
+    logger: &Logger,
+) -> Option<Vec<JsonValue>> {
+    let (offset, limit) = offset_and_limit(
+        i64::try_from(start).unwrap_or(i64::MAX),
+        i64::try_from(stop).unwrap_or(i64::MAX),
+        message::MESSAGES_PER_REQUEST,
+    );
+    Message::search(query, offset, limit, conn, logger)
+        .map(|messages| format_messages(&messages, conn, logger))
+}
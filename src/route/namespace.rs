@@ -1,29 +1,30 @@
 use diesel::result::Error;
 use rocket::http::Status;
 use rocket_contrib::json::{Json, JsonValue};
-use rocket_slog::SyncLogger;
 
 use crate::db::DbConn;
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
 use crate::model::namespace::{Namespace, NewNamespace};
 use crate::model::user::User;
 use crate::model::membership::{Membership, MembershipRole, NewMembership};
-use crate::response::Response;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error as ResponseError, Response};
 use crate::request::namespace::Namespace as RequestData;
 use crate::validation::namespace::Validator;
 
 pub mod preflight {
     use rocket::State;
     use rocket::response::Response as RawResponse;
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::no_content_for;
 
     #[options("/namespace/hget/<uuid>", rank = 2)]
     pub fn hget<'a>(
         uuid: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "hget uuid: {}", uuid);
         no_content_for("GET", &config)
@@ -32,7 +33,7 @@ pub mod preflight {
     #[options("/namespace/hgetall", rank = 2)]
     pub fn hgetall<'a>(
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "hgetall");
         no_content_for("GET", &config)
@@ -41,7 +42,7 @@ pub mod preflight {
     #[options("/namespace/hset", rank = 2)]
     pub fn hset<'a>(
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "hset");
         no_content_for("POST", &config)
@@ -53,7 +54,7 @@ pub fn hget(
     uuid: String,
     user: &User,
     conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response {
     info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
 
@@ -74,7 +75,7 @@ pub fn hget(
 }
 
 #[get("/namespace/hgetall", rank = 1)]
-pub fn hgetall(user: &User, conn: DbConn, logger: SyncLogger) -> Response {
+pub fn hgetall(user: &User, conn: DbConn, logger: RequestLogger) -> Response {
     let res: Response = Default::default();
 
     info!(logger, "user: {}", user.uuid);
@@ -94,7 +95,7 @@ pub fn hset(
     user: &User,
     data: Json<RequestData>,
     conn: DbConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response {
     let res: Response = Default::default();
 
@@ -102,11 +103,7 @@ pub fn hset(
 
     let v = Validator::new(&data, &logger);
     match v.validate() {
-        Err(errors) => {
-            res.status(Status::UnprocessableEntity).format(json!({
-                "errors": errors,
-            }))
-        },
+        Err(errors) => res.error(ResponseError::invalid(errors)),
         Ok(_) => {
             let result: Result<String, Error> = conn
                 .build_transaction()
@@ -124,7 +121,29 @@ pub fn hset(
                             user_id: user.id,
                             role: MembershipRole::PrimaryOwner,
                         };
-                        let _ = Membership::insert(&m, &conn, &logger).unwrap();
+                        let membership =
+                            Membership::insert(&m, &conn, &logger).unwrap();
+
+                        let events = vec![
+                            NewAuditEvent {
+                                namespace_id: Some(namespace.id),
+                                action: AuditEventAction::NamespaceCreation,
+                                target: Some(namespace.uuid.to_string()),
+
+                                ..NewAuditEvent::from(user)
+                            },
+                            NewAuditEvent {
+                                namespace_id: Some(namespace.id),
+                                action: AuditEventAction::MembershipCreation,
+                                target: Some(membership.role.to_string()),
+
+                                ..NewAuditEvent::from(user)
+                            },
+                        ];
+                        for e in events {
+                            AuditEvent::insert(&e, &conn, &logger)
+                                .ok_or(Error::RollbackTransaction)?;
+                        }
                         return Ok(namespace.uuid.to_string());
                     }
                     Err(Error::RollbackTransaction)
@@ -5,19 +5,20 @@ use redis::{Commands, RedisError};
 use rocket::State;
 use rocket::http::{Cookies, Status};
 use rocket_contrib::json::Json;
-use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
-use crate::job::{Job, JobKind};
+use crate::job::{Job, PasswordResetEmail, Payload};
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
 use crate::model::token::{VerificationClaims, Claims, TokenData};
 use crate::model::user::User;
 use crate::mq::MqConn;
+use crate::request::logger::RequestLogger;
 use crate::request::password_reset::{
     PasswordReset, PasswordResetRequest, PasswordResetUpdate,
 };
 use crate::request::token::verification::VerificationToken;
-use crate::response::Response;
+use crate::response::{Error as ResponseError, ErrorCode, Response};
 use crate::service::password_updater::PasswordUpdater;
 use crate::validation::ValidationError;
 use crate::validation::password_reset::Validator as PasswordResetValidator;
@@ -28,9 +29,9 @@ use crate::util::split_token;
 pub mod preflight {
     use rocket::State;
     use rocket::response::Response as RawResponse;
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::no_content_for;
 
     #[options("/password/reset", rank = 2)]
@@ -42,7 +43,7 @@ pub mod preflight {
     pub fn verify_update<'a>(
         session_id: String,
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
     ) -> RawResponse<'a> {
         info!(logger, "session_id: {}", session_id);
         no_content_for("GET,HEAD,PATCH", &config)
@@ -54,9 +55,9 @@ pub mod preignition {
     use redis::{Commands, RedisError};
     use rocket::State;
     use rocket::http::{Cookie, Cookies, SameSite, Status};
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::Response;
     use crate::ss::SsConn;
     use crate::util::generate_random_hash;
@@ -64,7 +65,7 @@ pub mod preignition {
     #[head("/password/reset", format = "json", rank = 3)]
     pub fn request<'a>(
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
         mut cookies: Cookies,
         mut ss_conn: SsConn,
     ) -> Response<'a> {
@@ -102,7 +103,7 @@ pub mod preignition {
     #[head("/password/reset/<session_id>", format = "json", rank = 3)]
     pub fn update<'a>(
         config: State<Config>,
-        logger: SyncLogger,
+        logger: RequestLogger,
         session_id: String,
         mut cookies: Cookies,
         mut ss_conn: SsConn,
@@ -143,7 +144,7 @@ pub mod preignition {
 
 #[put("/password/reset", data = "<payload>", format = "json", rank = 1)]
 pub fn request<'a>(
-    logger: SyncLogger,
+    logger: RequestLogger,
     mut cookies: Cookies,
     config: State<Config>,
     mut ss_conn: SsConn,
@@ -157,9 +158,11 @@ pub fn request<'a>(
     let cookie = cookies.get_private("csrf_token").ok_or("");
     if cookie.is_err() {
         info!(logger, "error: missing csrf_token");
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token is required."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token is required."),
+        );
     }
     let key = cookie.ok().unwrap().value().to_string();
     let result: Result<i64, RedisError> = ss_conn.get(&key).map_err(|e| {
@@ -167,9 +170,11 @@ pub fn request<'a>(
         e
     });
     if result.is_err() {
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token has been expired. Reload the page."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token has been expired. Reload the page."),
+        );
     }
 
     if PasswordResetRequestValidator::new(&db_conn, &payload, &logger)
@@ -217,6 +222,15 @@ pub fn request<'a>(
                     error!(logger, "error: {}", e);
                     return Err(Error::RollbackTransaction);
                 }
+
+                let e = NewAuditEvent {
+                    action: AuditEventAction::PasswordResetRequest,
+
+                    ..NewAuditEvent::from(&user)
+                };
+                AuditEvent::insert(&e, &db_conn, &logger)
+                    .ok_or(Error::RollbackTransaction)?;
+
                 Ok((user.id, raw_token))
             });
 
@@ -240,12 +254,16 @@ pub fn request<'a>(
                     });
 
                 if result.is_ok() {
-                    let job = Job::<String> {
-                        kind: JobKind::SendPasswordResetEmail,
-                        args: vec![id.to_string(), session_id, token],
-                    };
-                    let mut queue = Queue::new("default", &mut *mq_conn);
-                    if let Err(err) = queue.enqueue::<Job<String>>(job) {
+                    let job = Job::new(Payload::SendPasswordResetEmail(
+                        PasswordResetEmail {
+                            user_id: id,
+                            session_id,
+                            token,
+                        },
+                    ))
+                    .with_request_id(logger.request_id());
+                    let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+                    if let Err(err) = queue.enqueue::<Job>(job) {
                         error!(logger, "error: {}", err);
                     } else {
                         return res;
@@ -253,9 +271,10 @@ pub fn request<'a>(
                 }
             }
         }
-        return res.status(Status::InternalServerError).format(json!({
-            "message": "Something wrong happen, sorry :'("
-        }));
+        return res.error(
+            ResponseError::new(Status::InternalServerError)
+                .message("Something wrong happen, sorry :'("),
+        );
     }
     res.status(Status::NotFound)
 }
@@ -265,7 +284,7 @@ pub fn request<'a>(
 // https://github.com/SergioBenitez/Rocket/issues/2
 #[get("/password/reset/<session_id>", format = "json", rank = 1)]
 pub fn verify<'a>(
-    logger: SyncLogger,
+    logger: RequestLogger,
     session_id: String,
     token: VerificationToken,
 ) -> Response<'a> {
@@ -284,7 +303,7 @@ pub fn verify<'a>(
     rank = 1
 )]
 pub fn update<'a>(
-    logger: SyncLogger,
+    logger: RequestLogger,
     mut cookies: Cookies,
     token: VerificationToken,
     config: State<Config>,
@@ -300,9 +319,11 @@ pub fn update<'a>(
     let cookie = cookies.get_private("csrf_token").ok_or("");
     if cookie.is_err() {
         info!(logger, "error: missing csrf_token");
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token is required."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token is required."),
+        );
     }
     let key = cookie.ok().unwrap().value().to_string();
     let result: Result<i64, RedisError> = ss_conn.get(&key).map_err(|e| {
@@ -310,9 +331,11 @@ pub fn update<'a>(
         e
     });
     if result.is_err() {
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token has been expired. Reload the page."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token has been expired. Reload the page."),
+        );
     }
 
     let mut errors: Vec<ValidationError> = vec![];
@@ -368,9 +391,7 @@ pub fn update<'a>(
     match result {
         Ok(_) => res.status(Status::Ok),
         Err(_) if !errors.is_empty() => {
-            res.status(Status::UnprocessableEntity).format(json!({
-                "errors": errors,
-            }))
+            res.error(ResponseError::invalid(errors))
         },
         _ => res.status(Status::NotFound),
     }
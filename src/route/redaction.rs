@@ -0,0 +1,189 @@
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+
+use crate::db::DbConn;
+use crate::model::redaction_rule::{NewRedactionRule, RedactionRule};
+use crate::model::user::User;
+use crate::request::logger::RequestLogger;
+use crate::request::redaction_rule::RedactionRule as RequestData;
+use crate::response::{Error, Response};
+use crate::route::find_namespace;
+use crate::validation::redaction_rule::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/redaction/<namespace_key>/rule/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/redaction/<namespace_key>/rule/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/redaction/<namespace_key>/rule/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+}
+
+fn format_redaction_rule(rule: &RedactionRule) -> JsonValue {
+    json!({
+        "uuid": rule.uuid.to_string(),
+        "detector": rule.detector,
+        "name": rule.name,
+        "pattern": rule.pattern,
+        "created_at": rule.created_at,
+    })
+}
+
+#[get("/redaction/<namespace_key>/rule/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_key: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, false, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let data = match RedactionRule::find_all_by_namespace_id(
+        namespace.id,
+        &conn,
+        &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found namespace.id {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|r| json!({ "redaction_rule": format_redaction_rule(r) }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Enables a detector on the namespace. Messages saved after that are
+// redacted by it.
+#[post(
+    "/redaction/<namespace_key>/rule/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_key: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    // only owners can manage the rules
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => {
+                return res.error(Error::new(status).message(
+                    "You are not allowed to manage the redaction rules.",
+                ));
+            },
+            Ok(n) => n,
+        };
+
+    let v = Validator::new(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    let r = NewRedactionRule {
+        namespace_id: namespace.id,
+
+        ..NewRedactionRule::from(data.0.clone())
+    };
+    match RedactionRule::insert(&r, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(rule) => {
+            info!(logger, "redaction_rule: {}", rule.uuid);
+            res.format(json!({
+                "redaction_rule": format_redaction_rule(&rule),
+            }))
+        },
+    }
+}
+
+#[patch("/redaction/<namespace_key>/rule/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let rule = match RedactionRule::find_by_uuid(
+        &uuid,
+        namespace.id,
+        &conn,
+        &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(r) => r,
+    };
+    match rule.delete(&conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(_) => res,
+    }
+}
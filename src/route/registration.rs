@@ -1,27 +1,31 @@
-use chrono::{Duration, Utc};
 use diesel::result::Error;
 use fourche::queue::Queue;
 use redis::{Commands, RedisError};
 use rocket::State;
-use rocket::http::{Cookies, Status};
+use rocket::http::{Cookie, Cookies, Status};
 use rocket_contrib::json::Json;
-use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
-use crate::job::{Job, JobKind};
-use crate::model::token::{VerificationClaims, Claims, TokenData};
+use crate::job::{Job, Payload, UserActivationEmail, UserDeregistrationEmail};
+use crate::model::Authenticatable;
+use crate::model::access_token::AccessToken;
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
 use crate::model::namespace::{Namespace, NewNamespace};
 use crate::model::membership::{Membership, MembershipRole, NewMembership};
 use crate::model::stream::{Stream, NewStream};
 use crate::model::user::{NewUser, User};
 use crate::model::user_email::{NewUserEmail, UserEmail};
+use crate::model::user_recovery_code::UserRecoveryCode;
+use crate::model::user_totp::UserTotp;
 use crate::mq::MqConn;
-use crate::response::Response;
+use crate::request::logger::RequestLogger;
+use crate::response::{Error as ResponseError, ErrorCode, Response};
+use crate::request::user::deregistration::UserDeregistration;
 use crate::request::user::registration::UserRegistration;
 use crate::validation::user::Validator;
+use crate::service::token_issuer::{IssuedToken, TokenIssuer, TokenPurpose};
 use crate::ss::SsConn;
-use crate::util::split_token;
 
 pub mod preflight {
     use rocket::State;
@@ -46,9 +50,9 @@ pub mod preignition {
     use redis::{Commands, RedisError};
     use rocket::State;
     use rocket::http::{Cookie, Cookies, SameSite, Status};
-    use rocket_slog::SyncLogger;
 
     use crate::config::Config;
+    use crate::request::logger::RequestLogger;
     use crate::response::Response;
     use crate::ss::SsConn;
     use crate::util::generate_random_hash;
@@ -57,7 +61,7 @@ pub mod preignition {
     pub fn register<'a>(
         config: State<Config>,
         mut cookies: Cookies,
-        logger: SyncLogger,
+        logger: RequestLogger,
         mut ss_conn: SsConn,
     ) -> Response<'a> {
         // returns CSRF token
@@ -99,7 +103,7 @@ pub fn register<'a>(
     db_conn: DbConn,
     mut mq_conn: MqConn,
     mut ss_conn: SsConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
     config: State<Config>,
 ) -> Response<'a> {
     // FIXME: create `account_registrar` service
@@ -108,9 +112,11 @@ pub fn register<'a>(
     let cookie = cookies.get_private("csrf_token").ok_or("");
     if cookie.is_err() {
         info!(logger, "error: missing csrf_token");
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token is required."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token is required."),
+        );
     }
     let key = cookie.ok().unwrap().value().to_string();
     let result: Result<i64, RedisError> = ss_conn.get(&key).map_err(|e| {
@@ -118,32 +124,25 @@ pub fn register<'a>(
         e
     });
     if result.is_err() {
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token has been expired. Reload the page."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token has been expired. Reload the page."),
+        );
     }
 
     let v = Validator::new(&db_conn, &data, &logger);
     match v.validate() {
-        Err(errors) => {
-            res.status(Status::UnprocessableEntity).format(json!({
-                "errors": errors,
-            }))
-        },
+        Err(errors) => res.error(ResponseError::invalid(errors)),
         Ok(_) => {
-            // TODO:
-            // impl service object handles token generation/activation.
-            // see also login
-            let now = Utc::now();
-            let granted_at = now.timestamp();
-            let expires_at = (now + Duration::hours(1)).timestamp();
-
-            let result: Result<(i64, String), Error> = db_conn
+            let issuer = TokenIssuer::new(&config, &logger);
+
+            let result: Result<(i64, IssuedToken), Error> = db_conn
                 .build_transaction()
                 .serializable()
                 .deferrable()
                 .read_write()
-                .run::<(i64, String), diesel::result::Error, _>(|| {
+                .run::<(i64, IssuedToken), diesel::result::Error, _>(|| {
                     let mut u = NewUser::from(&data.0);
                     u.set_password(&data.password);
                     let user = User::insert(&u, &db_conn, &logger).unwrap();
@@ -173,83 +172,87 @@ pub fn register<'a>(
                             user_id: user.id,
                             role: MembershipRole::PrimaryOwner,
                         };
-                        let _ =
+                        let membership =
                             Membership::insert(&m, &db_conn, &logger).unwrap();
-                    }
 
-                    let data = TokenData {
-                        value: UserEmail::generate_token(),
-                        granted_at,
-                        expires_at,
-                    };
-                    let raw_token = VerificationClaims::encode(
-                        data,
-                        &config.verification_token_issuer,
-                        &config.verification_token_key_id,
-                        &config.verification_token_secret,
-                    );
-
-                    if let Err(e) = user_email
-                        .grant_token::<VerificationClaims>(
-                            &raw_token,
-                            &config.verification_token_issuer,
-                            &config.verification_token_secret,
-                            &db_conn,
-                            &logger,
-                        )
-                    {
-                        error!(logger, "error: {}", e);
-                        return Err(Error::RollbackTransaction);
+                        let events = vec![
+                            NewAuditEvent {
+                                namespace_id: Some(namespace.id),
+                                action: AuditEventAction::NamespaceCreation,
+                                target: Some(namespace.uuid.to_string()),
+
+                                ..NewAuditEvent::from(&user)
+                            },
+                            NewAuditEvent {
+                                namespace_id: Some(namespace.id),
+                                action: AuditEventAction::MembershipCreation,
+                                target: Some(membership.role.to_string()),
+
+                                ..NewAuditEvent::from(&user)
+                            },
+                        ];
+                        for e in events {
+                            AuditEvent::insert(&e, &db_conn, &logger)
+                                .ok_or(Error::RollbackTransaction)?;
+                        }
                     }
-                    Ok((user_email.id, raw_token))
-                });
 
-            if let Ok((id, raw_token)) = result {
-                if let Some((token, sign)) = split_token(raw_token) {
-                    // TODO: use general value
-                    let session_id = UserEmail::generate_token();
-                    let key = format!("ua-{}", session_id);
-
-                    // Instead of saving the signature into a cookie,
-                    // putting it in session store.
-                    //
-                    // Because we need to make it available users to activate
-                    // the account also via another device than signed up, so
-                    // we can't rely on a cookie of http client (browser).
-                    let result: Result<String, RedisError> = ss_conn
-                        .set_ex(&key, sign, expires_at as usize)
-                        .map_err(|e| {
+                    let issued =
+                        issuer.grant(&user_email, &db_conn).map_err(|e| {
                             error!(logger, "error: {}", e);
-                            e
-                        });
+                            Error::RollbackTransaction
+                        })?;
+                    Ok((user_email.id, issued))
+                });
 
-                    if result.is_ok() {
-                        let job = Job::<String> {
-                            kind: JobKind::SendUserActivationEmail,
-                            args: vec![id.to_string(), session_id, token],
-                        };
-                        let mut queue = Queue::new("default", &mut *mq_conn);
-                        if let Err(err) = queue.enqueue::<Job<String>>(job) {
-                            error!(logger, "error: {}", err);
-                        } else {
-                            return res;
-                        }
+            if let Ok((id, issued)) = result {
+                let result = issuer.store(
+                    issued,
+                    TokenPurpose::UserActivation,
+                    &mut *ss_conn,
+                );
+                if let Ok((session_id, token)) = result {
+                    let job = Job::new(Payload::SendUserActivationEmail(
+                        UserActivationEmail {
+                            user_email_id: id,
+                            session_id,
+                            token,
+                        },
+                    ))
+                    .with_request_id(logger.request_id());
+                    let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+                    if let Err(err) = queue.enqueue::<Job>(job) {
+                        error!(logger, "error: {}", err);
+                    } else {
+                        return res;
                     }
                 }
             }
-            res.status(Status::InternalServerError).format(json!({
-                "message": "Something wrong happen, sorry :'("
-            }))
+            res.error(
+                ResponseError::new(Status::InternalServerError)
+                    .message("Something wrong happen, sorry :'("),
+            )
         },
     }
 }
 
-#[post("/deregister", format = "json", rank = 1)]
+// deregister
+//
+// * Check the password
+// * Refuse if the user is a primary owner of namespaces shared with others
+// * Revoke access tokens and memberships, and archive owned namespaces
+// * Anonymize the user (the row is kept), and delete emails (and clear them in
+//   audit events)
+#[post("/deregister", data = "<data>", format = "json", rank = 1)]
 pub fn deregister<'a>(
-    mut cookies: Cookies,
+    data: Json<UserDeregistration>,
+    // the user guard reads cookies, so it must come before `Cookies`
     user: &User,
+    mut cookies: Cookies,
+    db_conn: DbConn,
+    mut mq_conn: MqConn,
     mut ss_conn: SsConn,
-    logger: SyncLogger,
+    logger: RequestLogger,
 ) -> Response<'a> {
     let res: Response = Default::default();
 
@@ -258,9 +261,11 @@ pub fn deregister<'a>(
     let cookie = cookies.get_private("csrf_token").ok_or("");
     if cookie.is_err() {
         info!(logger, "error: missing csrf_token");
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token is required."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token is required."),
+        );
     }
     let key = cookie.ok().unwrap().value().to_string();
     let result: Result<i64, RedisError> = ss_conn.get(&key).map_err(|e| {
@@ -268,11 +273,110 @@ pub fn deregister<'a>(
         e
     });
     if result.is_err() {
-        return res.status(Status::Unauthorized).format(json!({
-            "message": "The CSRF token has been expired. Reload the page."
-        }));
+        return res.error(
+            ResponseError::new(Status::Unauthorized)
+                .code(ErrorCode::InvalidCsrfToken)
+                .message("The CSRF token has been expired. Reload the page."),
+        );
     }
 
-    // TODO
-    res.status(Status::UnprocessableEntity)
+    let mut status = Status::InternalServerError;
+    let mut shared: Vec<Namespace> = vec![];
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), Error, _>(|| {
+            if !user.verify_password(&data.password) {
+                status = Status::Forbidden;
+                return Err(Error::RollbackTransaction);
+            }
+
+            shared = Namespace::find_all_shared_by_primary_owner(
+                &user, &db_conn, &logger,
+            )
+            .ok_or(Error::RollbackTransaction)?;
+            if !shared.is_empty() {
+                status = Status::Conflict;
+                return Err(Error::RollbackTransaction);
+            }
+
+            Namespace::archive_all_by_primary_owner(&user, &db_conn, &logger)
+                .and_then(|_| {
+                    Membership::revoke_all_by_user(&user, &db_conn, &logger)
+                })
+                .and_then(|_| {
+                    AccessToken::revoke_all_by_user(&user, &db_conn, &logger)
+                })
+                .and_then(|_| {
+                    UserTotp::delete_by_user(&user, &db_conn, &logger)
+                })
+                .and_then(|_| {
+                    UserRecoveryCode::delete_by_user(&user, &db_conn, &logger)
+                })
+                .and_then(|_| {
+                    UserEmail::delete_all_by_user(&user, &db_conn, &logger)
+                })
+                .and_then(|_| {
+                    AuditEvent::anonymize_email_targets_by_user(
+                        &user, &db_conn, &logger,
+                    )
+                })
+                .and_then(|_| user.deregister(&db_conn, &logger))
+                .map_err(|_| Error::RollbackTransaction)?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::AccountDeregistration,
+
+                ..NewAuditEvent::from(user)
+            };
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            Ok(())
+        });
+
+    match result {
+        Ok(_) => {
+            // the user has already been anonymized, but `user` still holds
+            // the email and name loaded at the beginning of this request
+            let job = Job::new(Payload::SendUserDeregistrationEmail(
+                UserDeregistrationEmail {
+                    email: user.email.to_string(),
+                    name: user.name.clone().unwrap_or_else(|| "".to_string()),
+                },
+            ))
+            .with_request_id(logger.request_id());
+            let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+            if let Err(err) = queue.enqueue::<Job>(job) {
+                error!(logger, "error: {}", err);
+            }
+
+            // TODO: remove_private
+            cookies.remove(Cookie::named("sign"));
+
+            res.status(Status::Ok)
+        },
+        Err(_) if status == Status::Forbidden => {
+            res.error(
+                ResponseError::new(status)
+                    .code(ErrorCode::InvalidCredentials)
+                    .message("The credentials you've entered are incorrect."),
+            )
+        },
+        Err(_) if status == Status::Conflict => {
+            let uuids: Vec<String> =
+                shared.iter().map(|n| n.uuid.to_string()).collect();
+            res.error(
+                ResponseError::new(status)
+                    .message(
+                        "Transfer or leave the namespaces shared with other \
+                         members before deleting your account.",
+                    )
+                    .details(json!({ "namespaces": uuids })),
+            )
+        },
+        Err(_) => res.status(status),
+    }
 }
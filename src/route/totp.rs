@@ -0,0 +1,232 @@
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use serde_json::Value;
+
+use crate::db::DbConn;
+use crate::model::Authenticatable;
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
+use crate::model::user::User;
+use crate::model::user_recovery_code::UserRecoveryCode;
+use crate::model::user_totp::UserTotp;
+use crate::request::logger::RequestLogger;
+use crate::request::totp::{TotpConfirmation, TotpDeactivation};
+use crate::response::{Error as ResponseError, ErrorCode, Response};
+use crate::validation::ValidationError;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/totp/append", rank = 2)]
+    pub fn append<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PUT", &config)
+    }
+
+    #[options("/totp/confirm", rank = 2)]
+    pub fn confirm<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/totp/del", rank = 2)]
+    pub fn del<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PATCH", &config)
+    }
+}
+
+// Starts enrollment. The secret must be confirmed with a code before it's
+// required at login.
+#[put("/totp/append", rank = 1)]
+pub fn append<'a>(
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let mut conflict = false;
+    let result: Result<UserTotp, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<UserTotp, Error, _>(|| {
+            match UserTotp::find_by_user(&user, &conn, &logger) {
+                Some(ref t) if t.is_confirmed() => {
+                    conflict = true;
+                    Err(Error::RollbackTransaction)
+                },
+                _ => {
+                    UserTotp::insert(&user, &conn, &logger)
+                        .ok_or(Error::RollbackTransaction)
+                },
+            }
+        });
+
+    match result {
+        Ok(t) => {
+            res.format(json!({
+                "totp": {
+                    "secret": t.encoded_secret(),
+                    "provisioning_uri": t.provisioning_uri(&user.email),
+                    "confirmed_at": Value::Null,
+                }
+            }))
+        },
+        Err(_) if conflict => {
+            res.error(
+                ResponseError::new(Status::Conflict).message(
+                    "The two-factor authentication is already enabled.",
+                ),
+            )
+        },
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Confirms enrollment with a code, and returns recovery codes (only once).
+#[patch("/totp/confirm", data = "<data>", format = "json", rank = 1)]
+pub fn confirm<'a>(
+    user: &User,
+    data: Json<TotpConfirmation>,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let mut status = Status::InternalServerError;
+    let result: Result<Vec<String>, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Vec<String>, Error, _>(|| {
+            let t = match UserTotp::find_by_user(&user, &conn, &logger) {
+                None => {
+                    status = Status::NotFound;
+                    return Err(Error::RollbackTransaction);
+                },
+                Some(ref t) if t.is_confirmed() => {
+                    status = Status::Conflict;
+                    return Err(Error::RollbackTransaction);
+                },
+                Some(t) => t,
+            };
+
+            if !t.verify(&data.code, &conn, &logger) {
+                status = Status::UnprocessableEntity;
+                return Err(Error::RollbackTransaction);
+            }
+            t.confirm(&conn, &logger)
+                .map_err(|_| Error::RollbackTransaction)?;
+
+            let codes = UserRecoveryCode::generate_for(&user, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::TotpActivation,
+
+                ..NewAuditEvent::from(user)
+            };
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            Ok(codes)
+        });
+
+    match result {
+        Ok(codes) => {
+            res.format(json!({
+                "totp": {
+                    "recovery_codes": codes,
+                }
+            }))
+        },
+        Err(_) if status == Status::UnprocessableEntity => {
+            res.error(ResponseError::invalid(vec![ValidationError {
+                field: "code".to_string(),
+                messages: vec!["The code is incorrect.".to_string()],
+            }]))
+        },
+        Err(_) => res.status(status),
+    }
+}
+
+// Disables TOTP. Both the password and a code (or a recovery code) are
+// required.
+#[patch("/totp/del", data = "<data>", format = "json", rank = 1)]
+pub fn del<'a>(
+    user: &User,
+    data: Json<TotpDeactivation>,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let mut status = Status::InternalServerError;
+    let result: Result<(), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), Error, _>(|| {
+            let t = match UserTotp::find_by_user(&user, &conn, &logger) {
+                Some(t) if t.is_confirmed() => t,
+                _ => {
+                    status = Status::NotFound;
+                    return Err(Error::RollbackTransaction);
+                },
+            };
+
+            if !user.verify_password(&data.password) ||
+                !(t.verify(&data.code, &conn, &logger) ||
+                    UserRecoveryCode::consume(
+                        &user, &data.code, &conn, &logger,
+                    ))
+            {
+                status = Status::Forbidden;
+                return Err(Error::RollbackTransaction);
+            }
+
+            UserTotp::delete_by_user(&user, &conn, &logger)
+                .and_then(|_| {
+                    UserRecoveryCode::delete_by_user(&user, &conn, &logger)
+                })
+                .map_err(|_| Error::RollbackTransaction)?;
+
+            let e = NewAuditEvent {
+                action: AuditEventAction::TotpDeactivation,
+
+                ..NewAuditEvent::from(user)
+            };
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            Ok(())
+        });
+
+    match result {
+        Ok(_) => {
+            res.format(json!({
+                "totp": 1,
+            }))
+        },
+        Err(_) if status == Status::Forbidden => {
+            res.error(
+                ResponseError::new(status)
+                    .code(ErrorCode::InvalidCredentials)
+                    .message("The credentials you've entered are incorrect."),
+            )
+        },
+        Err(_) => res.status(status),
+    }
+}
@@ -0,0 +1,424 @@
+use chrono::Utc;
+use fourche::queue::Queue;
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::job::{Job, Payload, WebhookEvent};
+use crate::model::stream::Stream;
+use crate::model::webhook::{NewWebhook, Webhook};
+use crate::model::webhook_delivery::WebhookDelivery;
+use crate::model::user::User;
+use crate::mq::MqConn;
+use crate::request::logger::RequestLogger;
+use crate::request::webhook::{Webhook as RequestData, WebhookState as StateData};
+use crate::response::{Error, Response};
+use crate::route::{find_namespace, offset_and_limit};
+use crate::validation::webhook::Validator;
+use crate::webhook::PING_EVENT;
+
+// the number of deliveries returned per request at most
+const DELIVERIES_LIMIT: i64 = 100;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::request::logger::RequestLogger;
+    use crate::response::no_content_for;
+
+    #[options("/webhook/<namespace_key>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/webhook/<namespace_key>/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_key: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}", namespace_key);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/webhook/<namespace_key>/hset/<uuid>/state", rank = 2)]
+    pub fn hset_state<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/webhook/<namespace_key>/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/webhook/<namespace_key>/ping/<uuid>", rank = 2)]
+    pub fn ping<'a>(
+        namespace_key: String,
+        uuid: String,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace_key: {}, uuid: {}", namespace_key, uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options(
+        "/webhook/<namespace_key>/lrange/<uuid>/<start>/<stop>",
+        rank = 2
+    )]
+    pub fn lrange<'a>(
+        namespace_key: String,
+        uuid: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: RequestLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace_key: {}, uuid: {}, start: {}, stop: {}",
+            namespace_key,
+            uuid,
+            start,
+            stop,
+        );
+        no_content_for("GET", &config)
+    }
+}
+
+// The secret is not included (it's returned only at the creation)
+fn format_webhook(webhook: &Webhook, stream_uuid: Option<Uuid>) -> JsonValue {
+    json!({
+        "uuid": webhook.uuid.to_string(),
+        "stream": stream_uuid.map(|u| u.to_string()),
+        "url": webhook.url,
+        "level": webhook.level.as_ref().map(|l| l.to_string()),
+        "code": webhook.code,
+        "enabled": webhook.enabled,
+        "created_at": webhook.created_at,
+    })
+}
+
+fn format_delivery(delivery: &WebhookDelivery) -> JsonValue {
+    json!({
+        "uuid": delivery.uuid.to_string(),
+        "event": delivery.event,
+        "attempt": delivery.attempt,
+        "status_code": delivery.status_code,
+        "error": delivery.error,
+        "succeeded": delivery.succeeded,
+        "created_at": delivery.created_at,
+    })
+}
+
+// All the actions on webhooks are allowed only to owners, because they can
+// send messages in the namespace to anywhere.
+
+#[get("/webhook/<namespace_key>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_key: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let data =
+        match Webhook::find_all_by_namespace_id(namespace.id, &conn, &logger) {
+            None => {
+                error!(
+                    logger,
+                    "err: not found namespace.id {}", namespace.uuid
+                );
+                vec![]
+            },
+            Some(a) => {
+                a.iter()
+                    .map(|w| {
+                        let stream_uuid = w.stream_id.and_then(|id| {
+                            Stream::find_by_id(id, &conn, &logger)
+                                .map(|s| s.uuid)
+                        });
+                        json!({ "webhook": format_webhook(w, stream_uuid) })
+                    })
+                    .collect()
+            },
+        };
+    res.format(json!(data))
+}
+
+#[post(
+    "/webhook/<namespace_key>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_key: String,
+    data: Json<RequestData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}", user.uuid, namespace_key
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => {
+                return res
+                    .error(Error::new(status).message(
+                        "You are not allowed to manage the webhooks.",
+                    ));
+            },
+            Ok(n) => n,
+        };
+
+    let v = Validator::new(&conn, &data, namespace.id, &logger);
+    if let Err(errors) = v.validate() {
+        return res.error(Error::invalid(errors));
+    }
+
+    // the stream (if any) has been validated
+    let stream = data
+        .stream
+        .as_ref()
+        .and_then(|uuid| Stream::find_by_uuid(uuid, &conn, &logger));
+    let w = NewWebhook {
+        namespace_id: namespace.id,
+        stream_id: stream.as_ref().map(|s| s.id),
+        secret: Webhook::generate_secret(),
+
+        ..NewWebhook::from(data.0.clone())
+    };
+    match Webhook::insert(&w, &conn, &logger) {
+        None => res.status(Status::InternalServerError),
+        Some(webhook) => {
+            info!(logger, "webhook: {}", webhook.uuid);
+            let mut value = format_webhook(&webhook, stream.map(|s| s.uuid));
+            value["secret"] = json!(webhook.secret).into();
+            res.format(json!({ "webhook": value }))
+        },
+    }
+}
+
+#[patch(
+    "/webhook/<namespace_key>/hset/<uuid>/state",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset_state<'a>(
+    namespace_key: String,
+    uuid: String,
+    data: Json<StateData>,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let webhook =
+        match Webhook::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(w) => w,
+        };
+
+    let now = Utc::now().naive_utc();
+    match webhook.set_enabled(data.enabled, now, &conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(w) => {
+            res.format(json!({
+                "webhook": {
+                    "uuid": w.uuid.to_string(),
+                    "enabled": w.enabled,
+                }
+            }))
+        },
+    }
+}
+
+#[patch("/webhook/<namespace_key>/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let webhook =
+        match Webhook::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(w) => w,
+        };
+    match webhook.delete(&conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(_) => res,
+    }
+}
+
+// Sends a test event through the queue. The result can be seen in the
+// delivery log.
+#[post("/webhook/<namespace_key>/ping/<uuid>", rank = 1)]
+pub fn ping<'a>(
+    namespace_key: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}", user.uuid, namespace_key, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let webhook =
+        match Webhook::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(w) => w,
+        };
+
+    let delivery_id = Uuid::new_v4().to_string();
+    let body = json!({
+        "event": PING_EVENT,
+        "webhook": webhook.uuid.to_string(),
+        "namespace": namespace.uuid.to_string(),
+    });
+    let job = Job::new(Payload::DeliverWebhook(WebhookEvent {
+        webhook_id: webhook.id,
+        delivery_id: delivery_id.clone(),
+        event: PING_EVENT.to_string(),
+        body: body.to_string(),
+    }))
+    .with_request_id(logger.request_id());
+    let mut queue = Queue::new(job.queue(), &mut *mq_conn);
+    if let Err(err) = queue.enqueue::<Job>(job) {
+        error!(logger, "error: {}", err);
+        return res.status(Status::InternalServerError);
+    }
+    res.status(Status::Accepted).format(json!({
+        "delivery": {
+            "uuid": delivery_id,
+            "event": PING_EVENT,
+        }
+    }))
+}
+
+#[get("/webhook/<namespace_key>/lrange/<uuid>/<start>/<stop>", rank = 1)]
+pub fn lrange<'a>(
+    namespace_key: String,
+    uuid: String,
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: RequestLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace_key: {}, uuid: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_key,
+        uuid,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match find_namespace(&namespace_key, true, user, &conn, &logger) {
+            Err(status) => return res.status(status),
+            Ok(n) => n,
+        };
+
+    let webhook =
+        match Webhook::find_by_uuid(&uuid, namespace.id, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(w) => w,
+        };
+
+    let (offset, limit) = offset_and_limit(start, stop, DELIVERIES_LIMIT);
+    let data = match WebhookDelivery::find_all_by_webhook(
+        &webhook, offset, limit, &conn, &logger,
+    ) {
+        None => {
+            error!(logger, "err: not found webhook.id {}", webhook.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|d| json!({ "delivery": format_delivery(d) }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
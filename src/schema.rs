@@ -46,6 +46,21 @@ table! {
         content -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        redactions_count -> Integer,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    use crate::model::message::ELogLevel;
+
+    message_counts (stream_id, bucket_at, level, code) {
+        stream_id -> Int8,
+        bucket_at -> Timestamp,
+        level -> ELogLevel,
+        code -> Varchar,
+        count -> Int8,
     }
 }
 
@@ -130,17 +145,307 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+
+    use crate::model::access_token::EAccessTokenState;
+
+    access_token_state_histories (id) {
+        id -> Int8,
+        access_token_id -> Int8,
+        user_id -> Int8,
+        from_state -> EAccessTokenState,
+        to_state -> EAccessTokenState,
+        revoked -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    use crate::model::audit_event::EAuditEventAction;
+
+    audit_events (id) {
+        id -> Int8,
+        user_id -> Nullable<Int8>,
+        namespace_id -> Nullable<Int8>,
+        action -> EAuditEventAction,
+        target -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    user_totps (id) {
+        id -> Int8,
+        user_id -> Int8,
+        secret -> Bytea,
+        last_used_step -> Int8,
+        confirmed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    user_recovery_codes (id) {
+        id -> Int8,
+        user_id -> Int8,
+        code -> Bytea,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::alert_rule::{EAlertChannel, EAlertCondition};
+    use crate::model::message::ELogLevel;
+
+    alert_rules (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        stream_id -> Int8,
+        name -> Varchar,
+        condition -> EAlertCondition,
+        level -> Nullable<ELogLevel>,
+        code -> Nullable<Varchar>,
+        threshold -> Integer,
+        window_seconds -> Integer,
+        channel -> EAlertChannel,
+        target -> Varchar,
+        muted_until -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::alert::EAlertState;
+
+    alerts (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        alert_rule_id -> Int8,
+        state -> EAlertState,
+        occurrences -> Integer,
+        notified_at -> Nullable<Timestamp>,
+        acknowledged_by -> Nullable<Int8>,
+        acknowledged_at -> Nullable<Timestamp>,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::message::ELogLevel;
+
+    webhooks (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        namespace_id -> Int8,
+        stream_id -> Nullable<Int8>,
+        url -> Varchar,
+        secret -> Varchar,
+        level -> Nullable<ELogLevel>,
+        code -> Nullable<Varchar>,
+        enabled -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    webhook_deliveries (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        webhook_id -> Int8,
+        event -> Varchar,
+        attempt -> Integer,
+        status_code -> Nullable<Integer>,
+        error -> Nullable<Text>,
+        succeeded -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::issue::EIssueState;
+    use crate::model::message::ELogLevel;
+
+    issues (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        stream_id -> Int8,
+        fingerprint -> Varchar,
+        level -> ELogLevel,
+        code -> Nullable<Varchar>,
+        title -> Varchar,
+        state -> EIssueState,
+        count -> Int8,
+        first_seen_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    message_deletions (id) {
+        id -> Int8,
+        stream_id -> Int8,
+        message_id -> Int8,
+        user_id -> Int8,
+        title -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::redaction_rule::ERedactionDetector;
+
+    redaction_rules (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        namespace_id -> Int8,
+        detector -> ERedactionDetector,
+        name -> Varchar,
+        pattern -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    annotations (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        message_id -> Int8,
+        user_id -> Int8,
+        label -> Nullable<Varchar>,
+        body -> Nullable<Text>,
+        ticket_url -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    message_views (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        namespace_id -> Int8,
+        user_id -> Int8,
+        stream_id -> Nullable<Int8>,
+        name -> Varchar,
+        shared -> Bool,
+        levels -> Array<Text>,
+        code -> Nullable<Varchar>,
+        lang -> Nullable<Varchar>,
+        time_window -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 joinable!(user_emails -> users (user_id));
+joinable!(user_totps -> users (user_id));
+joinable!(user_recovery_codes -> users (user_id));
 joinable!(streams -> namespaces (namespace_id));
 joinable!(messages -> streams (stream_id));
+joinable!(message_counts -> streams (stream_id));
+joinable!(message_deletions -> streams (stream_id));
+joinable!(message_deletions -> users (user_id));
 joinable!(memberships -> namespaces (namespace_id));
 joinable!(memberships -> users (user_id));
+joinable!(access_token_state_histories -> access_tokens (access_token_id));
+joinable!(access_token_state_histories -> users (user_id));
+joinable!(audit_events -> users (user_id));
+joinable!(audit_events -> namespaces (namespace_id));
+joinable!(alert_rules -> streams (stream_id));
+joinable!(alerts -> alert_rules (alert_rule_id));
+joinable!(alerts -> users (acknowledged_by));
+joinable!(webhooks -> namespaces (namespace_id));
+joinable!(webhooks -> streams (stream_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
+joinable!(issues -> streams (stream_id));
+joinable!(redaction_rules -> namespaces (namespace_id));
+joinable!(annotations -> messages (message_id));
+joinable!(annotations -> users (user_id));
+joinable!(message_views -> namespaces (namespace_id));
+joinable!(message_views -> streams (stream_id));
+joinable!(message_views -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(users, access_tokens);
+allow_tables_to_appear_in_same_query!(users, access_token_state_histories);
+allow_tables_to_appear_in_same_query!(users, audit_events);
 allow_tables_to_appear_in_same_query!(users, memberships);
+allow_tables_to_appear_in_same_query!(users, message_deletions);
 allow_tables_to_appear_in_same_query!(users, user_emails);
+allow_tables_to_appear_in_same_query!(users, user_recovery_codes);
+allow_tables_to_appear_in_same_query!(users, user_totps);
 
+allow_tables_to_appear_in_same_query!(namespaces, audit_events);
 allow_tables_to_appear_in_same_query!(namespaces, memberships);
 allow_tables_to_appear_in_same_query!(namespaces, streams);
 
 allow_tables_to_appear_in_same_query!(streams, messages);
+allow_tables_to_appear_in_same_query!(streams, message_counts);
+allow_tables_to_appear_in_same_query!(streams, message_deletions);
+allow_tables_to_appear_in_same_query!(streams, alert_rules);
+allow_tables_to_appear_in_same_query!(streams, alerts);
+
+allow_tables_to_appear_in_same_query!(alert_rules, alerts);
+
+allow_tables_to_appear_in_same_query!(namespaces, webhooks);
+allow_tables_to_appear_in_same_query!(streams, webhooks);
+allow_tables_to_appear_in_same_query!(webhooks, webhook_deliveries);
+
+allow_tables_to_appear_in_same_query!(streams, issues);
+
+allow_tables_to_appear_in_same_query!(namespaces, redaction_rules);
+
+allow_tables_to_appear_in_same_query!(annotations, messages);
+allow_tables_to_appear_in_same_query!(annotations, streams);
+allow_tables_to_appear_in_same_query!(annotations, users);
+
+allow_tables_to_appear_in_same_query!(message_views, namespaces);
+allow_tables_to_appear_in_same_query!(message_views, streams);
+allow_tables_to_appear_in_same_query!(message_views, users);
+
+allow_tables_to_appear_in_same_query!(
+    access_tokens,
+    access_token_state_histories
+);
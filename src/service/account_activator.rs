@@ -1,10 +1,10 @@
 use std::fmt;
 
-use rocket_slog::SyncLogger;
-
 use crate::config::Config;
 use crate::db::DbConn;
+use crate::logger::Logger;
 use crate::model::{Activatable, Verifiable};
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
 
 pub struct AccountActivator<'a, T, U>
 where
@@ -13,7 +13,7 @@ where
 {
     db_conn: &'a DbConn,
     config: &'a Config,
-    logger: &'a SyncLogger,
+    logger: &'a Logger,
     pub target: Option<(T, U)>,
 }
 
@@ -21,11 +21,12 @@ impl<'a, T, U> AccountActivator<'a, T, U>
 where
     T: Activatable + Clone + Verifiable<(T, U)> + fmt::Display,
     U: Activatable + Clone + fmt::Display,
+    for<'b> NewAuditEvent: From<&'b T>,
 {
     pub fn new(
         db_conn: &'a DbConn,
         config: &'a Config,
-        logger: &'a SyncLogger,
+        logger: &'a Logger,
     ) -> Self {
         Self {
             db_conn,
@@ -53,6 +54,7 @@ where
         Ok(self)
     }
 
+    /// Activates the target, and records it as an audit event.
     pub fn activate(&self) -> Result<(), &str> {
         if let Some(user) = self.target.as_ref().map(|v| v.0.clone()) {
             return user
@@ -62,6 +64,22 @@ where
                         self.logger,
                         "the user ({}) has been activated", &user
                     );
+
+                    // the activation has already been committed in its own
+                    // transaction
+                    let e = NewAuditEvent {
+                        action: AuditEventAction::AccountActivation,
+
+                        ..NewAuditEvent::from(&user)
+                    };
+                    if AuditEvent::insert(&e, &self.db_conn, &self.logger)
+                        .is_none()
+                    {
+                        error!(
+                            self.logger,
+                            "err: failed to record {}", e.action
+                        );
+                    }
                 })
                 .map_err(|e| {
                     warn!(
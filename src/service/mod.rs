@@ -1,2 +1,3 @@
 pub mod account_activator;
 pub mod password_updater;
+pub mod token_issuer;
@@ -1,27 +1,32 @@
 use std::fmt;
 
-use rocket_slog::SyncLogger;
+use diesel::Connection;
+use diesel::result::Error;
 
 use crate::config::Config;
 use crate::db::DbConn;
+use crate::logger::Logger;
 use crate::model::{Authenticatable, Verifiable};
+use crate::model::audit_event::{AuditEvent, AuditEventAction, NewAuditEvent};
 
 pub struct PasswordUpdater<'a, T>
 where T: Authenticatable + Clone + Verifiable<T> + fmt::Display
 {
     db_conn: &'a DbConn,
     config: &'a Config,
-    logger: &'a SyncLogger,
+    logger: &'a Logger,
     pub target: Option<T>,
 }
 
 impl<'a, T> PasswordUpdater<'a, T>
-where T: Authenticatable + Clone + Verifiable<T> + fmt::Display
+where
+    T: Authenticatable + Clone + Verifiable<T> + fmt::Display,
+    for<'b> NewAuditEvent: From<&'b T>,
 {
     pub fn new(
         db_conn: &'a DbConn,
         config: &'a Config,
-        logger: &'a SyncLogger,
+        logger: &'a Logger,
     ) -> Self {
         Self {
             db_conn,
@@ -49,22 +54,41 @@ where T: Authenticatable + Clone + Verifiable<T> + fmt::Display
         Ok(self)
     }
 
+    /// Updates the password of the target, and records it as an audit event.
+    /// The update is rolled back if the event can't be recorded.
     pub fn update(&self, new_password: &str) -> Result<(), &str> {
         if let Some(mut user) = self.target.clone() {
-            return user
-                .update_password(new_password, self.db_conn, self.logger)
+            return self
+                .db_conn
+                .transaction::<_, Error, _>(|| {
+                    user.update_password(
+                        new_password,
+                        self.db_conn,
+                        self.logger,
+                    )
+                    .map_err(|_| Error::RollbackTransaction)?;
+
+                    let e = NewAuditEvent {
+                        action: AuditEventAction::PasswordReset,
+
+                        ..NewAuditEvent::from(&user)
+                    };
+                    AuditEvent::insert(&e, self.db_conn, self.logger)
+                        .ok_or(Error::RollbackTransaction)?;
+                    Ok(())
+                })
                 .map(|_| {
                     info!(
                         self.logger,
                         "the password of an user ({}) has been re-set", &user
                     );
                 })
-                .map_err(|e| {
+                .map_err(|_| {
                     warn!(
                         self.logger,
                         "the password of an user ({}) couldn't be set", &user
                     );
-                    e
+                    "failed to update password"
                 });
         }
         Err("not found")
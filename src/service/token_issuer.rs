@@ -0,0 +1,107 @@
+use chrono::{Duration, Utc};
+use diesel::PgConnection;
+use redis::{Commands, ConnectionLike};
+
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::model::token::{Claims, TokenData, VerificationClaims};
+use crate::model::user_email::UserEmail;
+use crate::util::split_token;
+
+/// The lifetime of a verification token (hours).
+pub const TOKEN_DURATION: i64 = 1;
+
+/// TokenPurpose tells which link the token is sent for. It prefixes the key
+/// of the signature in session store (see `util::extract_session_key`).
+pub enum TokenPurpose {
+    UserActivation,
+    UserEmailVerification,
+}
+
+impl TokenPurpose {
+    fn prefix(&self) -> &'static str {
+        match self {
+            TokenPurpose::UserActivation => "ua",
+            TokenPurpose::UserEmailVerification => "ev",
+        }
+    }
+}
+
+/// IssuedToken is a token which has been granted to a user email, but its
+/// signature is not stored yet.
+pub struct IssuedToken {
+    raw_token: String,
+    expires_in: usize,
+}
+
+/// TokenIssuer issues verification tokens for user emails. A token is granted
+/// in the transaction which saves the user email, and then its signature is
+/// put in session store (instead of a cookie) so that the link works also on
+/// another device than the one used to sign up.
+pub struct TokenIssuer<'a> {
+    config: &'a Config,
+    logger: &'a Logger,
+}
+
+impl<'a> TokenIssuer<'a> {
+    pub fn new(config: &'a Config, logger: &'a Logger) -> Self {
+        Self { config, logger }
+    }
+
+    /// Grants a new token to the user email.
+    pub fn grant(
+        &self,
+        user_email: &UserEmail,
+        conn: &PgConnection,
+    ) -> Result<IssuedToken, &'static str> {
+        let now = Utc::now();
+        let granted_at = now.timestamp();
+        let expires_at = (now + Duration::hours(TOKEN_DURATION)).timestamp();
+
+        let data = TokenData {
+            value: UserEmail::generate_token(),
+            granted_at,
+            expires_at,
+        };
+        let raw_token = VerificationClaims::encode(
+            data,
+            &self.config.verification_token_issuer,
+            &self.config.verification_token_key_id,
+            &self.config.verification_token_secret,
+        );
+        user_email.grant_token::<VerificationClaims>(
+            &raw_token,
+            &self.config.verification_token_issuer,
+            &self.config.verification_token_secret,
+            conn,
+            self.logger,
+        )?;
+
+        Ok(IssuedToken {
+            raw_token,
+            expires_in: (expires_at - granted_at) as usize,
+        })
+    }
+
+    /// Puts the signature of the token in session store, and returns a new
+    /// session id and the token without the signature (for the link).
+    pub fn store<C: ConnectionLike>(
+        &self,
+        issued: IssuedToken,
+        purpose: TokenPurpose,
+        ss_conn: &mut C,
+    ) -> Result<(String, String), &'static str> {
+        let (token, sign) =
+            split_token(issued.raw_token).ok_or("invalid token")?;
+
+        let session_id = UserEmail::generate_token();
+        let key = format!("{}-{}", purpose.prefix(), session_id);
+        let result: Result<(), _> =
+            ss_conn.set_ex(&key, sign, issued.expires_in);
+        if let Err(e) = result {
+            error!(self.logger, "err: {}", e);
+            return Err("failed to store");
+        }
+        Ok((session_id, token))
+    }
+}
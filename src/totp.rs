@@ -0,0 +1,182 @@
+//! # TOTP
+//!
+//! Time-based one-time passwords (RFC 6238) with HMAC-SHA1, 6 digits and
+//! 30 seconds steps, which are the defaults most authenticator apps expect.
+use hmac::{Hmac, Mac, NewMac};
+use rand::prelude::*;
+use sha1::Sha1;
+
+const DIGITS: u32 = 6;
+const ISSUER: &str = "Eloquentlog";
+const PERIOD: i64 = 30; // seconds
+const SECRET_LENGTH: usize = 20; // 160 bits (recommended in RFC 4226)
+const SKEW: i64 = 1; // steps accepted before and after the current one
+
+/// Generates a new random shared secret.
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// Encodes a secret in Base32 (without padding) for authenticator apps.
+pub fn encode_secret(secret: &[u8]) -> String {
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, secret)
+}
+
+/// Returns an `otpauth://` URI (for a QR code) of the secret for the account.
+pub fn provisioning_uri(secret: &[u8], account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&\
+         algorithm=SHA1&digits={digits}&period={period}",
+        issuer = ISSUER,
+        account = encode_uri_component(account),
+        secret = encode_secret(secret),
+        digits = DIGITS,
+        period = PERIOD,
+    )
+}
+
+/// Generates the code at the step.
+pub fn generate(secret: &[u8], step: i64) -> String {
+    hotp(secret, step as u64, DIGITS)
+}
+
+/// Checks the code against the steps around the timestamp, and returns the
+/// matched step. The caller should reject a step already used once.
+pub fn verify(secret: &[u8], code: &str, timestamp: i64) -> Option<i64> {
+    if code.len() != DIGITS as usize ||
+        !code.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let current = timestamp / PERIOD;
+    (current - SKEW..=current + SKEW).find(|step| {
+        *step >= 0 &&
+            constant_time_eq(
+                generate(secret, *step).as_bytes(),
+                code.as_bytes(),
+            )
+    })
+}
+
+// HOTP (RFC 4226)
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret)
+        .expect("HMAC can take a key of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = (u32::from(hash[offset]) & 0x7f) << 24 |
+        u32::from(hash[offset + 1]) << 16 |
+        u32::from(hash[offset + 2]) << 8 |
+        u32::from(hash[offset + 3]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(digits),
+        width = digits as usize
+    )
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn encode_uri_component(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            match b {
+                b'A'..=b'Z' |
+                b'a'..=b'z' |
+                b'0'..=b'9' |
+                b'-' |
+                b'.' |
+                b'_' |
+                b'~' |
+                b'@' => (b as char).to_string(),
+                _ => format!("%{:02X}", b),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the secret of the test vectors in RFC 6238 (for SHA1)
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_hotp() {
+        // https://tools.ietf.org/html/rfc6238#appendix-B
+        let vectors = [
+            (59, "94287082"),
+            (1_111_111_109, "07081804"),
+            (1_111_111_111, "14050471"),
+            (1_234_567_890, "89005924"),
+            (2_000_000_000, "69279037"),
+            (20_000_000_000, "65353130"),
+        ];
+        for (t, code) in vectors.iter() {
+            assert_eq!(hotp(SECRET, (t / PERIOD) as u64, 8), *code);
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        assert_eq!(verify(SECRET, "287082", 59), Some(1));
+
+        // within skew
+        assert_eq!(verify(SECRET, "287082", 59 + PERIOD), Some(1));
+        assert_eq!(verify(SECRET, "287082", 59 - PERIOD), Some(1));
+
+        // outside of skew
+        assert_eq!(verify(SECRET, "287082", 59 + PERIOD * 2), None);
+
+        // invalid format
+        assert_eq!(verify(SECRET, "28708", 59), None);
+        assert_eq!(verify(SECRET, "2870821", 59), None);
+        assert_eq!(verify(SECRET, "28708a", 59), None);
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert_ne!(secret, generate_secret());
+    }
+
+    #[test]
+    fn test_encode_secret() {
+        assert_eq!(
+            encode_secret(SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string()
+        );
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri(SECRET, "oswald@example.org"),
+            "otpauth://totp/Eloquentlog:oswald@example.org?\
+             secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Eloquentlog&\
+             algorithm=SHA1&digits=6&period=30"
+                .to_string()
+        );
+        assert_eq!(
+            provisioning_uri(SECRET, "o w:ald"),
+            "otpauth://totp/Eloquentlog:o%20w%3Aald?\
+             secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Eloquentlog&\
+             algorithm=SHA1&digits=6&period=30"
+                .to_string()
+        );
+    }
+}
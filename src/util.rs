@@ -68,6 +68,8 @@ pub fn extract_session_key(req: &Request<'_>) -> String {
         (2, "pr")
     } else if s0 == "activate" {
         (1, "ua")
+    } else if s0 == "email" {
+        (2, "ev")
     } else {
         return "".to_string();
     };
@@ -166,5 +168,13 @@ mod test {
         let uri = Origin::parse("/activate/456/789").unwrap();
         req.set_uri(uri);
         assert_eq!(extract_session_key(&req), "ua-456");
+
+        let uri = Origin::parse("/email/verify").unwrap();
+        req.set_uri(uri);
+        assert_eq!(extract_session_key(&req), "");
+
+        let uri = Origin::parse("/email/verify/789").unwrap();
+        req.set_uri(uri);
+        assert_eq!(extract_session_key(&req), "ev-789");
     }
 }
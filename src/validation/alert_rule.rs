@@ -0,0 +1,257 @@
+use std::result::Result;
+
+use accord::validators::{length, length_if_present};
+use diesel::PgConnection;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::alert_rule::{AlertChannel, AlertCondition, NewAlertRule};
+use crate::model::message::LogLevel;
+use crate::model::stream::Stream;
+use crate::request::alert_rule::AlertRule as RequestData;
+use crate::validation::*;
+
+const THRESHOLD_MAX: i32 = 10_000;
+const WINDOW_SECONDS_MAX: i32 = 86_400; // a day
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a Json<RequestData>,
+    namespace_id: i64,
+    logger: &'a Logger,
+}
+
+fn to_strings<T: ToString>(values: Vec<T>) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a Json<RequestData>,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            namespace_id,
+            logger,
+        }
+    }
+
+    // The stream must be in the namespace
+    fn validate_stream(&self) -> Result<(), ValidationError> {
+        let found = self.data.0.stream.as_ref().and_then(|uuid| {
+            Stream::find_by_uuid(uuid, self.conn, self.logger)
+                .filter(|s| s.namespace_id == self.namespace_id)
+        });
+        if found.is_none() {
+            return Err(ValidationError {
+                field: "stream".to_string(),
+                messages: vec!["Not found".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    // An email address or a HTTP(S) URL, by the channel
+    fn validate_target(&self, r: &NewAlertRule) -> Result<(), ValidationError> {
+        let t = &r.target;
+        let valid = match r.channel {
+            AlertChannel::Email => t.contains('@') && t.contains('.'),
+            AlertChannel::Webhook => {
+                t.starts_with("https://") || t.starts_with("http://")
+            },
+        };
+        if !valid {
+            let message = match r.channel {
+                AlertChannel::Email => "Must be an email address",
+                AlertChannel::Webhook => "Must be a HTTP(S) URL",
+            };
+            return Err(ValidationError {
+                field: "target".to_string(),
+                messages: vec![message.to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let d = &self.data.0;
+        let r = NewAlertRule::from(d.clone());
+        let result = rules! {
+            "name" => r.name => [length(3, 128)],
+            "condition" => d.condition => [
+                either_if_present(to_strings(AlertCondition::as_vec()))
+            ],
+            "level" => d.level => [
+                either_if_present(to_strings(LogLevel::as_vec()))
+            ],
+            "code" => r.code => [length_if_present(1, 32)],
+            "threshold" => r.threshold => [within(1, THRESHOLD_MAX)],
+            "window_seconds" => r.window_seconds => [
+                within(1, WINDOW_SECONDS_MAX)
+            ],
+            "channel" => d.channel => [
+                either_if_present(to_strings(AlertChannel::as_vec()))
+            ],
+            "target" => r.target => [length(3, 255)]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if let Err(e) = self.validate_stream() {
+            errors.push(e);
+        }
+        if !errors
+            .iter()
+            .any(|e| e.field == "target" || e.field == "channel")
+        {
+            if let Err(e) = self.validate_target(&r) {
+                errors.push(e);
+            }
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+    use rocket_contrib::json::Json;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::streams;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_validate_stream_in_another_namespace() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                name: Some("errors".to_string()),
+                target: Some("oswald@example.org".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id + 1, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("stream", errors[0].field);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                name: Some("errors".to_string()),
+                condition: Some("sometimes".to_string()),
+                threshold: Some(0),
+                channel: Some("webhook".to_string()),
+                target: Some("oswald@example.org".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["condition", "threshold", "target"], fields);
+                assert_eq!(vec!["Must be a HTTP(S) URL"], errors[2].messages);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                name: Some("errors".to_string()),
+                condition: Some("threshold".to_string()),
+                level: Some("error".to_string()),
+                threshold: Some(5),
+                window_seconds: Some(600),
+                channel: Some("webhook".to_string()),
+                target: Some("https://example.org/hook".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
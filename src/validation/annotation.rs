@@ -0,0 +1,152 @@
+use std::result::Result;
+
+use accord::validators::length_if_present;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::request::annotation::Annotation as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, logger: &'a Logger) -> Self {
+        Self { data, logger }
+    }
+
+    // A HTTP(S) URL if it's given
+    fn validate_ticket_url(&self) -> Result<(), ValidationError> {
+        match &self.data.0.ticket_url {
+            Some(t)
+                if !t.starts_with("https://") && !t.starts_with("http://") =>
+            {
+                Err(ValidationError {
+                    field: "ticket_url".to_string(),
+                    messages: vec!["Must be a HTTP(S) URL".to_string()],
+                })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let d = &self.data.0;
+        let result = rules! {
+            "label" => d.label => [length_if_present(1, 32)],
+            "body" => d.body => [length_if_present(1, 8000)],
+            "ticket_url" => d.ticket_url => [length_if_present(10, 255)]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if !errors.iter().any(|e| e.field == "ticket_url") {
+            if let Err(e) = self.validate_ticket_url() {
+                errors.push(e);
+            }
+        }
+        if d.label.is_none() && d.body.is_none() && d.ticket_url.is_none() {
+            errors.push(ValidationError {
+                field: "body".to_string(),
+                messages: vec!["Must exist if neither label nor ticket_url \
+                                is given"
+                    .to_string()],
+            });
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use dotenv::dotenv;
+
+    use crate::config::Config;
+    use crate::logger::get_logger;
+
+    fn logger() -> Logger {
+        dotenv().ok();
+        get_logger(&Config::from("testing").unwrap())
+    }
+
+    #[test]
+    fn test_validate_empty() {
+        let logger = logger();
+        let data = Json(RequestData {
+            ..Default::default()
+        });
+        let result = Validator::new(&data, &logger).validate();
+
+        if let Err(errors) = &result {
+            assert_eq!(1, errors.len());
+            assert_eq!("body", errors[0].field);
+        } else {
+            panic!("must fail");
+        }
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        let logger = logger();
+        let data = Json(RequestData {
+            label: Some("".to_string()),
+            body: Some("checking the database".to_string()),
+            ticket_url: Some("ftp://example.org/issues/1".to_string()),
+        });
+        let result = Validator::new(&data, &logger).validate();
+
+        if let Err(errors) = &result {
+            let fields: Vec<&str> =
+                errors.iter().map(|e| e.field.as_str()).collect();
+            assert_eq!(vec!["label", "ticket_url"], fields);
+        } else {
+            panic!("must fail");
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let logger = logger();
+        let data = Json(RequestData {
+            label: Some("investigating".to_string()),
+            ticket_url: Some("https://example.org/issues/1".to_string()),
+
+            ..Default::default()
+        });
+        assert!(Validator::new(&data, &logger).validate().is_ok());
+    }
+}
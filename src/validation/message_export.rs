@@ -0,0 +1,230 @@
+use std::result::Result;
+
+use accord::validators::length_if_present;
+use diesel::PgConnection;
+use rocket_contrib::json::Json;
+
+use crate::export::{self, ExportFormat};
+use crate::logger::Logger;
+use crate::model::message::LogLevel;
+use crate::model::stream::Stream;
+use crate::request::message_export::MessageExport as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a Json<RequestData>,
+    namespace_id: i64,
+    logger: &'a Logger,
+}
+
+fn to_strings<T: ToString>(values: Vec<T>) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a Json<RequestData>,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            namespace_id,
+            logger,
+        }
+    }
+
+    // The stream must be in the namespace
+    fn validate_stream(&self) -> Result<(), ValidationError> {
+        let found = self.data.0.stream.as_ref().and_then(|uuid| {
+            Stream::find_by_uuid(uuid, self.conn, self.logger)
+                .filter(|s| s.namespace_id == self.namespace_id)
+        });
+        if found.is_none() {
+            return Err(ValidationError {
+                field: "stream".to_string(),
+                messages: vec!["Not found".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    // Both are required, and since must be before until
+    fn validate_time_range(&self) -> Vec<ValidationError> {
+        let d = &self.data.0;
+        let message = format!("Must be a time in {}", export::TIME_FORMAT);
+
+        let mut errors = vec![];
+        let since = d.since.as_ref().and_then(|s| export::parse_time(s));
+        if since.is_none() {
+            errors.push(ValidationError {
+                field: "since".to_string(),
+                messages: vec![message.clone()],
+            });
+        }
+        let until = d.until.as_ref().and_then(|s| export::parse_time(s));
+        if until.is_none() {
+            errors.push(ValidationError {
+                field: "until".to_string(),
+                messages: vec![message],
+            });
+        }
+        if let (Some(since), Some(until)) = (since, until) {
+            if since >= until {
+                errors.push(ValidationError {
+                    field: "until".to_string(),
+                    messages: vec!["Must be after since".to_string()],
+                });
+            }
+        }
+        errors
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let d = &self.data.0;
+        let result = rules! {
+            "format" => d.format => [
+                either_if_present(to_strings(ExportFormat::as_vec()))
+            ],
+            "level" => d.level => [
+                either_if_present(to_strings(LogLevel::as_vec()))
+            ],
+            "code" => d.code => [length_if_present(1, 32)]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if let Err(e) = self.validate_stream() {
+            errors.push(e);
+        }
+        errors.extend(self.validate_time_range());
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+    use rocket_contrib::json::Json;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::streams;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                format: Some("xml".to_string()),
+                since: Some("2021-01-02T00:00:00".to_string()),
+                until: Some("2021-01-01T00:00:00".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id + 1, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["format", "stream", "until"], fields);
+            } else {
+                panic!("must fail");
+            }
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                since: Some("2021-01-01".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["since", "until"], fields);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                format: Some("csv".to_string()),
+                level: Some("warning".to_string()),
+                code: Some("E1".to_string()),
+                since: Some("2021-01-01T00:00:00".to_string()),
+                until: Some("2021-01-02T00:00:00".to_string()),
+            });
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+        })
+    }
+}
@@ -0,0 +1,260 @@
+use std::result::Result;
+
+use diesel::PgConnection;
+
+use crate::export;
+use crate::logger::Logger;
+use crate::model::message_count::Bucket;
+use crate::model::stream::Stream;
+use crate::request::message_stats::MessageStats as RequestData;
+use crate::validation::*;
+
+/// The max number of buckets in a range.
+pub const MAX_BUCKETS: i64 = 1_440;
+
+/// The max number of codes.
+pub const MAX_TOP: i64 = 100;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a RequestData,
+    namespace_id: i64,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a RequestData,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            namespace_id,
+            logger,
+        }
+    }
+
+    // The stream is optional, but it must be in the namespace if given
+    fn validate_stream(&self) -> Result<(), ValidationError> {
+        let uuid = match &self.data.stream {
+            None => return Ok(()),
+            Some(uuid) => uuid,
+        };
+        let found = Stream::find_by_uuid(uuid, self.conn, self.logger)
+            .filter(|s| s.namespace_id == self.namespace_id);
+        if found.is_none() {
+            return Err(ValidationError {
+                field: "stream".to_string(),
+                messages: vec!["Not found".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_top(&self) -> Result<(), ValidationError> {
+        let top = match &self.data.top {
+            None => return Ok(()),
+            Some(top) => top,
+        };
+        match top.parse::<i64>() {
+            Ok(n) if (1..=MAX_TOP).contains(&n) => Ok(()),
+            _ => {
+                Err(ValidationError {
+                    field: "top".to_string(),
+                    messages: vec![format!(
+                        "Must be between 1 and {}",
+                        MAX_TOP
+                    )],
+                })
+            },
+        }
+    }
+
+    // Both are required, since must be before until, and the range must not
+    // have too many buckets
+    fn validate_time_range(&self, bucket: &Bucket) -> Vec<ValidationError> {
+        let d = self.data;
+        let message = format!("Must be a time in {}", export::TIME_FORMAT);
+
+        let mut errors = vec![];
+        let since = d.since.as_ref().and_then(|s| export::parse_time(s));
+        if since.is_none() {
+            errors.push(ValidationError {
+                field: "since".to_string(),
+                messages: vec![message.clone()],
+            });
+        }
+        let until = d.until.as_ref().and_then(|s| export::parse_time(s));
+        if until.is_none() {
+            errors.push(ValidationError {
+                field: "until".to_string(),
+                messages: vec![message],
+            });
+        }
+        if let (Some(since), Some(until)) = (since, until) {
+            if since >= until {
+                errors.push(ValidationError {
+                    field: "until".to_string(),
+                    messages: vec!["Must be after since".to_string()],
+                });
+            } else if (until - since).num_seconds() >
+                bucket.duration().num_seconds() * MAX_BUCKETS
+            {
+                errors.push(ValidationError {
+                    field: "until".to_string(),
+                    messages: vec![format!(
+                        "Must be within {} buckets from since",
+                        MAX_BUCKETS
+                    )],
+                });
+            }
+        }
+        errors
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors: Vec<ValidationError> = vec![];
+
+        let buckets: Vec<String> =
+            Bucket::as_vec().iter().map(|b| b.to_string()).collect();
+        if let Some(b) = &self.data.bucket {
+            if !buckets.contains(b) {
+                errors.push(ValidationError {
+                    field: "bucket".to_string(),
+                    messages: vec![format!(
+                        "Must be either {}",
+                        buckets.join(", ")
+                    )],
+                });
+            }
+        }
+        if let Err(e) = self.validate_stream() {
+            errors.push(e);
+        }
+        let bucket = Bucket::from(self.data.bucket.clone().unwrap_or_default());
+        errors.extend(self.validate_time_range(&bucket));
+        if let Err(e) = self.validate_top() {
+            errors.push(e);
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::streams;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = RequestData {
+                stream: Some(stream.uuid.to_string()),
+                bucket: Some("week".to_string()),
+                since: Some("2021-01-01T00:00:00".to_string()),
+                top: Some("0".to_string()),
+
+                ..Default::default()
+            };
+            let v =
+                Validator::new(conn, &data, stream.namespace_id + 1, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["bucket", "stream", "until", "top"], fields);
+            } else {
+                panic!("must fail");
+            }
+
+            // 2 days by minute
+            let data = RequestData {
+                bucket: Some("minute".to_string()),
+                since: Some("2021-01-01T00:00:00".to_string()),
+                until: Some("2021-01-03T00:00:00".to_string()),
+
+                ..Default::default()
+            };
+            let v = Validator::new(conn, &data, stream.namespace_id, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["until"], fields);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = RequestData {
+                stream: Some(stream.uuid.to_string()),
+                bucket: Some("day".to_string()),
+                since: Some("2021-01-01T00:00:00".to_string()),
+                until: Some("2021-03-01T00:00:00".to_string()),
+                top: Some("5".to_string()),
+            };
+            let v = Validator::new(conn, &data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+
+            let data = RequestData {
+                since: Some("2021-01-01T00:00:00".to_string()),
+                until: Some("2021-01-02T00:00:00".to_string()),
+
+                ..Default::default()
+            };
+            let v = Validator::new(conn, &data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+        })
+    }
+}
@@ -0,0 +1,261 @@
+use std::result::Result;
+
+use accord::validators::length_if_present;
+use diesel::PgConnection;
+
+use crate::logger::Logger;
+use crate::model::message::LogLevel;
+use crate::model::stream::Stream;
+use crate::request::message_view::MessageView as RequestData;
+use crate::validation::*;
+
+/// The max length of the time window (90 days in seconds).
+pub const MAX_TIME_WINDOW: i64 = 7_776_000;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a RequestData,
+    namespace_id: i64,
+    named: bool,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    /// Validates a view to be saved. It needs the name.
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a RequestData,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            namespace_id,
+            named: true,
+            logger,
+        }
+    }
+
+    /// Validates only the query (e.g. for a search).
+    pub fn for_search(
+        conn: &'a PgConnection,
+        data: &'a RequestData,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            named: false,
+
+            ..Self::new(conn, data, namespace_id, logger)
+        }
+    }
+
+    fn validate_name(&self) -> Result<(), ValidationError> {
+        let valid = match &self.data.name {
+            Some(name) => !name.trim().is_empty() && name.chars().count() <= 64,
+            None => false,
+        };
+        if !self.named || valid {
+            return Ok(());
+        }
+        Err(ValidationError {
+            field: "name".to_string(),
+            messages: vec![
+                "Must contain characters between 1 and 64".to_string()
+            ],
+        })
+    }
+
+    // The stream is optional, but it must be in the namespace if given
+    fn validate_stream(&self) -> Result<(), ValidationError> {
+        let uuid = match &self.data.stream {
+            None => return Ok(()),
+            Some(uuid) => uuid,
+        };
+        let found = Stream::find_by_uuid(uuid, self.conn, self.logger)
+            .filter(|s| s.namespace_id == self.namespace_id);
+        if found.is_none() {
+            return Err(ValidationError {
+                field: "stream".to_string(),
+                messages: vec!["Not found".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_levels(&self) -> Result<(), ValidationError> {
+        let levels: Vec<String> =
+            LogLevel::as_vec().iter().map(|l| l.to_string()).collect();
+        let invalid = self.data.levels.as_ref().map_or(false, |v| {
+            v.iter().any(|l| !levels.contains(&l.to_ascii_lowercase()))
+        });
+        if invalid {
+            return Err(ValidationError {
+                field: "levels".to_string(),
+                messages: vec![format!("Must be in {}", levels.join(", "))],
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_time_window(&self) -> Result<(), ValidationError> {
+        match self.data.time_window {
+            Some(t) if !(1..=MAX_TIME_WINDOW).contains(&t) => {
+                Err(ValidationError {
+                    field: "time_window".to_string(),
+                    messages: vec![format!(
+                        "Must be seconds between 1 and {}",
+                        MAX_TIME_WINDOW
+                    )],
+                })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let d = self.data;
+        let result = rules! {
+            "code" => d.code => [length_if_present(1, 128)],
+            "lang" => d.lang => [length_if_present(2, 8)]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if let Err(e) = self.validate_name() {
+            errors.push(e);
+        }
+        if let Err(e) = self.validate_stream() {
+            errors.push(e);
+        }
+        if let Err(e) = self.validate_levels() {
+            errors.push(e);
+        }
+        if let Err(e) = self.validate_time_window() {
+            errors.push(e);
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::streams;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &RequestData {
+                name: Some(" ".to_string()),
+                stream: Some(stream.uuid.to_string()),
+                levels: Some(vec!["error".to_string(), "fatal".to_string()]),
+                time_window: Some(0),
+
+                ..Default::default()
+            };
+            let v = Validator::new(conn, data, stream.namespace_id + 1, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(
+                    vec!["name", "stream", "levels", "time_window"],
+                    fields
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &RequestData {
+                name: Some("errors in the last hour".to_string()),
+                shared: Some(true),
+                stream: Some(stream.uuid.to_string()),
+                levels: Some(vec!["Error".to_string(), "critical".to_string()]),
+                code: Some("E1".to_string()),
+                lang: Some("en".to_string()),
+                time_window: Some(3600),
+            };
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+
+            // the name is not needed for a search
+            let data = &RequestData {
+                levels: Some(vec!["error".to_string()]),
+
+                ..Default::default()
+            };
+            let v =
+                Validator::for_search(conn, data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+            assert!(v.validate().is_err());
+        })
+    }
+}
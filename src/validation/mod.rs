@@ -1,11 +1,19 @@
+pub mod alert_rule;
+pub mod annotation;
 pub mod message;
+pub mod message_export;
+pub mod message_stats;
+pub mod message_view;
 pub mod namespace;
 pub mod password_reset;
 pub mod password_reset_request;
+pub mod redaction_rule;
 pub mod user;
+pub mod user_email;
+pub mod webhook;
 
 use accord::{Invalid, ValidatorResult};
-use accord::validators::{alphanumeric, max as original_max};
+use accord::validators::{alphanumeric, either, max as original_max};
 
 type SV = Box<dyn Fn(&String) -> ValidatorResult>;
 
@@ -149,6 +157,30 @@ fn max_if_present(
     })
 }
 
+fn either_if_present(
+    values: Vec<String>,
+) -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        match &s {
+            Some(v) => either(values.clone())(&v),
+            None => Ok(()),
+        }
+    })
+}
+
+fn within(min: i32, max: i32) -> Box<dyn Fn(&i32) -> ValidatorResult> {
+    Box::new(move |n: &i32| {
+        if min <= *n && *n <= max {
+            return Ok(());
+        }
+        Err(Invalid {
+            msg: "Must be between %1 and %2".to_string(),
+            args: vec![min.to_string(), max.to_string()],
+            human_readable: format!("Must be between {} and {}", min, max),
+        })
+    })
+}
+
 #[rustfmt::skip::attributes(rstest)]
 #[cfg(test)]
 mod test {
@@ -307,4 +339,34 @@ mod test {
 
         assert_eq!(expected, f(s).is_ok());
     }
+
+    #[rstest(
+        raw_s, expected,
+        case(Some("b".to_string()), true),
+        case(Some("c".to_string()), false),
+        case(None, true),
+        ::trace
+    )]
+    #[test]
+    fn test_either_if_present(raw_s: Option<String>, expected: bool) {
+        let f = either_if_present(vec!["a".to_string(), "b".to_string()]);
+        let s = &raw_s;
+
+        assert_eq!(expected, f(s).is_ok());
+    }
+
+    #[rstest(
+        n, expected,
+        case(0, false),
+        case(1, true),
+        case(10, true),
+        case(11, false),
+        ::trace
+    )]
+    #[test]
+    fn test_within(n: i32, expected: bool) {
+        let f = within(1, 10);
+
+        assert_eq!(expected, f(&n).is_ok());
+    }
 }
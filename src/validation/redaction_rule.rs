@@ -0,0 +1,242 @@
+use std::result::Result;
+
+use accord::validators::length;
+use diesel::PgConnection;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::redaction_rule::{
+    NewRedactionRule, RedactionDetector, RedactionRule,
+};
+use crate::redaction;
+use crate::request::redaction_rule::RedactionRule as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a Json<RequestData>,
+    namespace_id: i64,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a Json<RequestData>,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            namespace_id,
+            logger,
+        }
+    }
+
+    // The name must be unique in the namespace
+    fn validate_name(
+        &self,
+        r: &NewRedactionRule,
+    ) -> Result<(), ValidationError> {
+        let rules = RedactionRule::find_all_by_namespace_id(
+            self.namespace_id,
+            self.conn,
+            self.logger,
+        )
+        .unwrap_or_default();
+        if rules.iter().any(|v| v.name == r.name) {
+            return Err(ValidationError {
+                field: "name".to_string(),
+                messages: vec!["Already exists".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    // A custom rule needs a valid regular expression
+    fn validate_pattern(
+        &self,
+        r: &NewRedactionRule,
+    ) -> Result<(), ValidationError> {
+        if r.detector != RedactionDetector::Custom {
+            return Ok(());
+        }
+        let message = match &r.pattern {
+            None => "Must exist".to_string(),
+            Some(p) if p.is_empty() || p.len() > 255 => {
+                "Must contain characters between 1 and 255".to_string()
+            },
+            Some(p) => {
+                match redaction::compile(p) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => {
+                        format!("Must be a valid regular expression: {}", e)
+                    },
+                }
+            },
+        };
+        Err(ValidationError {
+            field: "pattern".to_string(),
+            messages: vec![message],
+        })
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let d = &self.data.0;
+        let r = NewRedactionRule::from(d.clone());
+        let detectors: Vec<String> =
+            RedactionDetector::iter().map(|v| v.to_string()).collect();
+        let result = rules! {
+            "detector" => d.detector => [either_if_present(detectors)],
+            "name" => r.name => [
+                length(1, 32),
+                contain_only_alphanumeric_or_underscore()
+            ]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if !errors.iter().any(|e| e.field == "name") {
+            if let Err(e) = self.validate_name(&r) {
+                errors.push(e);
+            }
+        }
+        if let Err(e) = self.validate_pattern(&r) {
+            errors.push(e);
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+    use rocket_contrib::json::Json;
+
+    use crate::model::namespace::{Namespace, namespaces};
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+
+    fn insert_namespace(conn: &PgConnection) -> Namespace {
+        let ns = NAMESPACES.get("piano").unwrap();
+        diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        run(|conn, _, logger| {
+            let namespace = insert_namespace(conn);
+
+            let data = &Json(RequestData {
+                detector: Some("phone".to_string()),
+                name: Some("api key".to_string()),
+                pattern: Some("(sk_".to_string()),
+            });
+            let v = Validator::new(conn, data, namespace.id, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["detector", "name", "pattern"], fields);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_duplicate_name() {
+        run(|conn, _, logger| {
+            let namespace = insert_namespace(conn);
+
+            let r = NewRedactionRule {
+                namespace_id: namespace.id,
+                detector: RedactionDetector::Email,
+                name: "email".to_string(),
+                pattern: None,
+            };
+            let _ = RedactionRule::insert(&r, conn, logger).unwrap();
+
+            let data = &Json(RequestData {
+                detector: Some("email".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, namespace.id, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("name", errors[0].field);
+                assert_eq!(vec!["Already exists"], errors[0].messages);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let namespace = insert_namespace(conn);
+
+            let data = &Json(RequestData {
+                detector: Some("credit_card".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, namespace.id, logger);
+            assert!(v.validate().is_ok());
+
+            let data = &Json(RequestData {
+                detector: Some("custom".to_string()),
+                name: Some("api_key".to_string()),
+                pattern: Some("sk_[0-9a-z]{24}".to_string()),
+            });
+            let v = Validator::new(conn, data, namespace.id, logger);
+            assert!(v.validate().is_ok());
+        })
+    }
+}
@@ -6,6 +6,7 @@ use rocket_contrib::json::Json;
 
 use crate::logger::Logger;
 use crate::model::user::{NewUser, User};
+use crate::model::user_email::UserEmail;
 use crate::request::user::registration::UserRegistration as RequestData;
 use crate::validation::*;
 
@@ -24,12 +25,12 @@ impl<'a> Validator<'a> {
         Self { conn, data, logger }
     }
 
+    // A verified secondary address of another user can't be used either.
     fn validate_email_uniqueness(&self) -> Result<(), ValidationError> {
-        if !User::check_email_uniqueness(
-            &self.data.0.email,
-            self.conn,
-            self.logger,
-        ) {
+        let email = &self.data.0.email;
+        if !User::check_email_uniqueness(email, self.conn, self.logger) ||
+            !UserEmail::check_email_uniqueness(email, self.conn, self.logger)
+        {
             return Err(ValidationError {
                 field: "email".to_string(),
                 messages: vec!["Already exists".to_string()],
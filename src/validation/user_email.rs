@@ -0,0 +1,244 @@
+use std::result::Result;
+
+use accord::validators::{contains, length};
+use diesel::PgConnection;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::user::User;
+use crate::model::user_email::UserEmail;
+use crate::request::user::email::UserEmailAddition as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a Json<RequestData>,
+    user: &'a User,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a Json<RequestData>,
+        user: &'a User,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            user,
+            logger,
+        }
+    }
+
+    // Addresses verified by anyone, or added by the user (even if it's not
+    // verified yet) can't be added.
+    fn validate_email_uniqueness(&self) -> Result<(), ValidationError> {
+        let email = &self.data.0.email;
+        let owned =
+            UserEmail::find_all_by_user(self.user, self.conn, self.logger)
+                .map_or(true, |v| {
+                    v.iter().any(|ue| ue.email.as_ref() == Some(email))
+                });
+        if owned ||
+            !User::check_email_uniqueness(email, self.conn, self.logger) ||
+            !UserEmail::check_email_uniqueness(email, self.conn, self.logger)
+        {
+            return Err(ValidationError {
+                field: "email".to_string(),
+                messages: vec!["Already exists".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let result = rules! {
+            // user_emails.email is VARCHAR(64)
+            "email" => self.data.0.email => [
+                contains("@"),
+                contains("."),
+                length(6, 64)
+            ]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if errors.is_empty() {
+            if let Err(e) = self.validate_email_uniqueness() {
+                errors.push(e);
+            }
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+    use rocket_contrib::json::Json;
+
+    use crate::model::user::users;
+    use crate::model::user_email::NewUserEmail;
+
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_validate_email_is_invalid() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = &diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let data = &Json(RequestData {
+                email: "this-is-not-email".to_string(),
+            });
+            let v = Validator {
+                conn,
+                data,
+                user,
+                logger,
+            };
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("email", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain '@'", "Must contain '.'"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_email_already_exists() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = &diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let data = &Json(RequestData {
+                email: user.email.to_string(),
+            });
+            let v = Validator {
+                conn,
+                data,
+                user,
+                logger,
+            };
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("email", errors[0].field);
+                assert_eq!(vec!["Already exists"], errors[0].messages);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = &diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let data = &Json(RequestData {
+                email: "oswald.new@example.org".to_string(),
+            });
+            let v = Validator {
+                conn,
+                data,
+                user,
+                logger,
+            };
+
+            assert!(v.validate().is_ok());
+        })
+    }
+
+    #[test]
+    fn test_validate_unverified_email() {
+        run(|conn, _, logger| {
+            let users = ["oswald", "weenie"]
+                .iter()
+                .map(|k| {
+                    diesel::insert_into(users::table)
+                        .values(USERS.get(k).unwrap())
+                        .get_result::<User>(conn)
+                        .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+                })
+                .collect::<Vec<User>>();
+
+            let ue = NewUserEmail {
+                user_id: users[0].id,
+                email: "shared@example.org".to_string(),
+
+                ..Default::default()
+            };
+            let _ = UserEmail::insert(&ue, conn, logger).unwrap();
+
+            let data = &Json(RequestData {
+                email: "shared@example.org".to_string(),
+            });
+
+            // the user has already added it
+            let v = Validator::new(conn, data, &users[0], logger);
+            assert!(v.validate().is_err());
+
+            // it's not verified yet
+            let v = Validator::new(conn, data, &users[1], logger);
+            assert!(v.validate().is_ok());
+        })
+    }
+}
@@ -0,0 +1,195 @@
+use std::result::Result;
+
+use accord::validators::{length, length_if_present};
+use diesel::PgConnection;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::message::LogLevel;
+use crate::model::stream::Stream;
+use crate::request::webhook::Webhook as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a Json<RequestData>,
+    namespace_id: i64,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a Json<RequestData>,
+        namespace_id: i64,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            data,
+            namespace_id,
+            logger,
+        }
+    }
+
+    // The stream (optional) must be in the namespace
+    fn validate_stream(&self) -> Result<(), ValidationError> {
+        let uuid = match self.data.0.stream {
+            None => return Ok(()),
+            Some(ref uuid) => uuid,
+        };
+        let found = Stream::find_by_uuid(uuid, self.conn, self.logger)
+            .filter(|s| s.namespace_id == self.namespace_id);
+        if found.is_none() {
+            return Err(ValidationError {
+                field: "stream".to_string(),
+                messages: vec!["Not found".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_url(&self, url: &str) -> Result<(), ValidationError> {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(ValidationError {
+                field: "url".to_string(),
+                messages: vec!["Must be a HTTP(S) URL".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let d = &self.data.0;
+        let url = d.url.clone().unwrap_or_default();
+        let levels = LogLevel::iter().map(|l| l.to_string()).collect();
+        let result = rules! {
+            "url" => url => [length(10, 255)],
+            "level" => d.level => [either_if_present(levels)],
+            "code" => d.code => [length_if_present(1, 32)]
+        };
+
+        let mut errors: Vec<ValidationError> = vec![];
+
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+        }
+
+        if let Err(e) = self.validate_stream() {
+            errors.push(e);
+        }
+        if !errors.iter().any(|e| e.field == "url") {
+            if let Err(e) = self.validate_url(&url) {
+                errors.push(e);
+            }
+        }
+
+        if !errors.is_empty() {
+            for e in &errors {
+                info!(
+                    self.logger,
+                    "validation error: {} {}",
+                    e.field,
+                    e.messages.join(",")
+                );
+            }
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+    use rocket_contrib::json::Json;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::stream::streams;
+
+    use crate::model::test::run;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+
+    fn insert_stream(conn: &PgConnection) -> Stream {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let _ = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let s = STREAMS.get("oswald's stream").unwrap();
+        diesel::insert_into(streams::table)
+            .values(s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                url: Some("ftp://example.org/hook".to_string()),
+                level: Some("fatal".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id + 1, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                let fields: Vec<&str> =
+                    errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(vec!["level", "stream", "url"], fields);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let stream = insert_stream(conn);
+
+            // all streams in the namespace
+            let data = &Json(RequestData {
+                url: Some("https://example.org/hook".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+
+            let data = &Json(RequestData {
+                stream: Some(stream.uuid.to_string()),
+                url: Some("http://127.0.0.1:8080/hook".to_string()),
+                level: Some("error".to_string()),
+                code: Some("E1".to_string()),
+            });
+            let v = Validator::new(conn, data, stream.namespace_id, logger);
+            assert!(v.validate().is_ok());
+        })
+    }
+}
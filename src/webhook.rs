@@ -0,0 +1,359 @@
+//! Webhook posts JSON payloads to URLs given by users.
+//!
+//! Deliveries to webhook subscriptions are signed. The receiver can verify
+//! the signature by computing HMAC-SHA256 of `<timestamp>.<body>` with the
+//! secret of the subscription, and comparing it with the signature header
+//! (`sha256=<hex digest>`).
+//!
+//! Requests are not sent to hosts resolved to a loopback, private or
+//! link-local address (unless `Config::webhook_allow_private_hosts` is set),
+//! and redirects are not followed, so that URLs given by users can't reach
+//! internal services.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac, NewMac};
+use serde_json::Value;
+use sha2::Sha256;
+
+pub const DELIVERY_HEADER: &str = "X-Eloquentlog-Delivery";
+pub const EVENT_HEADER: &str = "X-Eloquentlog-Event";
+pub const SIGNATURE_HEADER: &str = "X-Eloquentlog-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Eloquentlog-Timestamp";
+
+pub const MESSAGE_EVENT: &str = "message.append";
+pub const PING_EVENT: &str = "ping";
+
+const TIMEOUT: u64 = 10; // seconds
+const USER_AGENT: &str = "Eloquentlog-Webhook/1.0";
+
+/// WebhookError tells whether the request is worth retrying.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebhookError {
+    /// The response is not successful (429 and 5xx are transient)
+    Status(u16),
+    /// e.g. connection failure, timeout
+    Transient(String),
+    /// e.g. invalid URL
+    Permanent(String),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebhookError::Status(status) => write!(f, "status: {}", status),
+            WebhookError::Transient(ref e) => write!(f, "transient: {}", e),
+            WebhookError::Permanent(ref e) => write!(f, "permanent: {}", e),
+        }
+    }
+}
+
+impl WebhookError {
+    pub fn is_transient(&self) -> bool {
+        match self {
+            WebhookError::Status(status) => *status == 429 || *status >= 500,
+            WebhookError::Transient(_) => true,
+            WebhookError::Permanent(_) => false,
+        }
+    }
+
+    /// Returns the status of the response if it has been received.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            WebhookError::Status(status) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+/// Signs `<timestamp>.<body>` with the secret, and returns the value for the
+/// signature header.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    // HMAC accepts a key of any size
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+/// Returns true if the address is a public one (not a loopback, private,
+/// link-local or any other special purpose one).
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            !(v4.is_unspecified() ||
+                v4.is_loopback() ||
+                v4.is_private() ||
+                v4.is_link_local() ||
+                v4.is_broadcast() ||
+                v4.is_documentation() ||
+                o[0] == 0 ||
+                // shared address space (100.64.0.0/10)
+                (o[0] == 100 && (o[1] & 0xc0) == 64))
+        },
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4() {
+                // IPv4-mapped (or compatible) ones
+                if v6.segments()[..5].iter().all(|s| *s == 0) {
+                    return is_public(IpAddr::V4(v4));
+                }
+            }
+            let s = v6.segments();
+            !(v6.is_unspecified() ||
+                v6.is_loopback() ||
+                // unique local (fc00::/7)
+                (s[0] & 0xfe00) == 0xfc00 ||
+                // link-local (fe80::/10)
+                (s[0] & 0xffc0) == 0xfe80)
+        },
+    }
+}
+
+// Resolves the host, and drops addresses which are not public. It fails if
+// no address remains, and the error is told from others by its kind.
+fn resolve_public(netloc: &str) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = netloc
+        .to_socket_addrs()?
+        .filter(|a| is_public(a.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a public address", netloc),
+        ));
+    }
+    Ok(addrs)
+}
+
+// Builds an agent which doesn't follow redirects. The resolver checks
+// addresses on each connection, so that a host can't be changed to a
+// private one after it's checked.
+fn agent(allow_private: bool) -> ureq::Agent {
+    let builder = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(TIMEOUT))
+        .redirects(0);
+    if allow_private {
+        builder.build()
+    } else {
+        builder.resolver(resolve_public).build()
+    }
+}
+
+// Returns true if the error is the one by `resolve_public`
+fn is_rejected(e: &ureq::Error) -> bool {
+    e.kind() == ureq::ErrorKind::Dns &&
+        e.source()
+            .and_then(|s| s.downcast_ref::<io::Error>())
+            .map_or(false, |e| e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+fn send(req: ureq::Request, body: &str) -> Result<u16, WebhookError> {
+    let result = req
+        .set("Content-Type", "application/json")
+        .set("User-Agent", USER_AGENT)
+        .send_string(body);
+    match result {
+        // a redirect is not followed
+        Ok(res) if res.status() >= 300 => {
+            Err(WebhookError::Status(res.status()))
+        },
+        Ok(res) => Ok(res.status()),
+        Err(ureq::Error::Status(status, _)) => {
+            Err(WebhookError::Status(status))
+        },
+        Err(e) if is_rejected(&e) => {
+            Err(WebhookError::Permanent(e.source().unwrap().to_string()))
+        },
+        Err(e) => {
+            match e.kind() {
+                ureq::ErrorKind::InvalidUrl |
+                ureq::ErrorKind::UnknownScheme => {
+                    Err(WebhookError::Permanent(e.to_string()))
+                },
+                _ => Err(WebhookError::Transient(e.to_string())),
+            }
+        },
+    }
+}
+
+/// Posts the body as JSON, and returns the status of the response. A host
+/// which is not public is rejected unless `allow_private` is true.
+pub fn post(
+    url: &str,
+    body: &Value,
+    allow_private: bool,
+) -> Result<u16, WebhookError> {
+    send(agent(allow_private).post(url), &body.to_string())
+}
+
+/// Delivery is an event to a webhook subscription. Retries of it have the
+/// same id.
+pub struct Delivery<'a> {
+    pub id: &'a str,
+    pub event: &'a str,
+    pub timestamp: i64,
+    pub body: &'a str,
+}
+
+/// Posts the body of the delivery with the signature made by the secret, and
+/// returns the status of the response. A host which is not public is
+/// rejected unless `allow_private` is true.
+pub fn deliver(
+    url: &str,
+    secret: &str,
+    delivery: &Delivery,
+    allow_private: bool,
+) -> Result<u16, WebhookError> {
+    let signature = sign(secret, delivery.timestamp, delivery.body);
+    let req = agent(allow_private)
+        .post(url)
+        .set(DELIVERY_HEADER, delivery.id)
+        .set(EVENT_HEADER, delivery.event)
+        .set(TIMESTAMP_HEADER, &delivery.timestamp.to_string())
+        .set(SIGNATURE_HEADER, &signature);
+    send(req, delivery.body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        assert!(WebhookError::Status(500).is_transient());
+        assert!(WebhookError::Status(503).is_transient());
+        assert!(WebhookError::Status(429).is_transient());
+        assert!(!WebhookError::Status(404).is_transient());
+        assert!(!WebhookError::Status(410).is_transient());
+        assert!(WebhookError::Transient("timeout".to_string()).is_transient());
+        assert!(!WebhookError::Permanent("url".to_string()).is_transient());
+    }
+
+    #[test]
+    fn test_sign() {
+        // echo -n '1609459200.{}' | openssl dgst -sha256 -hmac 'secret'
+        let digest =
+            "c3951831ae6935b48c94e1165919cff34d5a733d531a5abed39cacbdd9d8f594";
+        let signature = sign("secret", 1_609_459_200, "{}");
+        assert_eq!(signature, format!("sha256={}", digest));
+
+        assert_ne!(sign("secret", 1_609_459_201, "{}"), signature);
+        assert_ne!(sign("secreT", 1_609_459_200, "{}"), signature);
+    }
+
+    #[test]
+    fn test_is_public() {
+        let public = |s: &str| is_public(s.parse().unwrap());
+
+        assert!(public("93.184.216.34"));
+        assert!(public("2606:2800:220:1:248:1893:25c8:1946"));
+
+        assert!(!public("0.0.0.0"));
+        assert!(!public("127.0.0.1"));
+        assert!(!public("10.0.0.1"));
+        assert!(!public("172.16.0.1"));
+        assert!(!public("192.168.1.1"));
+        assert!(!public("169.254.169.254"));
+        assert!(!public("100.64.0.1"));
+        assert!(!public("255.255.255.255"));
+        assert!(!public("::"));
+        assert!(!public("::1"));
+        assert!(!public("fd00::1"));
+        assert!(!public("fe80::1"));
+        assert!(!public("::ffff:127.0.0.1"));
+        assert!(!public("::ffff:169.254.169.254"));
+    }
+
+    #[test]
+    fn test_post_to_invalid_url() {
+        let result = post("not a url", &json!({}), false);
+        assert!(matches!(result, Err(WebhookError::Permanent(_))));
+    }
+
+    #[test]
+    fn test_post_to_private_host() {
+        for url in &[
+            "http://127.0.0.1:1/hook",
+            "http://localhost:1/hook",
+            "http://[::1]:1/hook",
+        ] {
+            let result = post(url, &json!({}), false);
+            assert!(
+                matches!(result, Err(WebhookError::Permanent(ref e)) if e.contains("not a public address")),
+                "{}: {:?}",
+                url,
+                result
+            );
+        }
+
+        // nothing listens on the port
+        let result = post("http://127.0.0.1:1/hook", &json!({}), true);
+        assert!(matches!(result, Err(WebhookError::Transient(_))));
+    }
+
+    #[test]
+    fn test_deliver_to_private_host() {
+        let delivery = Delivery {
+            id: "id",
+            event: PING_EVENT,
+            timestamp: 1_609_459_200,
+            body: "{}",
+        };
+        for url in &[
+            "http://127.0.0.1:1/hook",
+            "http://localhost:1/hook",
+            "http://[::1]:1/hook",
+            "http://169.254.169.254/latest/meta-data/",
+        ] {
+            let result = deliver(url, "secret", &delivery, false);
+            assert!(
+                matches!(result, Err(WebhookError::Permanent(ref e)) if e.contains("not a public address")),
+                "{}: {:?}",
+                url,
+                result
+            );
+        }
+
+        // nothing listens on the port
+        let result =
+            deliver("http://127.0.0.1:1/hook", "secret", &delivery, true);
+        assert!(matches!(result, Err(WebhookError::Transient(_))));
+    }
+
+    #[test]
+    fn test_post_without_following_redirect() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+        });
+
+        let result = post(&url, &json!({}), true);
+        assert_eq!(result, Err(WebhookError::Status(302)));
+        assert!(!result.unwrap_err().is_transient());
+        server.join().unwrap();
+    }
+}
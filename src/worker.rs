@@ -0,0 +1,588 @@
+//! The worker processes jobs in named queues with a pool of threads.
+//!
+//! Each queue has its own threads (see `Config::worker_queues`), so that
+//! a queue given more threads is processed with higher priority. Database
+//! connections are taken from a pool shared by the threads.
+//!
+//! Besides, a scheduler thread enqueues jobs for retry, delayed ones,
+//! postponed ones and recurring ones (only by the leader among workers), and
+//! a metrics thread serves metrics of jobs if `Config::worker_metrics_address`
+//! is given.
+//!
+//! A job taken from a queue is kept in the forked list of the worker until
+//! it's done. Jobs left in lists of a worker which has stopped heartbeating
+//! (e.g. crashed) are put back into their queue by the other workers.
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use redis::{Client, Commands, Connection, RedisResult};
+
+use crate::config::Config;
+use crate::db::{DbPoolHolder, init_pool_holder};
+use crate::job::scheduler::{self, Schedule, parse_schedules};
+use crate::job::{self, Job, JobError, delay, postpone, retry};
+use crate::logger::Logger;
+use crate::metrics;
+
+pub const HEARTBEAT_KEY_PREFIX: &str = "worker:heartbeat";
+
+const HEARTBEAT_INTERVAL: u64 = 10; // seconds
+const HEARTBEAT_TTL: usize = 30; // seconds
+const SCHEDULER_INTERVAL: u64 = 1; // seconds
+const RECONNECT_INTERVAL_MAX: u64 = 60; // seconds
+const SHUTDOWN_CHECK_INTERVAL: u64 = 500; // milliseconds
+
+/// Heartbeat is stored in Redis with TTL while the worker is alive.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Heartbeat {
+    pub id: String,
+    pub hostname: String,
+    pub pid: u32,
+    pub queues: Vec<(String, usize)>,
+    pub busy: usize,
+    pub processed: u64,
+    pub failed: u64,
+    pub started_at: i64,
+    pub beat_at: i64,
+}
+
+impl Heartbeat {
+    pub fn key(id: &str) -> String {
+        format!("{}:{}", HEARTBEAT_KEY_PREFIX, id)
+    }
+
+    pub fn beat(&self, conn: &mut Connection) -> RedisResult<()> {
+        let value = serde_json::to_string(self).unwrap();
+        conn.set_ex(Self::key(&self.id), value, HEARTBEAT_TTL)
+    }
+
+    pub fn delete(&self, conn: &mut Connection) -> RedisResult<()> {
+        conn.del(Self::key(&self.id))
+    }
+
+    /// Returns heartbeats of alive workers.
+    pub fn find_all(conn: &mut Connection) -> RedisResult<Vec<Self>> {
+        let keys: Vec<String> =
+            conn.keys(format!("{}:*", HEARTBEAT_KEY_PREFIX))?;
+        let mut heartbeats = vec![];
+        for key in keys {
+            // it may have been expired
+            let value: Option<String> = conn.get(key)?;
+            if let Some(h) = value.and_then(|v| serde_json::from_str(&v).ok()) {
+                heartbeats.push(h);
+            }
+        }
+        heartbeats.sort_by(|a: &Self, b: &Self| a.id.cmp(&b.id));
+        Ok(heartbeats)
+    }
+}
+
+// The state shared by threads
+struct State {
+    shutdown: Arc<AtomicBool>,
+    running: AtomicUsize,
+    busy: AtomicUsize,
+    processed: AtomicU64,
+    failed: AtomicU64,
+    // `CLIENT ID` of the redis connection of each thread (0 means none)
+    client_ids: Vec<AtomicI64>,
+}
+
+impl State {
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    // Sleeps, but wakes up early at shutdown
+    fn sleep(&self, secs: u64) {
+        let interval = Duration::from_millis(SHUTDOWN_CHECK_INTERVAL);
+        let mut slept = Duration::from_secs(0);
+        while !self.is_shutdown() && slept < Duration::from_secs(secs) {
+            thread::sleep(interval);
+            slept += interval;
+        }
+    }
+}
+
+pub struct Worker {
+    id: String,
+    client: Client,
+    config: Config,
+    db_pool_holder: DbPoolHolder,
+    logger: Logger,
+    schedules: Arc<Vec<Schedule>>,
+    state: Arc<State>,
+}
+
+impl Worker {
+    pub fn new(config: Config, logger: Logger) -> Self {
+        let threads: usize = config.worker_queues.iter().map(|(_, n)| n).sum();
+
+        let schedules = parse_schedules(&config.worker_schedules)
+            .unwrap_or_else(|e| panic!("invalid schedules: {:?}", e));
+
+        let client = Client::open(config.message_queue_url.as_str())
+            .expect("message queue url");
+        let db_pool_holder =
+            init_pool_holder(&config.database_url, threads.max(1) as u32);
+
+        let state = Arc::new(State {
+            shutdown: Arc::new(AtomicBool::new(false)),
+            running: AtomicUsize::new(0),
+            busy: AtomicUsize::new(0),
+            processed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            client_ids: (0..threads).map(|_| AtomicI64::new(0)).collect(),
+        });
+
+        let hostname = std::env::var("HOSTNAME")
+            .unwrap_or_else(|_| "localhost".to_string());
+        let id = format!("{}:{}", hostname, std::process::id());
+
+        Self {
+            id,
+            client,
+            config,
+            db_pool_holder,
+            logger,
+            schedules: Arc::new(schedules),
+            state,
+        }
+    }
+
+    /// Returns the flag which stops the worker gracefully when it's set.
+    /// (e.g. `signal_hook::flag::register(SIGTERM, worker.shutdown_flag())`)
+    pub fn shutdown_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.state.shutdown)
+    }
+
+    /// Runs until the shutdown flag is set. Jobs in progress are finished
+    /// before it returns.
+    pub fn run(&self) {
+        let mut heartbeat = Heartbeat {
+            id: self.id.clone(),
+            hostname: std::env::var("HOSTNAME")
+                .unwrap_or_else(|_| "localhost".to_string()),
+            pid: std::process::id(),
+            queues: self.config.worker_queues.clone(),
+            busy: 0,
+            processed: 0,
+            failed: 0,
+            started_at: Utc::now().timestamp(),
+            beat_at: 0,
+        };
+        info!(self.logger, "worker: {}", heartbeat.id);
+
+        // the first beat is put before taking jobs, so that they are not
+        // recovered by another worker as ones of a stopped worker
+        let mut conn = None;
+        self.beat(&mut heartbeat, &mut conn);
+
+        let mut handles = vec![];
+        let mut slot = 0;
+        for (name, n) in &self.config.worker_queues {
+            for _ in 0..*n {
+                handles.push(self.spawn_queue_thread(name.to_string(), slot));
+                slot += 1;
+            }
+        }
+        handles.push(self.spawn_scheduler_thread());
+        if let Some(ref address) = self.config.worker_metrics_address {
+            match TcpListener::bind(address) {
+                Ok(listener) => {
+                    info!(self.logger, "metrics: {}", address);
+                    handles.push(self.spawn_metrics_thread(listener));
+                },
+                Err(e) => error!(self.logger, "err: {} {}", address, e),
+            }
+        }
+
+        while !self.state.is_shutdown() {
+            self.state.sleep(HEARTBEAT_INTERVAL);
+            self.beat(&mut heartbeat, &mut conn);
+        }
+
+        info!(self.logger, "shutting down...");
+        self.stop(&mut conn);
+        for handle in handles {
+            let _ = handle.join();
+        }
+
+        if let Some(ref mut c) = conn {
+            let _ = heartbeat.delete(c);
+        }
+        info!(self.logger, "bye");
+    }
+
+    // Stores the heartbeat, and then puts jobs left by stopped workers back
+    // into their queue
+    fn beat(&self, heartbeat: &mut Heartbeat, conn: &mut Option<Connection>) {
+        if conn.is_none() {
+            *conn = self.connect();
+        }
+        let c = match conn {
+            Some(ref mut c) => c,
+            None => return,
+        };
+        heartbeat.busy = self.state.busy.load(Ordering::SeqCst);
+        heartbeat.processed = self.state.processed.load(Ordering::SeqCst);
+        heartbeat.failed = self.state.failed.load(Ordering::SeqCst);
+        heartbeat.beat_at = Utc::now().timestamp();
+        let result = heartbeat.beat(c).and_then(|_| {
+            let alive: Vec<String> =
+                Heartbeat::find_all(c)?.into_iter().map(|h| h.id).collect();
+            job::recover(&alive, c)
+        });
+        match result {
+            Ok(0) => (),
+            Ok(n) => info!(self.logger, "recovered: {}", n),
+            Err(e) => {
+                error!(self.logger, "err: {}", e);
+                *conn = None;
+            },
+        }
+    }
+
+    // Wakes up threads blocked at dequeue until all of them have exited.
+    // Threads processing a job exit after it's done.
+    fn stop(&self, conn: &mut Option<Connection>) {
+        while self.state.running.load(Ordering::SeqCst) > 0 {
+            if conn.is_none() {
+                *conn = self.connect();
+            }
+            if let Some(ref mut c) = conn {
+                for id in &self.state.client_ids {
+                    let id = id.load(Ordering::SeqCst);
+                    if id > 0 {
+                        let _: RedisResult<i64> = redis::cmd("CLIENT")
+                            .arg("UNBLOCK")
+                            .arg(id)
+                            .query(c);
+                    }
+                }
+            }
+            thread::sleep(Duration::from_millis(SHUTDOWN_CHECK_INTERVAL));
+        }
+    }
+
+    fn connect(&self) -> Option<Connection> {
+        self.client
+            .get_connection()
+            .map_err(|e| error!(self.logger, "err: {}", e))
+            .ok()
+    }
+
+    fn spawn_queue_thread(
+        &self,
+        name: String,
+        slot: usize,
+    ) -> thread::JoinHandle<()> {
+        let id = self.id.clone();
+        let client = self.client.clone();
+        let config = self.config.clone();
+        let db_pool_holder = self.db_pool_holder.clone();
+        let logger = self.logger.new(o!("queue" => name.clone()));
+        let state = Arc::clone(&self.state);
+
+        state.running.fetch_add(1, Ordering::SeqCst);
+        thread::spawn(move || {
+            let mut conn: Option<Connection> = None;
+            let mut failures: u32 = 0;
+
+            while !state.is_shutdown() {
+                if conn.is_none() {
+                    conn = client
+                        .get_connection()
+                        .map_err(|e| error!(logger, "err: {}", e))
+                        .ok()
+                        .and_then(|mut c| {
+                            let id: i64 = redis::cmd("CLIENT")
+                                .arg("ID")
+                                .query(&mut c)
+                                .ok()?;
+                            state.client_ids[slot].store(id, Ordering::SeqCst);
+                            Some(c)
+                        });
+                }
+                let c = match conn {
+                    Some(ref mut c) => c,
+                    None => {
+                        failures += 1;
+                        state.sleep(reconnect_interval(failures));
+                        continue;
+                    },
+                };
+
+                // a malformed job (e.g. invalid payload) goes to the dead
+                // letter list, and a newer one is postponed
+                let result = job::dequeue(&name, &id, c, &logger);
+                match result {
+                    Ok(Some((job, value))) => {
+                        failures = 0;
+                        state.busy.fetch_add(1, Ordering::SeqCst);
+                        perform(
+                            &name,
+                            job,
+                            c,
+                            &db_pool_holder,
+                            &config,
+                            &state,
+                            &logger,
+                        );
+                        state.busy.fetch_sub(1, Ordering::SeqCst);
+                        // it has been done, retried or dead-lettered
+                        if let Err(e) = job::ack(&name, &id, &value, c) {
+                            error!(logger, "err: {}", e);
+                        }
+                    },
+                    // unblocked at shutdown, or dead-lettered
+                    Ok(None) => failures = 0,
+                    Err(_) if state.is_shutdown() => (),
+                    Err(e) => {
+                        // the connection may have been lost, reconnect
+                        error!(logger, "err: {}", e);
+                        state.client_ids[slot].store(0, Ordering::SeqCst);
+                        conn = None;
+                        failures += 1;
+                        state.sleep(reconnect_interval(failures));
+                    },
+                }
+            }
+
+            state.client_ids[slot].store(0, Ordering::SeqCst);
+            state.running.fetch_sub(1, Ordering::SeqCst);
+        })
+    }
+
+    // Moves jobs which are due (for retry, delayed or postponed) into their
+    // queue, and enqueues recurring jobs if the worker is the leader
+    fn spawn_scheduler_thread(&self) -> thread::JoinHandle<()> {
+        let id = self.id.clone();
+        let client = self.client.clone();
+        let logger = self.logger.new(o!("thread" => "scheduler"));
+        let schedules = Arc::clone(&self.schedules);
+        let state = Arc::clone(&self.state);
+
+        thread::spawn(move || {
+            let mut conn = None;
+            let mut leader = false;
+            while !state.is_shutdown() {
+                state.sleep(SCHEDULER_INTERVAL);
+
+                if conn.is_none() {
+                    conn = client
+                        .get_connection()
+                        .map_err(|e| error!(logger, "err: {}", e))
+                        .ok();
+                }
+                let c = match conn {
+                    Some(ref mut c) => c,
+                    None => continue,
+                };
+
+                let now = Utc::now().timestamp();
+                let result = enqueue_scheduled(
+                    &id,
+                    &schedules,
+                    &mut leader,
+                    now,
+                    c,
+                    &logger,
+                );
+                match result {
+                    Ok(0) => (),
+                    Ok(n) => info!(logger, "enqueued: {}", n),
+                    Err(e) => {
+                        error!(logger, "err: {}", e);
+                        conn = None;
+                    },
+                }
+            }
+
+            if let Some(ref mut c) = conn {
+                if leader {
+                    let _ = scheduler::release_leadership(&id, c);
+                }
+            }
+        })
+    }
+
+    // Serves metrics to any request (a scraper) until the shutdown
+    fn spawn_metrics_thread(
+        &self,
+        listener: TcpListener,
+    ) -> thread::JoinHandle<()> {
+        let db_pool_holder = self.db_pool_holder.clone();
+        let logger = self.logger.new(o!("thread" => "metrics"));
+        let state = Arc::clone(&self.state);
+
+        thread::spawn(move || {
+            // not to block the shutdown
+            if let Err(e) = listener.set_nonblocking(true) {
+                error!(logger, "err: {}", e);
+            }
+            while !state.is_shutdown() {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let (connections, idle, max_size) =
+                            db_pool_holder.state();
+                        metrics::set_pool_connections(
+                            "database",
+                            connections,
+                            idle,
+                            max_size,
+                        );
+                        if let Err(e) = serve_metrics(stream) {
+                            error!(logger, "err: {}", e);
+                        }
+                    },
+                    Err(e) => {
+                        if e.kind() != io::ErrorKind::WouldBlock {
+                            error!(logger, "err: {}", e);
+                        }
+                        thread::sleep(Duration::from_millis(
+                            SHUTDOWN_CHECK_INTERVAL,
+                        ));
+                    },
+                }
+            }
+        })
+    }
+}
+
+// Responds with the metrics regardless of the request
+fn serve_metrics(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    // the request line and headers are not used
+    let mut buf = [0; 1024];
+    let _ = stream.read(&mut buf)?;
+
+    let body = metrics::gather();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: \
+         {}\r\nConnection: close\r\n\r\n{}",
+        metrics::CONTENT_TYPE,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn enqueue_scheduled(
+    id: &str,
+    schedules: &[Schedule],
+    leader: &mut bool,
+    now: i64,
+    conn: &mut Connection,
+    logger: &Logger,
+) -> RedisResult<usize> {
+    let mut count = retry::enqueue_due(now, conn)?;
+    count += delay::enqueue_due(now, conn)?;
+    count += postpone::enqueue_due(now, conn)?;
+
+    if schedules.is_empty() {
+        return Ok(count);
+    }
+    let acquired = scheduler::acquire_leadership(id, conn)?;
+    if acquired != *leader {
+        *leader = acquired;
+        info!(logger, "leader: {}", acquired);
+    }
+    if acquired {
+        count += scheduler::tick(schedules, now, conn)?;
+    }
+    Ok(count)
+}
+
+fn reconnect_interval(failures: u32) -> u64 {
+    2_u64
+        .saturating_pow(failures.min(6))
+        .min(RECONNECT_INTERVAL_MAX)
+}
+
+fn perform(
+    queue: &str,
+    job: Job,
+    conn: &mut Connection,
+    db_pool_holder: &DbPoolHolder,
+    config: &Config,
+    state: &State,
+    logger: &Logger,
+) {
+    // records of the job can be correlated with the request which enqueued it
+    let logger = &match job.request_id {
+        Some(ref id) => logger.new(o!("request_id" => id.clone())),
+        None => logger.clone(),
+    };
+    info!(logger, "job: {}, attempts: {}", job, job.attempts);
+
+    let kind = job.kind().to_string();
+    let started_at = Instant::now();
+    let result = match db_pool_holder.get() {
+        Some(db_conn) => job.invoke(&db_conn, config, logger),
+        None => {
+            Err(JobError::Transient(
+                "database connection is not available".to_string(),
+            ))
+        },
+    };
+
+    state.processed.fetch_add(1, Ordering::SeqCst);
+    metrics::observe_job(queue, &kind, result.is_err(), started_at.elapsed());
+    if let Err(e) = result {
+        state.failed.fetch_add(1, Ordering::SeqCst);
+        error!(logger, "err: {}", e);
+        if let Err(e) = job::fail(queue, job, &e, conn, logger) {
+            error!(logger, "err: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_interval() {
+        assert_eq!(reconnect_interval(1), 2);
+        assert_eq!(reconnect_interval(5), 32);
+        assert_eq!(reconnect_interval(6), 60);
+        assert_eq!(reconnect_interval(100), 60);
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        metrics::observe_job("test", "Test", false, Duration::from_secs(1));
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+            let mut res = String::new();
+            stream.read_to_string(&mut res).unwrap();
+            res
+        });
+        let (stream, _) = listener.accept().unwrap();
+        assert!(serve_metrics(stream).is_ok());
+
+        let res = client.join().unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.contains("eloquentlog_jobs_processed_total"));
+    }
+
+    #[test]
+    fn test_heartbeat_key() {
+        assert_eq!(
+            Heartbeat::key("localhost:1"),
+            "worker:heartbeat:localhost:1".to_string()
+        );
+    }
+}
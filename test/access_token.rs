@@ -136,6 +136,154 @@ fn test_access_token_hset_state() {
     });
 }
 
+#[test]
+fn test_access_token_hset_state_to_same_state() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // 2019-08-07T06:05:04.333
+        let dt = Utc.ymd(2019, 8, 7).and_hms_milli(6, 5, 4, 333);
+
+        let v = model::access_token::AccessToken::generate_token();
+        let t = model::access_token::AccessToken {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            agent_id: user.id,
+            agent_type: model::access_token::AgentType::Person,
+            name: "personal token".to_string(),
+            token: Some(v.into_bytes()),
+            state: model::access_token::AccessTokenState::Enabled,
+            revoked_at: None,
+            created_at: dt.naive_utc(),
+            updated_at: dt.naive_utc(),
+        };
+
+        let access_token =
+            diesel::insert_into(model::access_token::access_tokens::table)
+                .values(&t)
+                .get_result::<model::access_token::AccessToken>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", t));
+
+        let state = model::access_token::AccessTokenState::Enabled;
+        let res = client
+            .patch(format!("/v1/access_token/hset/{}/state", access_token.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{
+                  "access_token": {{
+                    "state": "{}"
+                  }}
+                }}"#,
+                &state,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Conflict);
+    });
+}
+
+#[test]
+fn test_access_token_hset_state_of_revoked_token() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // 2019-08-07T06:05:04.333
+        let dt = Utc.ymd(2019, 8, 7).and_hms_milli(6, 5, 4, 333);
+
+        let v = model::access_token::AccessToken::generate_token();
+        let t = model::access_token::AccessToken {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            agent_id: user.id,
+            agent_type: model::access_token::AgentType::Person,
+            name: "personal token".to_string(),
+            token: Some(v.into_bytes()),
+            state: model::access_token::AccessTokenState::Disabled,
+            revoked_at: Some(dt.naive_utc()),
+            created_at: dt.naive_utc(),
+            updated_at: dt.naive_utc(),
+        };
+
+        let access_token =
+            diesel::insert_into(model::access_token::access_tokens::table)
+                .values(&t)
+                .get_result::<model::access_token::AccessToken>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", t));
+
+        let state = model::access_token::AccessTokenState::Enabled;
+        let res = client
+            .patch(format!("/v1/access_token/hset/{}/state", access_token.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{
+                  "access_token": {{
+                    "state": "{}"
+                  }}
+                }}"#,
+                &state,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Gone);
+    });
+}
+
 #[test]
 fn test_access_token_lrange_returns_empty_if_not_exist() {
     run_test(|client, conn, _, _| {
@@ -295,3 +443,88 @@ fn test_access_token_lrange_returns_a_list_contain_tokens() {
         );
     });
 }
+
+#[test]
+fn test_access_token_dump_and_del() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let dt = Utc.ymd(2019, 8, 7).and_hms_milli(6, 5, 4, 333);
+        let t = model::access_token::AccessToken {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            agent_id: user.id,
+            agent_type: model::access_token::AgentType::Person,
+            name: "personal token".to_string(),
+            token: None,
+            state: model::access_token::AccessTokenState::Enabled,
+            revoked_at: None,
+            created_at: dt.naive_utc(),
+            updated_at: dt.naive_utc(),
+        };
+        let access_token =
+            diesel::insert_into(model::access_token::access_tokens::table)
+                .values(&t)
+                .get_result::<model::access_token::AccessToken>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", t));
+
+        let patch = |path: String| {
+            client
+                .patch(path)
+                .header(ContentType::JSON)
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .dispatch()
+        };
+
+        let res = patch(format!("/v1/access_token/dump/{}", Uuid::nil()));
+        assert_eq!(res.status(), Status::NotFound);
+
+        let res = patch(format!("/v1/access_token/dump/{}", access_token.uuid));
+        assert_eq!(res.status(), Status::Ok);
+
+        // it's available only once
+        let mut res =
+            patch(format!("/v1/access_token/dump/{}", access_token.uuid));
+        assert_eq!(res.status(), Status::Conflict);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["error"]["code"], "conflict");
+
+        let res = patch(format!("/v1/access_token/del/{}", access_token.uuid));
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = patch(format!("/v1/access_token/del/{}", access_token.uuid));
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
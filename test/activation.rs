@@ -35,12 +35,14 @@ fn test_activate_with_invalid_token() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
-
-        let session_id = job.args[1].to_string();
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
+
+        let session_id = payload.session_id;
         let token = "invalid-token";
 
         let res = client
@@ -86,13 +88,15 @@ fn test_activate_with_invalid_session_id() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
         let session_id = "invalid-session_id";
-        let token = job.args[2].to_string();
+        let token = payload.token;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -137,12 +141,14 @@ fn test_activate_without_authorization_header() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
+        let session_id = payload.session_id;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -185,13 +191,15 @@ fn test_activate_without_x_requested_with_header() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
-        let token = job.args[2].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.token;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -235,13 +243,15 @@ fn test_activate() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
-        let token = job.args[2].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.token;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -262,5 +272,15 @@ fn test_activate() {
         let result = model::access_token::AccessToken::by_user(&user)
             .first::<model::access_token::AccessToken>(conn.db);
         assert!(result.is_ok());
+
+        let events = model::audit_event::AuditEvent::find_all_by_user(
+            &user, 0, 10, conn.db, logger,
+        )
+        .unwrap();
+        // the default namespace and its membership are recorded too
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().any(|e| {
+            e.action == model::audit_event::AuditEventAction::AccountActivation
+        }));
     });
 }
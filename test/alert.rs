@@ -0,0 +1,327 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use eloquentlog_console_api::job::{
+    self, pending, EvaluateAlertRules, Job, JobError, Payload,
+};
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_alert_rule_hset_by_member() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .post(format!("/v1/alert/{}/rule/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+    });
+}
+
+#[test]
+fn test_alert_rule_and_alert() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::PrimaryOwner,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        // nothing listens on the port
+        let mut res = client
+            .post(format!("/v1/alert/{}/rule/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "stream": "{}",
+                    "name": "errors",
+                    "condition": "match",
+                    "level": "error",
+                    "channel": "webhook",
+                    "target": "http://127.0.0.1:1/hook"
+                }}"#,
+                stream.uuid,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let rule_uuid = result["alert_rule"]["uuid"].as_str().unwrap();
+        assert_eq!(result["alert_rule"]["level"], "error");
+
+        let mut res = client
+            .get(format!("/v1/alert/{}/rule/hgetall", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let rules = result.as_array().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0]["alert_rule"]["stream"], stream.uuid.to_string());
+
+        let message = model::message::NewMessage {
+            agent_id: user.id,
+            stream_id: stream.id,
+            level: model::message::LogLevel::Critical,
+            title: Some("title".to_string()),
+
+            ..Default::default()
+        };
+        let _ = model::message::Message::insert(&message, conn.db, logger);
+
+        // the alert is recorded even if the notification fails
+        let job = Job::new(Payload::EvaluateAlertRules(EvaluateAlertRules {
+            stream_id: Some(stream.id),
+        }));
+        let result = job.invoke(conn.db, config, logger);
+        assert!(matches!(result, Err(JobError::Transient(_))));
+
+        let mut res = client
+            .get(format!("/v1/alert/{}/lrange/0/9", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let alerts = result.as_array().unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0]["alert"]["state"], "firing");
+        assert_eq!(alerts[0]["alert"]["notified_at"], Value::Null);
+        let alert_uuid = alerts[0]["alert"]["uuid"].as_str().unwrap();
+
+        // muted
+        let mut res = client
+            .patch(format!(
+                "/v1/alert/{}/rule/mute/{}",
+                namespace.uuid, rule_uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"minutes": 30}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result["alert_rule"]["muted_until"].is_string());
+
+        let result = job.invoke(conn.db, config, logger);
+        assert!(result.is_ok());
+
+        // acknowledged (only once)
+        let res = client
+            .patch(format!("/v1/alert/{}/ack/{}", namespace.uuid, alert_uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .patch(format!("/v1/alert/{}/ack/{}", namespace.uuid, alert_uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Conflict);
+
+        let res = client
+            .patch(format!(
+                "/v1/alert/{}/rule/del/{}",
+                namespace.uuid, rule_uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_alert_evaluation_on_append() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::PrimaryOwner,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        // the message goes to the second one
+        let mut streams = vec![];
+        for name in &["main", "api"] {
+            let s = model::stream::NewStream {
+                namespace_id: namespace.id,
+                name: name.to_string(),
+
+                ..Default::default()
+            };
+            streams.push(
+                model::stream::Stream::insert(&s, conn.db, logger).unwrap(),
+            );
+        }
+        let stream = &streams[1];
+
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .post(format!("/v1/alert/{}/rule/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "stream": "{}",
+                    "name": "errors",
+                    "condition": "match",
+                    "level": "error",
+                    "channel": "webhook",
+                    "target": "http://127.0.0.1:1/hook"
+                }}"#,
+                stream.uuid,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace.uuid, stream.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(
+                r#"{
+                    "agent_id": 1,
+                    "stream_id": 1,
+                    "level": "error",
+                    "title": "connection refused"
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let values = pending::lrange(job::ALERT_QUEUE, 0, -1, conn.mq).unwrap();
+        assert_eq!(values.len(), 1);
+        let job: Job = serde_json::from_str(&values[0]).unwrap();
+        assert_eq!(
+            job.payload,
+            Payload::EvaluateAlertRules(EvaluateAlertRules {
+                stream_id: Some(stream.id),
+            })
+        );
+    });
+}
@@ -0,0 +1,169 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_annotation_by_member() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        let m = model::message::NewMessage {
+            agent_id: user.id,
+            stream_id: stream.id,
+            title: Some("connection refused".to_string()),
+
+            ..Default::default()
+        };
+        let id = model::message::Message::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .post(format!(
+                "/v1/annotation/{}/append/{}/{}",
+                namespace.uuid, stream.uuid, id
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"ticket_url": "ftp://example.org/issues/1"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .post(format!(
+                "/v1/annotation/{}/append/{}/{}",
+                namespace.uuid, stream.uuid, id
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"label": "investigating", "body": "db is down"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let uuid = result["annotation"]["uuid"].as_str().unwrap().to_string();
+        assert_eq!(result["annotation"]["user"], user.uuid.to_string());
+
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/lrange/{}/0/10",
+                namespace.uuid, stream.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result[0]["annotations_count"], 1);
+
+        let res = client
+            .patch(format!("/v1/annotation/{}/hset/{}", namespace.uuid, uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(
+                r#"{"label": "resolved", "ticket_url": "https://example.org/issues/1"}"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut res = client
+            .get(format!(
+                "/v1/annotation/{}/hgetall/{}/{}",
+                namespace.uuid, stream.uuid, id
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let annotations = result.as_array().unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0]["annotation"]["label"], "resolved");
+        assert_eq!(annotations[0]["annotation"]["body"], Value::Null);
+
+        let res = client
+            .patch(format!("/v1/annotation/{}/del/{}", namespace.uuid, uuid))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .patch(format!("/v1/annotation/{}/del/{}", namespace.uuid, uuid))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
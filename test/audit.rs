@@ -0,0 +1,146 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+#[test]
+fn test_audit_lrange() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let mut res = client
+            .get("/v1/audit/lrange/0/9")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let events = result.as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["audit_event"]["action"], "login");
+
+        // the range at the bounds of i64 doesn't overflow
+        let mut res = client
+            .get(format!("/v1/audit/lrange/{}/{}", i64::MIN, i64::MAX))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+    });
+}
+
+#[test]
+fn test_audit_lrange_namespace_by_member() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let res = client
+            .get(format!("/v1/audit/{}/lrange/0/9", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        // as an owner
+        let _ = diesel::update(model::membership::memberships::table)
+            .filter(model::membership::memberships::user_id.eq(user.id))
+            .set(
+                model::membership::memberships::role
+                    .eq(model::membership::MembershipRole::Owner),
+            )
+            .execute(conn.db)
+            .expect("Failed to update");
+
+        let res = client
+            .get(format!("/v1/audit/{}/lrange/0/9", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
@@ -1,5 +1,6 @@
 use fourche::queue::Queue;
 use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
 
 use eloquentlog_console_api::job;
 
@@ -34,14 +35,15 @@ fn test_login_with_wrong_username() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
-
-        let session_id = job.args[1].to_string();
-        let token = job.args[2].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.token;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -105,14 +107,15 @@ fn test_login_with_wrong_password() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
-        let token = job.args[2].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.token;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -131,7 +134,7 @@ fn test_login_with_wrong_password() {
             .body("{}")
             .dispatch();
 
-        let res = client
+        let mut res = client
             .post("/_/login")
             .header(ContentType::JSON)
             .body(format!(
@@ -144,6 +147,10 @@ fn test_login_with_wrong_password() {
             .dispatch();
 
         assert_eq!(res.status(), Status::Unauthorized);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["error"]["code"], "invalid_credentials");
     });
 }
 
@@ -176,14 +183,15 @@ fn test_login() {
 
         assert_eq!(res.status(), Status::Ok);
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
-        let token = job.args[2].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.token;
 
         let res = client
             .patch(format!("/_/activate/{}", session_id))
@@ -0,0 +1,212 @@
+use diesel::{self, prelude::*};
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, make_raw_password, MEMBERSHIPS, NAMESPACES, USERS,
+};
+
+#[test]
+fn test_deregister_refused() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // wrong password
+        let res = client
+            .post("/_/deregister")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"password": "wrong"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        // a namespace shared with another member
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let member = load_user(u, conn.db);
+
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values((
+                model::membership::memberships::namespace_id.eq(namespace.id),
+                model::membership::memberships::user_id.eq(member.id),
+                model::membership::memberships::role
+                    .eq(model::membership::MembershipRole::Member),
+            ))
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+        let mut res = client
+            .post("/_/deregister")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"password": "{}"}}"#, password))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Conflict);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["error"]["details"]["namespaces"][0]
+                .as_str()
+                .unwrap(),
+            namespace.uuid.to_string()
+        );
+
+        assert!(
+            model::user::User::find_by_email(&user.email, conn.db, logger)
+                .is_some()
+        );
+    });
+}
+
+#[test]
+fn test_deregister() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let res = client
+            .post("/_/deregister")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"password": "{}"}}"#, password))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserDeregistrationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
+        assert_eq!(payload.email, user.email);
+
+        assert!(
+            model::user::User::find_by_email(&user.email, conn.db, logger)
+                .is_none()
+        );
+        assert!(
+            model::namespace::Namespace::find_all(&user, conn.db, logger)
+                .unwrap()
+                .is_empty()
+        );
+
+        // can't log in anymore
+        let res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Unauthorized);
+    });
+}
@@ -0,0 +1,354 @@
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+#[test]
+fn test_append_with_validation_error() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // the primary address
+        let res = client
+            .put("/v1/email/append")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"email": "{}"}}"#, user.email))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
+
+#[test]
+fn test_append_verify_and_promote() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let email = "oswald.new@example.org";
+        let mut res = client
+            .put("/v1/email/append")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"email": "{}"}}"#, email))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["email"]["role"], "general");
+        assert_eq!(result["email"]["identification_state"], "pending");
+        let id = result["email"]["id"].as_i64().unwrap();
+
+        // not verified yet
+        let res = client
+            .patch(format!("/v1/email/hset/{}/primary", id))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserEmailVerificationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
+        assert_eq!(payload.user_email_id, id);
+
+        let session_id = payload.session_id.to_string();
+        let verification_token = payload.token;
+
+        let res = client
+            .patch(format!("/_/email/verify/{}", session_id))
+            .header(ContentType::JSON)
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", verification_token),
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .patch(format!("/v1/email/hset/{}/primary", id))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let result = model::user::User::find_by_email(email, conn.db, logger);
+        assert_eq!(result.map(|u| u.id), Some(user.id));
+
+        // the new primary address can't be removed
+        let res = client
+            .patch(format!("/v1/email/del/{}", id))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Conflict);
+
+        let mut res = client
+            .get("/v1/email/hgetall")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let emails = result["emails"].as_array().unwrap();
+        assert_eq!(emails.len(), 2);
+        assert_eq!(emails[0]["email"], user.email);
+        assert_eq!(emails[0]["role"], "general");
+
+        let old_id = emails[0]["id"].as_i64().unwrap();
+        let res = client
+            .patch(format!("/v1/email/del/{}", old_id))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let result =
+            model::user::User::find_by_email(&user.email, conn.db, logger);
+        assert!(result.is_none());
+
+        // addresses aren't recorded in audit events
+        let events = model::audit_event::AuditEvent::find_all_by_user(
+            &user, 0, 10, conn.db, logger,
+        )
+        .unwrap();
+        assert!(events.iter().any(|e| e.target == Some(id.to_string())));
+        assert!(events
+            .iter()
+            .all(|e| e.target.as_deref().map_or(true, |t| !t.contains('@'))));
+    });
+}
+
+#[test]
+fn test_verify_reserves_email() {
+    run_test(|client, conn, _, logger| {
+        use eloquentlog_console_api::model::Activatable;
+        use eloquentlog_console_api::model::user_email::{NewUserEmail, UserEmail};
+
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let others = ["weenie", "hennry"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let o = model::user::User {
+                    id: user.id + 1 + i as i64,
+                    uuid: Uuid::new_v4(),
+                    username: name.to_string(),
+                    email: format!("{}@example.org", name),
+
+                    ..user.clone()
+                };
+                load_user(o, conn.db)
+            })
+            .collect::<Vec<model::user::User>>();
+        let (weenie, hennry) = (&others[0], &others[1]);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // unverified addresses of others don't block appending
+        let shared = "shared@example.org";
+        let pending = UserEmail::insert(
+            &NewUserEmail {
+                user_id: weenie.id,
+                email: shared.to_string(),
+
+                ..Default::default()
+            },
+            conn.db,
+            logger,
+        )
+        .unwrap();
+
+        let taken = "taken@example.org";
+        let _ = UserEmail::insert(
+            &NewUserEmail {
+                user_id: hennry.id,
+                email: taken.to_string(),
+
+                ..Default::default()
+            },
+            conn.db,
+            logger,
+        )
+        .unwrap();
+
+        let mut tokens = vec![];
+        for email in &[shared, taken] {
+            let res = client
+                .put("/v1/email/append")
+                .header(ContentType::JSON)
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(format!(r#"{{"email": "{}"}}"#, email))
+                .dispatch();
+
+            assert_eq!(res.status(), Status::Ok);
+
+            let mut queue = Queue::new("mail", conn.mq);
+            let job = queue.dequeue::<job::Job>().ok().unwrap();
+            let payload = match job.payload {
+                job::Payload::SendUserEmailVerificationEmail(p) => p,
+                p => panic!("unexpected payload: {:?}", p),
+            };
+            tokens.push((payload.session_id.to_string(), payload.token));
+        }
+
+        // the same address can't be appended twice by the user
+        let res = client
+            .put("/v1/email/append")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"email": "{}"}}"#, shared))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let (session_id, verification_token) = &tokens[0];
+        let res = client
+            .patch(format!("/_/email/verify/{}", session_id))
+            .header(ContentType::JSON)
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", verification_token),
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        // the unverified one of the other user has been deleted
+        assert!(UserEmail::find_by_id(pending.id, conn.db, logger).is_none());
+
+        // the address has been verified by another user in the meantime
+        let verified = UserEmail::find_all_by_user(hennry, conn.db, logger)
+            .unwrap()
+            .pop()
+            .unwrap();
+        verified.activate(conn.db, logger).unwrap();
+
+        let (session_id, verification_token) = &tokens[1];
+        let mut res = client
+            .patch(format!("/_/email/verify/{}", session_id))
+            .header(ContentType::JSON)
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", verification_token),
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Conflict);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["error"]["code"], "conflict");
+    });
+}
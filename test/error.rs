@@ -1,4 +1,5 @@
-use rocket::http::Status;
+use rocket::http::{Header, Status};
+use serde_json::Value;
 
 use crate::run_test;
 
@@ -22,3 +23,50 @@ fn test_404_not_found() {
             .contains("'/_/unknown-path' is not found"));
     });
 }
+
+#[test]
+fn test_error_envelope() {
+    run_test(|client, _, _, _| {
+        let mut res = client
+            .get("/unknown-path")
+            .header(Header::new("X-Request-Id", "req-1"))
+            .dispatch();
+        assert_eq!(res.status(), Status::NotFound);
+        assert_eq!(res.headers().get_one("X-Request-Id"), Some("req-1"));
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["version"], 1);
+        assert_eq!(result["error"]["code"], "not_found");
+        assert_eq!(result["error"]["errors"], Value::Null);
+        assert_eq!(result["error"]["request_id"], "req-1");
+    });
+
+    // an invalid one is replaced
+    run_test(|client, _, _, _| {
+        let mut res = client
+            .get("/unknown-path")
+            .header(Header::new("X-Request-Id", "req 1"))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_ne!(result["error"]["request_id"], "req 1");
+        assert!(!result["error"]["request_id"].as_str().unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_401_unauthorized_without_token() {
+    run_test(|client, _, _, _| {
+        let mut res = client
+            .get("/v1/namespace/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["error"]["code"], "unauthorized");
+    });
+}
@@ -16,6 +16,78 @@ fn test_health_check() {
     });
 }
 
+#[test]
+fn test_health_check_with_request_id() {
+    run_test(|client, _, _, _| {
+        let res = client
+            .get("/_/health")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("X-Request-Id", "health-1"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(res.headers().get_one("X-Request-Id"), Some("health-1"));
+
+        // an invalid one is replaced, also in responses to preflight requests
+        let res = client
+            .options("/_/login")
+            .header(Header::new("X-Request-Id", "a b"))
+            .dispatch();
+
+        let id = res.headers().get_one("X-Request-Id").unwrap();
+        assert_ne!(id, "a b");
+        assert!(!id.is_empty());
+    });
+}
+
+#[test]
+fn test_health_live() {
+    run_test(|client, _, _, _| {
+        let res = client.get("/_/health/live").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client.get("/v1/health/live").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_health_ready() {
+    run_test(|client, _, _, _| {
+        let mut res = client.get("/_/health/ready").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["status"], "ready");
+
+        let checks = result["checks"].as_array().unwrap();
+        let names: Vec<&str> =
+            checks.iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "database",
+                "migrations",
+                "message_queue",
+                "session_store",
+                "smtp"
+            ]
+        );
+        for c in &checks[..4] {
+            assert_eq!(c["status"], "up");
+            assert!(c["latency_ms"].as_f64().is_some());
+            assert!(c.get("message").is_none());
+        }
+        assert_eq!(checks[4]["status"], "skipped");
+
+        // not public
+        let res = client.get("/v1/health/ready").dispatch();
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
+
 #[test]
 fn test_v1_health_check() {
     run_test(|client, conn, _, _| {
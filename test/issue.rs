@@ -0,0 +1,399 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use diesel::PgConnection;
+
+use eloquentlog_console_api::logger::Logger;
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+fn record(
+    stream: &model::stream::Stream,
+    title: &str,
+    conn: &PgConnection,
+    logger: &Logger,
+) {
+    let m = model::message::NewMessage {
+        stream_id: stream.id,
+        level: model::message::LogLevel::Error,
+        title: Some(title.to_string()),
+
+        ..Default::default()
+    };
+    assert!(model::issue::Issue::record(&m, conn, logger).is_some());
+}
+
+#[test]
+fn test_issue_lrange_and_hset_state() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        record(&stream, "timeout after 30s", conn.db, logger);
+        record(&stream, "timeout after 45s", conn.db, logger);
+        record(&stream, "connection refused", conn.db, logger);
+
+        let token = login(client, &user.email, &password);
+
+        let mut res = client
+            .get(format!(
+                "/v1/issue/{}/lrange/{}/0/9",
+                namespace.uuid, stream.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let issues = result.as_array().unwrap();
+        assert_eq!(issues.len(), 2);
+
+        let issue = issues
+            .iter()
+            .find(|i| i["issue"]["title"] == "timeout after 30s")
+            .unwrap();
+        assert_eq!(issue["issue"]["count"], 2);
+        assert_eq!(issue["issue"]["state"], "open");
+        let uuid = issue["issue"]["uuid"].as_str().unwrap().to_string();
+
+        let res = client
+            .patch(format!("/v1/issue/{}/hset_state/{}", namespace.uuid, uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"state": "unknown"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch(format!("/v1/issue/{}/hset_state/{}", namespace.uuid, uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"state": "resolved"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["issue"]["state"], "resolved");
+
+        let mut res = client
+            .get(format!(
+                "/v1/issue/{}/lrange/{}/0/9?state=open",
+                namespace.uuid, stream.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+
+        // it recurs
+        record(&stream, "timeout after 60s", conn.db, logger);
+
+        let mut res = client
+            .get(format!(
+                "/v1/issue/{}/lrange/{}/0/9?state=open",
+                namespace.uuid, stream.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let issues = result.as_array().unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0]["issue"]["uuid"], uuid.as_str());
+        assert_eq!(issues[0]["issue"]["count"], 3);
+    });
+}
+
+#[test]
+fn test_issue_on_append() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        // messages go to the second one
+        let mut streams = vec![];
+        for name in &["main", "api"] {
+            let s = model::stream::NewStream {
+                namespace_id: namespace.id,
+                name: name.to_string(),
+
+                ..Default::default()
+            };
+            streams.push(
+                model::stream::Stream::insert(&s, conn.db, logger).unwrap(),
+            );
+        }
+
+        let token = login(client, &user.email, &password);
+
+        for title in &["timeout after 30s", "timeout after 45s"] {
+            let res = client
+                .post(format!(
+                    "/v1/message/{}/append/{}",
+                    namespace.uuid, streams[1].uuid
+                ))
+                .header(ContentType::JSON)
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(format!(
+                    r#"{{
+                        "agent_id": 1,
+                        "stream_id": 1,
+                        "level": "error",
+                        "title": "{}"
+                    }}"#,
+                    title
+                ))
+                .dispatch();
+
+            assert_eq!(res.status(), Status::Ok);
+        }
+
+        for (stream, count) in streams.iter().zip(&[0, 1]) {
+            let mut res = client
+                .get(format!(
+                    "/v1/issue/{}/lrange/{}/0/9",
+                    namespace.uuid, stream.uuid
+                ))
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .dispatch();
+
+            assert_eq!(res.status(), Status::Ok);
+
+            let body = res.body_string().unwrap();
+            let result: Value = serde_json::from_str(&body).unwrap();
+            let issues = result.as_array().unwrap();
+            assert_eq!(issues.len(), *count);
+            if let Some(issue) = issues.first() {
+                assert_eq!(issue["issue"]["count"], 2);
+            }
+        }
+    });
+}
+
+fn lrange(
+    client: &Client,
+    token: &str,
+    namespace: &model::namespace::Namespace,
+    stream: &model::stream::Stream,
+) -> Vec<(String, i64)> {
+    let mut res = client
+        .get(format!(
+            "/v1/issue/{}/lrange/{}/0/9",
+            namespace.uuid, stream.uuid
+        ))
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .dispatch();
+
+    assert_eq!(res.status(), Status::Ok);
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    let mut issues: Vec<(String, i64)> = result
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| {
+            (
+                i["issue"]["level"].as_str().unwrap().to_string(),
+                i["issue"]["count"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    issues.sort();
+    issues
+}
+
+#[test]
+fn test_issue_on_hset_and_del() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Owner,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let mut ids = vec![];
+        for title in &["timeout after 30s", "timeout after 45s"] {
+            let mut res = client
+                .post(format!(
+                    "/v1/message/{}/append/{}",
+                    namespace.uuid, stream.uuid
+                ))
+                .header(ContentType::JSON)
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(format!(
+                    r#"{{
+                        "agent_id": 1,
+                        "stream_id": 1,
+                        "level": "error",
+                        "title": "{}"
+                    }}"#,
+                    title
+                ))
+                .dispatch();
+
+            assert_eq!(res.status(), Status::Ok);
+
+            let body = res.body_string().unwrap();
+            let result: Value = serde_json::from_str(&body).unwrap();
+            ids.push(result["message"]["id"].as_i64().unwrap());
+        }
+        assert_eq!(
+            lrange(client, &token, &namespace, &stream),
+            vec![("error".to_string(), 2)]
+        );
+
+        // the level changes the fingerprint
+        let res = client
+            .patch(format!(
+                "/v1/message/{}/hset/{}/{}",
+                namespace.uuid, stream.uuid, ids[0]
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"level": "warning"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(
+            lrange(client, &token, &namespace, &stream),
+            vec![("error".to_string(), 1), ("warning".to_string(), 1)]
+        );
+
+        let res = client
+            .patch(format!(
+                "/v1/message/{}/del/{}/{}",
+                namespace.uuid, stream.uuid, ids[1]
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(
+            lrange(client, &token, &namespace, &stream),
+            vec![("warning".to_string(), 1)]
+        );
+    });
+}
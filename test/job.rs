@@ -0,0 +1,412 @@
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use fourche::queue::Queue;
+use redis::Commands;
+
+use eloquentlog_console_api::job::scheduler::{self, Schedule};
+use eloquentlog_console_api::job::{
+    self, dead_letter, delay, pending, postpone, retry, FailedJob, Job,
+    JobError, JobKind, PasswordResetEmail, Payload, PurgeAuditEvents,
+    UndecodableJob,
+};
+use eloquentlog_console_api::worker::{Heartbeat, Worker};
+
+use crate::run_test;
+
+fn build_job() -> Job {
+    Job::new(Payload::SendPasswordResetEmail(PasswordResetEmail {
+        user_id: 1,
+        session_id: "session".to_string(),
+        token: "token".to_string(),
+    }))
+}
+
+#[test]
+fn test_job_invoke_with_unknown_user() {
+    run_test(|_, conn, config, logger| {
+        // not found
+        let job = build_job();
+        let result = job.invoke(conn.db, config, logger);
+        assert!(matches!(result, Err(JobError::Permanent(_))));
+    });
+}
+
+#[test]
+fn test_job_retry() {
+    run_test(|_, conn, _, logger| {
+        let err = JobError::Transient("timeout".to_string());
+        let result =
+            job::fail(job::MAIL_QUEUE, build_job(), &err, conn.mq, logger);
+        assert!(result.is_ok());
+
+        assert_eq!(retry::len(conn.mq).unwrap(), 1);
+        assert_eq!(dead_letter::len(conn.mq).unwrap(), 0);
+
+        // not yet
+        let now = Utc::now().timestamp();
+        let result = retry::enqueue_due(now, conn.mq);
+        assert_eq!(result.unwrap(), 0);
+
+        let result = retry::enqueue_due(now + retry::backoff(1), conn.mq);
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(retry::len(conn.mq).unwrap(), 0);
+
+        let mut queue = Queue::new(job::MAIL_QUEUE, conn.mq);
+        let job = queue.dequeue::<Job>().ok().unwrap();
+        assert_eq!(job.kind(), JobKind::SendPasswordResetEmail);
+        assert_eq!(job.attempts, 1);
+    });
+}
+
+#[test]
+fn test_job_dead_letter() {
+    run_test(|_, conn, _, logger| {
+        // exhausted
+        let mut j = build_job();
+        j.attempts = job::MAX_ATTEMPTS - 1;
+        let err = JobError::Transient("timeout".to_string());
+        let result = job::fail(job::MAIL_QUEUE, j, &err, conn.mq, logger);
+        assert!(result.is_ok());
+
+        // permanent
+        let err = JobError::Permanent("not found".to_string());
+        let result =
+            job::fail(job::MAIL_QUEUE, build_job(), &err, conn.mq, logger);
+        assert!(result.is_ok());
+
+        assert_eq!(retry::len(conn.mq).unwrap(), 0);
+        assert_eq!(dead_letter::len(conn.mq).unwrap(), 2);
+
+        let entries: Vec<FailedJob> =
+            dead_letter::lrange(0, -1, conn.mq).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].error, "permanent: not found");
+        assert_eq!(entries[1].job.attempts, job::MAX_ATTEMPTS);
+
+        assert!(!dead_letter::replay("unknown", conn.mq).unwrap());
+        assert!(dead_letter::replay(&entries[1].id, conn.mq).unwrap());
+        assert!(dead_letter::del(&entries[0].id, conn.mq).unwrap());
+        assert_eq!(dead_letter::len(conn.mq).unwrap(), 0);
+
+        let mut queue = Queue::new(job::MAIL_QUEUE, conn.mq);
+        let job = queue.dequeue::<Job>().ok().unwrap();
+        assert_eq!(job.kind(), JobKind::SendPasswordResetEmail);
+        assert_eq!(job.attempts, 0);
+    });
+}
+
+#[test]
+fn test_job_dead_letter_undecodable() {
+    run_test(|_, conn, _, _| {
+        let _: usize = conn.mq.zadd(retry::RETRY_KEY, "{}", 0).unwrap();
+
+        let result = retry::enqueue_due(Utc::now().timestamp(), conn.mq);
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(retry::len(conn.mq).unwrap(), 0);
+        assert_eq!(dead_letter::len(conn.mq).unwrap(), 1);
+
+        // it's not a failed job
+        assert!(dead_letter::lrange(0, -1, conn.mq).unwrap().is_empty());
+
+        let entries = dead_letter::entries(0, -1, conn.mq).unwrap();
+        let entry = match &entries[0] {
+            dead_letter::Entry::Undecodable(e) => e.clone(),
+            e => panic!("unexpected entry: {:?}", e),
+        };
+        assert_eq!(entry.queue, None);
+        assert_eq!(entry.payload, "{}");
+        assert!(entry.error.starts_with("job:retry: "));
+
+        // it can't be enqueued into the retry set again
+        assert!(!dead_letter::replay(&entry.id, conn.mq).unwrap());
+        assert!(dead_letter::del(&entry.id, conn.mq).unwrap());
+        assert_eq!(dead_letter::len(conn.mq).unwrap(), 0);
+
+        let entry =
+            UndecodableJob::new(Some(job::MAIL_QUEUE), b"unknown", "invalid");
+        dead_letter::push_undecodable(&entry, conn.mq).unwrap();
+        assert!(dead_letter::replay(&entry.id, conn.mq).unwrap());
+        assert_eq!(
+            pending::lrange(job::MAIL_QUEUE, 0, -1, conn.mq).unwrap(),
+            vec!["unknown".to_string()]
+        );
+    });
+}
+
+#[test]
+fn test_job_dequeue_undecodable() {
+    run_test(|_, conn, _, logger| {
+        let mut queue = Queue::new(job::MAIL_QUEUE, conn.mq);
+        queue.enqueue::<Job>(build_job()).unwrap();
+        let _: usize = conn.mq.lpush(job::MAIL_QUEUE, "unknown").unwrap();
+
+        let worker = "localhost:1";
+        let result = job::dequeue(job::MAIL_QUEUE, worker, conn.mq, logger);
+        let (j, value) = result.unwrap().unwrap();
+        assert_eq!(j.kind(), JobKind::SendPasswordResetEmail);
+
+        let forked = job::forked_key(job::MAIL_QUEUE, worker);
+        assert_eq!(conn.mq.llen::<_, usize>(&forked).unwrap(), 1);
+        assert!(job::ack(job::MAIL_QUEUE, worker, &value, conn.mq).is_ok());
+        assert_eq!(conn.mq.llen::<_, usize>(&forked).unwrap(), 0);
+
+        let result = job::dequeue(job::MAIL_QUEUE, worker, conn.mq, logger);
+        assert!(result.unwrap().is_none());
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 0);
+        assert_eq!(conn.mq.llen::<_, usize>(&forked).unwrap(), 0);
+
+        let entries = dead_letter::entries(0, -1, conn.mq).unwrap();
+        assert_eq!(entries.len(), 1);
+        match &entries[0] {
+            dead_letter::Entry::Undecodable(e) => {
+                assert_eq!(e.queue, Some(job::MAIL_QUEUE.to_string()));
+                assert_eq!(e.payload, "unknown");
+            },
+            e => panic!("unexpected entry: {:?}", e),
+        }
+    });
+}
+
+#[test]
+fn test_job_dequeue_newer() {
+    run_test(|_, conn, _, logger| {
+        let mut job = build_job();
+        job.version = job::VERSION + 1;
+        let values = vec![
+            serde_json::to_string(&job).unwrap(),
+            format!(
+                r#"{{"version":{},"payload":{{"kind":"Unknown"}},"attempts":0}}"#,
+                job::VERSION
+            ),
+        ];
+        for value in &values {
+            let _: usize = conn.mq.lpush(job::MAIL_QUEUE, value).unwrap();
+        }
+
+        // postponed without counting an attempt
+        let worker = "localhost:1";
+        for _ in &values {
+            let result = job::dequeue(job::MAIL_QUEUE, worker, conn.mq, logger);
+            assert!(result.unwrap().is_none());
+        }
+        let forked = job::forked_key(job::MAIL_QUEUE, worker);
+        assert_eq!(conn.mq.llen::<_, usize>(&forked).unwrap(), 0);
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 0);
+        assert_eq!(retry::len(conn.mq).unwrap(), 0);
+        assert_eq!(dead_letter::len(conn.mq).unwrap(), 0);
+        assert_eq!(postpone::len(conn.mq).unwrap(), 2);
+
+        let now = Utc::now().timestamp();
+        assert_eq!(postpone::enqueue_due(now, conn.mq).unwrap(), 0);
+
+        // enqueued again as they were
+        let run_at = now + postpone::POSTPONE_DELAY;
+        assert_eq!(postpone::enqueue_due(run_at, conn.mq).unwrap(), 2);
+        assert_eq!(postpone::len(conn.mq).unwrap(), 0);
+        let mut enqueued: Vec<String> =
+            conn.mq.lrange(job::MAIL_QUEUE, 0, -1).unwrap();
+        enqueued.sort();
+        let mut expected = values;
+        expected.sort();
+        assert_eq!(enqueued, expected);
+    });
+}
+
+#[test]
+fn test_job_recover() {
+    run_test(|_, conn, _, logger| {
+        let mut queue = Queue::new(job::MAIL_QUEUE, conn.mq);
+        queue.enqueue::<Job>(build_job()).unwrap();
+        queue.enqueue::<Job>(build_job()).unwrap();
+
+        // taken by workers, but not done
+        for worker in &["localhost:1", "localhost:2"] {
+            let result = job::dequeue(job::MAIL_QUEUE, worker, conn.mq, logger);
+            assert!(result.unwrap().is_some());
+        }
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 0);
+
+        // only the one of the worker which has stopped is put back
+        let alive = vec!["localhost:2".to_string()];
+        assert_eq!(job::recover(&alive, conn.mq).unwrap(), 1);
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 1);
+
+        let forked = job::forked_key(job::MAIL_QUEUE, "localhost:1");
+        assert_eq!(conn.mq.llen::<_, usize>(&forked).unwrap(), 0);
+        let forked = job::forked_key(job::MAIL_QUEUE, "localhost:2");
+        assert_eq!(conn.mq.llen::<_, usize>(&forked).unwrap(), 1);
+    });
+}
+
+#[test]
+fn test_worker_forked_is_empty_after_job() {
+    run_test(|_, conn, config, logger| {
+        let mut queue = Queue::new(job::MAIL_QUEUE, conn.mq);
+        // it fails as the user is not found, and goes to the dead letter list
+        queue.enqueue::<Job>(build_job()).unwrap();
+
+        let worker = Worker::new(config.clone(), logger.clone());
+        let shutdown = worker.shutdown_flag();
+        let handle = thread::spawn(move || worker.run());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while dead_letter::len(conn.mq).unwrap() == 0 {
+            assert!(Instant::now() < deadline, "the job is not processed");
+            thread::sleep(Duration::from_millis(100));
+        }
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+
+        let keys: Vec<String> = conn.mq.keys("*:forked:*").unwrap();
+        for key in keys {
+            assert_eq!(conn.mq.llen::<_, usize>(&key).unwrap(), 0);
+        }
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 0);
+    });
+}
+
+#[test]
+fn test_worker_heartbeat() {
+    run_test(|_, conn, _, _| {
+        let heartbeat = Heartbeat {
+            id: "localhost:1".to_string(),
+            hostname: "localhost".to_string(),
+            pid: 1,
+            queues: vec![(job::MAIL_QUEUE.to_string(), 2)],
+            busy: 0,
+            processed: 3,
+            failed: 1,
+            started_at: Utc::now().timestamp(),
+            beat_at: Utc::now().timestamp(),
+        };
+        assert!(heartbeat.beat(conn.mq).is_ok());
+
+        let heartbeats = Heartbeat::find_all(conn.mq).unwrap();
+        assert_eq!(heartbeats, vec![heartbeat.clone()]);
+
+        assert!(heartbeat.delete(conn.mq).is_ok());
+        assert!(Heartbeat::find_all(conn.mq).unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_job_delay() {
+    run_test(|_, conn, _, _| {
+        let job = Job::new(Payload::PurgeAuditEvents(PurgeAuditEvents {
+            retention_days: 30,
+        }));
+        let now = Utc::now().timestamp();
+        let entry = delay::enqueue_at(job, now + 60, conn.mq).unwrap();
+        assert_eq!(entry.queue, job::MAINTENANCE_QUEUE);
+
+        assert_eq!(delay::len(conn.mq).unwrap(), 1);
+        assert_eq!(delay::range(0, -1, conn.mq).unwrap(), vec![entry]);
+
+        assert_eq!(delay::enqueue_due(now, conn.mq).unwrap(), 0);
+        assert_eq!(delay::enqueue_due(now + 60, conn.mq).unwrap(), 1);
+        assert_eq!(delay::len(conn.mq).unwrap(), 0);
+
+        let mut queue = Queue::new(job::MAINTENANCE_QUEUE, conn.mq);
+        let job = queue.dequeue::<Job>().ok().unwrap();
+        assert_eq!(job.kind(), JobKind::PurgeAuditEvents);
+    });
+}
+
+#[test]
+fn test_job_scheduler() {
+    run_test(|_, conn, _, _| {
+        assert!(scheduler::acquire_leadership("a:1", conn.mq).unwrap());
+        // extends
+        assert!(scheduler::acquire_leadership("a:1", conn.mq).unwrap());
+        assert!(!scheduler::acquire_leadership("b:2", conn.mq).unwrap());
+        assert_eq!(
+            scheduler::leader(conn.mq).unwrap(),
+            Some("a:1".to_string())
+        );
+
+        // only by the leader
+        scheduler::release_leadership("b:2", conn.mq).unwrap();
+        assert!(scheduler::leader(conn.mq).unwrap().is_some());
+        scheduler::release_leadership("a:1", conn.mq).unwrap();
+        assert!(scheduler::acquire_leadership("b:2", conn.mq).unwrap());
+
+        let schedules = vec![Schedule::from_str(
+            r#"0 4 * * * {"kind":"PurgeAuditEvents","retention_days":30}"#,
+        )
+        .unwrap()];
+
+        // 2021-01-01T00:00:00Z
+        let t = 1_609_459_200;
+        // the first time
+        assert_eq!(scheduler::tick(&schedules, t, conn.mq).unwrap(), 0);
+        assert_eq!(scheduler::tick(&schedules, t + 3600, conn.mq).unwrap(), 0);
+        assert_eq!(
+            scheduler::tick(&schedules, t + 4 * 3600, conn.mq).unwrap(),
+            1
+        );
+        // just once
+        assert_eq!(
+            scheduler::tick(&schedules, t + 4 * 3600 + 1, conn.mq).unwrap(),
+            0
+        );
+
+        let mut queue = Queue::new(job::MAINTENANCE_QUEUE, conn.mq);
+        let job = queue.dequeue::<Job>().ok().unwrap();
+        assert_eq!(job.kind(), JobKind::PurgeAuditEvents);
+    });
+}
+
+#[test]
+fn test_job_pending() {
+    run_test(|_, conn, _, _| {
+        let mut queue = Queue::new(job::MAIL_QUEUE, conn.mq);
+        assert!(queue.enqueue::<Job>(build_job()).is_ok());
+        assert!(queue.enqueue::<Job>(build_job()).is_ok());
+
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 2);
+
+        let values = pending::lrange(job::MAIL_QUEUE, 0, 0, conn.mq).unwrap();
+        assert_eq!(values.len(), 1);
+        let job: Job = serde_json::from_str(&values[0]).unwrap();
+        assert_eq!(job, build_job());
+
+        assert!(!pending::del(job::MAIL_QUEUE, 2, conn.mq).unwrap());
+        assert!(pending::del(job::MAIL_QUEUE, 0, conn.mq).unwrap());
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 1);
+    });
+}
+
+#[test]
+fn test_job_run_now_and_del() {
+    run_test(|_, conn, _, logger| {
+        let err = JobError::Transient("timeout".to_string());
+        for _ in 0..2 {
+            let result =
+                job::fail(job::MAIL_QUEUE, build_job(), &err, conn.mq, logger);
+            assert!(result.is_ok());
+        }
+        let entries = retry::range(0, -1, conn.mq).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert!(!retry::run_now("unknown", conn.mq).unwrap());
+        assert!(retry::run_now(&entries[0].id, conn.mq).unwrap());
+        assert!(retry::del(&entries[1].id, conn.mq).unwrap());
+        assert!(!retry::del(&entries[1].id, conn.mq).unwrap());
+        assert_eq!(retry::len(conn.mq).unwrap(), 0);
+
+        let now = Utc::now().timestamp();
+        let a = delay::enqueue_at(build_job(), now + 60, conn.mq).unwrap();
+        let b = delay::enqueue_at(build_job(), now + 60, conn.mq).unwrap();
+
+        assert!(delay::run_now(&a.id, conn.mq).unwrap());
+        assert!(delay::del(&b.id, conn.mq).unwrap());
+        assert_eq!(delay::len(conn.mq).unwrap(), 0);
+
+        // the retried one and the delayed one
+        assert_eq!(pending::len(job::MAIL_QUEUE, conn.mq).unwrap(), 2);
+    });
+}
@@ -1,9 +1,12 @@
 use diesel::{self, prelude::*};
+use diesel::PgConnection;
 use chrono::{Utc, TimeZone};
 use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
 use serde_json::Value;
 use uuid::Uuid;
 
+use eloquentlog_console_api::logger::Logger;
 use eloquentlog_console_api::model;
 
 use crate::{
@@ -12,7 +15,7 @@ use crate::{
 
 #[test]
 fn test_lrange_no_message() {
-    run_test(|client, conn, _, _| {
+    run_test(|client, conn, _, logger| {
         let u = USERS.get("oswald").unwrap().clone();
         let password = make_raw_password(&u);
         let user = load_user(u, conn.db);
@@ -41,14 +44,31 @@ fn test_lrange_no_message() {
         let result: Value = serde_json::from_str(&body).unwrap();
         let token = result["token"].as_str().unwrap();
 
-        let namespace_key = "key";
-        let stream_slug = "slug";
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let m = model::membership::NewMembership {
+            namespace_id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let _ = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .execute(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
 
         let mut res = client
-            .get(format!(
-                "/v1/message/{}/lrange/{}/0/2",
-                namespace_key, stream_slug
-            ))
+            .get(format!("/v1/message/{}/lrange/{}/0/2", ns.uuid, s.uuid))
             .header(Header::new("X-Requested-With", "XMLHttpRequest"))
             .header(Header::new("Authorization", format!("Bearer {}", token)))
             .dispatch();
@@ -60,7 +80,7 @@ fn test_lrange_no_message() {
 
 #[test]
 fn test_lrange_messages() {
-    run_test(|client, conn, _, _| {
+    run_test(|client, conn, _, logger| {
         let u = USERS.get("oswald").unwrap().clone();
         let password = make_raw_password(&u);
         let user = load_user(u, conn.db);
@@ -97,6 +117,14 @@ fn test_lrange_messages() {
                 .get_result::<i64>(conn.db)
                 .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
 
+        let m = model::membership::NewMembership {
+            namespace_id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
         let mut s = STREAMS.get("oswald's stream").unwrap().clone();
         s.namespace_id = namespace_id;
         let stream_id = diesel::insert_into(model::stream::streams::table)
@@ -120,6 +148,7 @@ fn test_lrange_messages() {
             content: None,
             created_at: dt.naive_utc(),
             updated_at: dt.naive_utc(),
+            redactions_count: 0,
         };
 
         let id = diesel::insert_into(model::message::messages::table)
@@ -128,14 +157,8 @@ fn test_lrange_messages() {
             .get_result::<i64>(conn.db)
             .unwrap_or_else(|_| panic!("Error inserting: {}", m));
 
-        let namespace_key = "key";
-        let stream_slug = "slug"; // FIXME
-
         let mut res = client
-            .get(format!(
-                "/v1/message/{}/lrange/{}/0/2",
-                namespace_key, stream_slug
-            ))
+            .get(format!("/v1/message/{}/lrange/{}/0/2", ns.uuid, s.uuid))
             .header(Header::new("X-Requested-With", "XMLHttpRequest"))
             .header(Header::new("Authorization", format!("Bearer {}", token)))
             .dispatch();
@@ -146,6 +169,7 @@ fn test_lrange_messages() {
             res.body_string().unwrap(),
             minify(format!(
                 r#"[{{
+"annotations_count": 0,
 "message": {{
   "agent_id": 1,
   "agent_type": "Person",
@@ -156,6 +180,7 @@ fn test_lrange_messages() {
   "id": {},
   "lang": "en",
   "level": "Information",
+  "redactions_count": 0,
   "stream_id": 1,
   "title": "title",
   "updated_at": "2019-08-07T06:05:04.333"
@@ -169,7 +194,7 @@ fn test_lrange_messages() {
 
 #[test]
 fn test_append_with_validation_errors() {
-    run_test(|client, conn, _, _| {
+    run_test(|client, conn, _, logger| {
         let u = USERS.get("oswald").unwrap().clone();
         let password = make_raw_password(&u);
         let user = load_user(u, conn.db);
@@ -198,14 +223,32 @@ fn test_append_with_validation_errors() {
         let result: Value = serde_json::from_str(&body).unwrap();
         let token = result["token"].as_str().unwrap();
 
-        let namespace_key = "key";
-        let stream_slug = "slug";
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let m = model::membership::NewMembership {
+            namespace_id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let stream_uuid = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .returning(model::stream::streams::uuid)
+            .get_result::<Uuid>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
 
         let mut res = client
-            .post(format!(
-                "/v1/message/{}/append/{}",
-                namespace_key, stream_slug
-            ))
+            .post(format!("/v1/message/{}/append/{}", ns.uuid, stream_uuid))
             .header(ContentType::JSON)
             .header(Header::new("Authorization", format!("Bearer {}", token)))
             .header(Header::new("X-Requested-With", "XMLHttpRequest"))
@@ -228,7 +271,7 @@ fn test_append_with_validation_errors() {
 
 #[test]
 fn test_append() {
-    run_test(|client, conn, _, _| {
+    run_test(|client, conn, _, logger| {
         let u = USERS.get("oswald").unwrap().clone();
         let password = make_raw_password(&u);
         let user = load_user(u, conn.db);
@@ -257,9 +300,6 @@ fn test_append() {
         let result: Value = serde_json::from_str(&body).unwrap();
         let token = result["token"].as_str().unwrap();
 
-        let namespace_key = "key";
-        let stream_slug = "slug";
-
         let ns = NAMESPACES.get("piano").unwrap();
         let namespace_id =
             diesel::insert_into(model::namespace::namespaces::table)
@@ -268,6 +308,14 @@ fn test_append() {
                 .get_result::<i64>(conn.db)
                 .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
 
+        let m = model::membership::NewMembership {
+            namespace_id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
         let mut s = STREAMS.get("oswald's stream").unwrap().clone();
         s.namespace_id = namespace_id;
         let stream_uuid = diesel::insert_into(model::stream::streams::table)
@@ -277,10 +325,7 @@ fn test_append() {
             .unwrap_or_else(|_| panic!("Error inserting: {}", s));
 
         let mut res = client
-            .post(format!(
-                "/v1/message/{}/append/{}",
-                namespace_key, stream_slug
-            ))
+            .post(format!("/v1/message/{}/append/{}", ns.uuid, stream_uuid))
             .header(ContentType::JSON)
             .header(Header::new("Authorization", format!("Bearer {}", token)))
             .header(Header::new("X-Requested-With", "XMLHttpRequest"))
@@ -301,5 +346,230 @@ fn test_append() {
 
         assert_eq!(res.status(), Status::Ok);
         assert!(res.body_string().unwrap().contains("id"));
+
+        // not found in the namespace
+        let res = client
+            .post(format!("/v1/message/{}/append/{}", ns.uuid, Uuid::nil()))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"agent_id": 1, "stream_id": 1, "title": "New message"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
+
+fn setup_message(
+    role: model::membership::MembershipRole,
+    conn: &PgConnection,
+    logger: &Logger,
+) -> (model::user::User, String, String, String, i64) {
+    let u = USERS.get("oswald").unwrap().clone();
+    let password = make_raw_password(&u);
+    let user = load_user(u, conn);
+
+    let ns = model::namespace::NewNamespace {
+        name: "piano".to_string(),
+
+        ..Default::default()
+    };
+    let namespace =
+        model::namespace::Namespace::insert(&ns, conn, logger).unwrap();
+
+    let m = model::membership::NewMembership {
+        namespace_id: namespace.id,
+        user_id: user.id,
+        role,
+    };
+    let _ = model::membership::Membership::insert(&m, conn, logger).unwrap();
+
+    let s = model::stream::NewStream {
+        namespace_id: namespace.id,
+
+        ..Default::default()
+    };
+    let stream = model::stream::Stream::insert(&s, conn, logger).unwrap();
+
+    let m = model::message::NewMessage {
+        agent_id: user.id,
+        stream_id: stream.id,
+        title: Some("title".to_string()),
+        content: Some("token: s3cr3t".to_string()),
+
+        ..Default::default()
+    };
+    let id = model::message::Message::insert(&m, conn, logger).unwrap();
+
+    (
+        user,
+        password,
+        namespace.uuid.to_string(),
+        stream.uuid.to_string(),
+        id,
+    )
+}
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_hget_and_hset_by_member() {
+    run_test(|client, conn, _, logger| {
+        let (user, password, namespace_key, stream_uuid, id) = setup_message(
+            model::membership::MembershipRole::Member,
+            conn.db,
+            logger,
+        );
+        let token = login(client, &user.email, &password);
+
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/hget/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["message"]["id"], id);
+        assert_eq!(result["message"]["stream"], stream_uuid.as_str());
+        assert_eq!(result["message"]["content"], "token: s3cr3t");
+
+        let res = client
+            .get(format!(
+                "/v1/message/{}/hget/{}/{}",
+                namespace_key,
+                stream_uuid,
+                id + 1
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+
+        let res = client
+            .patch(format!(
+                "/v1/message/{}/hset/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"content": "token: [redacted]"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .patch(format!(
+                "/v1/message/{}/del/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+    });
+}
+
+#[test]
+fn test_hset_and_del_by_owner() {
+    run_test(|client, conn, _, logger| {
+        let (user, password, namespace_key, stream_uuid, id) = setup_message(
+            model::membership::MembershipRole::Owner,
+            conn.db,
+            logger,
+        );
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .patch(format!(
+                "/v1/message/{}/hset/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"title": "{}"}}"#, "a".repeat(256)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch(format!(
+                "/v1/message/{}/hset/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"content": "token: [redacted]"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["message"]["title"], "title");
+        assert_eq!(result["message"]["content"], "token: [redacted]");
+
+        let res = client
+            .patch(format!(
+                "/v1/message/{}/del/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let deletions = model::message_deletion::message_deletions::table
+            .load::<model::message_deletion::MessageDeletion>(conn.db)
+            .unwrap();
+        assert_eq!(deletions.len(), 1);
+        assert_eq!(deletions[0].message_id, id);
+        assert_eq!(deletions[0].user_id, user.id);
+
+        let res = client
+            .get(format!(
+                "/v1/message/{}/hget/{}/{}",
+                namespace_key, stream_uuid, id
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
     });
 }
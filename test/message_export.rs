@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::Read;
+
+use chrono::{Duration, Utc};
+use flate2::read::GzDecoder;
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use eloquentlog_console_api::export;
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+use eloquentlog_console_api::model::token::{Claims, TokenData, VerificationClaims};
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_export_with_invalid_time_range() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let mut res = client
+            .post(format!("/v1/message/{}/export", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "stream": "{}",
+                    "since": "2021-01-02T00:00:00",
+                    "until": "2021-01-01T00:00:00"
+                }}"#,
+                stream.uuid,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["error"]["code"], "invalid_fields");
+        assert_eq!(result["error"]["errors"][0]["field"], "until");
+    });
+}
+
+#[test]
+fn test_export_and_download() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        for level in &[
+            model::message::LogLevel::Information,
+            model::message::LogLevel::Error,
+        ] {
+            let message = model::message::NewMessage {
+                agent_id: user.id,
+                stream_id: stream.id,
+                level: level.clone(),
+                title: Some(format!("{}, title", level)),
+
+                ..Default::default()
+            };
+            let _ = model::message::Message::insert(&message, conn.db, logger);
+        }
+
+        let token = login(client, &user.email, &password);
+
+        let now = Utc::now().naive_utc();
+        let res = client
+            .post(format!("/v1/message/{}/export", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "stream": "{}",
+                    "format": "csv",
+                    "level": "warning",
+                    "since": "{}",
+                    "until": "{}"
+                }}"#,
+                stream.uuid,
+                (now - Duration::hours(1)).format(export::TIME_FORMAT),
+                (now + Duration::hours(1)).format(export::TIME_FORMAT),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Accepted);
+
+        let mut queue = Queue::new(job::EXPORT_QUEUE, conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        assert_eq!(job.kind(), job::JobKind::ExportMessages);
+
+        // the file is kept even if the email could not be sent
+        let _ = job.invoke(conn.db, config, logger);
+
+        let dir = format!("{}/{}", config.export_directory, user.uuid);
+        let name = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .find(|n| n.ends_with(".csv.gz"))
+            .unwrap();
+
+        let data = TokenData {
+            value: export::subject(&user.uuid, &name),
+            granted_at: Utc::now().timestamp(),
+            expires_at: (Utc::now() + Duration::hours(1)).timestamp(),
+        };
+        let t = VerificationClaims::encode(
+            data,
+            &config.verification_token_issuer,
+            &config.verification_token_key_id,
+            &config.verification_token_secret,
+        );
+
+        let res = client
+            .get(format!(
+                "/v1/message/export/download/{}?t={}",
+                name, "invalid"
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let mut res = client
+            .get(format!("/v1/message/export/download/{}?t={}", name, t))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_bytes().unwrap();
+        let mut csv = String::new();
+        GzDecoder::new(&body[..]).read_to_string(&mut csv).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("id,code,lang,level"));
+        assert!(lines[1].contains(",error,"));
+        assert!(lines[1].contains("\"error, title\""));
+
+        let _ = fs::remove_dir_all(&dir);
+    });
+}
@@ -0,0 +1,162 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_import_into_unknown_stream() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .post(format!(
+                "/v1/message/{}/import/{}",
+                namespace.uuid,
+                Uuid::new_v4()
+            ))
+            .header(ContentType::Plain)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"title": "a", "created_at": "2021-01-01T00:00:00Z"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
+
+#[test]
+fn test_import_toml() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let mut res = client
+            .post(format!(
+                "/v1/message/{}/import/{}?format=toml",
+                namespace.uuid, stream.uuid
+            ))
+            .header(ContentType::Plain)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(
+                r#"
+[[messages]]
+title = "a"
+level = "error"
+created_at = 2021-01-01T00:00:00Z
+
+[[messages]]
+level = "warning"
+created_at = 2021-01-01T00:00:00Z
+
+[[messages]]
+title = "c"
+created_at = "2021-01-02T00:00:00"
+"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["import"]["total"], 3);
+        assert_eq!(result["import"]["imported"], 2);
+        assert_eq!(result["import"]["failed"], 1);
+        assert_eq!(result["import"]["errors"][0]["record"], 2);
+        assert_eq!(
+            result["import"]["errors"][0]["errors"][0]["field"],
+            "title"
+        );
+
+        let res = client
+            .post(format!(
+                "/v1/message/{}/import/{}?format=xml",
+                namespace.uuid, stream.uuid
+            ))
+            .header(ContentType::Plain)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("")
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
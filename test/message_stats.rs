@@ -0,0 +1,197 @@
+use chrono::{Duration, Utc};
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use eloquentlog_console_api::export;
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_stats_with_invalid_bucket() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/stats?bucket=week&since={}&until={}",
+                namespace.uuid, "2021-01-01T00:00:00", "2021-01-02T00:00:00"
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["error"]["errors"][0]["field"], "bucket");
+    });
+}
+
+#[test]
+fn test_stats_with_rollups() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        // two days ago, and now
+        let now = Utc::now().naive_utc();
+        let messages: Vec<model::message::NewMessage> = vec![
+            (now - Duration::days(2), Some("E1".to_string())),
+            (now - Duration::days(2), Some("E1".to_string())),
+            (now, Some("E2".to_string())),
+            (now, None),
+        ]
+        .into_iter()
+        .map(|(created_at, code)| {
+            model::message::NewMessage {
+                agent_id: user.id,
+                stream_id: stream.id,
+                level: model::message::LogLevel::Error,
+                code,
+                title: Some("title".to_string()),
+                created_at: Some(created_at),
+
+                ..Default::default()
+            }
+        })
+        .collect();
+        let _ = model::message::Message::insert_all(&messages, conn.db, logger)
+            .unwrap();
+
+        let job = job::Job::new(job::Payload::RollupMessageCounts(
+            job::RollupMessageCounts::default(),
+        ));
+        assert_eq!(job.queue(), job::MAINTENANCE_QUEUE);
+        assert!(job.invoke(conn.db, config, logger).is_ok());
+
+        let token = login(client, &user.email, &password);
+
+        // 30 days (from rollups and messages after the watermark)
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/stats?stream={}&bucket=day&since={}&until={}",
+                namespace.uuid,
+                stream.uuid,
+                (now - Duration::days(29)).format(export::TIME_FORMAT),
+                (now + Duration::days(1)).format(export::TIME_FORMAT),
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let counts = result["stats"]["counts"].as_array().unwrap();
+        let total: i64 =
+            counts.iter().map(|c| c["count"].as_i64().unwrap()).sum();
+        assert_eq!(total, 4);
+        assert!(counts.iter().all(|c| c["level"] == "error"));
+
+        let codes = result["stats"]["codes"].as_array().unwrap();
+        assert_eq!(codes.len(), 2);
+        assert_eq!(codes[0]["code"], "E1");
+        assert_eq!(codes[0]["count"], 2);
+
+        // an hour (from messages)
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/stats?bucket=minute&since={}&until={}&top=1",
+                namespace.uuid,
+                (now - Duration::hours(1)).format(export::TIME_FORMAT),
+                (now + Duration::hours(1)).format(export::TIME_FORMAT),
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["stats"]["stream"], Value::Null);
+        let counts = result["stats"]["counts"].as_array().unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0]["count"], 2);
+
+        let codes = result["stats"]["codes"].as_array().unwrap();
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0]["code"], "E2");
+    });
+}
@@ -0,0 +1,252 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_message_view_by_member() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        for (level, title) in &[
+            (model::message::LogLevel::Error, "connection refused"),
+            (model::message::LogLevel::Debug, "connected"),
+            (model::message::LogLevel::Critical, "disk full"),
+        ] {
+            let m = model::message::NewMessage {
+                agent_id: user.id,
+                stream_id: stream.id,
+                level: level.clone(),
+                title: Some(title.to_string()),
+
+                ..Default::default()
+            };
+            let _ =
+                model::message::Message::insert(&m, conn.db, logger).unwrap();
+        }
+
+        let token = login(client, &user.email, &password);
+
+        // only owners can share views
+        let res = client
+            .post(format!("/v1/message/{}/view/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"name": "errors", "shared": true}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .post(format!("/v1/message/{}/view/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"name": "errors", "levels": ["fatal"]}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .post(format!("/v1/message/{}/view/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "name": "errors",
+                    "stream": "{}",
+                    "levels": ["error", "critical"],
+                    "time_window": 3600
+                }}"#,
+                stream.uuid
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let uuid = result["message_view"]["uuid"].as_str().unwrap().to_string();
+        assert_eq!(result["message_view"]["stream"], stream.uuid.to_string());
+
+        let mut res = client
+            .get(format!("/v1/message/{}/view/hgetall", namespace.uuid))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+        assert_eq!(result[0]["message_view"]["name"], "errors");
+
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/view/lrange/{}/0/9",
+                namespace.uuid, uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let view_body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&view_body).unwrap();
+        let titles: Vec<&str> = result
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["message"]["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["disk full", "connection refused"]);
+
+        // the search by the same query returns the same result
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/search/0/9?stream={}&levels=error,critical&\
+                 time_window=3600",
+                namespace.uuid, stream.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(res.body_string().unwrap(), view_body);
+
+        // the range is clamped instead of overflowing
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/search/0/{}?stream={}&levels=error,critical&\
+                 time_window=3600",
+                namespace.uuid,
+                u64::MAX,
+                stream.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(res.body_string().unwrap(), view_body);
+
+        let mut res = client
+            .get(format!(
+                "/v1/message/{}/search/{}/{}",
+                namespace.uuid,
+                u64::MAX,
+                u64::MAX
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(res.body_string().unwrap(), "[]");
+
+        let res = client
+            .get(format!(
+                "/v1/message/{}/search/0/9?time_window=x",
+                namespace.uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch(format!("/v1/message/{}/view/hset/{}", namespace.uuid, uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"name": "debug", "levels": ["debug"]}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["message_view"]["name"], "debug");
+        assert_eq!(result["message_view"]["stream"], Value::Null);
+
+        let res = client
+            .patch(format!("/v1/message/{}/view/del/{}", namespace.uuid, uuid))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .get(format!(
+                "/v1/message/{}/view/lrange/{}/0/9",
+                namespace.uuid, uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
@@ -0,0 +1,98 @@
+use chrono::{Duration, Utc};
+use rocket::http::{ContentType, Header, Status};
+
+use eloquentlog_console_api::model;
+
+use crate::run_test;
+
+#[test]
+fn test_metrics() {
+    run_test(|client, _, config, _| {
+        let res = client
+            .get("/_/health")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client.get("/_/metrics").dispatch();
+        assert_eq!(res.status(), Status::NotFound);
+
+        let res = client
+            .get("/_/metrics")
+            .header(Header::new("Authorization", "Bearer unknown"))
+            .dispatch();
+        assert_eq!(res.status(), Status::NotFound);
+
+        let token = config.metrics_token.as_ref().unwrap();
+        let mut res = client
+            .get("/_/metrics")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(
+            res.headers().get_one("Content-Type"),
+            Some("text/plain; version=0.0.4")
+        );
+
+        let body = res.body_string().unwrap();
+        assert!(body.contains(r#"route="/_/health",status="200""#));
+        assert!(body.contains(
+            "eloquentlog_pool_connections{pool=\"database\",state=\"max\"}"
+        ));
+        assert!(body.contains("eloquentlog_job_queue_length{queue=\"mail\"}"));
+    });
+}
+
+#[test]
+fn test_metrics_stream_messages() {
+    run_test(|client, conn, config, logger| {
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        // only ones in the last hour are counted
+        let now = Utc::now().naive_utc();
+        let messages: Vec<model::message::NewMessage> =
+            vec![now - Duration::hours(2), now, now]
+                .into_iter()
+                .map(|created_at| {
+                    model::message::NewMessage {
+                        agent_id: 1,
+                        stream_id: stream.id,
+                        title: Some("title".to_string()),
+                        created_at: Some(created_at),
+
+                        ..Default::default()
+                    }
+                })
+                .collect();
+        let _ = model::message::Message::insert_all(&messages, conn.db, logger)
+            .unwrap();
+
+        let token = config.metrics_token.as_ref().unwrap();
+        let mut res = client
+            .get("/_/metrics")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        assert!(body.contains(&format!(
+            "eloquentlog_stream_messages_ingested_last_hour{{stream=\"{}\"}} 2",
+            stream.uuid
+        )));
+    });
+}
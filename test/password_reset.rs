@@ -44,12 +44,14 @@ fn test_password_reset_with_invalid_token() {
         let request = password_reset_request_by(&user, &client);
         assert!(request.is_ok());
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendPasswordResetEmail);
-        assert!(!job.args.is_empty());
-
-        let session_id = job.args[1].to_string();
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendPasswordResetEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
+
+        let session_id = payload.session_id;
         let token = "invalid-token";
 
         let res = client
@@ -96,13 +98,15 @@ fn test_password_reset_with_invalid_session_id() {
         let request = password_reset_request_by(&user, &client);
         assert!(request.is_ok());
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendPasswordResetEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendPasswordResetEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
         let session_id = "invalid-session_id";
-        let token = job.args[2].to_string();
+        let token = payload.token;
 
         let res = client
             .get(format!("/_/password/reset/{}", session_id))
@@ -149,12 +153,14 @@ fn test_password_reset_without_authorization_header() {
         let request = password_reset_request_by(&user, &client);
         assert!(request.is_ok());
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendPasswordResetEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendPasswordResetEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
+        let session_id = payload.session_id;
 
         let res = client
             .get(format!("/_/password/reset/{}", session_id))
@@ -198,13 +204,15 @@ fn test_password_reset_without_x_requested_with_header() {
         let request = password_reset_request_by(&user, &client);
         assert!(request.is_ok());
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendPasswordResetEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendPasswordResetEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
-        let token = job.args[1].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.session_id;
 
         let res = client
             .get(format!("/_/password/reset/{}", session_id))
@@ -248,13 +256,15 @@ fn test_password_reset() {
         let request = password_reset_request_by(&user, &client);
         assert!(request.is_ok());
 
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendPasswordResetEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendPasswordResetEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
-        let token = job.args[2].to_string();
+        let session_id = payload.session_id.to_string();
+        let token = payload.token;
 
         let res = client
             .get(format!("/_/password/reset/{}", session_id))
@@ -60,6 +60,7 @@ fn test_password_reset_request() {
             .put("/_/password/reset")
             .header(ContentType::JSON)
             .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("X-Request-Id", "password-reset-1"))
             .body(format!(
                 r#"{{
                   "email": "{}"
@@ -69,17 +70,25 @@ fn test_password_reset_request() {
             .dispatch();
 
         assert_eq!(res.status(), Status::Ok);
+        assert_eq!(
+            res.headers().get_one("X-Request-Id"),
+            Some("password-reset-1")
+        );
 
         let result = model::user::User::find_by_email(&email, conn.db, logger);
         assert!(result.unwrap().reset_password_token.is_some());
 
         // TODO: check sent email
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendPasswordResetEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        // the worker logs it with the id of the request
+        assert_eq!(job.request_id, Some("password-reset-1".to_string()));
+        let payload = match job.payload {
+            job::Payload::SendPasswordResetEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
+        let session_id = payload.session_id;
         let key = format!("pr-{}", session_id);
         let value: Result<String, RedisError> = conn.ss.get(key);
         assert!(value.is_ok());
@@ -0,0 +1,219 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use diesel::prelude::*;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_redaction_rule_hset_by_member() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .post(format!("/v1/redaction/{}/rule/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"detector": "email"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+    });
+}
+
+#[test]
+fn test_redaction_on_append() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Owner,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let s = model::stream::NewStream {
+            namespace_id: namespace.id,
+
+            ..Default::default()
+        };
+        let stream =
+            model::stream::Stream::insert(&s, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        for body in &[
+            r#"{"detector": "email"}"#,
+            r#"{"detector": "custom", "name": "api_key", "pattern": "sk_[0-9a-z]{8}"}"#,
+        ] {
+            let res = client
+                .post(format!("/v1/redaction/{}/rule/hset", namespace.uuid))
+                .header(ContentType::JSON)
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(*body)
+                .dispatch();
+
+            assert_eq!(res.status(), Status::Ok);
+        }
+
+        let res = client
+            .post(format!("/v1/redaction/{}/rule/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"detector": "custom", "name": "broken", "pattern": "("}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .get(format!("/v1/redaction/{}/rule/hgetall", namespace.uuid))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let rules = result.as_array().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0]["redaction_rule"]["detector"], "email");
+        assert_eq!(rules[1]["redaction_rule"]["name"], "api_key");
+
+        let mut res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace.uuid, stream.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(
+                r#"{
+                    "agent_id": 1,
+                    "stream_id": 1,
+                    "title": "failed for oswald@example.org",
+                    "content": "key: sk_0123abcd"
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["message"]["redactions_count"], 2);
+
+        let message = model::message::messages::table
+            .filter(model::message::messages::stream_id.eq(stream.id))
+            .first::<model::message::Message>(conn.db)
+            .unwrap();
+        assert_eq!(message.title, "failed for [REDACTED:EMAIL]");
+        assert_eq!(message.content.unwrap(), "key: [REDACTED:API_KEY]");
+        assert_eq!(message.redactions_count, 2);
+
+        // an edit is redacted too
+        let mut res = client
+            .patch(format!(
+                "/v1/message/{}/hset/{}/{}",
+                namespace.uuid, stream.uuid, message.id
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"content": "key: sk_4567efgh"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["message"]["content"], "key: [REDACTED:API_KEY]");
+
+        let message = model::message::messages::table
+            .filter(model::message::messages::id.eq(message.id))
+            .first::<model::message::Message>(conn.db)
+            .unwrap();
+        assert_eq!(message.content.unwrap(), "key: [REDACTED:API_KEY]");
+        assert_eq!(message.redactions_count, 3);
+
+        let uuid = rules[1]["redaction_rule"]["uuid"].as_str().unwrap();
+        let res = client
+            .patch(format!(
+                "/v1/redaction/{}/rule/del/{}",
+                namespace.uuid, uuid
+            ))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
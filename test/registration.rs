@@ -71,12 +71,14 @@ fn test_register() {
         assert!(result.is_none());
 
         // TODO: check sent email
-        let mut queue = Queue::new("default", conn.mq);
-        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
-        assert_eq!(job.kind, job::JobKind::SendUserActivationEmail);
-        assert!(!job.args.is_empty());
+        let mut queue = Queue::new("mail", conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        let payload = match job.payload {
+            job::Payload::SendUserActivationEmail(p) => p,
+            p => panic!("unexpected payload: {:?}", p),
+        };
 
-        let session_id = job.args[1].to_string();
+        let session_id = payload.session_id;
         let key = format!("ua-{}", session_id);
 
         let result: Result<String, RedisError> = conn.ss.get(key);
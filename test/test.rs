@@ -6,6 +6,7 @@ extern crate lazy_static;
 extern crate chrono;
 extern crate diesel;
 extern crate dotenv;
+extern crate flate2;
 extern crate fourche;
 extern crate fnv;
 extern crate parking_lot;
@@ -20,15 +21,30 @@ extern crate eloquentlog_console_api;
 
 mod activation;
 mod authentication;
+mod deregistration;
 mod error;
 mod health;
+mod metrics;
 mod registration;
 mod password_reset;
 mod password_reset_request;
 
 mod access_token;
+mod alert;
+mod annotation;
+mod audit;
+mod email;
+mod issue;
+mod job;
 mod message;
+mod message_export;
+mod message_import;
+mod message_stats;
+mod message_view;
 mod namespace;
+mod redaction;
+mod totp;
+mod webhook;
 
 use std::panic::{self, AssertUnwindSafe};
 use regex::Regex;
@@ -0,0 +1,247 @@
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+#[test]
+fn test_totp_append() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let mut res = client
+            .put("/v1/totp/append")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let uri = result["totp"]["provisioning_uri"].as_str().unwrap();
+        assert!(uri.starts_with("otpauth://totp/Eloquentlog:"));
+
+        let res = client
+            .patch("/v1/totp/confirm")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"code": "000000x"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
+
+#[test]
+fn test_login_with_recovery_code() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let t =
+            model::user_totp::UserTotp::insert(&user, conn.db, logger).unwrap();
+        let _ = t.confirm(conn.db, logger).unwrap();
+        let codes = model::user_recovery_code::UserRecoveryCode::generate_for(
+            &user, conn.db, logger,
+        )
+        .unwrap();
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        // the second step is required
+        assert_eq!(res.status(), Status::Accepted);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result["token"].is_null());
+        let session_id = result["totp"]["session_id"].as_str().unwrap();
+
+        let res = client
+            .post("/_/login/totp")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "session_id": "{}",
+                    "code": "000000"
+                }}"#,
+                session_id,
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let mut res = client
+            .post("/_/login/totp")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "session_id": "{}",
+                    "code": "{}"
+                }}"#,
+                session_id, codes[0],
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // wrong password
+        let res = client
+            .patch("/v1/totp/del")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "password": "wrong",
+                    "code": "{}"
+                }}"#,
+                codes[1],
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .patch("/v1/totp/del")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "password": "{}",
+                    "code": "{}"
+                }}"#,
+                password, codes[1],
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert!(model::user_totp::UserTotp::find_by_user(
+            &user, conn.db, logger
+        )
+        .is_none());
+    });
+}
+
+#[test]
+fn test_login_totp_lockout_across_sessions() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let t =
+            model::user_totp::UserTotp::insert(&user, conn.db, logger).unwrap();
+        let _ = t.confirm(conn.db, logger).unwrap();
+        let codes = model::user_recovery_code::UserRecoveryCode::generate_for(
+            &user, conn.db, logger,
+        )
+        .unwrap();
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let login = || {
+            let mut res = client
+                .post("/_/login")
+                .header(ContentType::JSON)
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(format!(
+                    r#"{{
+                        "username": "{}",
+                        "password": "{}"
+                    }}"#,
+                    user.email, password,
+                ))
+                .dispatch();
+            assert_eq!(res.status(), Status::Accepted);
+
+            let body = res.body_string().unwrap();
+            let result: Value = serde_json::from_str(&body).unwrap();
+            result["totp"]["session_id"].as_str().unwrap().to_string()
+        };
+        let verify = |session_id: &str, code: &str| {
+            client
+                .post("/_/login/totp")
+                .header(ContentType::JSON)
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(format!(
+                    r#"{{
+                        "session_id": "{}",
+                        "code": "{}"
+                    }}"#,
+                    session_id, code,
+                ))
+                .dispatch()
+                .status()
+        };
+
+        let session_id = login();
+        for _ in 0..5 {
+            assert_eq!(verify(&session_id, "000000"), Status::Unauthorized);
+        }
+
+        // a new session doesn't reset the limit (even with a valid code)
+        let session_id = login();
+        assert_eq!(verify(&session_id, &codes[0]), Status::TooManyRequests);
+    });
+}
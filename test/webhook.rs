@@ -0,0 +1,414 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use eloquentlog_console_api::job::{self, pending, Job, JobError, Payload};
+use eloquentlog_console_api::model;
+use eloquentlog_console_api::webhook::{
+    self, DELIVERY_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+};
+
+use crate::{run_test, load_user, make_raw_password, USERS};
+
+// (headers, body)
+type Request = (Vec<(String, String)>, String);
+
+fn login(client: &Client, email: &str, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
+/// Starts a stub server which accepts a request, responds with the status,
+/// and sends the request back through the channel.
+fn serve(status: &'static str) -> (String, mpsc::Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut headers = vec![];
+        let mut line = String::new();
+        let _ = reader.read_line(&mut line); // request line
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(i) = line.find(':') {
+                let (name, value) = line.split_at(i);
+                headers
+                    .push((name.to_lowercase(), value[1..].trim().to_string()));
+            }
+        }
+
+        let length = headers
+            .iter()
+            .find(|(name, _)| name == "content-length")
+            .map_or(0, |(_, v)| v.parse::<usize>().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let mut stream = stream;
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        );
+        let _ = stream.flush();
+
+        tx.send((headers, String::from_utf8(body).unwrap()))
+            .unwrap();
+    });
+    (url, rx)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+    headers
+        .iter()
+        .find(|(n, _)| n == &name.to_lowercase())
+        .map(|(_, v)| v.as_str())
+        .unwrap()
+}
+
+#[test]
+fn test_webhook_hset_by_member() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::Member,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let res = client
+            .post(format!("/v1/webhook/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(r#"{"url": "https://example.org/hook"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+    });
+}
+
+#[test]
+fn test_webhook_ping_and_deliveries() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::PrimaryOwner,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        let token = login(client, &user.email, &password);
+
+        let (url, rx) = serve("200 OK");
+
+        let mut res = client
+            .post(format!("/v1/webhook/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(r#"{{"url": "{}", "level": "error"}}"#, url))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let webhook_uuid =
+            result["webhook"]["uuid"].as_str().unwrap().to_string();
+        let secret = result["webhook"]["secret"].as_str().unwrap().to_string();
+        assert_eq!(result["webhook"]["level"], "error");
+
+        // the secret is not shown again
+        let mut res = client
+            .get(format!("/v1/webhook/{}/hgetall", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let webhooks = result.as_array().unwrap();
+        assert_eq!(webhooks.len(), 1);
+        assert_eq!(webhooks[0]["webhook"]["secret"], Value::Null);
+
+        let mut res = client
+            .post(format!(
+                "/v1/webhook/{}/ping/{}",
+                namespace.uuid, webhook_uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Accepted);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let delivery_uuid = result["delivery"]["uuid"].as_str().unwrap();
+
+        let mut queue = Queue::new(job::WEBHOOK_QUEUE, conn.mq);
+        let job = queue.dequeue::<job::Job>().ok().unwrap();
+        assert!(job.invoke(conn.db, config, logger).is_ok());
+
+        // the stub has received a signed request
+        let (headers, body) = rx.recv().unwrap();
+        assert_eq!(header(&headers, DELIVERY_HEADER), delivery_uuid);
+        let timestamp =
+            header(&headers, TIMESTAMP_HEADER).parse::<i64>().unwrap();
+        assert_eq!(
+            header(&headers, SIGNATURE_HEADER),
+            webhook::sign(&secret, timestamp, &body)
+        );
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["event"], webhook::PING_EVENT);
+        assert_eq!(payload["webhook"], webhook_uuid.as_str());
+
+        // a failure is transient, and it's logged
+        let (url, _rx) = serve("500 Internal Server Error");
+        let w = model::webhook::NewWebhook {
+            namespace_id: namespace.id,
+            url,
+            secret: model::webhook::Webhook::generate_secret(),
+
+            ..Default::default()
+        };
+        let failing =
+            model::webhook::Webhook::insert(&w, conn.db, logger).unwrap();
+        let job =
+            job::Job::new(job::Payload::DeliverWebhook(job::WebhookEvent {
+                webhook_id: failing.id,
+                delivery_id: delivery_uuid.to_string(),
+                event: webhook::PING_EVENT.to_string(),
+                body: "{}".to_string(),
+            }));
+        let result = job.invoke(conn.db, config, logger);
+        assert!(matches!(result, Err(JobError::Transient(_))));
+
+        let mut res = client
+            .get(format!(
+                "/v1/webhook/{}/lrange/{}/0/9",
+                namespace.uuid, failing.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let deliveries = result.as_array().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0]["delivery"]["status_code"], 500);
+        assert_eq!(
+            deliveries[0]["delivery"]["succeeded"].as_bool(),
+            Some(false)
+        );
+
+        let mut res = client
+            .get(format!(
+                "/v1/webhook/{}/lrange/{}/0/9",
+                namespace.uuid, webhook_uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let deliveries = result.as_array().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0]["delivery"]["uuid"], delivery_uuid);
+        assert_eq!(deliveries[0]["delivery"]["status_code"], 200);
+        assert_eq!(
+            deliveries[0]["delivery"]["succeeded"].as_bool(),
+            Some(true)
+        );
+
+        let res = client
+            .patch(format!(
+                "/v1/webhook/{}/del/{}",
+                namespace.uuid, webhook_uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_webhook_deliveries_on_append() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let ns = model::namespace::NewNamespace {
+            name: "piano".to_string(),
+
+            ..Default::default()
+        };
+        let namespace =
+            model::namespace::Namespace::insert(&ns, conn.db, logger).unwrap();
+
+        let m = model::membership::NewMembership {
+            namespace_id: namespace.id,
+            user_id: user.id,
+            role: model::membership::MembershipRole::PrimaryOwner,
+        };
+        let _ =
+            model::membership::Membership::insert(&m, conn.db, logger).unwrap();
+
+        // the message goes to the second one
+        let mut streams = vec![];
+        for name in &["main", "api"] {
+            let s = model::stream::NewStream {
+                namespace_id: namespace.id,
+                name: name.to_string(),
+
+                ..Default::default()
+            };
+            streams.push(
+                model::stream::Stream::insert(&s, conn.db, logger).unwrap(),
+            );
+        }
+
+        let token = login(client, &user.email, &password);
+
+        // one for each stream
+        for stream in &streams {
+            let res = client
+                .post(format!("/v1/webhook/{}/hset", namespace.uuid))
+                .header(ContentType::JSON)
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(format!(
+                    r#"{{"url": "http://127.0.0.1:1/{}", "stream": "{}"}}"#,
+                    stream.name, stream.uuid
+                ))
+                .dispatch();
+
+            assert_eq!(res.status(), Status::Ok);
+        }
+
+        let stream = &streams[1];
+        let res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace.uuid, stream.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(
+                r#"{
+                    "agent_id": 1,
+                    "stream_id": 1,
+                    "title": "connection refused"
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let values =
+            pending::lrange(job::WEBHOOK_QUEUE, 0, -1, conn.mq).unwrap();
+        assert_eq!(values.len(), 1);
+        let job: Job = serde_json::from_str(&values[0]).unwrap();
+        let event = match job.payload {
+            Payload::DeliverWebhook(e) => e,
+            p => panic!("unexpected payload: {:?}", p),
+        };
+        let webhook = model::webhook::Webhook::find_by_id(
+            event.webhook_id,
+            conn.db,
+            logger,
+        )
+        .unwrap();
+        assert_eq!(webhook.stream_id, Some(stream.id));
+
+        let body: Value = serde_json::from_str(&event.body).unwrap();
+        assert_eq!(body["message"]["stream"], stream.uuid.to_string());
+    });
+}